@@ -1,6 +1,6 @@
 use regex_lite::Regex;
 
-use crate::{DocumentSyntax, SyntaxRule, document::syntax::parse_color_hex};
+use crate::{CommentTokens, DocumentSyntax, SyntaxRule, document::syntax::parse_color_hex};
 
 impl DocumentSyntax {
     pub fn builtin_python() -> Self {
@@ -52,6 +52,10 @@ impl DocumentSyntax {
                     color: parse_color_hex("#D4D4D4", false).unwrap(),
                 },
             ],
+            comment_tokens: CommentTokens {
+                line: Some("#"),
+                block: None,
+            },
         }
     }
 
@@ -105,6 +109,10 @@ impl DocumentSyntax {
                     color: parse_color_hex("#D4D4D4", false).unwrap(),
                 },
             ],
+            comment_tokens: CommentTokens {
+                line: Some("//"),
+                block: Some(("/*", "*/")),
+            },
         }
     }
 
@@ -169,6 +177,10 @@ impl DocumentSyntax {
                     color: parse_color_hex("#D4D4D4", false).unwrap(),
                 },
             ],
+            comment_tokens: CommentTokens {
+                line: Some("//"),
+                block: Some(("/*", "*/")),
+            },
         }
     }
 
@@ -222,6 +234,10 @@ impl DocumentSyntax {
                     color: parse_color_hex("#D4D4D4", false).unwrap(),
                 },
             ],
+            comment_tokens: CommentTokens {
+                line: Some("#"),
+                block: None,
+            },
         }
     }
 
@@ -281,6 +297,10 @@ impl DocumentSyntax {
                     color: parse_color_hex("#D4D4D4", false).unwrap(),
                 },
             ],
+            comment_tokens: CommentTokens {
+                line: Some("//"),
+                block: Some(("/*", "*/")),
+            },
         }
     }
 
@@ -338,4 +358,33 @@ impl DocumentSyntax {
 
         cpp_syntax
     }
+
+    pub fn builtin_diff() -> Self {
+        Self {
+            name: "Diff",
+            rules: vec![
+                // File headers
+                SyntaxRule::Inline {
+                    pattern: Regex::new(r"^(\+\+\+|---) .*$").unwrap(),
+                    color: parse_color_hex("#569CD6", false).unwrap(),
+                },
+                // Hunk headers
+                SyntaxRule::Inline {
+                    pattern: Regex::new(r"^@@.*$").unwrap(),
+                    color: parse_color_hex("#C586C0", false).unwrap(),
+                },
+                // Added lines
+                SyntaxRule::Inline {
+                    pattern: Regex::new(r"^\+.*$").unwrap(),
+                    color: parse_color_hex("#6A9955", false).unwrap(),
+                },
+                // Removed lines
+                SyntaxRule::Inline {
+                    pattern: Regex::new(r"^-.*$").unwrap(),
+                    color: parse_color_hex("#F44747", false).unwrap(),
+                },
+            ],
+            comment_tokens: CommentTokens::default(),
+        }
+    }
 }
@@ -0,0 +1,435 @@
+use std::{io, ops::Range};
+
+use crate::{Document, io::read_file};
+
+/// Number of unchanged lines shown around each change
+const N_CONTEXT_LINES: usize = 3;
+/// Past this many edits from either end, the search for the middle of the edit script settles
+/// for the furthest point it reached, trading a longer diff for bounded time
+const MAX_SEARCHED_EDITS: isize = 1024;
+
+#[derive(Clone, Copy)]
+enum DiffLine {
+    Unchanged(usize),
+    Removed(usize),
+    Added(usize),
+}
+
+impl Document {
+    /// Unified diff going from the file on disk to the document
+    pub fn get_disk_diff(&self) -> io::Result<Vec<String>> {
+        let disk_lines = read_file(&self.canonicalized_file_path, None)?.lines;
+        let old_lines = disk_lines
+            .iter()
+            .map(|line| line.as_ref())
+            .collect::<Vec<_>>();
+        let new_lines = self
+            .lines
+            .iter()
+            .map(|line| line.as_ref())
+            .collect::<Vec<_>>();
+
+        let path = self.canonicalized_file_path.display();
+        let mut diff = vec![format!("--- {} (on disk)", path), format!("+++ {}", path)];
+        diff.extend(get_unified_diff_hunks(&old_lines, &new_lines));
+
+        if diff.len() == 2 {
+            diff.push("No differences".to_owned());
+        }
+
+        Ok(diff)
+    }
+}
+
+/// Hunks of the unified diff going from `old_lines` to `new_lines`, with their headers
+fn get_unified_diff_hunks(old_lines: &[&str], new_lines: &[&str]) -> Vec<String> {
+    let diff_lines = diff_lines(old_lines, new_lines);
+    let mut diff = vec![];
+    let (mut old_pos, mut new_pos) = (0, 0);
+    let mut last_hunk_end = 0;
+    for hunk in get_hunks(&diff_lines) {
+        for diff_line in &diff_lines[last_hunk_end..hunk.start] {
+            match diff_line {
+                DiffLine::Unchanged(_) => (old_pos, new_pos) = (old_pos + 1, new_pos + 1),
+                DiffLine::Removed(_) => old_pos += 1,
+                DiffLine::Added(_) => new_pos += 1,
+            }
+        }
+
+        let hunk_lines = &diff_lines[hunk.clone()];
+        let old_len = hunk_lines
+            .iter()
+            .filter(|diff_line| !matches!(diff_line, DiffLine::Added(_)))
+            .count();
+        let new_len = hunk_lines
+            .iter()
+            .filter(|diff_line| !matches!(diff_line, DiffLine::Removed(_)))
+            .count();
+
+        // empty ranges point to the line preceding them
+        diff.push(format!(
+            "@@ -{},{} +{},{} @@",
+            old_pos + (old_len > 0) as usize,
+            old_len,
+            new_pos + (new_len > 0) as usize,
+            new_len
+        ));
+        diff.extend(hunk_lines.iter().map(|diff_line| match diff_line {
+            DiffLine::Unchanged(old_idx) => format!(" {}", old_lines[*old_idx]),
+            DiffLine::Removed(old_idx) => format!("-{}", old_lines[*old_idx]),
+            DiffLine::Added(new_idx) => format!("+{}", new_lines[*new_idx]),
+        }));
+
+        (old_pos, new_pos) = (old_pos + old_len, new_pos + new_len);
+        last_hunk_end = hunk.end;
+    }
+
+    diff
+}
+
+/// Shortest edit script turning `old` into `new`, using the linear space variant of
+/// Myers' algorithm: the middle of the script is found by searching from both ends at once,
+/// then the parts before and after it are diffed the same way
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<DiffLine> {
+    let mut diff_lines = Vec::with_capacity(old.len().max(new.len()));
+    diff_ranges(old, new, 0..old.len(), 0..new.len(), &mut diff_lines);
+
+    // removed lines are listed before the lines added in their place
+    for changes in diff_lines.split_mut(|diff_line| matches!(diff_line, DiffLine::Unchanged(_))) {
+        changes.sort_by_key(|diff_line| matches!(diff_line, DiffLine::Added(_)));
+    }
+
+    diff_lines
+}
+
+fn diff_ranges(
+    old: &[&str],
+    new: &[&str],
+    mut old_range: Range<usize>,
+    mut new_range: Range<usize>,
+    diff_lines: &mut Vec<DiffLine>,
+) {
+    // lines shared at both ends don't need to go through the algorithm
+    while !old_range.is_empty()
+        && !new_range.is_empty()
+        && old[old_range.start] == new[new_range.start]
+    {
+        diff_lines.push(DiffLine::Unchanged(old_range.start));
+        old_range.start += 1;
+        new_range.start += 1;
+    }
+
+    let mut suffix_len = 0;
+    while suffix_len < old_range.len().min(new_range.len())
+        && old[old_range.end - suffix_len - 1] == new[new_range.end - suffix_len - 1]
+    {
+        suffix_len += 1;
+    }
+    old_range.end -= suffix_len;
+    new_range.end -= suffix_len;
+
+    if old_range.is_empty() {
+        diff_lines.extend(new_range.map(DiffLine::Added));
+    } else if new_range.is_empty() {
+        diff_lines.extend(old_range.clone().map(DiffLine::Removed));
+    } else {
+        let ((x_start, y_start), (x_end, y_end)) =
+            find_middle_snake(&old[old_range.clone()], &new[new_range.clone()]);
+        let (x_start, x_end) = (old_range.start + x_start, old_range.start + x_end);
+        let (y_start, y_end) = (new_range.start + y_start, new_range.start + y_end);
+
+        diff_ranges(
+            old,
+            new,
+            old_range.start..x_start,
+            new_range.start..y_start,
+            diff_lines,
+        );
+        diff_lines.extend((x_start..x_end).map(DiffLine::Unchanged));
+        diff_ranges(
+            old,
+            new,
+            x_end..old_range.end,
+            y_end..new_range.end,
+            diff_lines,
+        );
+    }
+
+    diff_lines.extend((old_range.end..old_range.end + suffix_len).map(DiffLine::Unchanged));
+}
+
+/// Start and end of the run of unchanged lines in the middle of the shortest edit script
+/// turning `old` into `new`, which must differ at both ends. Past `MAX_SEARCHED_EDITS`,
+/// an empty run at the furthest point reached from either end
+fn find_middle_snake(old: &[&str], new: &[&str]) -> ((usize, usize), (usize, usize)) {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max_n_edits = (n + m + 1) / 2;
+    let delta = n - m;
+    let offset = max_n_edits + 1;
+
+    // furthest x reached on each diagonal k = x - y, from the start and from the end.
+    // The diagonal k of the forward search is the diagonal delta - k of the backward one
+    let mut forward_x = vec![0isize; 2 * offset as usize + 1];
+    let mut backward_x = vec![0isize; 2 * offset as usize + 1];
+    for n_edits in 0..=max_n_edits {
+        for k in (-n_edits..=n_edits).step_by(2) {
+            let k_idx = (k + offset) as usize;
+            let mut x =
+                if k == -n_edits || (k != n_edits && forward_x[k_idx - 1] < forward_x[k_idx + 1]) {
+                    forward_x[k_idx + 1]
+                } else {
+                    forward_x[k_idx - 1] + 1
+                };
+            let mut y = x - k;
+            let (snake_x, snake_y) = (x, y);
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            forward_x[k_idx] = x;
+            let backward_k = delta - k;
+            if delta % 2 != 0
+                && backward_k.abs() < n_edits
+                && x + backward_x[(backward_k + offset) as usize] >= n
+            {
+                return (
+                    (snake_x as usize, snake_y as usize),
+                    (x as usize, y as usize),
+                );
+            }
+        }
+
+        for k in (-n_edits..=n_edits).step_by(2) {
+            let k_idx = (k + offset) as usize;
+            let mut x = if k == -n_edits
+                || (k != n_edits && backward_x[k_idx - 1] < backward_x[k_idx + 1])
+            {
+                backward_x[k_idx + 1]
+            } else {
+                backward_x[k_idx - 1] + 1
+            };
+            let mut y = x - k;
+            let (snake_x, snake_y) = (x, y);
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            backward_x[k_idx] = x;
+            let forward_k = delta - k;
+            if delta % 2 == 0
+                && forward_k.abs() <= n_edits
+                && x + forward_x[(forward_k + offset) as usize] >= n
+            {
+                return (
+                    ((n - x) as usize, (m - y) as usize),
+                    ((n - snake_x) as usize, (m - snake_y) as usize),
+                );
+            }
+        }
+
+        if n_edits >= MAX_SEARCHED_EDITS {
+            // points strictly between both ends, so that both parts left to diff are smaller
+            let is_split_point = |(x, y): &(isize, isize)| {
+                (0..=n).contains(x) && (0..=m).contains(y) && 0 < x + y && x + y < n + m
+            };
+            let forward_split = (-n_edits..=n_edits)
+                .step_by(2)
+                .map(|k| (forward_x[(k + offset) as usize], k))
+                .map(|(x, k)| (x, x - k))
+                .filter(is_split_point)
+                .max_by_key(|(x, y)| x + y);
+            let backward_split = (-n_edits..=n_edits)
+                .step_by(2)
+                .map(|k| (backward_x[(k + offset) as usize], k))
+                .map(|(x, k)| (n - x, m - x + k))
+                .filter(is_split_point)
+                .min_by_key(|(x, y)| x + y);
+
+            let split = match (forward_split, backward_split) {
+                (Some((x, y)), Some((backward_x, backward_y)))
+                    if x + y < n + m - backward_x - backward_y =>
+                {
+                    (backward_x, backward_y)
+                }
+                (Some(split), _) | (None, Some(split)) => split,
+                (None, None) => continue,
+            };
+            let split = (split.0 as usize, split.1 as usize);
+
+            return (split, split);
+        }
+    }
+
+    unreachable!("the searches meet within (n + m) / 2 edits")
+}
+
+/// Ranges of `diff_lines` containing changes, with their surrounding context
+fn get_hunks(diff_lines: &[DiffLine]) -> Vec<Range<usize>> {
+    let mut hunks: Vec<Range<usize>> = vec![];
+    for (line_idx, diff_line) in diff_lines.iter().enumerate() {
+        if matches!(diff_line, DiffLine::Unchanged(_)) {
+            continue;
+        }
+
+        let start = line_idx.saturating_sub(N_CONTEXT_LINES);
+        let end = (line_idx + N_CONTEXT_LINES + 1).min(diff_lines.len());
+        match hunks.last_mut() {
+            Some(last_hunk) if last_hunk.end >= start => last_hunk.end = end,
+            _ => hunks.push(start..end),
+        }
+    }
+
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DiffLine, diff_lines, get_unified_diff_hunks};
+
+    fn get_hunks(old: &str, new: &str) -> Vec<String> {
+        let old_lines = old
+            .split(' ')
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        let new_lines = new
+            .split(' ')
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+
+        get_unified_diff_hunks(&old_lines, &new_lines)
+    }
+
+    /// Checks that the diff of `old` and `new` goes from one to the other,
+    /// returns its number of added and removed lines
+    fn check_diff(old: &[&str], new: &[&str]) -> usize {
+        let (mut diffed_old, mut diffed_new, mut n_edits) = (vec![], vec![], 0);
+        for diff_line in diff_lines(old, new) {
+            match diff_line {
+                DiffLine::Unchanged(old_idx) => {
+                    diffed_old.push(old[old_idx]);
+                    diffed_new.push(old[old_idx]);
+                }
+                DiffLine::Removed(old_idx) => {
+                    diffed_old.push(old[old_idx]);
+                    n_edits += 1;
+                }
+                DiffLine::Added(new_idx) => {
+                    diffed_new.push(new[new_idx]);
+                    n_edits += 1;
+                }
+            }
+        }
+        assert_eq!((diffed_old.as_slice(), diffed_new.as_slice()), (old, new));
+
+        n_edits
+    }
+
+    #[test]
+    fn diffs_identical_lines() {
+        assert!(get_hunks("", "").is_empty());
+        assert!(get_hunks("a b c", "a b c").is_empty());
+    }
+
+    #[test]
+    fn diffs_insertions() {
+        assert_eq!(get_hunks("", "a b"), ["@@ -0,0 +1,2 @@", "+a", "+b"]);
+        assert_eq!(
+            get_hunks("a b c d e f g", "a b c d X e f g"),
+            ["@@ -2,6 +2,7 @@", " b", " c", " d", "+X", " e", " f", " g"]
+        );
+        assert_eq!(
+            get_hunks("a b c d e f g h i j", "X a b c d e f g h i j Y"),
+            [
+                "@@ -1,3 +1,4 @@",
+                "+X",
+                " a",
+                " b",
+                " c",
+                "@@ -8,3 +9,4 @@",
+                " h",
+                " i",
+                " j",
+                "+Y"
+            ]
+        );
+    }
+
+    #[test]
+    fn diffs_deletions() {
+        assert_eq!(get_hunks("a b", ""), ["@@ -1,2 +0,0 @@", "-a", "-b"]);
+        assert_eq!(
+            get_hunks("a b c d e", "a b d e"),
+            ["@@ -1,5 +1,4 @@", " a", " b", "-c", " d", " e"]
+        );
+    }
+
+    #[test]
+    fn diffs_interleaved_changes() {
+        assert_eq!(
+            get_hunks("a b c d e", "a X c Y e Z"),
+            [
+                "@@ -1,5 +1,6 @@",
+                " a",
+                "-b",
+                "+X",
+                " c",
+                "-d",
+                "+Y",
+                " e",
+                "+Z"
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_shortest_edit_scripts() {
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut next_random = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound) as usize
+        };
+
+        let words = ["a", "b", "c", "d"];
+        for _ in 0..500 {
+            let old = (0..next_random(12))
+                .map(|_| words[next_random(4)])
+                .collect::<Vec<_>>();
+            let new = (0..next_random(12))
+                .map(|_| words[next_random(4)])
+                .collect::<Vec<_>>();
+
+            let n_edits = check_diff(&old, &new);
+
+            // the longest common subsequence, the slow way
+            let mut lcs_lens = vec![vec![0; new.len() + 1]; old.len() + 1];
+            for x in (0..old.len()).rev() {
+                for y in (0..new.len()).rev() {
+                    lcs_lens[x][y] = if old[x] == new[y] {
+                        lcs_lens[x + 1][y + 1] + 1
+                    } else {
+                        lcs_lens[x + 1][y].max(lcs_lens[x][y + 1])
+                    };
+                }
+            }
+            assert_eq!(n_edits, old.len() + new.len() - 2 * lcs_lens[0][0]);
+        }
+    }
+
+    #[test]
+    fn bounds_long_edit_scripts() {
+        // more edits than searched from either end, with some lines in common
+        let old = (0..3000)
+            .map(|idx| if idx % 7 == 0 { "same" } else { "old" })
+            .collect::<Vec<_>>();
+        let new = (0..2500)
+            .map(|idx| if idx % 5 == 0 { "same" } else { "new" })
+            .collect::<Vec<_>>();
+
+        check_diff(&old, &new);
+        check_diff(&new, &old);
+    }
+}
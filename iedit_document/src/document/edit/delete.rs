@@ -13,9 +13,7 @@ impl Document {
             let current_line = self.lines.remove(y);
             self.lines[y - 1].push_str(current_line.as_ref());
 
-            for line in &mut self.lines[y - 1..] {
-                line.needs_render = true;
-            }
+            self.mark_lines_need_render_from(y - 1);
 
             return ('\n', Some((prev_line_len, y - 1)));
         }
@@ -34,9 +32,9 @@ impl Document {
 
     /// NOTE: pos_to is EXCLUSIVE in the sense that:
     /// - (0, y) (self.lines\[y\].len(), y) will delete all the characters in the line and leave an empty line in its place
-    /// and return a Text::String with the contents of the deleted line
+    ///   and return a Text::String with the contents of the deleted line
     /// - (0, y) (0, y + 1) will delete line y and shift all lines up by 1
-    /// and return a Text::Lines with a vec like \[ self.lines\[y\], "" \]
+    ///   and return a Text::Lines with a vec like \[ self.lines\[y\], "" \]
     pub fn delete_range(&mut self, pos_from: (usize, usize), pos_to: (usize, usize)) -> Text {
         if pos_from.1 == pos_to.1
             && let Some(line) = self.lines.get_mut(pos_from.1)
@@ -79,9 +77,7 @@ impl Document {
             deleted_text.push(deleted_last.into());
         }
 
-        for line in &mut self.lines[pos_from.1..] {
-            line.needs_render = true;
-        }
+        self.mark_lines_need_render_from(pos_from.1);
 
         Text::Lines(deleted_text)
     }
@@ -48,7 +48,7 @@ impl Document {
             let line = if idx == n_strings - 1 {
                 let mut line = DocumentLine::new(string);
                 line.push_str(last_line_postfix.as_ref());
-                
+
                 line
             } else {
                 DocumentLine::new(string)
@@ -57,9 +57,7 @@ impl Document {
             self.lines.insert(y + idx, line);
         }
 
-        for line in &mut self.lines[y..] {
-            line.needs_render = true;
-        }
+        self.mark_lines_need_render_from(y);
 
         Some((landing_x, landing_y))
     }
@@ -81,9 +79,7 @@ impl Document {
         }
 
         self.lines.insert(y + 1, to_append);
-        for line in &mut self.lines[y + 1..] {
-            line.needs_render = true;
-        }
+        self.mark_lines_need_render_from(y + 1);
 
         Some((first_nonwhitespace_x, y + 1))
     }
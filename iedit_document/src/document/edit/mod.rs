@@ -1,10 +1,11 @@
-use crate::{CharacterIndexable, Document};
+use crate::{CharacterIndexable, Document, EditEvent};
 
 mod delete;
 mod insert;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub enum Text {
+    #[default]
     Empty,
     Char(char),
     String(String),
@@ -12,31 +13,27 @@ pub enum Text {
     Lines(Vec<String>),
 }
 
-impl Default for Text {
-    fn default() -> Self {
-        Self::Empty
-    }
-}
-
-impl ToString for Text {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for Text {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Text::Empty => String::new(),
-            Text::Char(ch) => String::from(*ch),
-            Text::String(string) => string.clone(),
-            Text::InverseString(string) => string.chars().into_iter().rev().collect(),
-            Text::Lines(lines) => lines.join("\n"),
+            Text::Empty => Ok(()),
+            Text::Char(ch) => write!(f, "{}", ch),
+            Text::String(string) => f.write_str(string),
+            Text::InverseString(string) => {
+                write!(f, "{}", string.chars().rev().collect::<String>())
+            }
+            Text::Lines(lines) => f.write_str(&lines.join("\n")),
         }
     }
 }
 
-impl Into<String> for Text {
-    fn into(self) -> String {
-        match self {
+impl From<Text> for String {
+    fn from(val: Text) -> Self {
+        match val {
             Text::Empty => String::new(),
             Text::Char(ch) => String::from(ch),
             Text::String(string) => string,
-            Text::InverseString(string) => string.chars().into_iter().rev().collect(),
+            Text::InverseString(string) => string.chars().rev().collect(),
             Text::Lines(lines) => lines.join("\n"),
         }
     }
@@ -60,11 +57,11 @@ impl From<String> for Text {
             );
         }
 
-        return Text::String(value);
+        Text::String(value)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum EditOperation {
     LineRemoval {
         idx: usize,
@@ -81,6 +78,8 @@ pub enum EditOperation {
         pos_to: (usize, usize),
         text: Text,
     },
+    /// A batch of operations, applied in order, that is undone and redone as a single step
+    Group(Vec<EditOperation>),
 }
 
 impl EditOperation {
@@ -114,6 +113,11 @@ impl EditOperation {
                 }
                 Text::Lines(items) => (pos_from.1, pos_to.1 + items.len()),
             },
+            EditOperation::Group(ops) => ops
+                .iter()
+                .map(EditOperation::get_affected_line_range)
+                .reduce(|(start, end), (op_start, op_end)| (start.min(op_start), end.max(op_end)))
+                .unwrap_or_default(),
         }
     }
 }
@@ -133,14 +137,80 @@ impl Document {
             return None;
         }
 
-        use EditOperation as Op;
-        use Text as T;
+        if inverse_stack == InverseStack::Undo {
+            self.park_redo_stack();
+        }
 
+        let undo_depth = self.undo_stack.len();
+        let edit_result = self.apply_edit_and_update_syntax(op, inverse_stack);
         if inverse_stack == InverseStack::Undo {
-            self.redo_stack.clear();
+            self.record_undo_state(undo_depth, edit_result);
         }
 
+        edit_result
+    }
+
+    /// Adds a state to the undo tree if the last edit pushed a new entry on the undo stack
+    fn record_undo_state(&mut self, undo_depth: usize, edit_result: EditResult) {
+        if self.undo_stack.len() > undo_depth {
+            self.undo_tree.push_state();
+        } else if !self.undo_tree.is_at_leaf() {
+            // nothing was edited, the undone states can still be redone
+            self.unpark_redo_stack();
+        } else if edit_result.is_some() {
+            self.undo_tree.touch_current_state();
+        }
+    }
+
+    fn apply_edit_and_update_syntax(
+        &mut self,
+        op: EditOperation,
+        inverse_stack: InverseStack,
+    ) -> EditResult {
         let affected_range = op.get_affected_line_range();
+        self.swap_journal
+            .record(self.auto_inserted_whitespace_line, &op);
+        let edit_result = self.apply_edit_inner(op, inverse_stack);
+
+        if self.should_recompute_syntax_blocks(affected_range) {
+            self.recompute_syntax_blocks();
+        }
+
+        edit_result
+    }
+
+    /// Applies `op` and pushes its inverse on `inverse_stack`, without touching
+    /// the redo stack or the syntax blocks, then passes it to the edit listeners
+    pub(crate) fn apply_edit_inner(
+        &mut self,
+        op: EditOperation,
+        inverse_stack: InverseStack,
+    ) -> EditResult {
+        // the operations of a group go through here one by one
+        if matches!(op, EditOperation::Group(_)) {
+            return self.apply_operation(op, inverse_stack);
+        }
+
+        let affected_lines = op.get_affected_line_range();
+        let notified_op = (!self.edit_listeners.is_empty()).then(|| op.clone());
+        let new_pos = self.apply_operation(op, inverse_stack)?;
+
+        self.version += 1;
+        if let Some(op) = notified_op {
+            self.edit_listeners.notify(&EditEvent {
+                version: self.version,
+                op: &op,
+                affected_lines,
+            });
+        }
+
+        Some(new_pos)
+    }
+
+    fn apply_operation(&mut self, op: EditOperation, inverse_stack: InverseStack) -> EditResult {
+        use EditOperation as Op;
+        use Text as T;
+
         if !matches!(
             op,
             Op::Insertion {
@@ -151,7 +221,7 @@ impl Document {
             self.auto_inserted_whitespace_line = None;
         }
 
-        let edit_result = match op {
+        match op {
             Op::Insertion {
                 pos,
                 text: T::Char('\n'),
@@ -163,7 +233,9 @@ impl Document {
                     self.auto_inserted_whitespace_line = None;
                     self.get_inverse_stack(inverse_stack)
                         .push(Op::Deletion { pos: new_pos });
-                } else if let Some(return_y) = self.auto_inserted_whitespace_line {
+                } else if let Some(return_y) = self.auto_inserted_whitespace_line
+                    && self.undo_tree.is_at_leaf()
+                {
                     self.lines[pos.1].truncate(0); // TODO: move into insert_newline_at
                     let return_x = self.lines[return_y].len();
                     *self.get_inverse_stack(inverse_stack).last_mut().unwrap() = Op::Replacement {
@@ -176,7 +248,7 @@ impl Document {
                     self.get_inverse_stack(inverse_stack).push(Op::Replacement {
                         pos_from: pos,
                         pos_to: new_pos,
-                        text: T::Empty
+                        text: T::Empty,
                     });
                 }
 
@@ -194,7 +266,11 @@ impl Document {
                             pos_from,
                             pos_to,
                             text: T::Empty,
-                        }) if !ch.is_whitespace() && pos_from.1 == pos_to.1 && *pos_to == pos => {
+                        }) if !ch.is_whitespace()
+                            && pos_from.1 == pos_to.1
+                            && *pos_to == pos
+                            && self.undo_tree.is_at_leaf() =>
+                        {
                             *pos_to = new_pos
                         }
                         _ => self.undo_stack.push(Op::Replacement {
@@ -213,7 +289,7 @@ impl Document {
                 pos,
                 text: T::InverseString(string),
             } => {
-                let string = string.chars().into_iter().rev().collect::<String>();
+                let string = string.chars().rev().collect::<String>();
                 let new_pos = self.insert_string_at(pos, string)?;
                 self.get_inverse_stack(inverse_stack).push(Op::Replacement {
                     pos_from: pos,
@@ -268,7 +344,8 @@ impl Document {
                                 text: T::InverseString(string),
                             }) if new_cursor_pos.1 == last_cursor_pos.1
                                 && new_cursor_pos.0 == last_cursor_pos.0 - 1
-                                && !ch.is_whitespace() =>
+                                && !ch.is_whitespace()
+                                && self.undo_tree.is_at_leaf() =>
                             {
                                 *last_cursor_pos = new_cursor_pos;
                                 string.push(ch);
@@ -305,7 +382,7 @@ impl Document {
                     T::Lines(lines) => self.insert_strings_at(pos_from, lines),
                     // NOTE: implemented for completeness but a Replacement should never be constructed with an InverseString
                     T::InverseString(string) => {
-                        let string = string.chars().into_iter().rev().collect::<String>();
+                        let string = string.chars().rev().collect::<String>();
                         self.insert_string_at(pos_from, string)
                     }
                 }?;
@@ -318,26 +395,112 @@ impl Document {
 
                 Some(new_pos)
             }
-            _ => None,
-        };
+            Op::Group(ops) => {
+                // collect the inverse of every operation in the group on a fresh stack,
+                // so that they can't be coalesced with edits outside the group
+                let outer_stack = std::mem::take(self.get_inverse_stack(inverse_stack));
+                let mut new_pos = None;
+                for op in ops {
+                    new_pos = self.apply_edit_inner(op, inverse_stack).or(new_pos);
+                }
 
-        if self.should_recompute_syntax_blocks(affected_range) {
-            self.recompute_syntax_blocks();
-        }
+                let mut inverse_ops =
+                    std::mem::replace(self.get_inverse_stack(inverse_stack), outer_stack);
+                if !inverse_ops.is_empty() {
+                    inverse_ops.reverse();
+                    self.get_inverse_stack(inverse_stack)
+                        .push(Op::Group(inverse_ops));
+                }
 
-        edit_result
+                new_pos
+            }
+            _ => None,
+        }
     }
 
     pub fn undo_last_edit(&mut self) -> EditResult {
+        // the edits of an open transaction aren't a step of their own yet
+        if self.is_readonly || self.is_in_transaction() {
+            return None;
+        }
+
         let op = self.undo_stack.pop()?;
+        self.undo_tree.move_to_parent();
+        self.auto_inserted_whitespace_line = None;
 
-        self.apply_edit(op, InverseStack::Redo)
+        self.apply_edit_and_update_syntax(op, InverseStack::Redo)
     }
 
     pub fn redo_last_edit(&mut self) -> EditResult {
+        // the edits of an open transaction aren't a step of their own yet
+        if self.is_readonly || self.is_in_transaction() {
+            return None;
+        }
+
         let op = self.redo_stack.pop()?;
+        self.undo_tree.move_to_preferred_child();
+        self.auto_inserted_whitespace_line = None;
 
-        self.apply_edit(op, InverseStack::Undo)
+        self.apply_edit_and_update_syntax(op, InverseStack::Undo)
+    }
+
+    /// Applies one operation per cursor as a single undo step.
+    /// `ops` must be sorted by position and must not overlap: they are applied from last
+    /// to first, so that the positions of the ones yet to be applied stay valid.
+    /// Returns the cursor position following each operation, in the same order as `ops`
+    pub fn apply_cursor_edits(&mut self, ops: Vec<EditOperation>) -> Vec<EditResult> {
+        if self.is_readonly {
+            return vec![None; ops.len()];
+        }
+
+        self.park_redo_stack();
+
+        let affected_range = ops
+            .iter()
+            .map(EditOperation::get_affected_line_range)
+            .reduce(|(start, end), (op_start, op_end)| (start.min(op_start), end.max(op_end)))
+            .unwrap_or_default();
+
+        let outer_stack = std::mem::take(&mut self.undo_stack);
+        let mut results: Vec<EditResult> = Vec::with_capacity(ops.len());
+        for op in ops.into_iter().rev() {
+            let edit_end = match &op {
+                EditOperation::Deletion { pos } | EditOperation::Insertion { pos, text: _ } => {
+                    Some(*pos)
+                }
+                EditOperation::Replacement { pos_to, .. } => Some(*pos_to),
+                EditOperation::LineRemoval { idx: _ } | EditOperation::Group(_) => None,
+            };
+
+            // every cursor gets its own auto-indentation
+            self.auto_inserted_whitespace_line = None;
+            self.swap_journal.record(None, &op);
+            let new_pos = self.apply_edit_inner(op, InverseStack::Undo);
+
+            // text following the edit has moved, and so have the cursors placed there
+            if let (Some(edit_end), Some(new_pos)) = (edit_end, new_pos) {
+                for pos in results.iter_mut().flatten() {
+                    *pos = shift_pos(*pos, edit_end, new_pos);
+                }
+            }
+
+            results.push(new_pos);
+        }
+
+        let mut inverse_ops = std::mem::replace(&mut self.undo_stack, outer_stack);
+        if !inverse_ops.is_empty() {
+            inverse_ops.reverse();
+            self.undo_stack.push(EditOperation::Group(inverse_ops));
+            self.undo_tree.push_state();
+        }
+
+        if self.should_recompute_syntax_blocks(affected_range) {
+            self.recompute_syntax_blocks();
+        }
+
+        results.reverse();
+
+        results
     }
 
     pub fn get_inverse_stack(
@@ -352,3 +515,19 @@ impl Document {
         inverse_stack
     }
 }
+
+/// Maps `pos`, located after an edit that ended at `edit_end`, to its position once
+/// the text up to `edit_end` has been replaced with text ending at `new_end`
+fn shift_pos(
+    pos: (usize, usize),
+    edit_end: (usize, usize),
+    new_end: (usize, usize),
+) -> (usize, usize) {
+    if pos.1 == edit_end.1 && pos.0 >= edit_end.0 {
+        (pos.0 - edit_end.0 + new_end.0, new_end.1)
+    } else if pos.1 > edit_end.1 {
+        (pos.0, pos.1 + new_end.1 - edit_end.1)
+    } else {
+        pos
+    }
+}
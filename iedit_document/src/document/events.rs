@@ -0,0 +1,145 @@
+use crate::{Document, EditOperation};
+
+/// An edit applied to a document, as passed to the listeners subscribed to it.
+/// NOTE: the operations of a group are passed one by one, in the order they are applied
+pub struct EditEvent<'op> {
+    /// Version of the document once the edit has been applied
+    pub version: u64,
+    pub op: &'op EditOperation,
+    /// First and last line affected by the edit
+    pub affected_lines: (usize, usize),
+}
+
+/// Identifies a listener subscribed to a document, to unsubscribe it later on
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ListenerId(usize);
+
+type Listener = Box<dyn FnMut(&EditEvent) + Send>;
+
+#[derive(Default)]
+pub struct EditListeners {
+    listeners: Vec<(ListenerId, Listener)>,
+    next_id: usize,
+}
+
+impl EditListeners {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.listeners.is_empty()
+    }
+
+    pub fn notify(&mut self, event: &EditEvent) {
+        for (_, listener) in self.listeners.iter_mut() {
+            listener(event);
+        }
+    }
+}
+
+impl Document {
+    /// Calls `listener` after every edit applied to the document, including undoing
+    /// and redoing, until it is unsubscribed
+    pub fn subscribe(&mut self, listener: impl FnMut(&EditEvent) + Send + 'static) -> ListenerId {
+        let id = ListenerId(self.edit_listeners.next_id);
+        self.edit_listeners.next_id += 1;
+        self.edit_listeners.listeners.push((id, Box::new(listener)));
+
+        id
+    }
+
+    /// Returns whether the listener was subscribed
+    pub fn unsubscribe(&mut self, id: ListenerId) -> bool {
+        let n_listeners = self.edit_listeners.listeners.len();
+        self.edit_listeners
+            .listeners
+            .retain(|(listener_id, _)| *listener_id != id);
+
+        self.edit_listeners.listeners.len() < n_listeners
+    }
+
+    /// Increases with every edit, and when the document is reloaded from its file.
+    /// NOTE: reloading isn't an edit, listeners aren't called for it
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::ListenerId;
+    use crate::document::test_utils::{get_document, insert};
+    use crate::{EditOperation, InverseStack, Text};
+
+    #[test]
+    fn passes_the_ops_of_a_group_in_order() {
+        let mut document = get_document(&["one", "two", "three"]);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let listener_events = Arc::clone(&events);
+        document.subscribe(move |event| {
+            listener_events.lock().unwrap().push((
+                event.version,
+                event.op.clone(),
+                event.affected_lines,
+            ));
+        });
+
+        let ops = vec![
+            EditOperation::Insertion {
+                pos: (1, 0),
+                text: Text::Char('\n'),
+            },
+            EditOperation::Insertion {
+                pos: (0, 3),
+                text: Text::String("x".to_owned()),
+            },
+        ];
+        let version = document.version();
+        document.apply_edit(EditOperation::Group(ops.clone()), InverseStack::Undo);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                (version + 1, ops[0].clone(), (0, 1)),
+                (version + 2, ops[1].clone(), (3, 3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn increases_the_version_on_undo_and_redo() {
+        let mut document = get_document(&["one"]);
+        let versions = Arc::new(Mutex::new(Vec::new()));
+        let listener_versions = Arc::clone(&versions);
+        document.subscribe(move |event| listener_versions.lock().unwrap().push(event.version));
+
+        let mut document_versions = vec![document.version()];
+        insert(&mut document, (3, 0), " two");
+        document_versions.push(document.version());
+        document.undo_last_edit();
+        document_versions.push(document.version());
+        document.redo_last_edit();
+        document_versions.push(document.version());
+
+        assert!(document_versions.is_sorted_by(|a, b| a < b));
+        assert_eq!(*versions.lock().unwrap(), document_versions[1..]);
+    }
+
+    #[test]
+    fn stops_passing_edits_once_unsubscribed() {
+        let mut document = get_document(&["one"]);
+        let n_events = Arc::new(Mutex::new(0));
+        let listener_n_events = Arc::clone(&n_events);
+        let id = document.subscribe(move |_| *listener_n_events.lock().unwrap() += 1);
+
+        insert(&mut document, (0, 0), "a");
+        assert!(!document.unsubscribe(ListenerId(id.0 + 1)));
+        insert(&mut document, (0, 0), "b");
+        assert!(document.unsubscribe(id));
+        assert!(!document.unsubscribe(id));
+        insert(&mut document, (0, 0), "c");
+
+        assert_eq!(*n_events.lock().unwrap(), 2);
+    }
+}
@@ -1,12 +1,66 @@
 use regex_lite::Regex;
 
-use crate::{CharacterIndexable, Document};
+use crate::{CharacterIndexable, Document, Text};
+
+/// Range of a regex match, together with its interpolated replacement
+pub type RegexReplacement = ((usize, usize), (usize, usize), String);
 
 impl Document {
     pub fn get_char_at_pos(&self, (x, y): (usize, usize)) -> Option<char> {
         self.lines.get(y).and_then(|line| line.at(x))
     }
 
+    /// Position of the first character that can't be represented in the encoding of the document
+    pub fn get_unencodable_char_pos(&self) -> Option<(usize, usize)> {
+        if self.encoding.is_unicode() {
+            return None;
+        }
+
+        // the lines that aren't loaded are read from the file, so in the same encoding
+        self.lines
+            .loaded_lines(0..self.n_lines())
+            .find_map(|(y, line)| {
+                if self.encoding.can_encode(line.as_ref()) {
+                    return None;
+                }
+
+                let x = line
+                    .iter()
+                    .position(|ch| !self.encoding.can_encode(ch.encode_utf8(&mut [0; 4])))
+                    .unwrap_or_default();
+
+                Some((x, y))
+            })
+    }
+
+    /// Text between `pos_from` and `pos_to`, with the same bounds as `delete_range`
+    pub fn get_range_text(&self, pos_from: (usize, usize), pos_to: (usize, usize)) -> Text {
+        let Some(first_line) = self.lines.get(pos_from.1) else {
+            return Text::Empty;
+        };
+
+        if pos_from.1 == pos_to.1 {
+            let x_to = pos_to.0.min(first_line.len());
+            let x_from = pos_from.0.min(x_to);
+            return Text::String(first_line.get_range(x_from..x_to).to_owned());
+        }
+
+        let lines = (pos_from.1..=pos_to.1)
+            .map(|line_idx| match self.lines.get(line_idx) {
+                Some(line) if line_idx == pos_from.1 => {
+                    line.get_range(pos_from.0.min(line.len())..).to_owned()
+                }
+                Some(line) if line_idx == pos_to.1 => {
+                    line.get_range(..pos_to.0.min(line.len())).to_owned()
+                }
+                Some(line) => line.as_ref().to_owned(),
+                None => String::new(),
+            })
+            .collect();
+
+        Text::Lines(lines)
+    }
+
     pub fn get_next_word_end_pos(&self, (x, y): (usize, usize)) -> (usize, usize) {
         let line = match self.lines.get(y) {
             Some(line) => line,
@@ -34,6 +88,75 @@ impl Document {
         (next_word_x, y)
     }
 
+    /// Start of the next word, vi style: words are runs of letters, digits and underscores,
+    /// or runs of other non-blank characters, and empty lines count as words
+    pub fn get_next_word_start_pos(&self, (x, y): (usize, usize)) -> (usize, usize) {
+        let Some(line) = self.lines.get(y) else {
+            return (x, y);
+        };
+
+        let mut x = x;
+        if let Some(class) = line.at(x).map(CharClass::of)
+            && class != CharClass::Blank
+        {
+            while line.at(x).is_some_and(|ch| CharClass::of(ch) == class) {
+                x += 1;
+            }
+        }
+
+        let (mut x, mut y) = (x, y);
+        loop {
+            let Some(line) = self.lines.get(y) else {
+                return (x, y);
+            };
+
+            while line.at(x).is_some_and(char::is_whitespace) {
+                x += 1;
+            }
+
+            if x < line.len() {
+                return (x, y);
+            } else if y + 1 >= self.n_lines() {
+                return (line.len(), y);
+            }
+
+            (x, y) = (0, y + 1);
+            if self.lines.get(y).is_some_and(|line| line.is_empty()) {
+                return (x, y);
+            }
+        }
+    }
+
+    /// Last character of the word ending after `(x, y)`, vi style like `get_next_word_start_pos`
+    /// except that empty lines are skipped
+    pub fn get_next_word_last_char_pos(&self, (x, y): (usize, usize)) -> (usize, usize) {
+        let (mut x, mut y) = (x + 1, y);
+        let line = loop {
+            let Some(line) = self.lines.get(y) else {
+                return (x - 1, y);
+            };
+
+            while line.at(x).is_some_and(char::is_whitespace) {
+                x += 1;
+            }
+
+            if x < line.len() {
+                break line;
+            } else if y + 1 >= self.n_lines() {
+                return (line.len().saturating_sub(1), y);
+            }
+
+            (x, y) = (0, y + 1);
+        };
+
+        let class = line.at(x).map(CharClass::of);
+        while line.at(x + 1).map(CharClass::of) == class {
+            x += 1;
+        }
+
+        (x, y)
+    }
+
     pub fn get_previous_word_start_pos(&self, (x, y): (usize, usize)) -> (usize, usize) {
         if x == 0 {
             return match y {
@@ -59,7 +182,11 @@ impl Document {
         }
 
         // Skip alpha backwards
-        while previous_word_x > 0 && line.at(previous_word_x - 1).is_some_and(char::is_alphanumeric) {
+        while previous_word_x > 0
+            && line
+                .at(previous_word_x - 1)
+                .is_some_and(char::is_alphanumeric)
+        {
             previous_word_x -= 1;
         }
 
@@ -80,14 +207,11 @@ impl Document {
             }
         }
 
-        for (line_idx, line) in self.lines.iter().enumerate().skip(y + 1) {
-            if let Some(idx) = line.as_ref().find(ch) {
-                let x = line.byte_to_char_idx(idx)?;
-                return Some((x, line_idx));
-            }
-        }
-
-        None
+        self.lines.find_map_from(y + 1, |line_idx, line| {
+            let idx = line.as_ref().find(ch)?;
+            let x = line.byte_to_char_idx(idx)?;
+            Some((x, line_idx))
+        })
     }
 
     pub fn get_word_boundaries(&self, (x, y): (usize, usize)) -> Option<(usize, usize)> {
@@ -96,21 +220,21 @@ impl Document {
             return None;
         };
 
-        let left_boundary = line
+        // counted in characters, not in bytes
+        let n_word_chars_before = line
             .get_range(..x)
-            .char_indices()
-            .rfind(|(_idx, ch)| !ch.is_alphanumeric())
-            .map(|(idx, _ch)| idx + 1)
-            .unwrap_or_default();
-
-        let right_boundary = line
-            .get_range(x..)
-            .char_indices()
-            .find(|(_idx, ch)| !ch.is_alphanumeric())
-            .map(|(idx, _ch)| idx.saturating_sub(1))
-            .unwrap_or(line.len() - x - 1);
-
-        Some((left_boundary, right_boundary + x))
+            .chars()
+            .rev()
+            .take_while(|ch| ch.is_alphanumeric())
+            .count();
+
+        let n_word_chars_from = line
+            .iter()
+            .skip(x)
+            .take_while(|ch| ch.is_alphanumeric())
+            .count();
+
+        Some((x - n_word_chars_before, x + n_word_chars_from - 1))
     }
 
     pub fn get_previous_occurrence_of_char(
@@ -140,12 +264,11 @@ impl Document {
     }
 
     pub fn get_next_blank_line_idx(&self, pos_y: usize) -> usize {
-        for (idx, line) in self.lines.iter().enumerate().skip(pos_y + 1) {
-            if line.iter().all(char::is_whitespace) {
-                return idx;
-            }
-        }
-        self.lines.len()
+        self.lines
+            .find_map_from(pos_y + 1, |idx, line| {
+                line.iter().all(char::is_whitespace).then_some(idx)
+            })
+            .unwrap_or(self.lines.len())
     }
 
     pub fn get_previous_blank_line_idx(&self, pos_y: usize) -> usize {
@@ -166,23 +289,51 @@ impl Document {
         from_pos: (usize, usize),
         lit: &str,
     ) -> Option<((usize, usize), (usize, usize))> {
-        for y in from_pos.1..self.n_lines() {
+        self.lines.find_map_from(from_pos.1, |y, line| {
             let line = if y == from_pos.1 {
-                self.lines
-                    .get(y)
-                    .map(|line| line.get_range(from_pos.0 + 1..))
+                line.get_range(from_pos.0 + 1..)
             } else {
-                self.lines.get(y).map(|line| line.get_range(..))
-            }?;
+                line.get_range(..)
+            };
 
             let offset = from_pos.0 * (y == from_pos.1) as usize;
-            if let Some(idx) = line.find(lit) {
-                let x = line.byte_to_char_idx(idx)?;
-                return Some(((x + offset, y), (x + offset + lit.n_chars(), y)));
+            let idx = line.find(lit)?;
+            let x = line.byte_to_char_idx(idx)?;
+            Some(((x + offset, y), (x + offset + lit.n_chars(), y)))
+        })
+    }
+
+    /// Finds the first occurrence of `lines`, at least two of them, starting at or after
+    /// `from_pos`: the first one ends a line, the ones in between are whole lines and the
+    /// last one starts a line
+    pub fn get_next_lines_match_pos(
+        &self,
+        from_pos: (usize, usize),
+        lines: &[String],
+    ) -> Option<((usize, usize), (usize, usize))> {
+        let (first_lit, rest) = lines.split_first()?;
+        let (last_lit, middle_lits) = rest.split_last()?;
+
+        self.lines.find_map_from(from_pos.1, |y, line| {
+            let x = line.as_ref().strip_suffix(first_lit.as_str())?.n_chars();
+            if y == from_pos.1 && x < from_pos.0 {
+                return None;
             }
-        }
 
-        None
+            let are_middle_lines_matching = middle_lits.iter().enumerate().all(|(idx, lit)| {
+                self.lines
+                    .get(y + 1 + idx)
+                    .is_some_and(|line| line.as_ref() == lit)
+            });
+            let last_y = y + lines.len() - 1;
+            let is_last_line_matching = self
+                .lines
+                .get(last_y)
+                .is_some_and(|line| line.as_ref().starts_with(last_lit.as_str()));
+
+            (are_middle_lines_matching && is_last_line_matching)
+                .then(|| ((x, y), (last_lit.n_chars(), last_y)))
+        })
     }
 
     pub fn get_next_regex_match_pos(
@@ -190,22 +341,51 @@ impl Document {
         from_pos: (usize, usize),
         regex: &Regex,
     ) -> Option<((usize, usize), (usize, usize))> {
-        for y in from_pos.1..self.n_lines() {
+        self.lines.find_map_from(from_pos.1, |y, line| {
             let line = if y == from_pos.1 {
-                self.lines
-                    .get(y)
-                    .map(|line| line.get_range(from_pos.0 + 1..))
+                line.get_range(from_pos.0 + 1..)
             } else {
-                self.lines.get(y).map(|line| line.get_range(..))
-            }?;
+                line.get_range(..)
+            };
 
             let offset = (from_pos.0 + 1) * (y == from_pos.1) as usize;
-            if let Some(reg_match) = regex.find(line) {
-                let x_start = line.byte_to_char_idx(reg_match.start())?;
-                let x_end = line
-                    .byte_to_char_idx(reg_match.end())
-                    .unwrap_or(line.n_chars());
-                return Some(((x_start + offset, y), (x_end + offset, y)));
+            let reg_match = regex.find(line)?;
+            let x_start = line.byte_to_char_idx(reg_match.start())?;
+            let x_end = line
+                .byte_to_char_idx(reg_match.end())
+                .unwrap_or(line.n_chars());
+            Some(((x_start + offset, y), (x_end + offset, y)))
+        })
+    }
+
+    /// Finds the first match of `regex` at or after `from_pos` and returns its range
+    /// together with `replacement` interpolated with the match's capture groups
+    /// (e.g. `$1`, `${name}`, `$$` for a literal dollar sign)
+    pub fn get_next_regex_replacement(
+        &self,
+        from_pos: (usize, usize),
+        regex: &Regex,
+        replacement: &str,
+    ) -> Option<RegexReplacement> {
+        for y in from_pos.1..self.n_lines() {
+            let line = self.lines.get(y)?;
+            let start_byte_idx = if y == from_pos.1 {
+                line.char_to_byte_idx(from_pos.0)
+            } else {
+                0
+            };
+
+            if let Some(captures) = regex.captures_at(line.as_ref(), start_byte_idx) {
+                let reg_match = captures.get(0)?;
+                let x_start = line
+                    .byte_to_char_idx(reg_match.start())
+                    .unwrap_or(line.len());
+                let x_end = line.byte_to_char_idx(reg_match.end()).unwrap_or(line.len());
+
+                let mut expanded = String::new();
+                captures.expand(replacement, &mut expanded);
+
+                return Some(((x_start, y), (x_end, y), expanded));
             }
         }
 
@@ -275,7 +455,7 @@ impl Document {
 
         if direction == 1 {
             // Search forward
-            for (line_idx, current_line) in self.lines.iter().enumerate().skip(y) {
+            return self.lines.find_map_from(y, |line_idx, current_line| {
                 let start_pos = if line_idx == y { x + 1 } else { 0 };
                 for (char_idx, ch) in current_line.iter().enumerate().skip(start_pos) {
                     if ch == start_char {
@@ -287,7 +467,9 @@ impl Document {
                         stack -= 1;
                     }
                 }
-            }
+
+                None
+            });
         } else {
             // Search backward
             for line_idx in (0..=y).rev() {
@@ -310,3 +492,97 @@ impl Document {
         None
     }
 }
+
+/// Kinds of characters that vi style words are made of
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+impl CharClass {
+    fn of(ch: char) -> Self {
+        if ch.is_whitespace() {
+            Self::Blank
+        } else if ch.is_alphanumeric() || ch == '_' {
+            Self::Word
+        } else {
+            Self::Punctuation
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::test_utils::get_document;
+
+    #[test]
+    fn finds_text_spanning_lines() {
+        let lines = ["a foo", "bar", "baz b", "foo", "bar", "baz", "x foo", "bar"];
+        let document = get_document(&lines);
+        let lit = ["foo".to_owned(), "bar".to_owned(), "baz".to_owned()];
+
+        assert_eq!(
+            document.get_next_lines_match_pos((0, 0), &lit),
+            Some(((2, 0), (3, 2)))
+        );
+        assert_eq!(
+            document.get_next_lines_match_pos((3, 0), &lit),
+            Some(((0, 3), (3, 5)))
+        );
+        // the document ends before the last line of the text
+        assert_eq!(document.get_next_lines_match_pos((0, 4), &lit), None);
+        assert_eq!(
+            document.get_next_lines_match_pos((0, 4), &lit[..2]),
+            Some(((2, 6), (3, 7)))
+        );
+    }
+
+    #[test]
+    fn moves_by_vi_words() {
+        let lines = ["foo.bar  baz", "", "  qux_1(x)", "end"];
+        let document = get_document(&lines);
+
+        let word_starts = std::iter::successors(Some((0, 0)), |pos| {
+            Some(document.get_next_word_start_pos(*pos)).filter(|next_pos| next_pos != pos)
+        })
+        .collect::<Vec<_>>();
+        assert_eq!(
+            word_starts,
+            [
+                (0, 0),
+                (3, 0),
+                (4, 0),
+                (9, 0),
+                (0, 1),
+                (2, 2),
+                (7, 2),
+                (8, 2),
+                (9, 2),
+                (0, 3),
+                (3, 3)
+            ]
+        );
+
+        let word_ends = std::iter::successors(Some((0, 0)), |pos| {
+            Some(document.get_next_word_last_char_pos(*pos)).filter(|next_pos| next_pos != pos)
+        })
+        .collect::<Vec<_>>();
+        assert_eq!(
+            word_ends,
+            [
+                (0, 0),
+                (2, 0),
+                (3, 0),
+                (6, 0),
+                (11, 0),
+                (6, 2),
+                (7, 2),
+                (8, 2),
+                (9, 2),
+                (2, 3)
+            ]
+        );
+    }
+}
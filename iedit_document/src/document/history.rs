@@ -0,0 +1,428 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{Document, EditOperation, Text, document::undo_tree::UndoTree};
+
+static HISTORY_FILE_HEADER: &str = "iedit-undo-history 1";
+
+impl Document {
+    /// Restores the undo and redo stacks saved by `save_undo_history`,
+    /// as long as the document still has the content they were saved with
+    pub fn load_undo_history(&mut self, history_dir: impl AsRef<Path>) -> io::Result<()> {
+        let Some(history_path) = self.get_undo_history_path(history_dir) else {
+            return Ok(());
+        };
+
+        let contents = match fs::read_to_string(history_path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            res => res?,
+        };
+
+        let mut lines = contents.lines();
+        let content_hash = format!("{:016x}", self.get_content_hash());
+        if lines.next() != Some(HISTORY_FILE_HEADER) || lines.next() != Some(&content_hash) {
+            return Ok(());
+        }
+
+        let (undo_stack, redo_stack) = parse_stacks(&mut lines)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "corrupted undo history"))?;
+
+        self.undo_tree = UndoTree::linear(undo_stack.len(), redo_stack.len());
+        self.undo_stack = undo_stack;
+        self.redo_stack = redo_stack;
+
+        Ok(())
+    }
+
+    /// Writes the undo and redo stacks to `history_dir`, keyed by the document's path
+    /// and content. The edits furthest from the current state, on either stack, are dropped
+    /// to keep the file within `max_size` bytes
+    pub fn save_undo_history(
+        &self,
+        history_dir: impl AsRef<Path>,
+        max_size: usize,
+    ) -> io::Result<()> {
+        let Some(history_path) = self.get_undo_history_path(history_dir.as_ref()) else {
+            return Ok(());
+        };
+
+        if self.undo_stack.is_empty() && self.redo_stack.is_empty() {
+            return match fs::remove_file(history_path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            };
+        }
+
+        let mut contents = format!(
+            "{}\n{:016x}\n",
+            HISTORY_FILE_HEADER,
+            self.get_content_hash()
+        );
+
+        // the edits closest to the current state are the ones worth keeping, so both stacks
+        // are trimmed from their bottom, taking turns so that neither crowds out the other
+        let mut size = contents.len();
+        let mut kept_stacks = [vec![], vec![]];
+        let mut stack_iters = [self.undo_stack.iter().rev(), self.redo_stack.iter().rev()];
+        let mut is_stack_full = [false, false];
+        while !is_stack_full.iter().all(|is_full| *is_full) {
+            for stack_idx in 0..2 {
+                if is_stack_full[stack_idx] {
+                    continue;
+                }
+
+                let Some(op) = stack_iters[stack_idx].next() else {
+                    is_stack_full[stack_idx] = true;
+                    continue;
+                };
+
+                let mut serialized = String::new();
+                write_op(&mut serialized, op);
+                if size + serialized.len() > max_size {
+                    is_stack_full[stack_idx] = true;
+                    continue;
+                }
+
+                size += serialized.len();
+                kept_stacks[stack_idx].push(serialized);
+            }
+        }
+
+        for kept in kept_stacks {
+            contents.push_str(&format!("{}\n", kept.len()));
+            kept.iter().rev().for_each(|op| contents.push_str(op));
+        }
+
+        fs::create_dir_all(history_dir)?;
+        fs::File::create(history_path)?.write_all(contents.as_bytes())
+    }
+
+    fn get_undo_history_path(&self, history_dir: impl AsRef<Path>) -> Option<PathBuf> {
+        // the content hash would read a large file whole
+        if self.canonicalized_file_path.as_os_str().is_empty() || self.is_large_file() {
+            return None;
+        }
+
+        Some(
+            history_dir
+                .as_ref()
+                .join(format!("{:016x}.undo", self.get_path_hash())),
+        )
+    }
+
+    pub(crate) fn get_path_hash(&self) -> u64 {
+        fnv1a_hash(
+            FNV_OFFSET_BASIS,
+            self.canonicalized_file_path.as_os_str().as_encoded_bytes(),
+        )
+    }
+
+    pub(crate) fn get_content_hash(&self) -> u64 {
+        self.lines
+            .iter()
+            .enumerate()
+            .fold(FNV_OFFSET_BASIS, |hash, (line_idx, line)| {
+                let hash = if line_idx > 0 {
+                    fnv1a_hash(hash, self.end_of_line_seq.as_bytes())
+                } else {
+                    hash
+                };
+
+                fnv1a_hash(hash, line.as_ref().as_bytes())
+            })
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// FNV-1a, unlike the std hasher its output is stable across releases
+fn fnv1a_hash(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+// Every operation is written on its own line, followed by the line(s) of its text:
+// r <idx>
+// d <x> <y>
+// i <x> <y>
+// p <x_from> <y_from> <x_to> <y_to>
+// g <n_ops>
+// Text is one of `e`, `c<char>`, `s<string>`, `v<inverse string>` or `l <n_lines>`
+// followed by the lines, with backslashes and line breaks escaped.
+
+pub(crate) fn write_op(out: &mut String, op: &EditOperation) {
+    match op {
+        EditOperation::LineRemoval { idx } => out.push_str(&format!("r {}\n", idx)),
+        EditOperation::Deletion { pos } => out.push_str(&format!("d {} {}\n", pos.0, pos.1)),
+        EditOperation::Insertion { pos, text } => {
+            out.push_str(&format!("i {} {}\n", pos.0, pos.1));
+            write_text(out, text);
+        }
+        EditOperation::Replacement {
+            pos_from,
+            pos_to,
+            text,
+        } => {
+            out.push_str(&format!(
+                "p {} {} {} {}\n",
+                pos_from.0, pos_from.1, pos_to.0, pos_to.1
+            ));
+            write_text(out, text);
+        }
+        EditOperation::Group(ops) => {
+            out.push_str(&format!("g {}\n", ops.len()));
+            ops.iter().for_each(|op| write_op(out, op));
+        }
+    }
+}
+
+fn write_text(out: &mut String, text: &Text) {
+    match text {
+        Text::Empty => out.push('e'),
+        Text::Char(ch) => {
+            out.push('c');
+            out.push_str(&escape(&String::from(*ch)));
+        }
+        Text::String(string) => {
+            out.push('s');
+            out.push_str(&escape(string));
+        }
+        Text::InverseString(string) => {
+            out.push('v');
+            out.push_str(&escape(string));
+        }
+        Text::Lines(lines) => {
+            out.push_str(&format!("l {}", lines.len()));
+            for line in lines {
+                out.push('\n');
+                out.push_str(&escape(line));
+            }
+        }
+    }
+
+    out.push('\n');
+}
+
+fn parse_stacks<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+) -> Option<(Vec<EditOperation>, Vec<EditOperation>)> {
+    let mut parse_stack = || -> Option<Vec<EditOperation>> {
+        let n_ops = lines.next()?.parse().ok()?;
+
+        (0..n_ops).map(|_| parse_op(lines)).collect()
+    };
+
+    let undo_stack = parse_stack()?;
+    let redo_stack = parse_stack()?;
+
+    Some((undo_stack, redo_stack))
+}
+
+pub(crate) fn parse_op<'a>(lines: &mut impl Iterator<Item = &'a str>) -> Option<EditOperation> {
+    let line = lines.next()?;
+    let mut fields = line.split(' ');
+    let kind = fields.next()?;
+    let numbers = fields
+        .map(str::parse)
+        .collect::<Result<Vec<usize>, _>>()
+        .ok()?;
+
+    match (kind, numbers.as_slice()) {
+        ("r", [idx]) => Some(EditOperation::LineRemoval { idx: *idx }),
+        ("d", [x, y]) => Some(EditOperation::Deletion { pos: (*x, *y) }),
+        ("i", [x, y]) => Some(EditOperation::Insertion {
+            pos: (*x, *y),
+            text: parse_text(lines)?,
+        }),
+        ("p", [x_from, y_from, x_to, y_to]) => Some(EditOperation::Replacement {
+            pos_from: (*x_from, *y_from),
+            pos_to: (*x_to, *y_to),
+            text: parse_text(lines)?,
+        }),
+        ("g", [n_ops]) => (0..*n_ops)
+            .map(|_| parse_op(lines))
+            .collect::<Option<Vec<_>>>()
+            .map(EditOperation::Group),
+        _ => None,
+    }
+}
+
+fn parse_text<'a>(lines: &mut impl Iterator<Item = &'a str>) -> Option<Text> {
+    let line = lines.next()?;
+    let mut chars = line.chars();
+
+    match chars.next()? {
+        'e' => Some(Text::Empty),
+        'c' => unescape(chars.as_str()).chars().next().map(Text::Char),
+        's' => Some(Text::String(unescape(chars.as_str()))),
+        'v' => Some(Text::InverseString(unescape(chars.as_str()))),
+        'l' => {
+            let n_lines = chars.as_str().trim().parse().ok()?;
+
+            (0..n_lines)
+                .map(|_| lines.next().map(unescape))
+                .collect::<Option<Vec<_>>>()
+                .map(Text::Lines)
+        }
+        _ => None,
+    }
+}
+
+fn escape(string: &str) -> String {
+    string
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(string: &str) -> String {
+    let mut unescaped = String::with_capacity(string.len());
+    let mut chars = string.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            unescaped.push(ch);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(escaped) => unescaped.push(escaped),
+            None => {}
+        }
+    }
+
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{parse_op, write_op};
+    use crate::document::test_utils::{get_document, get_text, insert};
+    use crate::{EditOperation, Text};
+
+    fn get_history_dir(name: &str) -> PathBuf {
+        let history_dir =
+            std::env::temp_dir().join(format!("iedit-history-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&history_dir);
+
+        history_dir
+    }
+
+    #[test]
+    fn writes_and_parses_ops() {
+        let ops = [
+            EditOperation::LineRemoval { idx: 3 },
+            EditOperation::Deletion { pos: (1, 2) },
+            EditOperation::Insertion {
+                pos: (0, 0),
+                text: Text::Char('\n'),
+            },
+            EditOperation::Replacement {
+                pos_from: (4, 1),
+                pos_to: (2, 5),
+                text: Text::String("back\\slash \r\n".to_owned()),
+            },
+            EditOperation::Group(vec![
+                EditOperation::Insertion {
+                    pos: (7, 8),
+                    text: Text::Lines(vec!["a".to_owned(), String::new(), "\\n".to_owned()]),
+                },
+                EditOperation::Replacement {
+                    pos_from: (0, 0),
+                    pos_to: (1, 0),
+                    text: Text::InverseString("ü".to_owned()),
+                },
+                EditOperation::Insertion {
+                    pos: (0, 1),
+                    text: Text::Empty,
+                },
+            ]),
+        ];
+
+        let mut serialized = String::new();
+        ops.iter().for_each(|op| write_op(&mut serialized, op));
+
+        let mut lines = serialized.lines();
+        for op in &ops {
+            assert_eq!(parse_op(&mut lines).as_ref(), Some(op));
+        }
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn restores_saved_stacks() {
+        let history_dir = get_history_dir("restore");
+        let mut document = get_document(&["foo", "bar"]);
+        insert(&mut document, (3, 0), "1");
+        insert(&mut document, (0, 1), "2\n");
+        insert(&mut document, (3, 2), "3");
+        document.undo_last_edit();
+        document
+            .save_undo_history(&history_dir, usize::MAX)
+            .unwrap();
+
+        let mut reopened = get_document(&["foo1", "2", "bar"]);
+        reopened.load_undo_history(&history_dir).unwrap();
+        assert_eq!(reopened.undo_stack, document.undo_stack);
+        assert_eq!(reopened.redo_stack, document.redo_stack);
+
+        reopened.redo_last_edit();
+        assert_eq!(get_text(&reopened), ["foo1", "2", "bar3"]);
+        reopened.undo_last_edit();
+        reopened.undo_last_edit();
+        reopened.undo_last_edit();
+        assert_eq!(get_text(&reopened), ["foo", "bar"]);
+
+        let _ = fs::remove_dir_all(history_dir);
+    }
+
+    #[test]
+    fn ignores_history_of_other_content() {
+        let history_dir = get_history_dir("mismatch");
+        let mut document = get_document(&["foo"]);
+        insert(&mut document, (3, 0), "!");
+        document
+            .save_undo_history(&history_dir, usize::MAX)
+            .unwrap();
+
+        // the file changed since, its edits would no longer apply
+        let mut reopened = get_document(&["foo?"]);
+        reopened.load_undo_history(&history_dir).unwrap();
+        assert!(reopened.undo_stack.is_empty());
+        assert!(reopened.redo_stack.is_empty());
+    }
+
+    #[test]
+    fn trims_both_stacks() {
+        let history_dir = get_history_dir("trim");
+        let mut document = get_document(&[""]);
+        for idx in 0..20 {
+            insert(&mut document, (idx * 3, 0), "abc");
+        }
+        for _ in 0..10 {
+            document.undo_last_edit();
+        }
+
+        document.save_undo_history(&history_dir, 200).unwrap();
+        let mut reopened = get_document(&[&"abc".repeat(10)]);
+        reopened.load_undo_history(&history_dir).unwrap();
+
+        // the edits right before and after the current state are kept
+        let (n_undo, n_redo) = (reopened.undo_stack.len(), reopened.redo_stack.len());
+        assert!(n_undo > 0 && n_undo < 10);
+        assert!(n_redo > 0 && n_redo < 10);
+        assert!(n_undo.abs_diff(n_redo) <= 1);
+        assert_eq!(reopened.undo_stack[..], document.undo_stack[10 - n_undo..]);
+        assert_eq!(reopened.redo_stack[..], document.redo_stack[10 - n_redo..]);
+
+        let _ = fs::remove_dir_all(history_dir);
+    }
+}
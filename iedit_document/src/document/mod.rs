@@ -1,7 +1,16 @@
 mod builtin_languages;
+mod diff;
 mod edit;
+mod events;
 mod find;
+mod history;
+mod swap;
 mod syntax;
+#[cfg(test)]
+pub(crate) mod test_utils;
+mod text_objects;
+mod transaction;
+mod undo_tree;
 
 use std::{
     ffi::OsStr,
@@ -11,14 +20,26 @@ use std::{
     time::SystemTime,
 };
 
-pub use crate::line::{CharacterIndexable, DocumentLine};
+pub use crate::line::{CharacterIndexable, DocumentLine, LineStore};
 pub use edit::{EditOperation, InverseStack, Text};
-pub use syntax::{DocumentSyntax, SyntaxBlock, SyntaxRule};
+pub use events::{EditEvent, ListenerId};
+pub use find::RegexReplacement;
+pub use syntax::{CommentTokens, DocumentSyntax, SyntaxBlock, SyntaxRule};
+pub use text_objects::{TextObject, TextRange};
+pub use transaction::Selection;
+pub use undo_tree::{TimeTravel, UndoState};
 
-use crate::io::read_file;
+use crate::{
+    encoding::Encoding,
+    io::{Pager, ReadFile, index_file, read_file},
+};
+use events::EditListeners;
+use swap::SwapJournal;
+use transaction::Transaction;
+use undo_tree::UndoTree;
 
 pub struct Document {
-    pub lines: Vec<DocumentLine>,
+    pub lines: LineStore,
     pub file: Option<File>,
     pub canonicalized_file_path: PathBuf,
     pub line_offsets: Vec<u64>,
@@ -26,13 +47,37 @@ pub struct Document {
     pub syntax_blocks: Vec<SyntaxBlock>,
     undo_stack: Vec<EditOperation>,
     redo_stack: Vec<EditOperation>,
+    undo_tree: UndoTree,
+    swap_journal: SwapJournal,
+    edit_listeners: EditListeners,
+    version: u64,
+    transaction: Option<Transaction>,
+    /// All lines starting from this index need to be re-rendered,
+    /// e.g. because lines have been inserted or removed above them.
+    needs_render_from: Option<usize>,
 
     pub end_of_line_seq: String,
+    /// Whether the file mixes LF and CRLF line endings, they are all saved as `end_of_line_seq`
+    pub has_mixed_line_endings: bool,
+    pub encoding: Encoding,
+    /// Whether the file starts with the byte order mark of its encoding
+    pub has_bom: bool,
     pub last_save_time: SystemTime,
     pub auto_inserted_whitespace_line: Option<usize>,
     pub is_readonly: bool,
 }
 
+/// How `Document::from_file_with` opens a file
+#[derive(Default, Clone)]
+pub struct DocumentOptions {
+    /// Directory of the `<extension>.syntax` files overriding the builtin syntaxes
+    pub syntaxes_path: Option<PathBuf>,
+    /// Directory the undo history of the file is loaded from, if it was persisted
+    pub undo_history_dir: Option<PathBuf>,
+    /// Directory unsaved edits are journaled to, to be recovered after a crash
+    pub swap_dir: Option<PathBuf>,
+}
+
 impl Default for Document {
     fn default() -> Self {
         Self {
@@ -44,7 +89,16 @@ impl Default for Document {
             line_offsets: Default::default(),
             undo_stack: Default::default(),
             redo_stack: Default::default(),
+            undo_tree: Default::default(),
+            swap_journal: Default::default(),
+            edit_listeners: Default::default(),
+            version: 0,
+            transaction: None,
+            needs_render_from: None,
             end_of_line_seq: "\n".to_owned(),
+            has_mixed_line_endings: false,
+            encoding: Encoding::default(),
+            has_bom: false,
             auto_inserted_whitespace_line: None,
             last_save_time: SystemTime::now(),
             is_readonly: false,
@@ -54,31 +108,67 @@ impl Default for Document {
 
 impl Document {
     pub fn line_needs_render(&self, line_idx: usize) -> bool {
-        self.lines
-            .get(line_idx)
-            .is_none_or(|line| line.needs_render)
+        self.needs_render_from.is_some_and(|from| line_idx >= from)
+            || self
+                .lines
+                .get(line_idx)
+                .is_none_or(|line| line.needs_render)
     }
 
     pub fn reset_lines_need_render(&mut self, line_range: ops::Range<usize>) {
-        for line_idx in line_range {
-            if let Some(line) = self.lines.get_mut(line_idx) {
-                line.needs_render = false;
+        if let Some(from) = self.needs_render_from {
+            // lines before the range are still dirty, flag them one by one.
+            // NOTE: paged out lines need render anyway once read
+            for (_, line) in self.lines.loaded_lines_mut(from..line_range.start) {
+                line.needs_render = true;
             }
+
+            self.needs_render_from = Some(line_range.end.max(from));
+        }
+
+        for (_, line) in self.lines.loaded_lines_mut(line_range) {
+            line.needs_render = false;
         }
     }
 
+    /// Flags every line from `line_idx` to the end of the document as needing render
+    #[inline]
+    pub fn mark_lines_need_render_from(&mut self, line_idx: usize) {
+        self.needs_render_from = Some(
+            self.needs_render_from
+                .map_or(line_idx, |from| from.min(line_idx)),
+        );
+    }
+
     pub fn from_strings(strings: Vec<String>, name: impl Into<PathBuf>, is_readonly: bool) -> Self {
         Self {
-            lines: strings.into_iter().map(DocumentLine::new).collect(),
+            // read-only documents can't be edited, so there's nothing to save
+            lines: strings
+                .into_iter()
+                .map(|string| {
+                    let mut line = DocumentLine::new(string);
+                    line.has_been_modified = !is_readonly;
+                    line
+                })
+                .collect(),
             file: None,
             canonicalized_file_path: name.into(),
             line_offsets: vec![],
             undo_stack: vec![],
             redo_stack: vec![],
+            undo_tree: Default::default(),
+            swap_journal: Default::default(),
+            edit_listeners: Default::default(),
+            version: 0,
+            transaction: None,
+            needs_render_from: None,
             syntax: None,
             syntax_blocks: Default::default(),
             auto_inserted_whitespace_line: None,
             end_of_line_seq: "\n".to_owned(),
+            has_mixed_line_endings: false,
+            encoding: Encoding::default(),
+            has_bom: false,
             last_save_time: SystemTime::now(),
             is_readonly,
         }
@@ -88,8 +178,57 @@ impl Document {
         file_path: impl AsRef<Path>,
         syntaxes_path: Option<impl AsRef<Path>>,
     ) -> std::io::Result<Self> {
-        let (file, canonicalized_file_path, lines, line_offsets, end_of_line_seq) =
-            read_file(file_path)?;
+        let mut doc = Self::from_read_file(read_file(file_path, None)?, syntaxes_path);
+        doc.recompute_syntax_blocks();
+
+        Ok(doc)
+    }
+
+    /// Opens the file at `file_path` like `from_file`, along with what `options` enables
+    pub fn from_file_with(
+        file_path: impl AsRef<Path>,
+        options: &DocumentOptions,
+    ) -> std::io::Result<Self> {
+        let mut doc = Self::from_file(file_path, options.syntaxes_path.as_ref())?;
+
+        if let Some(undo_history_dir) = &options.undo_history_dir {
+            // a missing or stale history just means starting from a clean slate
+            let _ = doc.load_undo_history(undo_history_dir);
+        }
+
+        if let Some(swap_dir) = &options.swap_dir {
+            doc.open_swap_journal(swap_dir);
+        }
+
+        Ok(doc)
+    }
+
+    /// Opens a file too large to be read whole, its lines are only read when accessed.
+    /// Multiline syntax blocks, the undo history and the swap file are left out, as they
+    /// all need to go through the whole file. `on_progress` is called with the number of
+    /// bytes indexed so far and the size of the file
+    pub fn from_large_file(
+        file_path: impl AsRef<Path>,
+        syntaxes_path: Option<impl AsRef<Path>>,
+        on_progress: impl FnMut(u64, u64),
+    ) -> std::io::Result<Self> {
+        Ok(Self::from_read_file(
+            index_file(file_path, None, on_progress)?,
+            syntaxes_path,
+        ))
+    }
+
+    fn from_read_file(read_file: ReadFile, syntaxes_path: Option<impl AsRef<Path>>) -> Self {
+        let ReadFile {
+            file,
+            path: canonicalized_file_path,
+            lines,
+            line_offsets,
+            end_of_line_seq,
+            has_mixed_line_endings,
+            encoding,
+            has_bom,
+        } = read_file;
         let is_readonly = if let Some(file) = &file
             && let Ok(metadata) = file.metadata()
         {
@@ -108,28 +247,116 @@ impl Document {
                 None
             };
 
-        let mut doc = Self {
+        Self {
             lines,
             file,
             canonicalized_file_path,
             end_of_line_seq,
+            has_mixed_line_endings,
+            encoding,
+            has_bom,
             syntax,
             syntax_blocks: Default::default(),
             line_offsets,
             undo_stack: vec![],
             redo_stack: vec![],
+            undo_tree: Default::default(),
+            swap_journal: Default::default(),
+            edit_listeners: Default::default(),
+            version: 0,
+            transaction: None,
+            needs_render_from: None,
             last_save_time: SystemTime::now(),
             auto_inserted_whitespace_line: None,
             is_readonly,
+        }
+    }
+
+    /// Replaces the content with the file on disk, discarding unsaved changes
+    /// along with the undo history. The file is decoded as `encoding`, or as the
+    /// detected one when `None`
+    pub fn reload(&mut self, encoding: Option<Encoding>) -> std::io::Result<()> {
+        let read_file = if self.is_large_file() {
+            index_file(&self.canonicalized_file_path, encoding, |_, _| {})?
+        } else {
+            read_file(&self.canonicalized_file_path, encoding)?
         };
 
-        doc.recompute_syntax_blocks();
+        self.file = read_file.file;
+        self.lines = read_file.lines;
+        self.line_offsets = read_file.line_offsets;
+        self.end_of_line_seq = read_file.end_of_line_seq;
+        self.has_mixed_line_endings = read_file.has_mixed_line_endings;
+        self.encoding = read_file.encoding;
+        self.has_bom = read_file.has_bom;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.undo_tree = Default::default();
+        self.transaction = None;
+        self.auto_inserted_whitespace_line = None;
+        self.last_save_time = SystemTime::now();
+        self.version += 1;
 
-        Ok(doc)
+        // the unsaved edits are gone, and so is the need to recover them
+        self.clear_swap_file()?;
+        self.recompute_syntax_blocks();
+        self.mark_lines_need_render_from(0);
+
+        Ok(())
+    }
+
+    /// Changes the encoding the document is saved with, the whole file gets rewritten on the next save
+    pub fn set_encoding(&mut self, encoding: Encoding, has_bom: bool) {
+        self.encoding = encoding;
+        self.has_bom = has_bom && !encoding.bom().is_empty();
+        self.mark_file_for_rewrite();
+    }
+
+    /// Changes the line ending the document is saved with, the whole file gets rewritten
+    /// on the next save
+    pub fn set_end_of_line_seq(&mut self, end_of_line_seq: &str) {
+        self.end_of_line_seq = end_of_line_seq.to_owned();
+        self.has_mixed_line_endings = false;
+        self.mark_file_for_rewrite();
+    }
+
+    fn mark_file_for_rewrite(&mut self) {
+        // the lines on disk no longer match their encoded content, rewrite from the start
+        self.line_offsets = vec![0];
+        if let Some(first_line) = self.lines.get_mut(0) {
+            first_line.has_been_modified = true;
+        }
+    }
+
+    /// Whether the lines of the document are read from its file when accessed
+    #[inline]
+    pub fn is_large_file(&self) -> bool {
+        self.lines.is_paged()
+    }
+
+    /// Frees the unmodified lines of a large file outside of `keep`, e.g. the viewport
+    #[inline]
+    pub fn release_unused_lines(&mut self, keep: ops::Range<usize>) {
+        self.lines.release_pages(keep);
+    }
+
+    /// Reads the lines of a large file from the file just saved, whose content matches them
+    pub fn update_pages(&mut self) -> std::io::Result<()> {
+        let Some(file) = self.file.as_ref().filter(|_| self.is_large_file()) else {
+            return Ok(());
+        };
+
+        let file_len = file.metadata()?.len();
+        let pager = Pager::new(file.try_clone()?, self.encoding, &self.end_of_line_seq);
+        self.lines.set_pages(pager, &self.line_offsets, file_len);
+
+        Ok(())
     }
 
     pub fn get_name(&self) -> Option<&str> {
-        None
+        self.canonicalized_file_path
+            .file_name()
+            .and_then(OsStr::to_str)
     }
 
     #[inline]
@@ -139,7 +366,10 @@ impl Document {
 
     #[inline]
     pub fn has_been_modified(&self) -> bool {
-        self.lines.iter().any(|line| line.has_been_modified)
+        // paged out lines are never modified
+        self.lines
+            .loaded_lines(0..self.lines.len())
+            .any(|(_, line)| line.has_been_modified)
     }
 
     #[inline]
@@ -0,0 +1,291 @@
+use std::{
+    ffi::OsStr,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Document, DocumentSyntax, EditOperation,
+    document::{
+        edit::InverseStack,
+        history::{parse_op, write_op},
+        undo_tree::UndoTree,
+    },
+};
+
+static SWAP_FILE_HEADER: &str = "iedit-swap 1";
+
+/// Edits made since the document was last saved, appended to a swap file
+/// so that they can be recovered if iedit doesn't exit cleanly.
+/// Each edit is stored with the `auto_inserted_whitespace_line` it was applied with,
+/// replaying them from the saved content then gives back the exact same document
+#[derive(Default)]
+pub struct SwapJournal {
+    swap_file_path: Option<PathBuf>,
+    edits: Vec<(Option<usize>, EditOperation)>,
+    n_written_edits: usize,
+    /// Hash of the content the edits are applied to
+    base_content_hash: u64,
+    has_stale_swap_file: bool,
+    /// Set when another running instance is journaling the edits it makes to the file
+    other_owner_pid: Option<u32>,
+}
+
+impl SwapJournal {
+    #[inline]
+    pub fn record(&mut self, auto_inserted_whitespace_line: Option<usize>, op: &EditOperation) {
+        if self.swap_file_path.is_some() {
+            self.edits.push((auto_inserted_whitespace_line, op.clone()));
+        }
+    }
+}
+
+impl Document {
+    /// Enables journaling edits to a swap file in `swap_dir`,
+    /// checking for one left behind by a session that didn't exit cleanly
+    pub(crate) fn open_swap_journal(&mut self, swap_dir: impl AsRef<Path>) {
+        if self.canonicalized_file_path.as_os_str().is_empty() {
+            return;
+        }
+
+        // every running instance editing the file has its own swap file, only the first
+        // one to open it doesn't have its pid in the name
+        let swap_dir = swap_dir.as_ref();
+        let swap_file_prefix = format!("{:016x}", self.get_path_hash());
+        let first_swap_file_path = swap_dir.join(format!("{}.swp", swap_file_prefix));
+        let mut other_swap_file_paths = fs::read_dir(swap_dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let file_name = entry.ok()?.file_name();
+                let pid = file_name
+                    .to_str()?
+                    .strip_prefix(&swap_file_prefix)?
+                    .strip_prefix('-')?
+                    .strip_suffix(".swp")?;
+                pid.parse::<u32>().ok()?;
+
+                Some(swap_dir.join(file_name))
+            })
+            .collect::<Vec<_>>();
+        other_swap_file_paths.sort();
+
+        self.swap_journal.base_content_hash = self.get_content_hash();
+        for swap_file_path in [first_swap_file_path.clone()]
+            .into_iter()
+            .chain(other_swap_file_paths)
+        {
+            let Ok(contents) = fs::read_to_string(&swap_file_path) else {
+                continue;
+            };
+
+            let mut lines = contents.lines();
+            let is_header_valid = lines.next() == Some(SWAP_FILE_HEADER);
+            let base_content_hash = lines.next();
+            let owner_pid = lines.next();
+
+            // the file is being edited by another instance, don't clobber its swap file
+            if let Some(pid) = owner_pid.and_then(get_alive_process_id) {
+                self.swap_journal.other_owner_pid.get_or_insert(pid);
+                continue;
+            }
+
+            self.swap_journal.has_stale_swap_file = is_header_valid
+                && base_content_hash
+                    == Some(&format!("{:016x}", self.swap_journal.base_content_hash))
+                && lines.next().is_some();
+            self.swap_journal.swap_file_path = Some(swap_file_path);
+
+            return;
+        }
+
+        let swap_file_path = if first_swap_file_path.exists() {
+            swap_dir.join(format!("{}-{}.swp", swap_file_prefix, std::process::id()))
+        } else {
+            first_swap_file_path
+        };
+        self.swap_journal.swap_file_path = Some(swap_file_path);
+    }
+
+    /// Pid of another running instance that has the file open with its own swap file
+    #[inline]
+    pub fn get_swap_file_owner_pid(&self) -> Option<u32> {
+        self.swap_journal.other_owner_pid
+    }
+
+    #[inline]
+    pub fn has_stale_swap_file(&self) -> bool {
+        self.swap_journal.has_stale_swap_file
+    }
+
+    #[inline]
+    pub fn has_unwritten_swap_edits(&self) -> bool {
+        self.swap_journal.n_written_edits < self.swap_journal.edits.len()
+    }
+
+    /// Appends the edits made since the last call to the swap file
+    pub fn write_swap_file(&mut self) -> io::Result<()> {
+        let journal = &mut self.swap_journal;
+        let Some(swap_file_path) = &journal.swap_file_path else {
+            return Ok(());
+        };
+
+        if journal.has_stale_swap_file || journal.n_written_edits == journal.edits.len() {
+            return Ok(());
+        }
+
+        let mut contents = String::new();
+        if journal.n_written_edits == 0 {
+            contents.push_str(&format!(
+                "{}\n{:016x}\n{}\n",
+                SWAP_FILE_HEADER,
+                journal.base_content_hash,
+                std::process::id()
+            ));
+        }
+
+        for (auto_inserted_whitespace_line, op) in &journal.edits[journal.n_written_edits..] {
+            match auto_inserted_whitespace_line {
+                Some(line_idx) => contents.push_str(&format!("e {}\n", line_idx)),
+                None => contents.push_str("e\n"),
+            }
+
+            write_op(&mut contents, op);
+        }
+
+        if let Some(swap_dir) = swap_file_path.parent() {
+            fs::create_dir_all(swap_dir)?;
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(journal.n_written_edits > 0)
+            .write(true)
+            .truncate(journal.n_written_edits == 0)
+            .open(swap_file_path)?
+            .write_all(contents.as_bytes())?;
+
+        journal.n_written_edits = journal.edits.len();
+
+        Ok(())
+    }
+
+    /// Forgets the journaled edits, e.g. once they have been saved, and deletes the swap file
+    pub fn clear_swap_file(&mut self) -> io::Result<()> {
+        let Some(swap_file_path) = &self.swap_journal.swap_file_path else {
+            return Ok(());
+        };
+
+        let was_written = self.swap_journal.n_written_edits > 0;
+        self.swap_journal.edits.clear();
+        self.swap_journal.n_written_edits = 0;
+        self.swap_journal.has_stale_swap_file = false;
+        self.swap_journal.base_content_hash = self.get_content_hash();
+
+        match fs::remove_file(swap_file_path) {
+            Err(err) if was_written && err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Replays the edits of the stale swap file, returns how many were recovered
+    pub fn recover_from_swap_file(&mut self) -> io::Result<usize> {
+        let n_recovered = self.replay_swap_file()?;
+
+        // the recovered edits are still unsaved and already in the swap file
+        self.swap_journal.n_written_edits = self.swap_journal.edits.len();
+        self.swap_journal.has_stale_swap_file = false;
+
+        Ok(n_recovered)
+    }
+
+    /// A read-only copy of the document with the edits of the stale swap file replayed,
+    /// to compare it with the saved version before recovering
+    pub fn get_swap_file_recovery(&self) -> io::Result<Document> {
+        let mut recovered = Document::from_strings(
+            self.lines
+                .iter()
+                .map(|line| line.as_ref().to_owned())
+                .collect(),
+            &self.canonicalized_file_path,
+            true,
+        );
+        recovered.end_of_line_seq = self.end_of_line_seq.clone();
+        recovered.encoding = self.encoding;
+        recovered.has_bom = self.has_bom;
+        recovered.swap_journal.swap_file_path = self.swap_journal.swap_file_path.clone();
+        recovered.swap_journal.base_content_hash = self.swap_journal.base_content_hash;
+        recovered.replay_swap_file()?;
+
+        let mut recovered_path = self.canonicalized_file_path.clone().into_os_string();
+        recovered_path.push(".recovered");
+        recovered.canonicalized_file_path = recovered_path.into();
+        recovered.swap_journal = SwapJournal::default();
+        recovered.syntax = self
+            .canonicalized_file_path
+            .extension()
+            .and_then(OsStr::to_str)
+            .and_then(DocumentSyntax::infer_from_extension);
+        recovered.recompute_syntax_blocks();
+        for line in recovered.lines.iter_mut() {
+            line.has_been_modified = false;
+        }
+
+        Ok(recovered)
+    }
+
+    fn replay_swap_file(&mut self) -> io::Result<usize> {
+        let invalid_data = || io::Error::new(io::ErrorKind::InvalidData, "corrupted swap file");
+        let swap_file_path = self
+            .swap_journal
+            .swap_file_path
+            .as_ref()
+            .ok_or_else(invalid_data)?;
+
+        if self.get_content_hash() != self.swap_journal.base_content_hash {
+            return Err(io::Error::other(
+                "the document has been edited since it was opened",
+            ));
+        }
+
+        let contents = fs::read_to_string(swap_file_path)?;
+        // the last edit may have been cut short by the crash, only keep complete lines
+        let contents = &contents[..contents.rfind('\n').map_or(0, |idx| idx + 1)];
+        let mut lines = contents.lines().skip(3);
+
+        self.swap_journal.edits.clear();
+        let mut n_replayed = 0;
+        while let Some(line) = lines.next() {
+            let auto_inserted_whitespace_line = match line.strip_prefix("e") {
+                Some("") => None,
+                Some(line_idx) => Some(line_idx.trim().parse().map_err(|_| invalid_data())?),
+                None => return Err(invalid_data()),
+            };
+
+            let Some(op) = parse_op(&mut lines) else {
+                break;
+            };
+
+            self.swap_journal.record(auto_inserted_whitespace_line, &op);
+            self.auto_inserted_whitespace_line = auto_inserted_whitespace_line;
+            self.apply_edit_inner(op, InverseStack::Undo);
+            n_replayed += 1;
+        }
+
+        self.redo_stack.clear();
+        self.undo_tree = UndoTree::linear(self.undo_stack.len(), 0);
+        self.recompute_syntax_blocks();
+        self.mark_lines_need_render_from(0);
+
+        Ok(n_replayed)
+    }
+}
+
+/// Pid of the process that wrote a swap file if it is still running,
+/// only known on systems exposing `/proc`
+fn get_alive_process_id(pid: &str) -> Option<u32> {
+    pid.parse::<u32>().ok().filter(|pid| {
+        *pid != std::process::id() && Path::new("/proc").join(pid.to_string()).exists()
+    })
+}
@@ -7,6 +7,16 @@ use crate::Document;
 pub struct DocumentSyntax {
     pub name: &'static str,
     pub rules: Vec<SyntaxRule>,
+    pub comment_tokens: CommentTokens,
+}
+
+/// Delimiters of the comments of a language
+#[derive(Clone, Copy, Default, Debug)]
+pub struct CommentTokens {
+    /// Starts a comment running to the end of the line, e.g. `//`
+    pub line: Option<&'static str>,
+    /// Start and end of a comment spanning any number of lines, e.g. `/*` and `*/`
+    pub block: Option<(&'static str, &'static str)>,
 }
 
 #[derive(Debug)]
@@ -67,6 +77,7 @@ impl DocumentSyntax {
             "sh" | "bash" => Some(Self::builtin_bash()),
             "c" | "h" => Some(Self::builtin_c()),
             "cpp" | "cc" | "cxx" | "hpp" | "hxx" => Some(Self::builtin_cpp()),
+            "diff" | "patch" => Some(Self::builtin_diff()),
             _ => None,
         }
     }
@@ -79,9 +90,23 @@ impl DocumentSyntax {
         let name = Box::leak(name.to_string().into_boxed_str());
 
         let mut rules = Vec::new();
+        let mut comment_tokens = CommentTokens::default();
 
         for line in lines {
             let line = line.trim();
+
+            // `comment <token>` for line comments, `comment <start> <end>` for block comments
+            if let Some(tokens) = line.strip_prefix("comment ") {
+                let leak = |token: &str| &*Box::leak(token.to_string().into_boxed_str());
+                match tokens.split_whitespace().collect::<Vec<_>>()[..] {
+                    [token] => comment_tokens.line = Some(leak(token)),
+                    [start, end] => comment_tokens.block = Some((leak(start), leak(end))),
+                    _ => {}
+                }
+
+                continue;
+            }
+
             if line.is_empty() || !line.starts_with('#') {
                 continue;
             }
@@ -118,7 +143,7 @@ impl DocumentSyntax {
 
                 let start_pattern = Regex::new(start_pattern_str);
                 let end_pattern = Regex::new(end_pattern_str);
-                if !start_pattern.is_ok() || !end_pattern.is_ok() {
+                if start_pattern.is_err() || end_pattern.is_err() {
                     continue;
                 }
 
@@ -134,7 +159,11 @@ impl DocumentSyntax {
             return None;
         }
 
-        Some(DocumentSyntax { name, rules })
+        Some(DocumentSyntax {
+            name,
+            rules,
+            comment_tokens,
+        })
     }
 }
 
@@ -159,13 +188,12 @@ pub fn parse_color_hex(color_hex: &str, is_bg: bool) -> Option<String> {
 
 impl Document {
     pub fn recompute_syntax_blocks(&mut self) {
-        if self.syntax.is_none() {
+        // scanning a large file for blocks would read it whole, inline rules still apply
+        if self.syntax.is_none() || self.is_large_file() {
             return;
         }
 
-        self.lines
-            .iter_mut()
-            .for_each(|line| line.needs_render = true);
+        self.mark_lines_need_render_from(0);
 
         let syntax = self.syntax.as_ref().unwrap();
         let mut cur_x = 0;
@@ -210,7 +238,7 @@ impl Document {
                             .unwrap_or(line.len());
 
                         current_block = Some(SyntaxBlock {
-                            start_pos: ((block_start_x, cur_y)),
+                            start_pos: (block_start_x, cur_y),
                             end_pos: None,
                             end_symbol_len: 0,
                             rule_idx,
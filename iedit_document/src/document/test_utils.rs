@@ -0,0 +1,26 @@
+//! Helpers shared by the tests of the document modules
+
+use crate::{Document, EditOperation, InverseStack, Text};
+
+pub(crate) fn get_document(lines: &[&str]) -> Document {
+    Document::from_strings(
+        lines.iter().map(|line| line.to_string()).collect(),
+        "test",
+        false,
+    )
+}
+
+pub(crate) fn get_text(document: &Document) -> Vec<String> {
+    (0..document.n_lines())
+        .filter_map(|line_idx| document.lines.get(line_idx))
+        .map(|line| line.as_ref().to_owned())
+        .collect()
+}
+
+pub(crate) fn insert(document: &mut Document, pos: (usize, usize), text: &str) {
+    let op = EditOperation::Insertion {
+        pos,
+        text: Text::from(text.to_owned()),
+    };
+    document.apply_edit(op, InverseStack::Undo);
+}
@@ -0,0 +1,356 @@
+use crate::Document;
+
+/// A piece of text selected as a whole
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextObject {
+    Word,
+    /// The text between an opening and a closing bracket, e.g. `(` and `)`
+    Brackets(char, char),
+    /// The text between two occurrences of a quote on the same line
+    Quotes(char),
+    /// Lines up to the next and previous blank lines
+    Paragraph,
+}
+
+/// Start and end (exclusive) of a range of text
+pub type TextRange = ((usize, usize), (usize, usize));
+
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+const QUOTES: [char; 3] = ['"', '\'', '`'];
+
+/// Lines looked through on each side of a range for the brackets around it
+const MAX_BRACKET_SEARCH_LINES: usize = 10_000;
+
+impl Document {
+    /// Range of the `object` around `range`, along with its delimiters when `is_around`.
+    /// For words, `is_around` includes the whitespace following them (or preceding them
+    /// if there's none), for paragraphs the blank lines following them (or preceding them).
+    /// NOTE: quotes are looked for on the line `range` starts on, if none are around it
+    /// the first quoted string following it is returned
+    pub fn get_text_object_range(
+        &self,
+        object: TextObject,
+        range: TextRange,
+        is_around: bool,
+    ) -> Option<TextRange> {
+        match object {
+            TextObject::Word => self.get_word_range(range.0, is_around),
+            TextObject::Brackets(open, close) => {
+                let (open_pos, close_pos) = self.get_enclosing_brackets((open, close), range)?;
+                if is_around {
+                    Some((open_pos, (close_pos.0 + 1, close_pos.1)))
+                } else {
+                    Some(((open_pos.0 + 1, open_pos.1), close_pos))
+                }
+            }
+            TextObject::Quotes(quote) => {
+                let (open_pos, close_pos) = self.get_enclosing_quotes(quote, range)?;
+                if is_around {
+                    Some((open_pos, (close_pos.0 + 1, close_pos.1)))
+                } else {
+                    Some(((open_pos.0 + 1, open_pos.1), close_pos))
+                }
+            }
+            TextObject::Paragraph => self.get_paragraph_range(range, is_around),
+        }
+    }
+
+    /// Smallest range strictly containing `range` among the word, quoted string, contents of
+    /// brackets, brackets, lines and paragraph around it, or the whole document
+    pub fn get_expanded_range(&self, range: TextRange) -> Option<TextRange> {
+        let last_line_idx = self.n_lines().checked_sub(1)?;
+        let line_len = |line_idx| self.lines.get(line_idx).map_or(0, |line| line.len());
+
+        let delimited_ranges = QUOTES
+            .into_iter()
+            .filter_map(|quote| self.get_enclosing_quotes(quote, range))
+            .chain(
+                BRACKETS
+                    .into_iter()
+                    .filter_map(|brackets| self.get_enclosing_brackets(brackets, range)),
+            )
+            .collect::<Vec<_>>();
+
+        let mut candidates = vec![];
+        candidates.extend(self.get_word_range(range.0, false));
+        for (open_pos, close_pos) in delimited_ranges {
+            candidates.push(((open_pos.0 + 1, open_pos.1), close_pos));
+            candidates.push((open_pos, (close_pos.0 + 1, close_pos.1)));
+        }
+
+        candidates.push(((0, range.0.1), (line_len(range.1.1), range.1.1)));
+        candidates.extend(self.get_paragraph_range(range, false));
+        candidates.push(((0, 0), (line_len(last_line_idx), last_line_idx)));
+
+        // candidates come in order of preference, unless a later one is nested in the best one
+        let mut expanded_range: Option<TextRange> = None;
+        for candidate in candidates {
+            if !strictly_contains(candidate, range) {
+                continue;
+            }
+
+            if expanded_range.is_none_or(|best| strictly_contains(best, candidate)) {
+                expanded_range = Some(candidate);
+            }
+        }
+
+        expanded_range
+    }
+
+    fn get_word_range(&self, (x, y): (usize, usize), is_around: bool) -> Option<TextRange> {
+        let (word_start_x, word_end_x) = self.get_word_boundaries((x, y))?;
+        let line = self.lines.get(y)?;
+        let (mut x_from, mut x_to) = (word_start_x, word_end_x + 1);
+
+        if is_around {
+            let n_trailing = line
+                .iter()
+                .skip(x_to)
+                .take_while(|ch| ch.is_whitespace())
+                .count();
+            if n_trailing > 0 {
+                x_to += n_trailing;
+            } else {
+                x_from -= line
+                    .get_range(..x_from)
+                    .chars()
+                    .rev()
+                    .take_while(|ch| ch.is_whitespace())
+                    .count();
+            }
+        }
+
+        Some(((x_from, y), (x_to, y)))
+    }
+
+    /// Positions of the innermost `open` and `close` brackets around `range`,
+    /// or of the pair the cursor is on when `range` is empty
+    fn get_enclosing_brackets(
+        &self,
+        (open, close): (char, char),
+        (pos_from, pos_to): TextRange,
+    ) -> Option<TextRange> {
+        if pos_from == pos_to {
+            match self.get_char_at_pos(pos_from) {
+                Some(ch) if ch == open => {
+                    return Some((pos_from, self.get_matching_paren_pos(pos_from)?));
+                }
+                Some(ch) if ch == close => {
+                    return Some((self.get_matching_paren_pos(pos_from)?, pos_from));
+                }
+                _ => {}
+            }
+        }
+
+        let last_line_idx = self.n_lines().checked_sub(1)?;
+        let first_searched_idx = pos_from.1.saturating_sub(MAX_BRACKET_SEARCH_LINES);
+        let last_searched_idx = last_line_idx.min(pos_from.1 + MAX_BRACKET_SEARCH_LINES);
+
+        // brackets opened before the range and not closed before it, innermost first
+        let mut n_closed = 0;
+        let mut open_positions = (first_searched_idx..=pos_from.1.min(last_line_idx))
+            .rev()
+            .flat_map(|line_idx| {
+                let line = &self.lines[line_idx];
+                let end_x = if line_idx == pos_from.1 {
+                    pos_from.0.min(line.len())
+                } else {
+                    line.len()
+                };
+
+                let chars = line.iter().take(end_x).enumerate().collect::<Vec<_>>();
+                chars
+                    .into_iter()
+                    .rev()
+                    .map(move |(char_idx, ch)| ((char_idx, line_idx), ch))
+            })
+            .filter_map(|(pos, ch)| match ch {
+                _ if ch == close => {
+                    n_closed += 1;
+                    None
+                }
+                _ if ch == open && n_closed == 0 => Some(pos),
+                _ if ch == open => {
+                    n_closed -= 1;
+                    None
+                }
+                _ => None,
+            });
+
+        // brackets closed from the range on and not opened after its start, outermost last
+        let mut n_opened = 0;
+        let mut close_positions = (pos_from.1..=last_searched_idx)
+            .flat_map(|line_idx| {
+                let start_x = if line_idx == pos_from.1 {
+                    pos_from.0
+                } else {
+                    0
+                };
+                self.lines[line_idx]
+                    .iter()
+                    .enumerate()
+                    .skip(start_x)
+                    .map(move |(char_idx, ch)| ((char_idx, line_idx), ch))
+            })
+            .filter_map(|(pos, ch)| match ch {
+                _ if ch == open => {
+                    n_opened += 1;
+                    None
+                }
+                _ if ch == close && n_opened == 0 => Some(pos),
+                _ if ch == close => {
+                    n_opened -= 1;
+                    None
+                }
+                _ => None,
+            });
+
+        // brackets closed within the range don't enclose it, the next ones might
+        loop {
+            let (open_pos, close_pos) = (open_positions.next()?, close_positions.next()?);
+            if !is_before(close_pos, pos_to) {
+                return Some((open_pos, close_pos));
+            }
+        }
+    }
+
+    /// Positions of the quotes around `range`, or of the first ones following it on its line.
+    /// NOTE: a range spanning several lines is never within quotes
+    fn get_enclosing_quotes(
+        &self,
+        quote: char,
+        (pos_from, pos_to): TextRange,
+    ) -> Option<TextRange> {
+        let y = pos_from.1;
+        if pos_to.1 != y {
+            return None;
+        }
+
+        let quote_xs = self
+            .lines
+            .get(y)?
+            .iter()
+            .enumerate()
+            .filter(|(_, ch)| *ch == quote)
+            .map(|(char_idx, _)| char_idx)
+            .collect::<Vec<_>>();
+
+        // the range can end right after the closing quote
+        let (open_x, close_x) = quote_xs
+            .chunks_exact(2)
+            .map(|quotes| (quotes[0], quotes[1]))
+            .find(|(_, close_x)| pos_from.0 <= *close_x && pos_to.0 <= *close_x + 1)?;
+
+        Some(((open_x, y), (close_x, y)))
+    }
+
+    fn get_paragraph_range(
+        &self,
+        (pos_from, pos_to): TextRange,
+        is_around: bool,
+    ) -> Option<TextRange> {
+        let last_line_idx = self.n_lines().checked_sub(1)?;
+        let is_blank = |line_idx: usize| {
+            self.lines
+                .get(line_idx)
+                .is_none_or(|line| line.iter().all(char::is_whitespace))
+        };
+        let line_len = |line_idx| self.lines.get(line_idx).map_or(0, |line| line.len());
+
+        // a run of blank lines is a paragraph of its own
+        let get_run_start = |line_idx: usize| {
+            let is_run_blank = is_blank(line_idx);
+            (0..line_idx)
+                .rev()
+                .find(|idx| is_blank(*idx) != is_run_blank)
+                .map_or(0, |idx| idx + 1)
+        };
+        let get_run_end = |line_idx: usize| {
+            let is_run_blank = is_blank(line_idx);
+            (line_idx + 1..=last_line_idx)
+                .find(|idx| is_blank(*idx) != is_run_blank)
+                .map_or(last_line_idx, |idx| idx - 1)
+        };
+
+        let start_idx = get_run_start(pos_from.1.min(last_line_idx));
+        let end_idx = get_run_end(pos_to.1.min(last_line_idx));
+        if !is_around {
+            return Some(((0, start_idx), (line_len(end_idx), end_idx)));
+        }
+
+        // the following run of lines, up to the start of the next one,
+        // or the preceding run, from the end of the previous one
+        if end_idx < last_line_idx {
+            let following_end_idx = get_run_end(end_idx + 1);
+            if following_end_idx < last_line_idx {
+                Some(((0, start_idx), (0, following_end_idx + 1)))
+            } else {
+                let end = (line_len(following_end_idx), following_end_idx);
+                match start_idx.checked_sub(1) {
+                    Some(previous_idx) => Some(((line_len(previous_idx), previous_idx), end)),
+                    None => Some(((0, start_idx), end)),
+                }
+            }
+        } else if start_idx > 0 {
+            let preceding_start_idx = get_run_start(start_idx - 1);
+            let end = (line_len(end_idx), end_idx);
+            match preceding_start_idx.checked_sub(1) {
+                Some(previous_idx) => Some(((line_len(previous_idx), previous_idx), end)),
+                None => Some(((0, 0), end)),
+            }
+        } else {
+            Some(((0, start_idx), (line_len(end_idx), end_idx)))
+        }
+    }
+}
+
+#[inline]
+fn is_before(pos: (usize, usize), other_pos: (usize, usize)) -> bool {
+    (pos.1, pos.0) < (other_pos.1, other_pos.0)
+}
+
+/// Whether `outer` contains `inner` and is larger than it
+fn strictly_contains(outer: TextRange, inner: TextRange) -> bool {
+    !is_before(inner.0, outer.0) && !is_before(outer.1, inner.1) && outer != inner
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TextObject;
+    use crate::document::test_utils::get_document;
+
+    #[test]
+    fn finds_enclosing_brackets() {
+        let document = get_document(&["f(a, (b), [c", "  (d)], e)"]);
+        let brackets = TextObject::Brackets('(', ')');
+
+        // inside the innermost pair, and after a pair closed before the cursor
+        let inside_b = ((7, 0), (7, 0));
+        assert_eq!(
+            document.get_text_object_range(brackets, inside_b, false),
+            Some(((6, 0), (7, 0)))
+        );
+        let after_d = ((6, 1), (6, 1));
+        assert_eq!(
+            document.get_text_object_range(brackets, after_d, true),
+            Some(((1, 0), (10, 1)))
+        );
+
+        // a range ending past the closing bracket isn't enclosed by it
+        let over_b = ((6, 0), (9, 0));
+        assert_eq!(
+            document.get_text_object_range(brackets, over_b, false),
+            Some(((2, 0), (9, 1)))
+        );
+
+        let square_brackets = TextObject::Brackets('[', ']');
+        assert_eq!(
+            document.get_text_object_range(square_brackets, ((3, 1), (3, 1)), false),
+            Some(((11, 0), (5, 1)))
+        );
+        assert_eq!(
+            document.get_text_object_range(square_brackets, ((2, 0), (2, 0)), false),
+            None
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{Document, EditOperation};
+
+/// Cursor position and selection anchor, as they were when a transaction began
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Selection {
+    pub cursor: (usize, usize),
+    pub anchor: Option<(usize, usize)>,
+}
+
+pub(crate) struct Transaction {
+    /// The undo stack as it was when the transaction began, the edits made since
+    /// are collected on their own stack until it is committed
+    outer_stack: Vec<EditOperation>,
+    selection: Selection,
+    /// Number of transactions begun while this one was already open
+    n_nested: usize,
+}
+
+impl Document {
+    /// Starts collecting the edits applied to the document, until `commit` is called,
+    /// so that they are undone and redone as a single step.
+    /// `cursor` and `selection_anchor` are given back by `get_undo_selection` once the
+    /// transaction is committed, to be restored when undoing it.
+    /// NOTE: transactions can be nested, only the outermost one is recorded
+    pub fn begin_transaction(
+        &mut self,
+        cursor: (usize, usize),
+        selection_anchor: Option<(usize, usize)>,
+    ) {
+        if let Some(transaction) = self.transaction.as_mut() {
+            transaction.n_nested += 1;
+            return;
+        }
+
+        // edits made before the transaction can't be coalesced with the ones made in it
+        let outer_stack = std::mem::take(&mut self.undo_stack);
+        self.transaction = Some(Transaction {
+            outer_stack,
+            selection: Selection {
+                cursor,
+                anchor: selection_anchor,
+            },
+            n_nested: 0,
+        });
+    }
+
+    /// Ends the transaction begun by the last call to `begin_transaction`, the edits
+    /// made in it become a single entry of the undo stack
+    pub fn commit(&mut self) {
+        let Some(transaction) = self.transaction.as_mut() else {
+            return;
+        };
+
+        if transaction.n_nested > 0 {
+            transaction.n_nested -= 1;
+            return;
+        }
+
+        let Some(transaction) = self.transaction.take() else {
+            return;
+        };
+
+        let mut inverse_ops = std::mem::replace(&mut self.undo_stack, transaction.outer_stack);
+        if inverse_ops.is_empty() {
+            return;
+        }
+
+        // every edit left on the stack has its own state on the path to the current one
+        let n_edits = inverse_ops.len();
+        inverse_ops.reverse();
+        self.undo_stack.push(EditOperation::Group(inverse_ops));
+        self.undo_tree.merge_last_states(n_edits);
+        self.undo_tree.set_selection_before(transaction.selection);
+    }
+
+    #[inline]
+    pub fn is_in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Where the cursor and the selection were before the edits `undo_last_edit` would undo,
+    /// if they were made in a transaction
+    #[inline]
+    pub fn get_undo_selection(&self) -> Option<Selection> {
+        self.undo_tree.get_selection_before()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::test_utils::{get_document, get_text, insert};
+
+    #[test]
+    fn groups_only_its_own_edits() {
+        let mut document = get_document(&["foo bar"]);
+        insert(&mut document, (7, 0), " baz");
+
+        document.begin_transaction((0, 0), Some((3, 0)));
+        insert(&mut document, (0, 0), "1");
+        document.begin_transaction((0, 0), None);
+        insert(&mut document, (5, 0), "2");
+        document.commit();
+        insert(&mut document, (9, 0), "3");
+        document.commit();
+        assert_eq!(get_text(&document), ["1foo 2bar3 baz"]);
+
+        // the edit made before the transaction is left out of it
+        let selection = document.get_undo_selection().unwrap();
+        assert_eq!((selection.cursor, selection.anchor), ((0, 0), Some((3, 0))));
+        document.undo_last_edit();
+        assert_eq!(get_text(&document), ["foo bar baz"]);
+        document.undo_last_edit();
+        assert_eq!(get_text(&document), ["foo bar"]);
+
+        document.redo_last_edit();
+        document.redo_last_edit();
+        assert_eq!(get_text(&document), ["1foo 2bar3 baz"]);
+    }
+
+    #[test]
+    fn drops_empty_transactions() {
+        let mut document = get_document(&["foo"]);
+        insert(&mut document, (3, 0), "!");
+        document.begin_transaction((0, 0), None);
+        document.commit();
+
+        document.undo_last_edit();
+        assert_eq!(get_text(&document), ["foo"]);
+    }
+
+    #[test]
+    fn refuses_undoing_while_open() {
+        let mut document = get_document(&["foo"]);
+        insert(&mut document, (3, 0), "!");
+        document.begin_transaction((0, 0), None);
+        insert(&mut document, (0, 0), "a");
+
+        assert_eq!(document.undo_last_edit(), None);
+        assert_eq!(get_text(&document), ["afoo!"]);
+
+        document.commit();
+        document.undo_last_edit();
+        assert_eq!(get_text(&document), ["foo!"]);
+    }
+}
@@ -0,0 +1,351 @@
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    Document, EditOperation,
+    document::{edit::EditResult, transaction::Selection},
+};
+
+/// Every state the document went through, edits made after an undo start a new branch
+/// instead of discarding the undone ones.
+///
+/// The operations leading to the states on the path from the root to the current state
+/// live in the document's undo stack, the ones leading to the states reached by redoing
+/// (following `preferred_child`) in its redo stack. The states on every other branch keep
+/// the operation leading to them from their parent in `redo_op`.
+pub struct UndoTree {
+    states: Vec<UndoTreeState>,
+    current_state_id: usize,
+}
+
+struct UndoTreeState {
+    parent_id: Option<usize>,
+    children_ids: Vec<usize>,
+    preferred_child_id: Option<usize>,
+    redo_op: Option<EditOperation>,
+    /// Set on the states reached by committing a transaction
+    selection_before: Option<Selection>,
+    time: SystemTime,
+}
+
+/// A state of the undo tree, as listed by `Document::get_undo_states`
+pub struct UndoState {
+    pub id: usize,
+    /// Number of branches this state is nested in
+    pub depth: usize,
+    pub time: SystemTime,
+    pub is_current: bool,
+}
+
+/// How far to travel with `Document::go_earlier` and `Document::go_later`
+#[derive(Clone, Copy)]
+pub enum TimeTravel {
+    Steps(usize),
+    Duration(Duration),
+}
+
+impl UndoTreeState {
+    fn new(parent_id: Option<usize>) -> Self {
+        Self {
+            parent_id,
+            children_ids: vec![],
+            preferred_child_id: None,
+            redo_op: None,
+            selection_before: None,
+            time: SystemTime::now(),
+        }
+    }
+}
+
+impl Default for UndoTree {
+    fn default() -> Self {
+        Self {
+            states: vec![UndoTreeState::new(None)],
+            current_state_id: 0,
+        }
+    }
+}
+
+impl UndoTree {
+    /// A tree with a single branch, made of `n_undo` states up to the current one
+    /// and `n_redo` states after it
+    pub fn linear(n_undo: usize, n_redo: usize) -> Self {
+        let mut tree = Self::default();
+        for _ in 0..n_undo + n_redo {
+            tree.push_state();
+        }
+
+        tree.current_state_id = n_undo;
+
+        tree
+    }
+
+    #[inline]
+    pub fn is_at_leaf(&self) -> bool {
+        self.states[self.current_state_id].children_ids.is_empty()
+    }
+
+    /// Adds a state following the current one and moves to it
+    pub fn push_state(&mut self) {
+        let new_state_id = self.states.len();
+        self.states
+            .push(UndoTreeState::new(Some(self.current_state_id)));
+
+        let current_state = &mut self.states[self.current_state_id];
+        current_state.children_ids.push(new_state_id);
+        current_state.preferred_child_id = Some(new_state_id);
+        self.current_state_id = new_state_id;
+    }
+
+    /// The current state has been modified in place, e.g. by typing another character
+    #[inline]
+    pub fn touch_current_state(&mut self) {
+        self.states[self.current_state_id].time = SystemTime::now();
+    }
+
+    #[inline]
+    pub fn set_selection_before(&mut self, selection: Selection) {
+        self.states[self.current_state_id].selection_before = Some(selection);
+    }
+
+    #[inline]
+    pub fn get_selection_before(&self) -> Option<Selection> {
+        self.states[self.current_state_id].selection_before
+    }
+
+    pub fn move_to_parent(&mut self) {
+        if let Some(parent_id) = self.states[self.current_state_id].parent_id {
+            self.states[parent_id].preferred_child_id = Some(self.current_state_id);
+            self.current_state_id = parent_id;
+        }
+    }
+
+    pub fn move_to_preferred_child(&mut self) {
+        if let Some(child_id) = self.states[self.current_state_id].preferred_child_id {
+            self.current_state_id = child_id;
+        }
+    }
+
+    /// Merges the last `n_states` states on the path to the current one into the current one
+    pub fn merge_last_states(&mut self, n_states: usize) {
+        let mut first_merged_id = self.current_state_id;
+        for _ in 1..n_states {
+            match self.states[first_merged_id].parent_id {
+                Some(parent_id) if parent_id != 0 => first_merged_id = parent_id,
+                _ => break,
+            }
+        }
+
+        let Some(ancestor_id) = self.states[first_merged_id].parent_id else {
+            return;
+        };
+
+        // the merged states are left unreachable
+        let ancestor = &mut self.states[ancestor_id];
+        ancestor
+            .children_ids
+            .retain(|child_id| *child_id != first_merged_id);
+        ancestor.children_ids.push(self.current_state_id);
+        ancestor.preferred_child_id = Some(self.current_state_id);
+        self.states[self.current_state_id].parent_id = Some(ancestor_id);
+    }
+
+    /// `None` if the state isn't reachable from the root, e.g. because it has been merged
+    fn get_path_from_root(&self, state_id: usize) -> Option<Vec<usize>> {
+        let mut path = vec![state_id];
+        while let Some(parent_id) = self.states[*path.last().unwrap()].parent_id {
+            if !self.states[parent_id]
+                .children_ids
+                .contains(path.last().unwrap())
+            {
+                return None;
+            }
+
+            path.push(parent_id);
+        }
+
+        path.reverse();
+
+        Some(path)
+    }
+
+    /// Ids of the states reachable from the root, in chronological order
+    fn get_chronological_state_ids(&self) -> Vec<usize> {
+        let mut state_ids = vec![];
+        let mut to_visit = vec![0];
+        while let Some(state_id) = to_visit.pop() {
+            state_ids.push(state_id);
+            to_visit.extend(self.states[state_id].children_ids.iter());
+        }
+
+        state_ids.sort_by_key(|state_id| (self.states[*state_id].time, *state_id));
+
+        state_ids
+    }
+}
+
+impl Document {
+    #[inline]
+    pub fn get_current_undo_state_id(&self) -> usize {
+        self.undo_tree.current_state_id
+    }
+
+    /// Every reachable state of the undo tree, depth first, with branches
+    /// listed before the continuation of the branch they fork from
+    pub fn get_undo_states(&self) -> Vec<UndoState> {
+        let tree = &self.undo_tree;
+        let mut undo_states = vec![];
+        let mut to_visit = vec![(0, 0)];
+        while let Some((state_id, depth)) = to_visit.pop() {
+            let state = &tree.states[state_id];
+            undo_states.push(UndoState {
+                id: state_id,
+                depth,
+                time: state.time,
+                is_current: state_id == tree.current_state_id,
+            });
+
+            // the most recent child continues the branch, the others fork from it
+            let mut children_ids = state.children_ids.clone();
+            children_ids.sort_by_key(|child_id| tree.states[*child_id].time);
+            if let Some(last_child_id) = children_ids.pop() {
+                to_visit.push((last_child_id, depth));
+            }
+
+            to_visit.extend(
+                children_ids
+                    .into_iter()
+                    .rev()
+                    .map(|child_id| (child_id, depth + 1)),
+            );
+        }
+
+        undo_states
+    }
+
+    /// Brings the document to the state with id `state_id`, undoing and redoing
+    /// across branches as needed
+    pub fn go_to_undo_state(&mut self, state_id: usize) -> EditResult {
+        if self.is_readonly || state_id >= self.undo_tree.states.len() {
+            return None;
+        }
+
+        let target_path = self.undo_tree.get_path_from_root(state_id)?;
+
+        let mut new_pos = None;
+        while !target_path.contains(&self.undo_tree.current_state_id) {
+            let undo_depth = self.undo_stack.len();
+            new_pos = self.undo_last_edit().or(new_pos);
+            if self.undo_stack.len() == undo_depth {
+                return new_pos;
+            }
+        }
+
+        let current_idx = target_path
+            .iter()
+            .position(|state_id| *state_id == self.undo_tree.current_state_id)
+            .unwrap_or_default();
+
+        for next_state_id in target_path.into_iter().skip(current_idx + 1) {
+            let current_state = &self.undo_tree.states[self.undo_tree.current_state_id];
+            if current_state.preferred_child_id != Some(next_state_id) || self.redo_stack.is_empty()
+            {
+                self.park_redo_stack();
+                self.undo_tree.states[self.undo_tree.current_state_id].preferred_child_id =
+                    Some(next_state_id);
+                self.unpark_redo_stack();
+            }
+
+            let redo_depth = self.redo_stack.len();
+            new_pos = self.redo_last_edit().or(new_pos);
+            if self.redo_stack.len() == redo_depth {
+                break;
+            }
+        }
+
+        new_pos
+    }
+
+    /// Goes back to the state the document was in `by` steps or time ago,
+    /// regardless of the branch it is on
+    pub fn go_earlier(&mut self, by: TimeTravel) -> EditResult {
+        let state_ids = self.undo_tree.get_chronological_state_ids();
+        let current_idx = state_ids
+            .iter()
+            .position(|state_id| *state_id == self.undo_tree.current_state_id)?;
+
+        let target_idx = match by {
+            TimeTravel::Steps(n_steps) => current_idx.saturating_sub(n_steps),
+            TimeTravel::Duration(duration) => {
+                let current_time = self.undo_tree.states[state_ids[current_idx]].time;
+                let target_time = current_time.checked_sub(duration)?;
+                state_ids[..current_idx]
+                    .iter()
+                    .rposition(|state_id| self.undo_tree.states[*state_id].time <= target_time)
+                    .unwrap_or_default()
+            }
+        };
+
+        if target_idx == current_idx {
+            return None;
+        }
+
+        self.go_to_undo_state(state_ids[target_idx])
+    }
+
+    /// Goes forward to the state the document was in `by` steps or time later,
+    /// regardless of the branch it is on
+    pub fn go_later(&mut self, by: TimeTravel) -> EditResult {
+        let state_ids = self.undo_tree.get_chronological_state_ids();
+        let current_idx = state_ids
+            .iter()
+            .position(|state_id| *state_id == self.undo_tree.current_state_id)?;
+        if current_idx + 1 >= state_ids.len() {
+            return None;
+        }
+
+        let target_idx = match by {
+            TimeTravel::Steps(n_steps) => (current_idx + n_steps).min(state_ids.len() - 1),
+            TimeTravel::Duration(duration) => {
+                let current_time = self.undo_tree.states[state_ids[current_idx]].time;
+                let target_time = current_time + duration;
+                state_ids
+                    .iter()
+                    .rposition(|state_id| self.undo_tree.states[*state_id].time <= target_time)
+                    .unwrap_or_default()
+                    .max(current_idx + 1)
+            }
+        };
+
+        self.go_to_undo_state(state_ids[target_idx])
+    }
+
+    /// Moves the operations of the redo stack to the states they lead to,
+    /// before they get replaced by a new branch
+    pub(crate) fn park_redo_stack(&mut self) {
+        let mut state_id = self.undo_tree.current_state_id;
+        while let Some(child_id) = self.undo_tree.states[state_id].preferred_child_id
+            && let Some(op) = self.redo_stack.pop()
+        {
+            self.undo_tree.states[child_id].redo_op = Some(op);
+            state_id = child_id;
+        }
+
+        self.redo_stack.clear();
+    }
+
+    /// Loads the operations leading to the states following the current one
+    /// on its preferred branch into the redo stack
+    pub(crate) fn unpark_redo_stack(&mut self) {
+        let mut ops = vec![];
+        let mut state_id = self.undo_tree.current_state_id;
+        while let Some(child_id) = self.undo_tree.states[state_id].preferred_child_id
+            && let Some(op) = self.undo_tree.states[child_id].redo_op.take()
+        {
+            ops.push(op);
+            state_id = child_id;
+        }
+
+        ops.reverse();
+        self.redo_stack = ops;
+    }
+}
@@ -0,0 +1,301 @@
+use std::{fmt::Display, io, str::FromStr};
+
+/// Bytes 0x80 to 0x9F in Windows-1252, the rest of the code page matches Latin-1.
+/// NOTE: the 5 undefined bytes are mapped to the C1 control with the same value
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// Number of bytes looked at when guessing whether a file without BOM is UTF-16
+const UTF16_SNIFF_LEN: usize = 4096;
+
+/// Text encodings a file can be read from and written back to
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Windows1252,
+}
+
+impl Encoding {
+    /// Byte order mark of the encoding, empty for the ones that don't have one
+    pub fn bom(&self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => &[0xEF, 0xBB, 0xBF],
+            Encoding::Utf16Le => &[0xFF, 0xFE],
+            Encoding::Utf16Be => &[0xFE, 0xFF],
+            Encoding::Latin1 | Encoding::Windows1252 => &[],
+        }
+    }
+
+    #[inline]
+    pub fn is_utf16(&self) -> bool {
+        matches!(self, Encoding::Utf16Le | Encoding::Utf16Be)
+    }
+
+    /// Whether the encoding can represent every character
+    #[inline]
+    pub fn is_unicode(&self) -> bool {
+        matches!(self, Encoding::Utf8 | Encoding::Utf16Le | Encoding::Utf16Be)
+    }
+
+    /// Guesses the encoding of the content of a file, returns it along with whether
+    /// the content starts with a BOM
+    pub fn detect(bytes: &[u8]) -> (Self, bool) {
+        for encoding in [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be] {
+            if bytes.starts_with(encoding.bom()) {
+                return (encoding, true);
+            }
+        }
+
+        if let Some(encoding) = sniff_utf16(bytes) {
+            (encoding, false)
+        } else if str::from_utf8(bytes).is_ok() {
+            (Encoding::Utf8, false)
+        } else if bytes.iter().any(|byte| (0x80..0xA0).contains(byte)) {
+            // C1 controls are unlikely in actual Latin-1 text
+            (Encoding::Windows1252, false)
+        } else {
+            (Encoding::Latin1, false)
+        }
+    }
+
+    /// Same as `detect`, for the first bytes of a file too large to be looked at whole,
+    /// which may end in the middle of a character
+    pub fn detect_from_start(bytes: &[u8]) -> (Self, bool) {
+        match Self::detect(bytes) {
+            (Encoding::Latin1 | Encoding::Windows1252, _)
+                if str::from_utf8(bytes).is_err_and(|err| err.error_len().is_none()) =>
+            {
+                (Encoding::Utf8, false)
+            }
+            detected => detected,
+        }
+    }
+
+    /// Decodes `bytes`, which must not include the BOM
+    pub fn decode(&self, bytes: &[u8]) -> io::Result<String> {
+        let invalid_data = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("File is not valid {}", self),
+            )
+        };
+
+        match self {
+            Encoding::Utf8 => str::from_utf8(bytes)
+                .map(str::to_owned)
+                .map_err(|_| invalid_data()),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                if !bytes.len().is_multiple_of(2) {
+                    return Err(invalid_data());
+                }
+
+                let is_le = *self == Encoding::Utf16Le;
+                let units = bytes.chunks_exact(2).map(|pair| {
+                    if is_le {
+                        u16::from_le_bytes([pair[0], pair[1]])
+                    } else {
+                        u16::from_be_bytes([pair[0], pair[1]])
+                    }
+                });
+
+                char::decode_utf16(units)
+                    .collect::<Result<String, _>>()
+                    .map_err(|_| invalid_data())
+            }
+            Encoding::Latin1 => Ok(bytes.iter().map(|byte| *byte as char).collect()),
+            Encoding::Windows1252 => Ok(bytes
+                .iter()
+                .map(|byte| match byte {
+                    0x80..0xA0 => WINDOWS_1252_HIGH[(byte - 0x80) as usize],
+                    _ => *byte as char,
+                })
+                .collect()),
+        }
+    }
+
+    /// Appends `text` encoded to `out`, characters the encoding can't represent become '?'.
+    /// NOTE: use `can_encode` first to avoid losing them
+    pub fn encode(&self, text: &str, out: &mut Vec<u8>) {
+        match self {
+            Encoding::Utf8 => out.extend_from_slice(text.as_bytes()),
+            Encoding::Utf16Le => text
+                .encode_utf16()
+                .for_each(|unit| out.extend_from_slice(&unit.to_le_bytes())),
+            Encoding::Utf16Be => text
+                .encode_utf16()
+                .for_each(|unit| out.extend_from_slice(&unit.to_be_bytes())),
+            Encoding::Latin1 | Encoding::Windows1252 => {
+                out.extend(text.chars().map(|ch| self.encode_char(ch).unwrap_or(b'?')))
+            }
+        }
+    }
+
+    /// Whether every character of `text` can be represented in the encoding
+    pub fn can_encode(&self, text: &str) -> bool {
+        self.is_unicode()
+            || text.is_ascii()
+            || text.chars().all(|ch| self.encode_char(ch).is_some())
+    }
+
+    /// Number of bytes taken by `text` once encoded
+    pub fn encoded_len(&self, text: &str) -> usize {
+        match self {
+            Encoding::Utf8 => text.len(),
+            Encoding::Utf16Le | Encoding::Utf16Be => 2 * text.encode_utf16().count(),
+            Encoding::Latin1 | Encoding::Windows1252 => text.chars().count(),
+        }
+    }
+
+    /// Byte of a character in the single byte encodings
+    fn encode_char(&self, ch: char) -> Option<u8> {
+        match (self, ch as u32) {
+            (_, 0..0x80) => Some(ch as u8),
+            (Encoding::Latin1, 0x80..0x100) => Some(ch as u8),
+            (Encoding::Windows1252, 0xA0..0x100) => Some(ch as u8),
+            (Encoding::Windows1252, _) => WINDOWS_1252_HIGH
+                .iter()
+                .position(|high_ch| *high_ch == ch)
+                .map(|idx| 0x80 + idx as u8),
+            _ => None,
+        }
+    }
+}
+
+/// UTF-16 text without BOM is recognizable by the zero high bytes of its ASCII characters
+fn sniff_utf16(bytes: &[u8]) -> Option<Encoding> {
+    let sample = &bytes[..bytes.len().min(UTF16_SNIFF_LEN)];
+    if sample.len() < 2 || !sample.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let n_units = sample.len() / 2;
+    let (n_even_zeros, n_odd_zeros) =
+        sample
+            .chunks_exact(2)
+            .fold((0, 0), |(n_even_zeros, n_odd_zeros), pair| {
+                (
+                    n_even_zeros + (pair[0] == 0 && pair[1] != 0) as usize,
+                    n_odd_zeros + (pair[1] == 0 && pair[0] != 0) as usize,
+                )
+            });
+
+    if n_odd_zeros * 2 > n_units {
+        Some(Encoding::Utf16Le)
+    } else if n_even_zeros * 2 > n_units {
+        Some(Encoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+            Encoding::Latin1 => "latin-1",
+            Encoding::Windows1252 => "windows-1252",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "utf-16le" | "utf16le" | "utf-16" | "utf16" => Ok(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Encoding::Utf16Be),
+            "latin-1" | "latin1" | "iso-8859-1" | "iso8859-1" => Ok(Encoding::Latin1),
+            "windows-1252" | "cp1252" => Ok(Encoding::Windows1252),
+            _ => Err(format!("Unknown encoding: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Encoding;
+
+    #[test]
+    fn detects_boms() {
+        assert_eq!(Encoding::detect(b"\xEF\xBB\xBFabc"), (Encoding::Utf8, true));
+        assert_eq!(Encoding::detect(b"\xFF\xFEa\0"), (Encoding::Utf16Le, true));
+        assert_eq!(Encoding::detect(b"\xFE\xFF\0a"), (Encoding::Utf16Be, true));
+        assert_eq!(Encoding::detect(b"a\0b\0c\0"), (Encoding::Utf16Le, false));
+        assert_eq!(Encoding::detect(b"\0a\0b\0c"), (Encoding::Utf16Be, false));
+        assert_eq!(Encoding::detect("é".as_bytes()), (Encoding::Utf8, false));
+    }
+
+    #[test]
+    fn falls_back_to_single_byte_encodings() {
+        assert_eq!(Encoding::detect(b"caf\xE9"), (Encoding::Latin1, false));
+        assert_eq!(
+            Encoding::detect(b"\x93caf\xE9\x94"),
+            (Encoding::Windows1252, false)
+        );
+
+        assert_eq!(Encoding::Latin1.decode(b"caf\xE9").unwrap(), "café");
+        assert_eq!(
+            Encoding::Windows1252.decode(b"\x80\x93\x94").unwrap(),
+            "€“”"
+        );
+    }
+
+    #[test]
+    fn round_trips_undefined_windows_1252_bytes() {
+        let bytes = [0x81, 0x8D, 0x8F, 0x90, 0x9D];
+        let text = Encoding::Windows1252.decode(&bytes).unwrap();
+        assert_eq!(text, "\u{81}\u{8D}\u{8F}\u{90}\u{9D}");
+        assert!(Encoding::Windows1252.can_encode(&text));
+
+        let mut encoded = vec![];
+        Encoding::Windows1252.encode(&text, &mut encoded);
+        assert_eq!(encoded, bytes);
+
+        // the other C1 controls are only in Latin-1
+        assert!(!Encoding::Windows1252.can_encode("\u{80}"));
+        assert!(Encoding::Latin1.can_encode("\u{80}"));
+        assert!(!Encoding::Latin1.can_encode("€"));
+    }
+
+    #[test]
+    fn detects_utf8_cut_in_the_middle_of_a_char() {
+        let bytes = "abc€".as_bytes();
+        let truncated = &bytes[..bytes.len() - 1];
+
+        assert_eq!(Encoding::detect(truncated), (Encoding::Windows1252, false));
+        assert_eq!(
+            Encoding::detect_from_start(truncated),
+            (Encoding::Utf8, false)
+        );
+        // an invalid sequence before the end is not a truncation
+        assert_eq!(
+            Encoding::detect_from_start(b"caf\xE9 ok"),
+            (Encoding::Latin1, false)
+        );
+        assert!(Encoding::Utf8.decode(truncated).is_err());
+    }
+
+    #[test]
+    fn round_trips_utf16() {
+        let text = "a€😀";
+        for encoding in [Encoding::Utf16Le, Encoding::Utf16Be] {
+            let mut encoded = vec![];
+            encoding.encode(text, &mut encoded);
+            assert_eq!(encoded.len(), encoding.encoded_len(text));
+            assert_eq!(encoding.decode(&encoded).unwrap(), text);
+            assert!(encoding.decode(&encoded[1..]).is_err());
+        }
+    }
+}
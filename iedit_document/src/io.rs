@@ -1,15 +1,96 @@
 use std::{
     fs::{File, OpenOptions},
-    io::{self, BufRead, BufReader},
+    io::{self, Read},
+    ops::Range,
+    os::unix::fs::{FileExt, MetadataExt},
     path::{Path, PathBuf},
 };
 
-use crate::line::DocumentLine;
+use crate::{
+    encoding::Encoding,
+    line::{DocumentLine, LineStore},
+};
+
+/// Size of the blocks large files are read in while indexing their lines
+const INDEX_BLOCK_SIZE: usize = 1 << 20;
+
+/// Content of a file as read by `read_file`
+pub struct ReadFile {
+    pub file: Option<File>,
+    pub path: PathBuf,
+    pub lines: LineStore,
+    pub line_offsets: Vec<u64>,
+    /// The most common line ending of the file
+    pub end_of_line_seq: String,
+    /// Whether lines end with both LF and CRLF
+    pub has_mixed_line_endings: bool,
+    pub encoding: Encoding,
+    pub has_bom: bool,
+}
+
+/// Source of the lines of a large file, which are read on demand instead of on opening
+pub struct Pager {
+    file: File,
+    encoding: Encoding,
+    line_terminator: char,
+}
+
+impl Pager {
+    pub fn new(file: File, encoding: Encoding, end_of_line_seq: &str) -> Self {
+        Self {
+            file,
+            encoding,
+            line_terminator: if end_of_line_seq == "\r" { '\r' } else { '\n' },
+        }
+    }
+
+    /// Whether the lines are read from the same file as `file`, which may be another handle to it
+    pub fn reads_from(&self, file: &File) -> bool {
+        match (self.file.metadata(), file.metadata()) {
+            (Ok(own), Ok(other)) => own.dev() == other.dev() && own.ino() == other.ino(),
+            _ => false,
+        }
+    }
 
-type ReadFile = (Option<File>, PathBuf, Vec<DocumentLine>, Vec<u64>, String);
+    /// Reads the `n_lines` lines stored in `bytes`.
+    /// NOTE: lines that can't be read are left empty, bytes that can't be decoded are replaced
+    pub fn read_lines(&self, bytes: Range<u64>, n_lines: usize) -> Vec<DocumentLine> {
+        let mut buf = vec![0; (bytes.end - bytes.start) as usize];
+        let text = match self.file.read_exact_at(&mut buf, bytes.start) {
+            Ok(()) => self
+                .encoding
+                .decode(&buf)
+                .unwrap_or_else(|_| String::from_utf8_lossy(&buf).into_owned()),
+            Err(_) => String::new(),
+        };
+
+        let mut lines = text
+            .split(self.line_terminator)
+            .take(n_lines)
+            .map(|file_line| {
+                let trimmed = match self.line_terminator {
+                    '\n' => file_line.strip_suffix('\r').unwrap_or(file_line),
+                    _ => file_line,
+                };
+
+                let mut line = DocumentLine::new(trimmed.to_owned());
+                line.has_been_modified = false;
+                line
+            })
+            .collect::<Vec<_>>();
 
-pub fn read_file(path: impl AsRef<Path>) -> io::Result<ReadFile> {
-    let file = OpenOptions::new()
+        lines.resize_with(n_lines, || {
+            let mut line = DocumentLine::default();
+            line.has_been_modified = false;
+            line
+        });
+
+        lines
+    }
+}
+
+fn open_file(path: impl AsRef<Path>) -> io::Result<File> {
+    OpenOptions::new()
         .read(true)
         .write(true)
         .append(false)
@@ -20,49 +101,77 @@ pub fn read_file(path: impl AsRef<Path>) -> io::Result<ReadFile> {
             } else {
                 Ok(file)
             }
-        });
+        })
+}
 
-    match file {
+/// Reads the lines of a file, decoding it as `encoding` or as the detected one when `None`
+pub fn read_file(path: impl AsRef<Path>, encoding: Option<Encoding>) -> io::Result<ReadFile> {
+    match open_file(path.as_ref()) {
         Err(err)
             if path.as_ref().as_os_str().is_empty() || err.kind() == io::ErrorKind::NotFound =>
         {
-            Ok((
-                None,
-                path.as_ref().to_owned(),
-                Vec::new(),
-                Vec::new(),
-                "\n".to_owned(),
-            ))
+            Ok(ReadFile {
+                file: None,
+                path: path.as_ref().to_owned(),
+                lines: LineStore::new(),
+                line_offsets: Vec::new(),
+                end_of_line_seq: "\n".to_owned(),
+                has_mixed_line_endings: false,
+                encoding: encoding.unwrap_or_default(),
+                has_bom: false,
+            })
         }
         Err(err) => Err(err),
-        Ok(file) => {
-            let mut file_lines = Vec::new();
-            let mut file_reader = BufReader::new(file);
-            let mut file_line = String::default();
-            let mut line_offsets = vec![];
-            let mut last_offset = 0;
-            let mut is_last_line_newline_terminated = false;
-            let mut end_of_line_seq: Option<String> = None;
-            while file_reader.read_line(&mut file_line)? > 0 {
-                let bytes_read = file_line.as_bytes().len();
+        Ok(mut file) => {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
 
-                let trimmed = if let Some(end_of_line_seq) = &end_of_line_seq {
-                    file_line.trim_end_matches(end_of_line_seq)
-                } else {
-                    let trimmed = file_line.trim_end_matches(&['\n', '\r']);
-                    end_of_line_seq = Some(file_line[trimmed.len()..bytes_read].to_owned());
+            let (encoding, has_bom) = match encoding {
+                Some(encoding) => (
+                    encoding,
+                    !encoding.bom().is_empty() && bytes.starts_with(encoding.bom()),
+                ),
+                None => Encoding::detect(&bytes),
+            };
+            let bom_len = if has_bom { encoding.bom().len() } else { 0 };
+            let text = encoding.decode(&bytes[bom_len..])?;
+            drop(bytes);
 
-                    trimmed
+            // lone CRs only end lines in files without any LF, elsewhere they are
+            // part of the line content
+            let line_terminator = if text.contains('\n') { '\n' } else { '\r' };
+
+            let mut file_lines = LineStore::new();
+            let mut line_offsets = vec![];
+            let mut last_offset = bom_len as u64;
+            let mut is_last_line_newline_terminated = false;
+            let (mut n_lf, mut n_crlf, mut n_cr) = (0, 0, 0);
+            for file_line in text.split_inclusive(line_terminator) {
+                let trimmed = match file_line.strip_suffix(line_terminator) {
+                    Some(trimmed) if line_terminator == '\r' => {
+                        n_cr += 1;
+                        trimmed
+                    }
+                    Some(trimmed) => match trimmed.strip_suffix('\r') {
+                        Some(trimmed) => {
+                            n_crlf += 1;
+                            trimmed
+                        }
+                        None => {
+                            n_lf += 1;
+                            trimmed
+                        }
+                    },
+                    None => file_line,
                 };
 
-                is_last_line_newline_terminated = trimmed.len() < bytes_read;
+                is_last_line_newline_terminated = trimmed.len() < file_line.len();
                 let mut line = DocumentLine::new(trimmed.to_string());
                 line.has_been_modified = false;
                 file_lines.push(line);
-                file_line.truncate(0);
 
                 line_offsets.push(last_offset);
-                last_offset += bytes_read as u64;
+                last_offset += encoding.encoded_len(file_line) as u64;
             }
 
             if is_last_line_newline_terminated {
@@ -72,14 +181,162 @@ pub fn read_file(path: impl AsRef<Path>) -> io::Result<ReadFile> {
                 line_offsets.push(last_offset);
             }
 
-            let file = file_reader.into_inner();
-            Ok((
-                Some(file),
-                path.as_ref().to_owned(),
-                file_lines,
+            let end_of_line_seq = get_end_of_line_seq(n_lf, n_crlf, n_cr);
+
+            Ok(ReadFile {
+                file: Some(file),
+                path: path.as_ref().to_owned(),
+                lines: file_lines,
                 line_offsets,
-                end_of_line_seq.unwrap_or("\n".to_string()),
-            ))
+                end_of_line_seq: end_of_line_seq.to_owned(),
+                has_mixed_line_endings: n_lf > 0 && n_crlf > 0,
+                encoding,
+                has_bom,
+            })
         }
     }
 }
+
+/// Like `read_file`, but only indexes where the lines of the file start, they are read
+/// when accessed. `on_progress` is called with the number of bytes indexed so far
+/// and the size of the file.
+/// NOTE: the line terminator is guessed from the first block of the file
+pub fn index_file(
+    path: impl AsRef<Path>,
+    encoding: Option<Encoding>,
+    mut on_progress: impl FnMut(u64, u64),
+) -> io::Result<ReadFile> {
+    let file = open_file(path.as_ref())?;
+    let file_len = file.metadata()?.len();
+
+    let is_encoding_detected = encoding.is_none();
+    let mut block = vec![0; INDEX_BLOCK_SIZE];
+    let n_read = read_block(&file, 0, &mut block)?;
+    let (mut encoding, has_bom) = match encoding {
+        Some(encoding) => (
+            encoding,
+            !encoding.bom().is_empty() && block[..n_read].starts_with(encoding.bom()),
+        ),
+        None => Encoding::detect_from_start(&block[..n_read]),
+    };
+    let bom_len = if has_bom { encoding.bom().len() } else { 0 };
+
+    let get_unit = |unit_bytes: &[u8]| match encoding {
+        Encoding::Utf16Le => u16::from_le_bytes([unit_bytes[0], unit_bytes[1]]),
+        Encoding::Utf16Be => u16::from_be_bytes([unit_bytes[0], unit_bytes[1]]),
+        _ => unit_bytes[0] as u16,
+    };
+    let unit_len = if encoding.is_utf16() { 2 } else { 1 };
+
+    // lone CRs only end lines in files without any LF, as far as the first block tells
+    let first_units = || block[bom_len..n_read].chunks_exact(unit_len).map(get_unit);
+    let (lf, cr) = (b'\n' as u16, b'\r' as u16);
+    let line_terminator =
+        if first_units().all(|unit| unit != lf) && first_units().any(|unit| unit == cr) {
+            cr
+        } else {
+            lf
+        };
+
+    // a file that turns out not to be valid UTF-8 has the same line offsets in the
+    // single byte encodings
+    let mut is_checking_utf8 = is_encoding_detected && encoding == Encoding::Utf8 && !has_bom;
+    let mut has_c1_bytes = false;
+    let mut utf8_carry = Vec::new();
+
+    let mut line_offsets = if file_len > bom_len as u64 {
+        vec![bom_len as u64]
+    } else {
+        vec![]
+    };
+    let (mut n_lf, mut n_crlf, mut n_cr) = (0, 0, 0);
+    let mut previous_unit = 0;
+    let mut pos = bom_len as u64;
+    loop {
+        let n_read = read_block(&file, pos, &mut block)?;
+        if n_read == 0 {
+            break;
+        }
+
+        let bytes = &block[..n_read];
+        for (unit_idx, unit) in bytes.chunks_exact(unit_len).map(get_unit).enumerate() {
+            if unit == line_terminator {
+                if line_terminator == cr {
+                    n_cr += 1;
+                } else if previous_unit == cr {
+                    n_crlf += 1;
+                } else {
+                    n_lf += 1;
+                }
+
+                line_offsets.push(pos + ((unit_idx + 1) * unit_len) as u64);
+            }
+
+            previous_unit = unit;
+        }
+
+        if is_checking_utf8 {
+            has_c1_bytes |= bytes.iter().any(|byte| (0x80..0xA0).contains(byte));
+            utf8_carry.extend_from_slice(bytes);
+            match str::from_utf8(&utf8_carry) {
+                Ok(_) => utf8_carry.clear(),
+                // the last character continues in the next block
+                Err(err) if err.error_len().is_none() => {
+                    utf8_carry.drain(..err.valid_up_to());
+                }
+                Err(_) => is_checking_utf8 = false,
+            }
+        }
+
+        pos += n_read as u64;
+        on_progress(pos, file_len);
+    }
+
+    let is_invalid_utf8 = !is_checking_utf8 || !utf8_carry.is_empty();
+    if is_encoding_detected && encoding == Encoding::Utf8 && !has_bom && is_invalid_utf8 {
+        encoding = if has_c1_bytes {
+            Encoding::Windows1252
+        } else {
+            Encoding::Latin1
+        };
+    }
+
+    let end_of_line_seq = get_end_of_line_seq(n_lf, n_crlf, n_cr);
+    let pager = Pager::new(file.try_clone()?, encoding, end_of_line_seq);
+
+    Ok(ReadFile {
+        file: Some(file),
+        path: path.as_ref().to_owned(),
+        lines: LineStore::paged(pager, &line_offsets, file_len),
+        line_offsets,
+        end_of_line_seq: end_of_line_seq.to_owned(),
+        has_mixed_line_endings: n_lf > 0 && n_crlf > 0,
+        encoding,
+        has_bom,
+    })
+}
+
+/// Fills `buf` with the bytes from `pos`, returns how many were read
+fn read_block(file: &File, pos: u64, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n_read = 0;
+    while n_read < buf.len() {
+        match file.read_at(&mut buf[n_read..], pos + n_read as u64) {
+            Ok(0) => break,
+            Ok(n) => n_read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(n_read)
+}
+
+fn get_end_of_line_seq(n_lf: usize, n_crlf: usize, n_cr: usize) -> &'static str {
+    if n_crlf > n_lf {
+        "\r\n"
+    } else if n_cr > 0 {
+        "\r"
+    } else {
+        "\n"
+    }
+}
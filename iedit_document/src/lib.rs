@@ -1,9 +1,12 @@
 mod document;
+mod encoding;
 mod io;
 mod line;
 
 pub use document::{
-    CharacterIndexable, Document, DocumentSyntax, EditOperation, InverseStack, SyntaxBlock,
-    SyntaxRule, Text,
+    CharacterIndexable, CommentTokens, Document, DocumentOptions, DocumentSyntax, EditEvent,
+    EditOperation, InverseStack, ListenerId, RegexReplacement, Selection, SyntaxBlock, SyntaxRule,
+    Text, TextObject, TextRange, TimeTravel, UndoState,
 };
-pub use line::DocumentLine;
+pub use encoding::Encoding;
+pub use line::{DocumentLine, LineStore, grapheme_width};
@@ -0,0 +1,149 @@
+use crate::line::prefix_sums::PrefixSums;
+
+/// Lines are split in blocks of about this many chars, that char to byte conversions scan
+pub(crate) const BLOCK_SIZE: usize = 128;
+
+/// Char and byte lengths of consecutive blocks of a non-ASCII line, so that char to byte
+/// conversions only scan a single block, and edits only update the blocks they touch.
+/// NOTE: blocks are split once they grow past twice `BLOCK_SIZE`, emptied ones are
+/// dropped when the blocks are laid out again
+pub(crate) struct LineIndex {
+    blocks: Vec<(usize, usize)>,
+    char_sums: PrefixSums,
+    byte_sums: PrefixSums,
+}
+
+impl LineIndex {
+    pub fn new(buf: &str) -> Self {
+        let mut index = Self {
+            blocks: get_blocks(buf),
+            char_sums: PrefixSums::new(),
+            byte_sums: PrefixSums::new(),
+        };
+        index.rebuild_sums();
+
+        index
+    }
+
+    /// Index of a line made of `len` ASCII chars, laid out without reading it
+    pub fn new_ascii(len: usize) -> Self {
+        let mut index = Self {
+            blocks: (0..len)
+                .step_by(BLOCK_SIZE)
+                .map(|start| {
+                    let block_len = BLOCK_SIZE.min(len - start);
+                    (block_len, block_len)
+                })
+                .collect(),
+            char_sums: PrefixSums::new(),
+            byte_sums: PrefixSums::new(),
+        };
+        index.rebuild_sums();
+
+        index
+    }
+
+    /// Byte offset of the char at `char_idx`, which must be within `buf`
+    pub fn char_to_byte_idx(&self, buf: &str, char_idx: usize) -> usize {
+        let (block_idx, offset) = self.char_sums.locate(char_idx);
+        let block_start = self.byte_sums.prefix_sum(block_idx);
+
+        buf[block_start..]
+            .char_indices()
+            .nth(offset)
+            .map_or(buf.len(), |(byte_idx, _)| block_start + byte_idx)
+    }
+
+    /// Index of the char starting at `byte_idx`, `None` if it is within a char
+    pub fn byte_to_char_idx(&self, buf: &str, byte_idx: usize) -> Option<usize> {
+        let (block_idx, offset) = self.byte_sums.locate(byte_idx);
+        let block_start = byte_idx - offset;
+        let block_char_idx = self.char_sums.prefix_sum(block_idx);
+
+        buf[block_start..]
+            .char_indices()
+            .take_while(|(char_byte_idx, _)| *char_byte_idx <= offset)
+            .enumerate()
+            .find(|(_, (char_byte_idx, _))| *char_byte_idx == offset)
+            .map(|(char_idx, _)| block_char_idx + char_idx)
+    }
+
+    /// Accounts for `n_chars` chars taking `n_bytes` bytes inserted at `char_idx`,
+    /// `buf` being the line once they are
+    pub fn insert(&mut self, buf: &str, char_idx: usize, n_chars: usize, n_bytes: usize) {
+        if self.blocks.is_empty() {
+            self.blocks.push((0, 0));
+            self.rebuild_sums();
+        }
+
+        // chars appended at the end of the line go to the last block
+        let block_idx = self.char_sums.locate(char_idx).0.min(self.blocks.len() - 1);
+
+        self.blocks[block_idx].0 += n_chars;
+        self.blocks[block_idx].1 += n_bytes;
+        self.char_sums.add(block_idx, n_chars);
+        self.byte_sums.add(block_idx, n_bytes);
+
+        if self.blocks[block_idx].0 > 2 * BLOCK_SIZE {
+            self.split_block(buf, block_idx);
+        }
+    }
+
+    /// Accounts for `removed` being removed from `char_idx`
+    pub fn remove(&mut self, char_idx: usize, removed: &str) {
+        let (mut block_idx, mut offset) = self.char_sums.locate(char_idx);
+        let mut rest = removed;
+
+        while !rest.is_empty() && block_idx < self.blocks.len() {
+            let (mut n_chars, mut n_bytes) = (0, 0);
+            for ch in rest.chars().take(self.blocks[block_idx].0 - offset) {
+                n_chars += 1;
+                n_bytes += ch.len_utf8();
+            }
+
+            self.blocks[block_idx].0 -= n_chars;
+            self.blocks[block_idx].1 -= n_bytes;
+            self.char_sums.sub(block_idx, n_chars);
+            self.byte_sums.sub(block_idx, n_bytes);
+
+            rest = &rest[n_bytes..];
+            block_idx += 1;
+            offset = 0;
+        }
+    }
+
+    fn split_block(&mut self, buf: &str, block_idx: usize) {
+        let block_start = self.byte_sums.prefix_sum(block_idx);
+        let block_end = block_start + self.blocks[block_idx].1;
+        let blocks = get_blocks(&buf[block_start..block_end]);
+        self.blocks.splice(block_idx..=block_idx, blocks);
+        self.blocks.retain(|(n_chars, _)| *n_chars > 0);
+        self.rebuild_sums();
+    }
+
+    fn rebuild_sums(&mut self) {
+        self.char_sums = PrefixSums::from_lens(self.blocks.iter().map(|(n_chars, _)| *n_chars));
+        self.byte_sums = PrefixSums::from_lens(self.blocks.iter().map(|(_, n_bytes)| *n_bytes));
+    }
+}
+
+/// Char and byte lengths of the blocks of `BLOCK_SIZE` chars of `text`
+fn get_blocks(text: &str) -> Vec<(usize, usize)> {
+    let mut blocks = Vec::with_capacity(text.len() / BLOCK_SIZE + 1);
+    let mut block_start = 0;
+    let mut n_chars = 0;
+    for (byte_idx, _) in text.char_indices() {
+        if n_chars == BLOCK_SIZE {
+            blocks.push((n_chars, byte_idx - block_start));
+            block_start = byte_idx;
+            n_chars = 0;
+        }
+        n_chars += 1;
+    }
+
+    if n_chars > 0 {
+        blocks.push((n_chars, text.len() - block_start));
+    }
+
+    blocks
+}
@@ -1,12 +1,24 @@
 mod char_indexable;
+mod index;
+mod prefix_sums;
+mod store;
 
 pub use char_indexable::CharacterIndexable;
+use index::{BLOCK_SIZE, LineIndex};
 use std::ops::RangeBounds;
+pub use store::LineStore;
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+use unicode_width::UnicodeWidthStr;
 
 /// A line in a document. This is mostly a wrapper around String with some additional metadata.
 /// All indexing operations are done in terms of characters, not bytes.
 pub struct DocumentLine {
     buf: String,
+    /// Cached number of characters in `buf`, kept up to date from the edited text
+    n_chars: usize,
+    /// NOTE: this is only maintained for non-ASCII lines longer than two blocks,
+    /// for pure ASCII lines char and byte indices coincide and short lines are scanned whole.
+    index: Option<LineIndex>,
     /// NOTE: every line is considered dirty when created or modified.
     pub needs_render: bool,
     pub has_been_modified: bool,
@@ -16,6 +28,8 @@ impl Default for DocumentLine {
     fn default() -> Self {
         DocumentLine {
             buf: String::new(),
+            n_chars: 0,
+            index: None,
             needs_render: true,
             has_been_modified: true,
         }
@@ -24,8 +38,14 @@ impl Default for DocumentLine {
 
 impl DocumentLine {
     pub fn new(line: String) -> Self {
+        let n_chars = line.chars().count();
+        let index =
+            (n_chars != line.len() && n_chars > 2 * BLOCK_SIZE).then(|| LineIndex::new(&line));
+
         DocumentLine {
             buf: line,
+            n_chars,
+            index,
             needs_render: true,
             has_been_modified: true,
         }
@@ -37,9 +57,48 @@ impl DocumentLine {
         self.needs_render = true;
     }
 
+    #[inline(always)]
+    fn is_ascii(&self) -> bool {
+        self.n_chars == self.buf.len()
+    }
+
+    /// Updates the char count and the index once `n_chars` chars taking `n_bytes` bytes
+    /// have been inserted at `char_idx`
+    fn index_insertion(&mut self, char_idx: usize, n_chars: usize, n_bytes: usize) {
+        let was_ascii = self.is_ascii_before(n_bytes);
+        self.n_chars += n_chars;
+        let is_scanned_whole = self.is_ascii() || self.n_chars <= 2 * BLOCK_SIZE;
+
+        match &mut self.index {
+            Some(index) => index.insert(&self.buf, char_idx, n_chars, n_bytes),
+            None if is_scanned_whole => {}
+            // the line was laid out in blocks of ASCII chars, only the inserted text is read
+            None if was_ascii => {
+                let mut index = LineIndex::new_ascii(self.n_chars - n_chars);
+                index.insert(&self.buf, char_idx, n_chars, n_bytes);
+                self.index = Some(index);
+            }
+            None => self.index = Some(LineIndex::new(&self.buf)),
+        }
+    }
+
+    /// Whether the line was ASCII before `n_bytes` bytes were inserted in it
+    #[inline(always)]
+    fn is_ascii_before(&self, n_bytes: usize) -> bool {
+        self.n_chars == self.buf.len() - n_bytes
+    }
+
+    /// Updates the char count and the index once `removed` has been removed from `char_idx`
+    fn index_removal(&mut self, char_idx: usize, removed: &str) {
+        self.n_chars -= removed.chars().count();
+        if let Some(index) = &mut self.index {
+            index.remove(char_idx, removed);
+        }
+    }
+
     #[inline(always)]
     pub fn len(&self) -> usize {
-        self.buf.chars().count()
+        self.n_chars
     }
 
     #[inline(always)]
@@ -54,20 +113,27 @@ impl DocumentLine {
 
     #[inline(always)]
     pub fn push(&mut self, ch: char) {
+        let n_chars = self.n_chars;
         self.buf.push(ch);
+        self.index_insertion(n_chars, 1, ch.len_utf8());
         self.set_dirty();
     }
 
     #[inline(always)]
     pub fn push_str(&mut self, string: &str) {
+        let n_chars = self.n_chars;
         self.buf.push_str(string);
+        self.index_insertion(n_chars, string.chars().count(), string.len());
         self.set_dirty();
     }
 
+    /// Keeps the first `new_len` chars of the line.
+    /// NOTE: `new_len` counts chars, like every other index of `DocumentLine`
     #[inline(always)]
     pub fn truncate(&mut self, new_len: usize) {
-        let byte_idx = self.char_to_byte_idx(new_len.saturating_sub(1));
-        self.buf.truncate(byte_idx);
+        let byte_idx = self.char_to_byte_idx(new_len);
+        let removed = self.buf.split_off(byte_idx);
+        self.index_removal(new_len, &removed);
         self.set_dirty();
     }
 
@@ -75,6 +141,7 @@ impl DocumentLine {
     pub fn insert(&mut self, idx: usize, ch: char) {
         let byte_idx = self.char_to_byte_idx(idx);
         self.buf.insert(byte_idx, ch);
+        self.index_insertion(idx, 1, ch.len_utf8());
         self.set_dirty();
     }
 
@@ -82,12 +149,17 @@ impl DocumentLine {
     pub fn insert_str(&mut self, idx: usize, string: &str) {
         let byte_idx = self.char_to_byte_idx(idx);
         self.buf.insert_str(byte_idx, string);
+        self.index_insertion(idx, string.chars().count(), string.len());
         self.set_dirty();
     }
 
     #[inline(always)]
     pub fn at(&self, idx: usize) -> Option<char> {
-        self.buf.chars().nth(idx)
+        if idx >= self.n_chars {
+            return None;
+        }
+
+        self.buf[self.char_to_byte_idx(idx)..].chars().next()
     }
 
     pub fn remove(&mut self, idx: usize) -> Option<char> {
@@ -95,6 +167,7 @@ impl DocumentLine {
         let ch = self.buf[byte_idx..].chars().next()?;
         let ch_len = ch.len_utf8();
         self.buf.replace_range(byte_idx..byte_idx + ch_len, "");
+        self.index_removal(idx, ch.encode_utf8(&mut [0; 4]));
         self.set_dirty();
 
         Some(ch)
@@ -102,9 +175,15 @@ impl DocumentLine {
 
     #[inline]
     pub fn remove_range(&mut self, range: impl RangeBounds<usize>) -> String {
+        let first_char_idx = match range.start_bound() {
+            std::ops::Bound::Included(&idx) => idx,
+            std::ops::Bound::Excluded(&idx) => idx + 1,
+            std::ops::Bound::Unbounded => 0,
+        };
         let byte_range = self.char_to_byte_range(range);
         let removed = self.buf[byte_range.clone()].to_owned();
         self.buf.replace_range(byte_range, "");
+        self.index_removal(first_char_idx, &removed);
         self.set_dirty();
 
         removed
@@ -124,54 +203,129 @@ impl DocumentLine {
 
     #[inline]
     pub fn char_to_byte_idx(&self, char_idx: usize) -> usize {
-        self.buf
-            .char_indices()
-            .nth(char_idx)
-            .map(|(i, _)| i)
-            .unwrap_or(self.buf.len())
+        if char_idx >= self.n_chars {
+            return self.buf.len();
+        } else if self.is_ascii() {
+            return char_idx;
+        }
+
+        match &self.index {
+            Some(index) => index.char_to_byte_idx(&self.buf, char_idx),
+            None => self
+                .buf
+                .char_indices()
+                .nth(char_idx)
+                .map_or(self.buf.len(), |(byte_idx, _)| byte_idx),
+        }
     }
 
     #[inline]
     pub fn byte_to_char_idx(&self, byte_idx: usize) -> Option<usize> {
-        self.buf
-            .char_indices()
-            .enumerate()
-            .find(|(_, (char_byte_idx, _))| *char_byte_idx == byte_idx)
-            .map(|(char_idx, _)| char_idx)
+        if byte_idx >= self.buf.len() {
+            return None;
+        } else if self.is_ascii() {
+            return Some(byte_idx);
+        }
+
+        match &self.index {
+            Some(index) => index.byte_to_char_idx(&self.buf, byte_idx),
+            None => self
+                .buf
+                .char_indices()
+                .take_while(|(char_byte_idx, _)| *char_byte_idx <= byte_idx)
+                .position(|(char_byte_idx, _)| char_byte_idx == byte_idx),
+        }
     }
 
+    /// NOTE: a char in the middle of a grapheme cluster is placed after the whole cluster
     #[inline]
     pub fn char_to_visual_idx(&self, char_idx: usize, tab_size: usize) -> usize {
         let mut visual_idx = 0;
-        for ch in self.iter().take(char_idx) {
-            if ch != '\t' {
-                visual_idx += 1;
-            } else {
-                visual_idx += tab_size - (visual_idx % tab_size)
+        for (grapheme_idx, grapheme) in self.graphemes() {
+            if grapheme_idx >= char_idx {
+                break;
             }
+
+            visual_idx += grapheme_width(grapheme, visual_idx, tab_size);
         }
 
         visual_idx
     }
 
+    /// Returns the start of the grapheme cluster displayed at `visual_idx`
     #[inline]
     pub fn visual_to_char_idx(&self, visual_idx: usize, tab_size: usize) -> usize {
         let mut running_visual_idx = 0;
-        for (char_idx, ch) in self.iter().enumerate() {
-            running_visual_idx += if ch == '\t' {
-                tab_size - (running_visual_idx % tab_size)
-            } else {
-                1
-            };
+        for (grapheme_idx, grapheme) in self.graphemes() {
+            running_visual_idx += grapheme_width(grapheme, running_visual_idx, tab_size);
 
             if running_visual_idx > visual_idx {
-                return char_idx;
+                return grapheme_idx;
             }
         }
 
         self.len()
     }
 
+    /// Iterates over the extended grapheme clusters of the line, along with the
+    /// char index they start at
+    #[inline]
+    pub fn graphemes(&self) -> impl Iterator<Item = (usize, &str)> {
+        let mut char_idx = 0;
+        self.buf.graphemes(true).map(move |grapheme| {
+            let grapheme_idx = char_idx;
+            char_idx += grapheme.chars().count();
+            (grapheme_idx, grapheme)
+        })
+    }
+
+    /// Start of the grapheme cluster containing the char at `idx`
+    pub fn grapheme_start(&self, idx: usize) -> usize {
+        if self.is_ascii() || idx >= self.n_chars {
+            return idx;
+        }
+
+        // only the text around `idx` is looked at to find the boundaries
+        let byte_idx = self.char_to_byte_idx(idx);
+        let mut cursor = GraphemeCursor::new(byte_idx, self.buf.len(), true);
+        match cursor.is_boundary(&self.buf, 0) {
+            Ok(false) => cursor
+                .prev_boundary(&self.buf, 0)
+                .ok()
+                .flatten()
+                .and_then(|boundary| self.byte_to_char_idx(boundary))
+                .unwrap_or_default(),
+            _ => idx,
+        }
+    }
+
+    /// Start of the grapheme cluster following the one containing the char at `idx`
+    pub fn next_grapheme_idx(&self, idx: usize) -> usize {
+        if self.is_ascii() || idx >= self.n_chars {
+            return idx + 1;
+        }
+
+        let byte_idx = self.char_to_byte_idx(idx);
+        let mut cursor = GraphemeCursor::new(byte_idx, self.buf.len(), true);
+        cursor
+            .next_boundary(&self.buf, 0)
+            .ok()
+            .flatten()
+            .and_then(|boundary| self.byte_to_char_idx(boundary))
+            .unwrap_or(self.n_chars)
+    }
+
+    /// Start of the grapheme cluster preceding the char at `idx`
+    pub fn previous_grapheme_idx(&self, idx: usize) -> usize {
+        if idx == 0 {
+            return 0;
+        } else if idx > self.n_chars {
+            return idx - 1;
+        }
+
+        self.grapheme_start(idx - 1)
+    }
+
     #[inline(always)]
     pub fn iter(&self) -> impl Iterator<Item = char> {
         self.buf.chars()
@@ -181,6 +335,7 @@ impl DocumentLine {
     pub fn split_off(&mut self, idx: usize) -> Self {
         let byte_idx = self.char_to_byte_idx(idx);
         let other_buf = self.buf.split_off(byte_idx);
+        self.index_removal(idx, &other_buf);
         self.set_dirty();
         DocumentLine::new(other_buf)
     }
@@ -214,14 +369,102 @@ impl DocumentLine {
     }
 }
 
+/// Number of terminal columns taken by a grapheme cluster displayed at `visual_idx`:
+/// 2 for East Asian wide characters, 0 for zero width ones, up to the next tab stop for tabs
+#[inline]
+pub fn grapheme_width(grapheme: &str, visual_idx: usize, tab_size: usize) -> usize {
+    if grapheme == "\t" {
+        tab_size - (visual_idx % tab_size)
+    } else {
+        grapheme.width()
+    }
+}
+
 impl AsRef<str> for DocumentLine {
     fn as_ref(&self) -> &str {
         &self.buf
     }
 }
 
-impl Into<String> for DocumentLine {
-    fn into(self) -> String {
-        self.buf
+impl From<DocumentLine> for String {
+    fn from(line: DocumentLine) -> Self {
+        line.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DocumentLine;
+
+    /// Deterministic xorshift, to generate edits
+    fn next_random(state: &mut u64) -> usize {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state as usize
+    }
+
+    fn assert_indexed(line: &DocumentLine, expected: &str) {
+        assert_eq!(line.as_ref(), expected);
+        assert_eq!(line.len(), expected.chars().count());
+        for (char_idx, (byte_idx, _)) in expected.char_indices().enumerate() {
+            assert_eq!(line.char_to_byte_idx(char_idx), byte_idx);
+            assert_eq!(line.byte_to_char_idx(byte_idx), Some(char_idx));
+        }
+    }
+
+    #[test]
+    fn keeps_index_through_edits() {
+        let pieces = ["a", "é", "bc", "日本", "😀x", "", "long ascii text "];
+        let mut state = 0x2545_f491_4f6c_dd1d;
+        let mut line = DocumentLine::new("x".repeat(600));
+        let mut expected = "x".repeat(600);
+
+        for _ in 0..2000 {
+            let n_chars = expected.chars().count();
+            let char_idx = next_random(&mut state) % (n_chars + 1);
+            let byte_idx = expected
+                .char_indices()
+                .nth(char_idx)
+                .map_or(expected.len(), |(byte_idx, _)| byte_idx);
+
+            if next_random(&mut state).is_multiple_of(3) && char_idx < n_chars {
+                let n_removed = 1 + next_random(&mut state) % 4;
+                let end_idx = expected
+                    .char_indices()
+                    .nth(char_idx + n_removed)
+                    .map_or(expected.len(), |(byte_idx, _)| byte_idx);
+                let removed = line.remove_range(char_idx..char_idx + n_removed);
+                assert_eq!(removed, expected[byte_idx..end_idx]);
+                expected.replace_range(byte_idx..end_idx, "");
+            } else {
+                let piece = pieces[next_random(&mut state) % pieces.len()];
+                line.insert_str(char_idx, piece);
+                expected.insert_str(byte_idx, piece);
+            }
+        }
+
+        assert!(line.index.is_some());
+        assert_indexed(&line, &expected);
+
+        let tail = line.split_off(300);
+        let split_byte_idx = expected.char_indices().nth(300).unwrap().0;
+        assert_indexed(&tail, &expected[split_byte_idx..]);
+        expected.truncate(split_byte_idx);
+        assert_indexed(&line, &expected);
+
+        line.truncate(10);
+        assert_indexed(&line, &expected.chars().take(10).collect::<String>());
+    }
+
+    #[test]
+    fn moves_by_grapheme_cluster() {
+        let line = DocumentLine::new(format!("{}e\u{301}👍🏽z", "é".repeat(300)));
+        assert_eq!(line.grapheme_start(301), 300);
+        assert_eq!(line.next_grapheme_idx(300), 302);
+        assert_eq!(line.next_grapheme_idx(302), 304);
+        assert_eq!(line.grapheme_start(303), 302);
+        assert_eq!(line.previous_grapheme_idx(304), 302);
+        assert_eq!(line.next_grapheme_idx(304), 305);
     }
 }
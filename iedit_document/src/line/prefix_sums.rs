@@ -0,0 +1,135 @@
+/// Lengths of consecutive spans (lines of chunks, chars of blocks, ...) stored as a
+/// Fenwick tree, so that updating a length, summing the lengths before a span and
+/// finding the span containing a position are all logarithmic.
+#[derive(Default, Clone)]
+pub(crate) struct PrefixSums {
+    /// 1-based, `tree[i]` holds the sum of the `i & i.wrapping_neg()` lengths ending at `i`
+    tree: Vec<usize>,
+}
+
+impl PrefixSums {
+    pub fn new() -> Self {
+        Self { tree: vec![0] }
+    }
+
+    pub fn from_lens(lens: impl IntoIterator<Item = usize>) -> Self {
+        let mut tree = vec![0];
+        tree.extend(lens);
+
+        // each node adds its sum to its parent, in linear time
+        for idx in 1..tree.len() {
+            let parent_idx = idx + (idx & idx.wrapping_neg());
+            if parent_idx < tree.len() {
+                tree[parent_idx] += tree[idx];
+            }
+        }
+
+        Self { tree }
+    }
+
+    /// Number of spans
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.tree.len().saturating_sub(1)
+    }
+
+    pub fn push(&mut self, len: usize) {
+        if self.tree.is_empty() {
+            self.tree.push(0);
+        }
+
+        // the new node covers the nodes ending right before it, down to its lowest bit
+        let idx = self.tree.len();
+        let covered_start = idx - (idx & idx.wrapping_neg());
+        let covered_sum = self.prefix_sum(idx - 1) - self.prefix_sum(covered_start);
+        self.tree.push(len + covered_sum);
+    }
+
+    pub fn add(&mut self, span_idx: usize, delta: usize) {
+        let mut idx = span_idx + 1;
+        while idx < self.tree.len() {
+            self.tree[idx] += delta;
+            idx += idx & idx.wrapping_neg();
+        }
+    }
+
+    pub fn sub(&mut self, span_idx: usize, delta: usize) {
+        let mut idx = span_idx + 1;
+        while idx < self.tree.len() {
+            self.tree[idx] -= delta;
+            idx += idx & idx.wrapping_neg();
+        }
+    }
+
+    /// Sum of the lengths of the spans before `span_idx`
+    pub fn prefix_sum(&self, span_idx: usize) -> usize {
+        let mut idx = span_idx.min(self.len());
+        let mut sum = 0;
+        while idx > 0 {
+            sum += self.tree[idx];
+            idx -= idx & idx.wrapping_neg();
+        }
+
+        sum
+    }
+
+    /// Returns (span index, offset within the span) of position `pos`, skipping empty spans.
+    /// Positions past the last span map to `(self.len(), pos - total length)`
+    pub fn locate(&self, pos: usize) -> (usize, usize) {
+        let n_spans = self.len();
+        let mut span_idx = 0;
+        let mut offset = pos;
+        let mut step = if n_spans == 0 {
+            0
+        } else {
+            1 << n_spans.ilog2()
+        };
+
+        // the last span starting at or before `pos` is after all the ones ending at or before it
+        while step > 0 {
+            if span_idx + step <= n_spans && self.tree[span_idx + step] <= offset {
+                span_idx += step;
+                offset -= self.tree[span_idx];
+            }
+            step >>= 1;
+        }
+
+        (span_idx, offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PrefixSums;
+
+    #[test]
+    fn sums_and_locates_spans() {
+        let lens = [3, 0, 5, 1, 0, 0, 7, 2, 4];
+        let mut sums = PrefixSums::new();
+        for len in lens {
+            sums.push(len);
+        }
+
+        let built = PrefixSums::from_lens(lens);
+        for span_idx in 0..=lens.len() {
+            let expected = lens[..span_idx].iter().sum::<usize>();
+            assert_eq!(sums.prefix_sum(span_idx), expected);
+            assert_eq!(built.prefix_sum(span_idx), expected);
+        }
+
+        assert_eq!(sums.locate(0), (0, 0));
+        assert_eq!(sums.locate(2), (0, 2));
+        // the empty span is skipped
+        assert_eq!(sums.locate(3), (2, 0));
+        assert_eq!(sums.locate(8), (3, 0));
+        assert_eq!(sums.locate(9), (6, 0));
+        assert_eq!(sums.locate(22), (9, 0));
+
+        sums.add(1, 4);
+        sums.sub(6, 7);
+        assert_eq!(sums.prefix_sum(2), 7);
+        assert_eq!(sums.prefix_sum(7), 13);
+        assert_eq!(sums.locate(3), (1, 0));
+        assert_eq!(sums.locate(13), (7, 0));
+    }
+}
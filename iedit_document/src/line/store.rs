@@ -0,0 +1,838 @@
+use std::{
+    cell::OnceCell,
+    fs::File,
+    ops::{Index, IndexMut, Range},
+};
+
+use crate::{
+    io::Pager,
+    line::{DocumentLine, prefix_sums::PrefixSums},
+};
+
+/// Lines are kept in chunks of roughly this size. Structural edits only move
+/// lines within a single chunk and update its length in the index of chunk lengths,
+/// which is only rebuilt when chunks are split, merged or dropped.
+const CHUNK_SIZE: usize = 512;
+
+/// Chunks shrinking below this many lines are merged with a neighbour
+const MIN_CHUNK_SIZE: usize = CHUNK_SIZE / 4;
+
+/// Storage for the lines of a document.
+///
+/// This behaves like a `Vec<DocumentLine>` (indexing, `get`, `insert`, `remove`,
+/// `drain`, iteration) but keeps lines in chunks with an index of their lengths,
+/// so that random access and inserting or removing lines are logarithmic in the
+/// number of chunks, and don't shift the whole document in memory.
+///
+/// The chunks of a large file are paged: their lines are only read from the file
+/// when accessed, and can be released again while they are unmodified.
+#[derive(Default)]
+pub struct LineStore {
+    chunks: Vec<Chunk>,
+    /// Number of lines of each chunk
+    chunk_lens: PrefixSums,
+    len: usize,
+    pager: Option<Pager>,
+}
+
+#[derive(Default)]
+struct Chunk {
+    lines: OnceCell<Vec<DocumentLine>>,
+    /// Where the lines of the chunk are in the paged file, until lines are inserted
+    /// or removed
+    page: Option<Page>,
+}
+
+struct Page {
+    bytes: Range<u64>,
+    n_lines: usize,
+}
+
+impl Chunk {
+    fn new(lines: Vec<DocumentLine>) -> Self {
+        Self {
+            lines: OnceCell::from(lines),
+            page: None,
+        }
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        match (self.lines.get(), &self.page) {
+            (Some(lines), _) => lines.len(),
+            (None, Some(page)) => page.n_lines,
+            (None, None) => 0,
+        }
+    }
+
+    /// Reads the lines of the page, without keeping them
+    fn read_page(&self, pager: Option<&Pager>) -> Vec<DocumentLine> {
+        match (pager, &self.page) {
+            (Some(pager), Some(page)) => pager.read_lines(page.bytes.clone(), page.n_lines),
+            _ => Vec::new(),
+        }
+    }
+
+    #[inline]
+    fn lines(&self, pager: Option<&Pager>) -> &Vec<DocumentLine> {
+        self.lines.get_or_init(|| self.read_page(pager))
+    }
+
+    #[inline]
+    fn lines_mut(&mut self, pager: Option<&Pager>) -> &mut Vec<DocumentLine> {
+        self.lines(pager);
+        self.lines.get_mut().unwrap()
+    }
+
+    /// Lines that are about to be inserted or removed, they no longer match the page
+    #[inline]
+    fn detached_lines_mut(&mut self, pager: Option<&Pager>) -> &mut Vec<DocumentLine> {
+        self.lines(pager);
+        self.page = None;
+        self.lines.get_mut().unwrap()
+    }
+}
+
+impl LineStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lines of a file read through `pager`, starting at `line_offsets` and
+    /// ending at `file_len`
+    pub(crate) fn paged(pager: Pager, line_offsets: &[u64], file_len: u64) -> Self {
+        let mut store = Self::new();
+        store.set_pages(pager, line_offsets, file_len);
+
+        store
+    }
+
+    #[inline]
+    pub fn is_paged(&self) -> bool {
+        self.pager.is_some()
+    }
+
+    /// Whether the lines that aren't loaded are read from `file`
+    pub fn is_paged_from(&self, file: &File) -> bool {
+        self.pager
+            .as_ref()
+            .is_some_and(|pager| pager.reads_from(file))
+    }
+
+    /// Points every chunk to where its lines are in the file read by `pager`, which
+    /// start at `line_offsets`. Chunks keep their loaded lines, that are released later on
+    pub(crate) fn set_pages(&mut self, pager: Pager, line_offsets: &[u64], file_len: u64) {
+        if self.chunks.is_empty() {
+            // the lines of a new store are laid out in chunks of the same size
+            for chunk_start in (0..line_offsets.len()).step_by(CHUNK_SIZE) {
+                let n_lines = CHUNK_SIZE.min(line_offsets.len() - chunk_start);
+                self.chunks.push(Chunk {
+                    lines: OnceCell::new(),
+                    page: Some(Page {
+                        bytes: 0..0,
+                        n_lines,
+                    }),
+                });
+            }
+
+            self.rebuild_index();
+        }
+
+        let mut chunk_start = 0;
+        for chunk in self.chunks.iter_mut() {
+            let n_lines = chunk.len();
+            let start = line_offsets.get(chunk_start).copied().unwrap_or(file_len);
+            let end = line_offsets
+                .get(chunk_start + n_lines)
+                .copied()
+                .unwrap_or(file_len);
+
+            chunk.page = Some(Page {
+                bytes: start..end.max(start),
+                n_lines,
+            });
+            chunk_start += n_lines;
+        }
+
+        self.pager = Some(pager);
+    }
+
+    /// Drops the lines of the paged chunks outside of `keep` that are unmodified,
+    /// they are read from the file again when accessed
+    pub fn release_pages(&mut self, keep: Range<usize>) {
+        if self.pager.is_none() {
+            return;
+        }
+
+        let mut chunk_start = 0;
+        for chunk in self.chunks.iter_mut() {
+            let chunk_end = chunk_start + chunk.len();
+            if chunk.page.is_some()
+                && (chunk_end <= keep.start || chunk_start >= keep.end)
+                && chunk
+                    .lines
+                    .get()
+                    .is_some_and(|lines| lines.iter().all(|line| !line.has_been_modified))
+            {
+                chunk.lines.take();
+            }
+            chunk_start = chunk_end;
+        }
+    }
+
+    /// Reads in all the lines from `line_idx`, e.g. before their file gets overwritten
+    pub fn load_from(&mut self, line_idx: usize) {
+        if self.pager.is_none() {
+            return;
+        }
+
+        let first_chunk_idx = self.locate(line_idx).0;
+        for chunk in self.chunks.iter_mut().skip(first_chunk_idx) {
+            chunk.detached_lines_mut(self.pager.as_ref());
+        }
+    }
+
+    /// Reads in the paged chunks after the one of `line_idx` whose lines start in the file
+    /// before `file_pos`, so that they survive the file being overwritten up to there
+    pub fn load_pages_after(&self, line_idx: usize, file_pos: u64) {
+        if self.pager.is_none() {
+            return;
+        }
+
+        let chunk_idx = self.locate(line_idx).0;
+        for chunk in self.chunks.iter().skip(chunk_idx + 1) {
+            match &chunk.page {
+                // the chunks are laid out in the file in order
+                Some(page) if page.bytes.start >= file_pos => return,
+                Some(_) => {
+                    chunk.lines(self.pager.as_ref());
+                }
+                None => {}
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns (chunk index, index within chunk) of the line at `idx`.
+    /// `idx == self.len()` maps to the end of the last chunk.
+    #[inline]
+    fn locate(&self, idx: usize) -> (usize, usize) {
+        if idx >= self.len {
+            return match self.chunks.len().checked_sub(1) {
+                Some(last_chunk_idx) => (last_chunk_idx, idx - self.chunk_start(last_chunk_idx)),
+                None => (0, idx),
+            };
+        }
+
+        self.chunk_lens.locate(idx)
+    }
+
+    /// Index of the first line of the chunk at `chunk_idx`
+    #[inline]
+    fn chunk_start(&self, chunk_idx: usize) -> usize {
+        self.chunk_lens.prefix_sum(chunk_idx)
+    }
+
+    fn rebuild_index(&mut self) {
+        self.chunk_lens = PrefixSums::from_lens(self.chunks.iter().map(Chunk::len));
+        self.len = self.chunk_lens.prefix_sum(self.chunks.len());
+    }
+
+    /// Merges the chunk at `chunk_idx` with the next one, or the previous one, if it
+    /// has become too small, splitting them again if they are too large together
+    fn merge_underfull_chunk(&mut self, chunk_idx: usize) {
+        if chunk_idx >= self.chunks.len()
+            || self.chunks.len() < 2
+            || self.chunks[chunk_idx].len() >= MIN_CHUNK_SIZE
+        {
+            return;
+        }
+
+        let first_chunk_idx = chunk_idx.min(self.chunks.len() - 2);
+        let pager = self.pager.as_ref();
+        let mut next_chunk = self.chunks.remove(first_chunk_idx + 1);
+        let lines = self.chunks[first_chunk_idx].detached_lines_mut(pager);
+        lines.append(next_chunk.detached_lines_mut(pager));
+
+        if lines.len() >= 2 * CHUNK_SIZE {
+            let tail = lines.split_off(lines.len() / 2);
+            self.chunks.insert(first_chunk_idx + 1, Chunk::new(tail));
+        }
+
+        self.rebuild_index();
+    }
+
+    #[inline]
+    pub fn get(&self, idx: usize) -> Option<&DocumentLine> {
+        if idx >= self.len {
+            return None;
+        }
+
+        let (chunk_idx, offset) = self.locate(idx);
+        self.chunks[chunk_idx]
+            .lines(self.pager.as_ref())
+            .get(offset)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut DocumentLine> {
+        if idx >= self.len {
+            return None;
+        }
+
+        let (chunk_idx, offset) = self.locate(idx);
+        self.chunks[chunk_idx]
+            .lines_mut(self.pager.as_ref())
+            .get_mut(offset)
+    }
+
+    #[inline]
+    pub fn first(&self) -> Option<&DocumentLine> {
+        self.chunks
+            .first()
+            .and_then(|chunk| chunk.lines(self.pager.as_ref()).first())
+    }
+
+    #[inline]
+    pub fn last(&self) -> Option<&DocumentLine> {
+        self.chunks
+            .last()
+            .and_then(|chunk| chunk.lines(self.pager.as_ref()).last())
+    }
+
+    #[inline]
+    pub fn last_mut(&mut self) -> Option<&mut DocumentLine> {
+        self.chunks
+            .last_mut()
+            .and_then(|chunk| chunk.lines_mut(self.pager.as_ref()).last_mut())
+    }
+
+    pub fn push(&mut self, line: DocumentLine) {
+        match self.chunks.last_mut() {
+            Some(chunk) if chunk.len() < CHUNK_SIZE => {
+                chunk.detached_lines_mut(self.pager.as_ref()).push(line);
+                self.chunk_lens.add(self.chunks.len() - 1, 1);
+            }
+            _ => {
+                let mut lines = Vec::with_capacity(CHUNK_SIZE);
+                lines.push(line);
+                self.chunks.push(Chunk::new(lines));
+                self.chunk_lens.push(1);
+            }
+        }
+
+        self.len += 1;
+    }
+
+    pub fn extend(&mut self, lines: impl IntoIterator<Item = DocumentLine>) {
+        for line in lines {
+            self.push(line);
+        }
+    }
+
+    /// Inserts a line at `idx`, shifting all lines after it down by one.
+    ///
+    /// Panics if `idx > self.len()`
+    pub fn insert(&mut self, idx: usize, line: DocumentLine) {
+        assert!(idx <= self.len, "insertion index out of bounds");
+        if idx == self.len {
+            return self.push(line);
+        }
+
+        let (chunk_idx, offset) = self.locate(idx);
+        let chunk = self.chunks[chunk_idx].detached_lines_mut(self.pager.as_ref());
+        chunk.insert(offset, line);
+
+        self.len += 1;
+        if chunk.len() >= 2 * CHUNK_SIZE {
+            let tail = chunk.split_off(CHUNK_SIZE);
+            self.chunks.insert(chunk_idx + 1, Chunk::new(tail));
+            self.rebuild_index();
+        } else {
+            self.chunk_lens.add(chunk_idx, 1);
+        }
+    }
+
+    /// Removes the line at `idx`, shifting all lines after it up by one.
+    ///
+    /// Panics if `idx >= self.len()`
+    pub fn remove(&mut self, idx: usize) -> DocumentLine {
+        assert!(idx < self.len, "removal index out of bounds");
+
+        let (chunk_idx, offset) = self.locate(idx);
+        let line = self.chunks[chunk_idx]
+            .detached_lines_mut(self.pager.as_ref())
+            .remove(offset);
+        self.chunk_lens.sub(chunk_idx, 1);
+        self.len -= 1;
+        self.merge_underfull_chunk(chunk_idx);
+
+        line
+    }
+
+    /// Removes the lines in `range` and returns them in order
+    pub fn drain(&mut self, range: Range<usize>) -> std::vec::IntoIter<DocumentLine> {
+        let Range { start, end } = range;
+        assert!(start <= end && end <= self.len, "drain range out of bounds");
+        if start == end {
+            return Vec::new().into_iter();
+        }
+
+        let (first_chunk_idx, first_offset) = self.locate(start);
+        let (last_chunk_idx, last_offset) = self.locate(end - 1);
+
+        let pager = self.pager.as_ref();
+        let mut drained = Vec::with_capacity(end - start);
+        if first_chunk_idx == last_chunk_idx {
+            drained.extend(
+                self.chunks[first_chunk_idx]
+                    .detached_lines_mut(pager)
+                    .drain(first_offset..=last_offset),
+            );
+            self.chunk_lens.sub(first_chunk_idx, drained.len());
+            self.len -= drained.len();
+        } else {
+            drained.extend(
+                self.chunks[first_chunk_idx]
+                    .detached_lines_mut(pager)
+                    .drain(first_offset..),
+            );
+            for mut chunk in self.chunks.drain(first_chunk_idx + 1..last_chunk_idx) {
+                drained.append(chunk.detached_lines_mut(pager));
+            }
+            // the last chunk has shifted to sit right after the first one
+            drained.extend(
+                self.chunks[first_chunk_idx + 1]
+                    .detached_lines_mut(pager)
+                    .drain(..=last_offset),
+            );
+            self.rebuild_index();
+        }
+
+        // the last chunk may be underfull too once the first one is merged
+        self.merge_underfull_chunk(first_chunk_idx);
+        self.merge_underfull_chunk(first_chunk_idx + 1);
+
+        drained.into_iter()
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.drain(len..self.len);
+        }
+    }
+
+    #[inline]
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            chunks: self.chunks.iter(),
+            pager: self.pager.as_ref(),
+            front: [].iter(),
+            back: [].iter(),
+            remaining: self.len,
+        }
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        IterMut {
+            chunks: self.chunks.iter_mut(),
+            pager: self.pager.as_ref(),
+            front: [].iter_mut(),
+            remaining: self.len,
+        }
+    }
+
+    /// Iterates over the lines in `range` that are loaded, along with their index.
+    /// The lines of paged chunks that haven't been read are skipped
+    pub fn loaded_lines(
+        &self,
+        range: Range<usize>,
+    ) -> impl Iterator<Item = (usize, &DocumentLine)> {
+        let Range { start, end } = range;
+        let first_chunk_idx = self.locate(start).0;
+        self.chunks
+            .iter()
+            .skip(first_chunk_idx)
+            .scan(self.chunk_start(first_chunk_idx), |chunk_start, chunk| {
+                let start = *chunk_start;
+                *chunk_start += chunk.len();
+                Some((chunk, start))
+            })
+            .take_while(move |(_, chunk_start)| *chunk_start < end)
+            .filter_map(|(chunk, chunk_start)| Some((chunk.lines.get()?, chunk_start)))
+            .flat_map(move |(lines, chunk_start)| {
+                lines
+                    .iter()
+                    .enumerate()
+                    .map(move |(offset, line)| (chunk_start + offset, line))
+                    .filter(move |(line_idx, _)| (start..end).contains(line_idx))
+            })
+    }
+
+    /// Mutable version of `loaded_lines`
+    pub fn loaded_lines_mut(
+        &mut self,
+        range: Range<usize>,
+    ) -> impl Iterator<Item = (usize, &mut DocumentLine)> {
+        let Range { start, end } = range;
+        let first_chunk_idx = self.locate(start).0;
+        let first_chunk_start = self.chunk_start(first_chunk_idx);
+        self.chunks
+            .iter_mut()
+            .skip(first_chunk_idx)
+            .scan(first_chunk_start, |chunk_start, chunk| {
+                let start = *chunk_start;
+                *chunk_start += chunk.len();
+                Some((chunk, start))
+            })
+            .take_while(move |(_, chunk_start)| *chunk_start < end)
+            .filter_map(|(chunk, chunk_start)| Some((chunk.lines.get_mut()?, chunk_start)))
+            .flat_map(move |(lines, chunk_start)| {
+                lines
+                    .iter_mut()
+                    .enumerate()
+                    .map(move |(offset, line)| (chunk_start + offset, line))
+                    .filter(move |(line_idx, _)| (start..end).contains(line_idx))
+            })
+    }
+
+    /// Calls `f` on the lines from `first_line_idx` with their index, until it returns
+    /// a value. Unlike `iter`, the paged chunks that are read are not kept in memory
+    pub fn find_map_from<T>(
+        &self,
+        first_line_idx: usize,
+        mut f: impl FnMut(usize, &DocumentLine) -> Option<T>,
+    ) -> Option<T> {
+        let (first_chunk_idx, first_offset) = self.locate(first_line_idx);
+        let mut chunk_start = self.chunk_start(first_chunk_idx);
+        for (chunk_idx, chunk) in self.chunks.iter().enumerate().skip(first_chunk_idx) {
+            let read_lines;
+            let lines = match chunk.lines.get() {
+                Some(lines) => lines,
+                None => {
+                    read_lines = chunk.read_page(self.pager.as_ref());
+                    &read_lines
+                }
+            };
+
+            let skip = if chunk_idx == first_chunk_idx {
+                first_offset
+            } else {
+                0
+            };
+
+            let found = lines
+                .iter()
+                .enumerate()
+                .skip(skip)
+                .find_map(|(offset, line)| f(chunk_start + offset, line));
+            if found.is_some() {
+                return found;
+            }
+
+            chunk_start += lines.len();
+        }
+
+        None
+    }
+
+    /// Calls `f` on the lines from `first_line_idx` with their index, stopping at the first
+    /// error. The paged chunks read to do so are released right after
+    pub fn try_for_each_from<E>(
+        &mut self,
+        first_line_idx: usize,
+        mut f: impl FnMut(usize, &mut DocumentLine) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let (first_chunk_idx, first_offset) = self.locate(first_line_idx);
+        let mut chunk_start = self.chunk_start(first_chunk_idx);
+        let pager = self.pager.as_ref();
+        for (chunk_idx, chunk) in self.chunks.iter_mut().enumerate().skip(first_chunk_idx) {
+            let was_loaded = chunk.lines.get().is_some();
+            let skip = if chunk_idx == first_chunk_idx {
+                first_offset
+            } else {
+                0
+            };
+
+            let res = chunk
+                .lines_mut(pager)
+                .iter_mut()
+                .enumerate()
+                .skip(skip)
+                .try_for_each(|(offset, line)| f(chunk_start + offset, line));
+            chunk_start += chunk.len();
+
+            // the lines were read from the file just now, they can't have been modified
+            if !was_loaded && chunk.page.is_some() {
+                chunk.lines.take();
+            }
+
+            res?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Index<usize> for LineStore {
+    type Output = DocumentLine;
+
+    #[inline]
+    fn index(&self, idx: usize) -> &Self::Output {
+        self.get(idx).expect("line index out of bounds")
+    }
+}
+
+impl IndexMut<usize> for LineStore {
+    #[inline]
+    fn index_mut(&mut self, idx: usize) -> &mut Self::Output {
+        self.get_mut(idx).expect("line index out of bounds")
+    }
+}
+
+impl From<Vec<DocumentLine>> for LineStore {
+    fn from(lines: Vec<DocumentLine>) -> Self {
+        lines.into_iter().collect()
+    }
+}
+
+impl FromIterator<DocumentLine> for LineStore {
+    fn from_iter<T: IntoIterator<Item = DocumentLine>>(iter: T) -> Self {
+        let mut store = Self::new();
+        store.extend(iter);
+
+        store
+    }
+}
+
+impl<'a> IntoIterator for &'a LineStore {
+    type Item = &'a DocumentLine;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut LineStore {
+    type Item = &'a mut DocumentLine;
+    type IntoIter = IterMut<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+pub struct Iter<'a> {
+    chunks: std::slice::Iter<'a, Chunk>,
+    pager: Option<&'a Pager>,
+    front: std::slice::Iter<'a, DocumentLine>,
+    back: std::slice::Iter<'a, DocumentLine>,
+    remaining: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a DocumentLine;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(line) = self.front.next() {
+                self.remaining -= 1;
+                return Some(line);
+            }
+
+            match self.chunks.next() {
+                Some(chunk) => self.front = chunk.lines(self.pager).iter(),
+                None => {
+                    let line = self.back.next();
+                    self.remaining -= line.is_some() as usize;
+                    return line;
+                }
+            }
+        }
+    }
+
+    /// Skips whole chunks at a time, so that `skip(n)` does not visit every line,
+    /// nor reads the skipped paged chunks
+    fn nth(&mut self, mut n: usize) -> Option<Self::Item> {
+        while n >= self.front.len() {
+            n -= self.front.len();
+            self.remaining -= self.front.len();
+            self.front = [].iter();
+            match self.chunks.next() {
+                Some(chunk) if n >= chunk.len() => {
+                    n -= chunk.len();
+                    self.remaining -= chunk.len();
+                }
+                Some(chunk) => self.front = chunk.lines(self.pager).iter(),
+                None => {
+                    self.front = [].iter();
+                    let skipped = n.min(self.back.len());
+                    self.remaining -= skipped;
+                    let line = self.back.nth(n);
+                    self.remaining -= line.is_some() as usize;
+                    return line;
+                }
+            }
+        }
+
+        self.remaining -= n + 1;
+        self.front.nth(n)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(line) = self.back.next_back() {
+                self.remaining -= 1;
+                return Some(line);
+            }
+
+            match self.chunks.next_back() {
+                Some(chunk) => self.back = chunk.lines(self.pager).iter(),
+                None => {
+                    let line = self.front.next_back();
+                    self.remaining -= line.is_some() as usize;
+                    return line;
+                }
+            }
+        }
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+pub struct IterMut<'a> {
+    chunks: std::slice::IterMut<'a, Chunk>,
+    pager: Option<&'a Pager>,
+    front: std::slice::IterMut<'a, DocumentLine>,
+    remaining: usize,
+}
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = &'a mut DocumentLine;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(line) = self.front.next() {
+                self.remaining -= 1;
+                return Some(line);
+            }
+
+            self.front = self.chunks.next()?.lines_mut(self.pager).iter_mut();
+        }
+    }
+
+    /// Skips whole chunks at a time, so that `skip(n)` does not visit every line,
+    /// nor reads the skipped paged chunks
+    fn nth(&mut self, mut n: usize) -> Option<Self::Item> {
+        while n >= self.front.len() {
+            n -= self.front.len();
+            self.remaining -= self.front.len();
+            self.front = [].iter_mut();
+            match self.chunks.next() {
+                Some(chunk) if n >= chunk.len() => {
+                    n -= chunk.len();
+                    self.remaining -= chunk.len();
+                }
+                Some(chunk) => self.front = chunk.lines_mut(self.pager).iter_mut(),
+                None => return None,
+            }
+        }
+
+        self.remaining -= n + 1;
+        self.front.nth(n)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for IterMut<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{CHUNK_SIZE, LineStore, MIN_CHUNK_SIZE};
+    use crate::DocumentLine;
+
+    fn assert_lines(store: &LineStore, expected: &[usize]) {
+        assert_eq!(store.len(), expected.len());
+        let lines = store
+            .iter()
+            .map(|line| line.as_ref().parse::<usize>().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines, expected);
+        for (line_idx, line) in expected.iter().enumerate().step_by(97) {
+            assert_eq!(store[line_idx].as_ref(), line.to_string());
+        }
+    }
+
+    #[test]
+    fn edits_lines_across_chunks() {
+        let mut expected = (0..5000).collect::<Vec<_>>();
+        let mut store = expected
+            .iter()
+            .map(|line| DocumentLine::new(line.to_string()))
+            .collect::<LineStore>();
+
+        for (line_idx, line) in [(0, 10_000), (2500, 10_001), (5002, 10_002)] {
+            store.insert(line_idx, DocumentLine::new(line.to_string()));
+            expected.insert(line_idx, line);
+        }
+        for line in 0..2 * CHUNK_SIZE {
+            store.insert(700, DocumentLine::new((20_000 + line).to_string()));
+            expected.insert(700, 20_000 + line);
+        }
+        assert_lines(&store, &expected);
+
+        for line_idx in [0, 1000, 1000, expected.len() - 4] {
+            assert_eq!(
+                store.remove(line_idx).as_ref(),
+                expected.remove(line_idx).to_string()
+            );
+        }
+        let drained = store.drain(300..2900).collect::<Vec<_>>();
+        assert_eq!(drained.len(), 2600);
+        expected.drain(300..2900);
+        assert_lines(&store, &expected);
+
+        // removing lines one by one merges the chunks they leave underfull
+        while expected.len() > 10 {
+            store.remove(5);
+            expected.remove(5);
+        }
+        assert_lines(&store, &expected);
+        assert!(
+            store
+                .chunks
+                .iter()
+                .filter(|chunk| chunk.len() < MIN_CHUNK_SIZE)
+                .count()
+                <= 1
+        );
+
+        store.truncate(3);
+        expected.truncate(3);
+        assert_lines(&store, &expected);
+    }
+}
@@ -40,10 +40,7 @@ fn install_syntax_files() -> Result<(), Box<dyn std::error::Error>> {
 
     // Update config file (keeping this part the same)
     let config_path = home_dir.join(".iedit.conf");
-    update_config_file(
-        &config_path,
-        &target_dir,
-    )?;
+    update_config_file(&config_path, &target_dir)?;
 
     Ok(())
 }
@@ -1,9 +1,11 @@
-use std::env;
+use std::{env, fmt::Display, str::FromStr};
 
-use iedit_document::{DocumentSyntax, SyntaxRule};
+use iedit_document::{CommentTokens, DocumentSyntax, SyntaxRule};
 use iedit_macros::{ConfigParse, Reflective};
 use regex_lite::Regex;
 
+use crate::editor::KeyBinding;
+
 #[derive(ConfigParse, Reflective)]
 pub struct EditorConfig {
     pub fullscreen: bool,
@@ -14,11 +16,122 @@ pub struct EditorConfig {
     pub show_line_numbers: bool,
     pub show_keybindings: bool,
     pub render_trailing_whitespace: bool,
+    pub soft_wrap: bool,
     pub confirm_quit_unsaved_changes: bool,
     pub tab_emit_spaces: bool,
     pub enable_syntax_highlighting: bool,
     pub use_system_clipboard: bool,
     pub syntax_highlighting_dir: Option<String>,
+    pub persist_undo_history: bool,
+    pub undo_history_dir: Option<String>,
+    pub undo_history_max_kb: usize,
+    pub use_swap_files: bool,
+    pub swap_dir: Option<String>,
+    pub save_mode: SaveMode,
+    pub backup_mode: BackupMode,
+    pub backup_dir: Option<String>,
+    pub final_newline: FinalNewline,
+    pub large_file_threshold_mb: usize,
+    pub vi_mode: bool,
+    pub bind: Vec<KeyBinding>,
+}
+
+/// How files are written to disk
+#[derive(PartialEq, Clone, Copy)]
+pub enum SaveMode {
+    /// Only rewrite the file from its first modified line, fast on large files
+    /// but a crash mid-write leaves the file corrupted
+    InPlace,
+    /// Write a temporary file and rename it over the original one
+    Atomic,
+}
+
+impl FromStr for SaveMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "in_place" => Ok(Self::InPlace),
+            "atomic" => Ok(Self::Atomic),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for SaveMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InPlace => write!(f, "in_place"),
+            Self::Atomic => write!(f, "atomic"),
+        }
+    }
+}
+
+/// Whether to keep a copy of the file as it was before saving
+#[derive(PartialEq, Clone, Copy)]
+pub enum BackupMode {
+    Off,
+    /// `file~`, overwritten on each save
+    Tilde,
+    /// `file.YYYYMMDD-HHMMSS~`, one per save
+    Timestamped,
+}
+
+impl FromStr for BackupMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "tilde" => Ok(Self::Tilde),
+            "timestamp" => Ok(Self::Timestamped),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for BackupMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Tilde => write!(f, "tilde"),
+            Self::Timestamped => write!(f, "timestamp"),
+        }
+    }
+}
+
+/// What happens to the newline at the end of the file when saving
+#[derive(PartialEq, Clone, Copy)]
+pub enum FinalNewline {
+    /// Save the file as it is
+    Keep,
+    /// Add a newline after the last line if there isn't one
+    Ensure,
+    /// Remove the newline after the last line
+    Strip,
+}
+
+impl FromStr for FinalNewline {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "ensure" => Ok(Self::Ensure),
+            "strip" => Ok(Self::Strip),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for FinalNewline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Keep => write!(f, "keep"),
+            Self::Ensure => write!(f, "ensure"),
+            Self::Strip => write!(f, "strip"),
+        }
+    }
 }
 
 impl Default for EditorConfig {
@@ -29,6 +142,12 @@ impl Default for EditorConfig {
                 .to_string()
         });
 
+        let undo_history_dir =
+            env::home_dir().map(|dir| dir.join(".cache/iedit/undo").to_string_lossy().to_string());
+
+        let swap_dir =
+            env::home_dir().map(|dir| dir.join(".cache/iedit/swap").to_string_lossy().to_string());
+
         Self {
             fullscreen: false,
             min_lines: 0,
@@ -36,6 +155,7 @@ impl Default for EditorConfig {
             tab_size: 4,
             vertical_margin: 4,
             render_trailing_whitespace: true,
+            soft_wrap: false,
             show_line_numbers: true,
             show_keybindings: true,
             use_system_clipboard: true,
@@ -43,10 +163,36 @@ impl Default for EditorConfig {
             tab_emit_spaces: true,
             enable_syntax_highlighting: true,
             syntax_highlighting_dir,
+            persist_undo_history: true,
+            undo_history_dir,
+            undo_history_max_kb: 1024,
+            use_swap_files: true,
+            swap_dir,
+            save_mode: SaveMode::InPlace,
+            backup_mode: BackupMode::Off,
+            backup_dir: None,
+            final_newline: FinalNewline::Keep,
+            large_file_threshold_mb: 100,
+            vi_mode: false,
+            bind: vec![],
         }
     }
 }
 
+impl EditorConfig {
+    /// Where undo histories are kept, `None` if they shouldn't be persisted
+    pub fn get_undo_history_dir(&self) -> Option<&String> {
+        self.undo_history_dir
+            .as_ref()
+            .filter(|_| self.persist_undo_history)
+    }
+
+    /// Where unsaved edits are journaled, `None` if swap files are disabled
+    pub fn get_swap_dir(&self) -> Option<&String> {
+        self.swap_dir.as_ref().filter(|_| self.use_swap_files)
+    }
+}
+
 pub fn editor_config_syntax() -> DocumentSyntax {
     let regex = format!("^({})", EditorConfig::field_names().join("|"));
 
@@ -56,5 +202,6 @@ pub fn editor_config_syntax() -> DocumentSyntax {
             color: termion::color::Green.fg_str().to_owned(),
             pattern: Regex::new(&regex).unwrap(),
         }],
+        comment_tokens: CommentTokens::default(),
     }
 }
@@ -0,0 +1,274 @@
+use std::{mem, path::Path};
+
+use iedit_document::Document;
+
+use crate::editor::{
+    Editor, commands::send_simple_notification, cursor::Cursor, modes::EditorMode,
+    viewport::Viewport,
+};
+
+/// A document open in the editor, together with the cursor and viewport
+/// to restore when switching back to it.
+/// NOTE: the active buffer is only a placeholder, its document, cursor and viewport
+/// live in the corresponding `Editor` fields until another buffer is activated.
+pub struct Buffer {
+    pub document: Document,
+    pub cursor: Cursor,
+    pub viewport: Viewport,
+    pub is_execution_output: bool,
+    /// Whether text appended to the file, or to the input, is added as it arrives
+    pub is_followed: bool,
+}
+
+impl Buffer {
+    pub fn new(document: Document, is_execution_output: bool) -> Self {
+        Self {
+            document,
+            cursor: Cursor::new((0, 0)),
+            viewport: Viewport::default(),
+            is_execution_output,
+            is_followed: false,
+        }
+    }
+}
+
+impl Editor {
+    #[inline]
+    pub fn is_viewing_execution_output(&self) -> bool {
+        self.buffers[self.active_buffer_idx].is_execution_output
+    }
+
+    /// Adds a document to the buffer list without switching to it, returns its index
+    pub fn add_buffer(&mut self, document: Document) -> usize {
+        self.file_watcher.watch(&document.canonicalized_file_path);
+        self.buffers.push(Buffer::new(document, false));
+
+        self.buffers.len() - 1
+    }
+
+    /// Switches to the buffer for `path`, opening the file if it isn't open already.
+    /// Returns whether the file is now in the active buffer
+    pub fn open_file(&mut self, path: &str) -> bool {
+        let path = path.trim();
+        if path.is_empty() {
+            send_simple_notification("Usage: e <path>");
+            return false;
+        }
+
+        let canonicalized_path = Path::new(path).canonicalize().ok();
+        let is_same_file = |document: &Document| {
+            let document_path = document.canonicalized_file_path.as_path();
+            !document_path.as_os_str().is_empty()
+                && match (&canonicalized_path, document_path.canonicalize()) {
+                    (Some(path), Ok(document_path)) => *path == document_path,
+                    _ => document_path == Path::new(path),
+                }
+        };
+
+        let open_buffer_idx = (0..self.buffers.len()).position(|buffer_idx| {
+            !self.buffers[buffer_idx].is_execution_output
+                && is_same_file(self.get_buffer_document(buffer_idx))
+        });
+
+        if let Some(buffer_idx) = open_buffer_idx {
+            self.switch_to_buffer(buffer_idx);
+            return true;
+        }
+
+        match Editor::open_document(path, &self.config) {
+            Ok(mut document) => {
+                document.is_readonly |= self.is_view_mode;
+                let buffer_idx = self.add_buffer(document);
+                self.switch_to_buffer(buffer_idx);
+                true
+            }
+            Err(err) => {
+                send_simple_notification(format!("Could not open {}: {}", path, err));
+                false
+            }
+        }
+    }
+
+    pub fn switch_to_buffer(&mut self, buffer_idx: usize) {
+        if buffer_idx == self.active_buffer_idx || buffer_idx >= self.buffers.len() {
+            return;
+        }
+
+        // the change being typed would be left open in the background
+        self.commit_vi_insert();
+        // the edits made so far would otherwise wait for the buffer to be active again
+        self.write_swap_file(true);
+        self.swap_active_buffer();
+        self.previous_buffer_idx = self.active_buffer_idx;
+        self.active_buffer_idx = buffer_idx;
+        self.swap_active_buffer();
+
+        self.matched_range = None;
+        self.is_selection_locked = false;
+        self.first_quit_sent = false;
+        self.needs_full_rerender = true;
+        self.document.mark_lines_need_render_from(0);
+        self.check_swap_file();
+        self.check_line_endings();
+        self.check_swap_file_owner();
+    }
+
+    pub fn switch_to_next_buffer(&mut self) {
+        self.switch_to_buffer((self.active_buffer_idx + 1) % self.buffers.len());
+    }
+
+    pub fn switch_to_previous_buffer(&mut self) {
+        let n_buffers = self.buffers.len();
+        self.switch_to_buffer((self.active_buffer_idx + n_buffers - 1) % n_buffers);
+    }
+
+    /// Closes the active buffer, refusing to discard unsaved changes unless `force` is set
+    pub fn close_active_buffer(&mut self, force: bool) {
+        if self.buffers.len() == 1 {
+            send_simple_notification("Can't close the last buffer, use Ctrl-q to quit");
+            return;
+        }
+
+        if !force && self.is_buffer_modified(self.active_buffer_idx) {
+            send_simple_notification("Buffer contains unsaved changes. Ctrl-s: save, bd!: discard");
+            return;
+        }
+
+        let closed_buffer_idx = self.active_buffer_idx;
+        let next_buffer_idx = if self.previous_buffer_idx != closed_buffer_idx {
+            self.previous_buffer_idx
+        } else {
+            (closed_buffer_idx + 1) % self.buffers.len()
+        };
+
+        self.switch_to_buffer(next_buffer_idx);
+        let mut closed_buffer = self.buffers.remove(closed_buffer_idx);
+        let _ = closed_buffer.document.clear_swap_file();
+        self.file_watcher
+            .unwatch(&closed_buffer.document.canonicalized_file_path);
+
+        if self.active_buffer_idx > closed_buffer_idx {
+            self.active_buffer_idx -= 1;
+        }
+        self.previous_buffer_idx = self.active_buffer_idx;
+    }
+
+    /// Replaces the execution output buffer with `output`, creating it if needed
+    pub fn set_execution_output(&mut self, output: Document) {
+        match self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.is_execution_output)
+        {
+            Some(buffer_idx) if buffer_idx == self.active_buffer_idx => {
+                self.document = output;
+                self.cursor = Cursor::new((0, 0));
+                self.viewport = Viewport::default();
+                self.needs_full_rerender = true;
+            }
+            Some(buffer_idx) => self.buffers[buffer_idx] = Buffer::new(output, true),
+            None => self.buffers.push(Buffer::new(output, true)),
+        }
+    }
+
+    pub fn get_execution_output_mut(&mut self) -> Option<&mut Document> {
+        let buffer_idx = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.is_execution_output)?;
+
+        if buffer_idx == self.active_buffer_idx {
+            Some(&mut self.document)
+        } else {
+            Some(&mut self.buffers[buffer_idx].document)
+        }
+    }
+
+    pub fn get_execution_output_cursor_mut(&mut self) -> Option<&mut Cursor> {
+        let buffer_idx = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.is_execution_output)?;
+
+        if buffer_idx == self.active_buffer_idx {
+            Some(&mut self.cursor)
+        } else {
+            Some(&mut self.buffers[buffer_idx].cursor)
+        }
+    }
+
+    pub fn toggle_execution_output(&mut self) {
+        if self.is_viewing_execution_output() {
+            self.switch_to_buffer(self.previous_buffer_idx);
+        } else if let Some(buffer_idx) = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.is_execution_output)
+        {
+            self.switch_to_buffer(buffer_idx);
+        } else {
+            send_simple_notification("No execution output available");
+        }
+    }
+
+    pub fn has_unsaved_buffers(&self) -> bool {
+        (0..self.buffers.len()).any(|buffer_idx| self.is_buffer_modified(buffer_idx))
+    }
+
+    pub fn open_buffer_picker(&mut self) {
+        self.mode = EditorMode::PickBuffer {
+            selected_idx: self.active_buffer_idx,
+        };
+    }
+
+    /// Lines of the buffer picker popup, the selected buffer is marked with an arrow
+    pub fn get_buffer_picker_lines(&self, selected_idx: usize) -> Vec<String> {
+        (0..self.buffers.len())
+            .map(|buffer_idx| {
+                let name = if self.buffers[buffer_idx].is_execution_output {
+                    "[output]"
+                } else {
+                    self.get_buffer_document(buffer_idx)
+                        .get_name()
+                        .unwrap_or("[scratch]")
+                };
+
+                format!(
+                    "{} {} │ {}{}",
+                    if buffer_idx == selected_idx { '>' } else { ' ' },
+                    buffer_idx + 1,
+                    name,
+                    if self.is_buffer_modified(buffer_idx) {
+                        " *"
+                    } else {
+                        ""
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[inline]
+    fn get_buffer_document(&self, buffer_idx: usize) -> &Document {
+        if buffer_idx == self.active_buffer_idx {
+            &self.document
+        } else {
+            &self.buffers[buffer_idx].document
+        }
+    }
+
+    #[inline]
+    fn is_buffer_modified(&self, buffer_idx: usize) -> bool {
+        !self.buffers[buffer_idx].is_execution_output
+            && self.get_buffer_document(buffer_idx).has_been_modified()
+    }
+
+    /// Exchanges the active buffer's placeholder with the editor's document, cursor and viewport
+    fn swap_active_buffer(&mut self) {
+        let buffer = &mut self.buffers[self.active_buffer_idx];
+        mem::swap(&mut self.document, &mut buffer.document);
+        mem::swap(&mut self.cursor, &mut buffer.cursor);
+        mem::swap(&mut self.viewport, &mut buffer.viewport);
+        self.selection_expansions.clear();
+    }
+}
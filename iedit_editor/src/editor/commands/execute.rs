@@ -1,41 +1,101 @@
-#![allow(clippy::manual_flatten)]
-
 use std::{
-    io::{BufRead, BufReader},
-    process::{Command, Stdio},
-    thread::{JoinHandle, spawn},
+    io::{BufRead, BufReader, Read},
+    os::unix::process::CommandExt,
+    process::{Child, Command, ExitStatus, Stdio},
+    thread::spawn,
+    time::{Duration, Instant},
 };
 
-use iedit_document::Document;
-use termion::event::Key;
+use iedit_document::{CommentTokens, Document, DocumentLine, DocumentSyntax, SyntaxRule};
+use regex_lite::Regex;
+use termion::{color, event::Key};
 
 use crate::{
     Editor,
     editor::{
-        FILE_EXECUTION_OUTPUT,
-        commands::notify::{send_notification, send_simple_notification},
+        EXECUTION_OUTPUT,
+        commands::{send_notification, send_simple_notification},
+        quickfix::parse_error_locations,
     },
     input::Notification,
 };
 
+pub static STDOUT_TAG: &str = "out│ ";
+pub static STDERR_TAG: &str = "err│ ";
+
 pub enum Executor {
     Literal(String),
     Key(Key),
 }
 
+/// A command running in the background, its output is streamed to the output buffer
+pub struct RunningExecution {
+    pid: u32,
+    start_time: Instant,
+}
+
 impl Editor {
     pub fn execute_shell_command(&mut self, cmd: String) {
-        self.is_running_external_command = true;
+        if self.running_execution.is_some() {
+            send_simple_notification("A command is already running. Ctrl-k x i: interrupt");
+            return;
+        }
+
+        // its own process group, so that signals reach the processes it spawns too
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(&cmd)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn();
+
+        let mut child = match child {
+            Ok(child) => child,
+            Err(err) => {
+                send_simple_notification(format!("Error executing command: {}", err));
+                return;
+            }
+        };
+
+        if let Ok(mut pending_lines) = EXECUTION_OUTPUT.lock() {
+            pending_lines.clear();
+        }
+
+        let mut output = Document::from_strings(vec![format!("$ {}", cmd)], &cmd, true);
+        output.syntax = Some(execution_output_syntax());
+        self.set_execution_output(output);
+
+        self.error_locations.clear();
+        self.current_error_idx = None;
+        self.running_execution = Some(RunningExecution {
+            pid: child.id(),
+            start_time: Instant::now(),
+        });
 
-        let _handle: JoinHandle<()> = spawn(move || {
-            if let Err(e) = run_command(&cmd) {
-                send_simple_notification(format!("Error executing command: {}", e));
+        let stdout = child
+            .stdout
+            .take()
+            .map(|stdout| stream_output(stdout, STDOUT_TAG));
+        let stderr = child
+            .stderr
+            .take()
+            .map(|stderr| stream_output(stderr, STDERR_TAG));
+        spawn(move || {
+            // all the output has been sent once both streams are closed
+            for reader in [stdout, stderr].into_iter().flatten() {
+                let _ = reader.join();
             }
+
+            wait_for_child(child);
         });
+
+        send_simple_notification(format!("Executing: {}", cmd));
     }
 
     pub fn execute_file(&mut self, executor: Executor) {
-        if self.is_viewing_execution_output {
+        if self.is_viewing_execution_output() {
             send_simple_notification("Not an executable file");
             return;
         }
@@ -63,6 +123,95 @@ impl Editor {
         self.execute_shell_command(command);
     }
 
+    /// Sends `signal` (e.g. `SIGINT`) to the running command and the processes it spawned
+    pub fn signal_execution(&mut self, signal: i32) {
+        let Some(execution) = &self.running_execution else {
+            send_simple_notification("No command running");
+            return;
+        };
+
+        // SAFETY: plain syscall, a stale pid group at worst makes it fail with ESRCH
+        if unsafe { libc::kill(-(execution.pid as i32), signal) } != 0 {
+            send_simple_notification(format!(
+                "Could not signal command: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+
+    /// Moves the lines printed by the running command to the output buffer
+    pub fn append_execution_output(&mut self) {
+        let new_lines = match EXECUTION_OUTPUT.lock() {
+            Ok(mut pending_lines) => std::mem::take(&mut *pending_lines),
+            Err(_) => return,
+        };
+
+        self.append_to_execution_output(new_lines);
+    }
+
+    pub fn end_execution(&mut self, status: Option<ExitStatus>) {
+        self.append_execution_output();
+
+        let elapsed = self
+            .running_execution
+            .take()
+            .map(|execution| execution.start_time.elapsed())
+            .unwrap_or_default();
+
+        let status = status.map_or("unknown exit status".to_owned(), |status| {
+            status.to_string()
+        });
+        self.append_to_execution_output(vec![format!(
+            "[{} after {}]",
+            status,
+            format_elapsed(elapsed)
+        )]);
+
+        self.error_locations = self
+            .get_execution_output_mut()
+            .map(|output| parse_error_locations(output))
+            .unwrap_or_default();
+
+        self.status_bar.notification = match self.error_locations.len() {
+            0 => format!("{}. Ctrl-k + v + o: view output", status),
+            n_errors => format!(
+                "{}, {} error locations. Ctrl-k + e + n: go to first",
+                status, n_errors
+            ),
+        };
+    }
+
+    /// Time since the running command started, if any
+    pub fn get_execution_elapsed(&self) -> Option<String> {
+        self.running_execution
+            .as_ref()
+            .map(|execution| format_elapsed(execution.start_time.elapsed()))
+    }
+
+    fn append_to_execution_output(&mut self, new_lines: Vec<String>) {
+        let cur_y = self.cursor.cur_y;
+        let is_active = self.is_viewing_execution_output();
+        let Some(output) = self.get_execution_output_mut() else {
+            return;
+        };
+
+        // keep following the output when the cursor is on its last line
+        let n_lines = output.n_lines();
+        let should_follow = is_active && cur_y + 1 >= n_lines;
+
+        output.lines.extend(new_lines.into_iter().map(|line| {
+            let mut line = DocumentLine::new(line);
+            line.has_been_modified = false;
+            line
+        }));
+        output.mark_lines_need_render_from(n_lines);
+
+        let last_line_idx = output.n_lines().saturating_sub(1);
+        if should_follow {
+            self.cursor.update_pos((0, last_line_idx), false);
+        }
+    }
+
     fn infer_executor(&self, executor_key: Key) -> Option<&str> {
         match executor_key {
             Key::Char('p') => Some("/usr/bin/env python3"),
@@ -93,59 +242,74 @@ impl Editor {
     }
 }
 
-fn run_command(command: &str) -> std::io::Result<()> {
-    let mut child = Command::new("sh")
-        .arg("-c")
-        .arg(command)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
-
-    let stdout = child
-        .stdout
-        .take()
-        .ok_or_else(|| std::io::Error::other("Failed to capture stdout"))?;
-    let stderr = child
-        .stderr
-        .take()
-        .ok_or_else(|| std::io::Error::other("Failed to capture stderr"))?;
-
-    send_simple_notification(format!("Executing: {}", command));
-
-    let stdout_reader = BufReader::new(stdout);
-    let stderr_reader = BufReader::new(stderr);
-
-    let mut output_lines = vec![];
-
-    // Process stdout and stderr concurrently
-    output_lines.push("// STDOUT //".to_owned());
-    for line in stdout_reader.lines() {
-        if let Ok(line) = line {
-            output_lines.push(line);
-        }
-    }
+/// Reads `stream` line by line from a background thread, queueing its lines
+/// in `EXECUTION_OUTPUT` prefixed by `tag`
+fn stream_output(
+    stream: impl Read + Send + 'static,
+    tag: &'static str,
+) -> std::thread::JoinHandle<()> {
+    spawn(move || {
+        let mut reader = BufReader::new(stream);
+        let mut line = vec![];
+        while let Ok(n_bytes) = reader.read_until(b'\n', &mut line)
+            && n_bytes > 0
+        {
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim_end_matches(['\n', '\r']);
 
-    output_lines.push("// STDERR //".to_owned());
-    for line in stderr_reader.lines() {
-        if let Ok(line) = line {
-            output_lines.push(line);
-        }
-    }
+            let Ok(mut pending_lines) = EXECUTION_OUTPUT.lock() else {
+                return;
+            };
 
-    let status = child.wait()?;
-    let output = Document::from_strings(output_lines, command, true);
+            // the editor drains all the pending lines at once, one wake up is enough
+            if pending_lines.is_empty() {
+                send_notification(Notification::ExecutionOutput);
+            }
+
+            pending_lines.push(format!("{}{}", tag, text));
+            drop(pending_lines);
+            line.clear();
+        }
+    })
+}
 
-    let output_available = if let Ok(mut file_execution_output) = FILE_EXECUTION_OUTPUT.lock() {
-        *file_execution_output = Some(output);
-        true
-    } else {
-        false
+fn wait_for_child(mut child: Child) {
+    let status = match child.wait() {
+        Ok(status) => Some(status),
+        Err(err) => {
+            send_simple_notification(format!("Error executing command: {}", err));
+            None
+        }
     };
 
-    send_notification(Notification::ExecutionEnd {
-        status,
-        output_available,
-    });
+    send_notification(Notification::ExecutionEnd { status });
+}
 
-    Ok(())
+fn format_elapsed(elapsed: Duration) -> String {
+    match elapsed.as_secs() {
+        ..60 => format!("{:.1}s", elapsed.as_secs_f32()),
+        secs @ ..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        secs => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+fn execution_output_syntax() -> DocumentSyntax {
+    DocumentSyntax {
+        name: "output",
+        rules: vec![
+            SyntaxRule::Inline {
+                color: color::Red.fg_str().to_owned(),
+                pattern: Regex::new(&format!("^{}.*$", STDERR_TAG)).unwrap(),
+            },
+            SyntaxRule::Inline {
+                color: color::LightBlack.fg_str().to_owned(),
+                pattern: Regex::new(&format!("^{}", STDOUT_TAG)).unwrap(),
+            },
+            SyntaxRule::Inline {
+                color: color::Cyan.fg_str().to_owned(),
+                pattern: Regex::new(r"^(\$ .*|\[.*\])$").unwrap(),
+            },
+        ],
+        comment_tokens: CommentTokens::default(),
+    }
 }
@@ -2,15 +2,15 @@ mod execute;
 mod move_cursor;
 mod notify;
 
-use std::process::ExitStatus;
+use std::{path::PathBuf, process::ExitStatus};
 
-use crate::editor::modes::EditorMode;
-pub use execute::Executor;
-use iedit_document::EditOperation;
+use crate::editor::{lines::LineOrder, modes::EditorMode, vi::ViAction};
+pub use execute::{Executor, RunningExecution, STDERR_TAG, STDOUT_TAG};
+use iedit_document::{EditOperation, TextObject};
 pub use move_cursor::CursorMovement;
 use termion::event::Key;
 
-pub use notify::{send_simple_notification};
+pub use notify::{send_notification, send_simple_notification};
 pub enum EditorCommand {
     Quit,
     Save,
@@ -22,14 +22,37 @@ pub enum EditorCommand {
     ClearSelection,
     SwitchMode(EditorMode),
     Edit(EditOperation),
+    EditAtCursors(Vec<EditOperation>),
+    AddCursorAbove,
+    AddCursorBelow,
+    AddCursorAtNextOccurrence,
+    SplitSelectionIntoLines,
+    ExpandSelection,
+    ShrinkSelection,
+    SelectTextObject {
+        object: TextObject,
+        is_around: bool,
+    },
     OpenCommandLine,
     YankSelection,
     CutSelection,
     Paste,
+    DeleteLine,
+    MoveLines {
+        is_up: bool,
+    },
+    DuplicateLines,
+    JoinLines,
+    ReorderLines(LineOrder),
+    ToggleComment,
     UndoLastEdit,
     RedoLastEdit,
+    OpenUndoTree,
+    GoToUndoState(usize),
     MovePromptCursorLeft,
     MovePromptCursorRight,
+    /// Tab-completion of the command prompt
+    CompletePrompt,
     InsertCharPrompt {
         pos_x: usize,
         ch: char,
@@ -39,22 +62,49 @@ pub enum EditorCommand {
     },
     SubmitPrompt,
     ToggleLineNumbers,
+    ToggleSoftWrap,
     ScrollViewportUp,
     ScrollViewportDown,
     FindMatchForward,
     FindMatchBackward,
+    OpenReplacePrompt {
+        confirm: bool,
+    },
+    ReplaceCurrentMatch,
+    SkipCurrentMatch,
+    ReplaceRemainingMatches,
+    StopReplacing,
     PromptExecutor,
     ExecuteFile(Key),
     DisplayMessage(String),
     DisplayHelp,
-    DisplayChordsHelp,
-    DisplayExecuteChordHelp,
-    DisplayLineChordHelp,
-    DisplayViewChordHelp,
-    DisplaySelectionChordHelp,
-    DisplayPressCharacterPopup,
-    EndFileExecution(ExitStatus, bool),
+    /// Chords starting with the given key, or all of them
+    DisplayChordsHelp(Option<Key>),
+    DisplayPressKeyPopup,
+    AppendExecutionOutput,
+    AppendFollowedOutput,
+    EndFileExecution(Option<ExitStatus>),
+    InterruptExecution,
+    KillExecution,
+    RefreshStatus,
+    HandleFileChangedOnDisk(PathBuf),
     ViewExecutionOutput,
+    SwitchToBuffer(usize),
+    SwitchToNextBuffer,
+    SwitchToPreviousBuffer,
+    CloseBuffer,
+    OpenBufferPicker,
+    GoToNextError,
+    GoToPreviousError,
+    GoToErrorAtCursor,
+    GoToError(usize),
+    OpenErrorList,
+    SetViPendingKeys(String),
+    ExecuteViAction {
+        action: ViAction,
+        count: Option<usize>,
+    },
+    ExitInsertMode,
 }
 
 #[non_exhaustive]
@@ -1,5 +1,6 @@
 use crate::{Editor, editor::commands::EditorCommand};
 
+#[derive(Clone, Copy)]
 pub enum CursorMovement {
     AbsolutePos((usize, usize)),
     Up(usize),
@@ -7,9 +8,12 @@ pub enum CursorMovement {
     Left(usize),
     Right(usize),
     NextWordEnd,
+    NextWordStart,
+    NextWordLastChar,
     PreviousWordStart,
     NextParagraph,
     StartOfLine,
+    FirstNonBlank,
     EndOfLine,
     StartOfFile,
     EndOfFile,
@@ -28,83 +32,134 @@ impl Editor {
                 movement,
                 with_selection,
             } => {
-                if !with_selection && self.cursor.selection_anchor.is_some() {
-                    self.needs_full_rerender = true;
-                    self.cursor.selection_anchor = None;
-                } else if with_selection && self.cursor.selection_anchor.is_none() {
-                    self.cursor.selection_anchor = Some(self.cursor.pos())
-                }
+                self.move_cursor(movement, with_selection);
 
-                match movement {
-                    CursorMovement::AbsolutePos(pos) => self.cursor.update_pos(pos, true),
-                    CursorMovement::Up(lines) => self.cursor.move_up(lines),
-                    CursorMovement::Down(lines) => self.cursor.move_down(lines),
-                    CursorMovement::Left(cols) => self.cursor.move_left(cols),
-                    CursorMovement::Right(cols) => self.cursor.move_right(cols),
-                    CursorMovement::NextWordEnd => {
-                        let next_word_pos = self.document.get_next_word_end_pos(self.cursor.pos());
-                        self.cursor.update_pos(next_word_pos, false);
-                    }
-                    CursorMovement::PreviousWordStart => {
-                        let previous_word_pos =
-                            self.document.get_previous_word_start_pos(self.cursor.pos());
-                        self.cursor.update_pos(previous_word_pos, false);
-                    }
-                    CursorMovement::NextParagraph => {
-                        let next_paragraph_row =
-                            self.document.get_next_blank_line_idx(self.cursor.cur_y);
-                        self.needs_full_rerender = true;
-                        self.cursor.update_pos((0, next_paragraph_row), true);
-                    }
-                    CursorMovement::PreviousParagraph => {
-                        let previous_paragraph_row =
-                            self.document.get_previous_blank_line_idx(self.cursor.cur_y);
-                        self.needs_full_rerender = true;
-                        self.cursor.update_pos((0, previous_paragraph_row), true);
-                    }
-                    CursorMovement::MatchingParenthesis => {
-                        if let Some(pos) = self.document.get_matching_paren_pos(self.cursor.pos()) {
-                            self.cursor.update_pos(pos, true);
-                        }
-                    }
-                    CursorMovement::NextOccurrenceOf(ch) => {
-                        if let Some(pos) = self
-                            .document
-                            .get_next_occurrence_of_char(self.cursor.pos(), ch)
-                        {
-                            self.cursor.update_pos(pos, false);
-                        }
-                    }
-                    CursorMovement::PreviousOccurrenceOf(ch) => {
-                        if let Some(pos) = self
-                            .document
-                            .get_previous_occurrence_of_char(self.cursor.pos(), ch)
-                        {
-                            self.cursor.update_pos(pos, false);
-                        }
-                    }
-                    CursorMovement::StartOfLine => {
-                        self.cursor.update_pos((0, self.cursor.cur_y), false);
-                    }
-                    CursorMovement::EndOfLine => {
-                        self.cursor
-                            .update_pos((usize::MAX, self.cursor.cur_y), false);
-                    }
-                    CursorMovement::StartOfFile => {
-                        self.cursor.update_pos((0, 0), true);
-                    }
-                    CursorMovement::EndOfFile => {
-                        self.cursor.update_pos((0, self.document.n_lines()), true);
-                    }
-                    CursorMovement::PreviousJump => {
-                        self.cursor.jump_back();
-                    }
-                    CursorMovement::NextJump => {
-                        self.cursor.jump_forward();
-                    }
+                if self.has_multiple_cursors() {
+                    self.move_secondary_carets(movement, with_selection);
                 }
             }
             _ => unreachable!(),
         }
     }
+
+    pub fn move_cursor(&mut self, movement: CursorMovement, with_selection: bool) {
+        if !with_selection && self.cursor.selection_anchor.is_some() {
+            self.needs_full_rerender = true;
+            self.cursor.selection_anchor = None;
+        } else if with_selection && self.cursor.selection_anchor.is_none() {
+            self.cursor.selection_anchor = Some(self.cursor.pos())
+        }
+
+        match movement {
+            CursorMovement::AbsolutePos(pos) => self.cursor.update_pos(pos, true),
+            CursorMovement::Up(rows) if self.config.soft_wrap => {
+                self.move_cursor_by_rows(rows, true)
+            }
+            CursorMovement::Down(rows) if self.config.soft_wrap => {
+                self.move_cursor_by_rows(rows, false)
+            }
+            CursorMovement::Up(lines) => self.cursor.move_up(lines),
+            CursorMovement::Down(lines) => self.cursor.move_down(lines),
+            CursorMovement::Left(cols) => self.move_cursor_by_graphemes(cols, true),
+            CursorMovement::Right(cols) => self.move_cursor_by_graphemes(cols, false),
+            CursorMovement::NextWordEnd => {
+                let next_word_pos = self.document.get_next_word_end_pos(self.cursor.pos());
+                self.cursor.update_pos(next_word_pos, false);
+            }
+            CursorMovement::NextWordStart => {
+                let next_word_pos = self.document.get_next_word_start_pos(self.cursor.pos());
+                self.cursor.update_pos(next_word_pos, false);
+            }
+            CursorMovement::NextWordLastChar => {
+                let word_end_pos = self.document.get_next_word_last_char_pos(self.cursor.pos());
+                self.cursor.update_pos(word_end_pos, false);
+            }
+            CursorMovement::PreviousWordStart => {
+                let previous_word_pos =
+                    self.document.get_previous_word_start_pos(self.cursor.pos());
+                self.cursor.update_pos(previous_word_pos, false);
+            }
+            CursorMovement::NextParagraph => {
+                let next_paragraph_row = self.document.get_next_blank_line_idx(self.cursor.cur_y);
+                self.needs_full_rerender = true;
+                self.cursor.update_pos((0, next_paragraph_row), true);
+            }
+            CursorMovement::PreviousParagraph => {
+                let previous_paragraph_row =
+                    self.document.get_previous_blank_line_idx(self.cursor.cur_y);
+                self.needs_full_rerender = true;
+                self.cursor.update_pos((0, previous_paragraph_row), true);
+            }
+            CursorMovement::MatchingParenthesis => {
+                if let Some(pos) = self.document.get_matching_paren_pos(self.cursor.pos()) {
+                    self.cursor.update_pos(pos, true);
+                }
+            }
+            CursorMovement::NextOccurrenceOf(ch) => {
+                if let Some(pos) = self
+                    .document
+                    .get_next_occurrence_of_char(self.cursor.pos(), ch)
+                {
+                    self.cursor.update_pos(pos, false);
+                }
+            }
+            CursorMovement::PreviousOccurrenceOf(ch) => {
+                if let Some(pos) = self
+                    .document
+                    .get_previous_occurrence_of_char(self.cursor.pos(), ch)
+                {
+                    self.cursor.update_pos(pos, false);
+                }
+            }
+            CursorMovement::StartOfLine => {
+                self.cursor.update_pos((0, self.cursor.cur_y), false);
+            }
+            CursorMovement::FirstNonBlank => {
+                let first_non_blank_x = self.get_first_non_blank_x(self.cursor.cur_y);
+                self.cursor
+                    .update_pos((first_non_blank_x, self.cursor.cur_y), false);
+            }
+            CursorMovement::EndOfLine => {
+                self.cursor
+                    .update_pos((usize::MAX, self.cursor.cur_y), false);
+            }
+            CursorMovement::StartOfFile => {
+                self.cursor.update_pos((0, 0), true);
+            }
+            CursorMovement::EndOfFile => {
+                self.cursor.update_pos((0, self.document.n_lines()), true);
+            }
+            CursorMovement::PreviousJump => {
+                self.cursor.jump_back();
+            }
+            CursorMovement::NextJump => {
+                self.cursor.jump_forward();
+            }
+        }
+    }
+
+    /// Moves the cursor horizontally by `n_graphemes` grapheme clusters rather than by chars,
+    /// so that it never lands in the middle of one
+    fn move_cursor_by_graphemes(&mut self, n_graphemes: usize, is_left: bool) {
+        let Some(line) = self.document.lines.get(self.cursor.cur_y) else {
+            if is_left {
+                self.cursor.move_left(n_graphemes);
+            } else {
+                self.cursor.move_right(n_graphemes);
+            }
+            return;
+        };
+
+        let mut x = self.cursor.cur_x;
+        for _ in 0..n_graphemes {
+            x = if is_left {
+                line.previous_grapheme_idx(x)
+            } else {
+                line.next_grapheme_idx(x)
+            };
+        }
+
+        self.cursor
+            .update_pos((x, self.cursor.cur_y), n_graphemes > 1);
+    }
 }
@@ -0,0 +1,225 @@
+use iedit_document::{CommentTokens, EditOperation, Text};
+
+use crate::editor::{Editor, commands::send_simple_notification};
+
+/// Comment token inserted at, or removed from, a line
+struct CommentEdit {
+    pos: (usize, usize),
+    text: String,
+    is_removal: bool,
+}
+
+impl CommentEdit {
+    fn to_edit_operation(&self) -> EditOperation {
+        if self.is_removal {
+            EditOperation::Replacement {
+                pos_from: self.pos,
+                pos_to: (self.pos.0 + self.text.chars().count(), self.pos.1),
+                text: Text::Empty,
+            }
+        } else {
+            EditOperation::Insertion {
+                pos: self.pos,
+                text: Text::from(self.text.clone()),
+            }
+        }
+    }
+
+    /// Where the text at `pos` is once the edit is applied
+    fn shift_pos(&self, (x, y): (usize, usize)) -> (usize, usize) {
+        let len = self.text.chars().count();
+        match self.pos {
+            (edit_x, edit_y) if edit_y != y || x < edit_x => (x, y),
+            (edit_x, _) if self.is_removal => (x.saturating_sub(len).max(edit_x), y),
+            _ => (x + len, y),
+        }
+    }
+}
+
+impl Editor {
+    /// Comments out the selected lines, or the cursor's line, or uncomments them
+    /// if they are all commented out
+    pub fn toggle_comment(&mut self) {
+        let (y_from, y_to) = self.get_selected_lines();
+        self.toggle_comment_lines(y_from, y_to);
+    }
+
+    /// Comments out the lines from `y_from` to `y_to` with the line comments of the language,
+    /// or wraps them in a block comment if it has none. Blank lines are left untouched
+    pub fn toggle_comment_lines(&mut self, y_from: usize, y_to: usize) {
+        let Some(syntax) = &self.document.syntax else {
+            send_simple_notification("No comment syntax for this file");
+            return;
+        };
+
+        let (syntax_name, comment_tokens) = (syntax.name, syntax.comment_tokens);
+        let lines = self.get_lines(y_from, y_to);
+        let lines = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(line_idx, line)| (y_from + line_idx, line.as_str()))
+            .collect::<Vec<_>>();
+
+        if lines.is_empty() {
+            return;
+        }
+
+        let edits = match comment_tokens {
+            CommentTokens {
+                line: Some(token), ..
+            } => get_line_comment_edits(token, &lines),
+            CommentTokens {
+                block: Some(tokens),
+                ..
+            } => get_block_comment_edits(tokens, &lines),
+            _ => {
+                send_simple_notification(format!("No comment syntax for {}", syntax_name));
+                return;
+            }
+        };
+
+        self.apply_comment_edits(edits);
+    }
+
+    /// Applies `edits` as a single step, the cursor and the selection stay on the same text
+    fn apply_comment_edits(&mut self, edits: Vec<CommentEdit>) {
+        let shift_pos = |pos| {
+            edits
+                .iter()
+                .rev()
+                .fold(pos, |pos, edit| edit.shift_pos(pos))
+        };
+        let cursor_pos = shift_pos(self.cursor.pos());
+        let selection_anchor = self.cursor.selection_anchor.map(shift_pos);
+
+        self.first_quit_sent = false;
+        self.clear_secondary_carets();
+        self.document
+            .begin_transaction(self.cursor.pos(), self.cursor.selection_anchor);
+        let results = self
+            .document
+            .apply_cursor_edits(edits.iter().map(CommentEdit::to_edit_operation).collect());
+        self.document.commit();
+
+        if results.iter().all(Option::is_none) {
+            return;
+        }
+
+        self.cursor.update_pos(cursor_pos, false);
+        self.cursor.selection_anchor = selection_anchor;
+        self.needs_full_rerender = true;
+    }
+}
+
+/// Removes `token` from the start of `lines` if they all begin with it, otherwise inserts it
+/// on each of them at the smallest indentation among them, so that mixed lines are commented
+/// out all together and uncommenting them gives them back as they were
+fn get_line_comment_edits(token: &str, lines: &[(usize, &str)]) -> Vec<CommentEdit> {
+    if lines
+        .iter()
+        .all(|(_, line)| line.trim_start().starts_with(token))
+    {
+        return lines
+            .iter()
+            .map(|(line_idx, line)| {
+                let is_spaced = line.trim_start()[token.len()..].starts_with(' ');
+                CommentEdit {
+                    pos: (get_indent_len(line), *line_idx),
+                    text: if is_spaced {
+                        format!("{} ", token)
+                    } else {
+                        token.to_owned()
+                    },
+                    is_removal: true,
+                }
+            })
+            .collect();
+    }
+
+    let indent_len = lines
+        .iter()
+        .map(|(_, line)| get_indent_len(line))
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|(line_idx, _)| CommentEdit {
+            pos: (indent_len, *line_idx),
+            text: format!("{} ", token),
+            is_removal: false,
+        })
+        .collect()
+}
+
+/// Removes the block comment starting `lines` and ending them, or wraps them in one
+fn get_block_comment_edits(
+    (start, end): (&str, &str),
+    lines: &[(usize, &str)],
+) -> Vec<CommentEdit> {
+    let (first_idx, first_line) = lines[0];
+    let (last_idx, last_line) = lines[lines.len() - 1];
+    let indent_len = get_indent_len(first_line);
+    let first_text = first_line.trim_start();
+    let last_text = last_line.trim_end();
+
+    let is_commented = first_text.starts_with(start)
+        && last_text.ends_with(end)
+        && (first_idx != last_idx || first_text.len() >= start.len() + end.len());
+
+    if !is_commented {
+        return vec![
+            CommentEdit {
+                pos: (indent_len, first_idx),
+                text: format!("{} ", start),
+                is_removal: false,
+            },
+            CommentEdit {
+                pos: (last_line.chars().count(), last_idx),
+                text: format!(" {}", end),
+                is_removal: false,
+            },
+        ];
+    }
+
+    let start_text = if first_text[start.len()..].starts_with(' ') {
+        format!("{} ", start)
+    } else {
+        start.to_owned()
+    };
+
+    // on a single line, the space following the start can't also precede the end
+    let body_start = if first_idx == last_idx {
+        first_line.len() - first_text.len() + start_text.len()
+    } else {
+        0
+    };
+    let body_end = last_text.len() - end.len();
+    let end_text = if body_end > body_start && last_line[..body_end].ends_with(' ') {
+        format!(" {}", end)
+    } else {
+        end.to_owned()
+    };
+
+    vec![
+        CommentEdit {
+            pos: (indent_len, first_idx),
+            text: start_text,
+            is_removal: true,
+        },
+        CommentEdit {
+            pos: (
+                last_text.chars().count() - end_text.chars().count(),
+                last_idx,
+            ),
+            text: end_text,
+            is_removal: true,
+        },
+    ]
+}
+
+#[inline]
+fn get_indent_len(line: &str) -> usize {
+    line.chars().take_while(|ch| ch.is_whitespace()).count()
+}
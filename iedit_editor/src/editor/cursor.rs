@@ -1,6 +1,6 @@
 use std::cmp::min;
 
-use crate::Editor;
+use crate::{Editor, editor::modes::EditorMode};
 
 pub struct Cursor {
     pub cur_x: usize,
@@ -11,6 +11,40 @@ pub struct Cursor {
     pub selection_anchor: Option<(usize, usize)>,
     pub jump_history: Vec<(usize, usize)>,
     pub jump_history_head: usize,
+    /// Additional carets for multi-cursor editing, kept sorted by position
+    pub secondary_carets: Vec<Caret>,
+}
+
+/// Position and selection of one of the cursors when editing with multiple cursors,
+/// the primary one is the `Cursor` itself
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Caret {
+    pub cur_x: usize,
+    pub cur_y: usize,
+    pub ideal_x: usize,
+    pub selection_anchor: Option<(usize, usize)>,
+}
+
+impl Caret {
+    pub fn new(pos: (usize, usize), selection_anchor: Option<(usize, usize)>) -> Self {
+        Self {
+            cur_x: pos.0,
+            cur_y: pos.1,
+            ideal_x: pos.0,
+            selection_anchor,
+        }
+    }
+
+    #[inline]
+    pub const fn pos(&self) -> (usize, usize) {
+        (self.cur_x, self.cur_y)
+    }
+
+    #[inline]
+    pub fn get_selected_range(&self) -> Option<((usize, usize), (usize, usize))> {
+        self.selection_anchor
+            .map(|anchor_pos| get_ordered_range(anchor_pos, self.pos()))
+    }
 }
 
 impl Cursor {
@@ -85,9 +119,28 @@ impl Cursor {
             selection_anchor: None,
             jump_history: vec![],
             jump_history_head: 0,
+            secondary_carets: vec![],
         }
     }
 
+    #[inline]
+    pub fn caret(&self) -> Caret {
+        Caret {
+            cur_x: self.cur_x,
+            cur_y: self.cur_y,
+            ideal_x: self.ideal_x,
+            selection_anchor: self.selection_anchor,
+        }
+    }
+
+    #[inline]
+    pub fn set_caret(&mut self, caret: Caret) {
+        self.cur_x = caret.cur_x;
+        self.cur_y = caret.cur_y;
+        self.ideal_x = caret.ideal_x;
+        self.selection_anchor = caret.selection_anchor;
+    }
+
     #[inline]
     pub const fn pos(&self) -> (usize, usize) {
         (self.cur_x, self.cur_y)
@@ -96,27 +149,43 @@ impl Cursor {
     pub fn get_selected_range(&self) -> Option<((usize, usize), (usize, usize))> {
         let cursor_pos = self.pos();
 
-        self.selection_anchor.map(|anchor_pos| {
-            if anchor_pos.1 < cursor_pos.1
-                || (anchor_pos.1 == cursor_pos.1 && anchor_pos.0 < cursor_pos.0)
-            {
-                (anchor_pos, cursor_pos)
-            } else {
-                (cursor_pos, anchor_pos)
-            }
-        })
+        self.selection_anchor
+            .map(|anchor_pos| get_ordered_range(anchor_pos, cursor_pos))
+    }
+}
+
+#[inline]
+fn get_ordered_range(
+    anchor_pos: (usize, usize),
+    cursor_pos: (usize, usize),
+) -> ((usize, usize), (usize, usize)) {
+    if anchor_pos.1 < cursor_pos.1 || (anchor_pos.1 == cursor_pos.1 && anchor_pos.0 < cursor_pos.0)
+    {
+        (anchor_pos, cursor_pos)
+    } else {
+        (cursor_pos, anchor_pos)
     }
 }
 
 impl Editor {
     pub fn clamp_cursor(&mut self) {
-        let max_x = self
-            .document
-            .lines
-            .get(self.cursor.cur_y)
-            .map_or(0, |line| line.len());
-
-        self.cursor.cur_x = min(self.cursor.ideal_x, max_x);
-        self.cursor.cur_y = min(self.cursor.cur_y, self.document.n_lines());
+        let Some(line) = self.document.lines.get(self.cursor.cur_y) else {
+            self.cursor.cur_x = 0;
+            self.cursor.cur_y = min(self.cursor.cur_y, self.document.n_lines());
+            return;
+        };
+
+        // outside of insert mode the vi cursor stays on a character
+        let max_x = if matches!(
+            self.mode,
+            EditorMode::Normal | EditorMode::Visual | EditorMode::VisualLine
+        ) {
+            line.previous_grapheme_idx(line.len())
+        } else {
+            line.len()
+        };
+
+        // the cursor can't be in the middle of a grapheme cluster
+        self.cursor.cur_x = line.grapheme_start(min(self.cursor.ideal_x, max_x));
     }
 }
@@ -0,0 +1,264 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    mem,
+    os::unix::fs::FileExt,
+    thread,
+    time::Duration,
+};
+
+use iedit_document::{DocumentLine, Encoding};
+
+use crate::{
+    Editor,
+    editor::{
+        FOLLOWED_OUTPUT,
+        commands::{send_notification, send_simple_notification},
+    },
+    input::Notification,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const READ_BUF_SIZE: usize = 64 * 1024;
+
+impl Editor {
+    /// Keeps appending to the active document what is written at the end of its file,
+    /// like `tail -f`, or what is read from stdin when it has no path.
+    /// NOTE: the cursor is moved to the last line, where it follows the new lines
+    pub fn follow(&mut self) -> io::Result<()> {
+        let path = self.document.canonicalized_file_path.clone();
+        if path.as_os_str().is_empty() {
+            thread::spawn(|| follow_input(io::stdin()));
+        } else {
+            let file = File::open(&path)?;
+            let pos = file.metadata()?.len();
+            let encoding = self.document.encoding;
+            thread::spawn(move || follow_file(file, encoding, pos));
+
+            // it changes on disk all the time, and that's expected
+            self.file_watcher.unwatch(&path);
+        }
+
+        self.buffers[self.active_buffer_idx].is_followed = true;
+        let last_line_idx = self.document.n_lines().saturating_sub(1);
+        self.cursor.update_pos((0, last_line_idx), false);
+        self.adjust_viewport();
+
+        Ok(())
+    }
+
+    /// Appends the text read so far to the followed buffer, scrolling along with it
+    /// while the cursor is on its last line
+    pub fn append_followed_output(&mut self) {
+        let text = match FOLLOWED_OUTPUT.lock() {
+            Ok(mut pending_text) => mem::take(&mut *pending_text),
+            Err(_) => return,
+        };
+
+        if text.is_empty() {
+            return;
+        }
+
+        let Some(buffer_idx) = self.buffers.iter().position(|buffer| buffer.is_followed) else {
+            return;
+        };
+
+        let (document, cursor) = if buffer_idx == self.active_buffer_idx {
+            (&mut self.document, &mut self.cursor)
+        } else {
+            let buffer = &mut self.buffers[buffer_idx];
+            (&mut buffer.document, &mut buffer.cursor)
+        };
+
+        let n_lines = document.n_lines();
+        let should_follow = cursor.cur_y + 1 >= n_lines;
+
+        let line_terminator = if document.end_of_line_seq == "\r" {
+            '\r'
+        } else {
+            '\n'
+        };
+
+        // the text continues the last line, which is taken out and pushed back so that
+        // the paged lines of a large file don't get read from the file again
+        let mut last_line = if n_lines > 0 {
+            document.lines.remove(n_lines - 1)
+        } else {
+            DocumentLine::default()
+        };
+
+        for (idx, text_line) in text.split(line_terminator).enumerate() {
+            if idx > 0 {
+                if line_terminator == '\n' && last_line.as_ref().ends_with('\r') {
+                    last_line.truncate(last_line.len() - 1);
+                }
+
+                last_line.has_been_modified = false;
+                document.lines.push(mem::take(&mut last_line));
+            }
+
+            last_line.push_str(text_line);
+        }
+
+        last_line.has_been_modified = false;
+        document.lines.push(last_line);
+        document.mark_lines_need_render_from(n_lines.saturating_sub(1));
+
+        if should_follow {
+            let last_line_idx = document.n_lines().saturating_sub(1);
+            cursor.update_pos((0, last_line_idx), false);
+        }
+    }
+}
+
+/// Reads what is appended to `file` past `pos` from a background thread,
+/// queueing it in `FOLLOWED_OUTPUT`
+fn follow_file(file: File, encoding: Encoding, mut pos: u64) {
+    let mut buf = vec![0; READ_BUF_SIZE];
+    let mut undecoded = vec![];
+    loop {
+        let file_len = match file.metadata() {
+            Ok(metadata) => metadata.len(),
+            Err(err) => {
+                send_simple_notification(format!("Stopped following file: {}", err));
+                return;
+            }
+        };
+
+        if file_len < pos {
+            send_simple_notification("File truncated, following it from the start");
+            pos = 0;
+            undecoded.clear();
+        }
+
+        if file_len == pos {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+
+        match file.read_at(&mut buf, pos) {
+            Ok(0) => thread::sleep(POLL_INTERVAL),
+            Ok(n_bytes) => {
+                pos += n_bytes as u64;
+                undecoded.extend_from_slice(&buf[..n_bytes]);
+                queue_followed_text(decode_available(encoding, &mut undecoded));
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => {
+                send_simple_notification(format!("Stopped following file: {}", err));
+                return;
+            }
+        }
+    }
+}
+
+/// Reads `input` until it is closed from a background thread, queueing what it
+/// reads in `FOLLOWED_OUTPUT`
+fn follow_input(mut input: impl Read) {
+    let mut buf = vec![0; READ_BUF_SIZE];
+    let mut undecoded = vec![];
+    loop {
+        match input.read(&mut buf) {
+            Ok(0) => return,
+            Ok(n_bytes) => {
+                undecoded.extend_from_slice(&buf[..n_bytes]);
+                queue_followed_text(decode_available(Encoding::Utf8, &mut undecoded));
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => {
+                send_simple_notification(format!("Stopped reading input: {}", err));
+                return;
+            }
+        }
+    }
+}
+
+/// Decodes the bytes up to the last complete character, the rest is left in
+/// `undecoded` to be completed by the next read
+fn decode_available(encoding: Encoding, undecoded: &mut Vec<u8>) -> String {
+    let n_complete_bytes = match encoding {
+        Encoding::Utf8 => match str::from_utf8(undecoded) {
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            _ => undecoded.len(),
+        },
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let n_unit_bytes = undecoded.len() - undecoded.len() % 2;
+            let last_unit = n_unit_bytes.checked_sub(2).map(|start| {
+                let pair = [undecoded[start], undecoded[start + 1]];
+                if encoding == Encoding::Utf16Le {
+                    u16::from_le_bytes(pair)
+                } else {
+                    u16::from_be_bytes(pair)
+                }
+            });
+
+            // a high surrogate is completed by the next unit
+            match last_unit {
+                Some(0xD800..0xDC00) => n_unit_bytes - 2,
+                _ => n_unit_bytes,
+            }
+        }
+        Encoding::Latin1 | Encoding::Windows1252 => undecoded.len(),
+    };
+
+    let bytes = undecoded.drain(..n_complete_bytes).collect::<Vec<_>>();
+    encoding
+        .decode(&bytes)
+        .unwrap_or_else(|_| String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn queue_followed_text(text: String) {
+    if text.is_empty() {
+        return;
+    }
+
+    let Ok(mut pending_text) = FOLLOWED_OUTPUT.lock() else {
+        return;
+    };
+
+    // the editor takes all the pending text at once, one wake up is enough
+    if pending_text.is_empty() {
+        send_notification(Notification::FollowedOutput);
+    }
+
+    pending_text.push_str(&text);
+}
+
+#[cfg(test)]
+mod tests {
+    use iedit_document::Encoding;
+
+    use super::decode_available;
+
+    #[test]
+    fn keeps_incomplete_utf8_chars_for_the_next_read() {
+        let bytes = "a€".as_bytes();
+        let mut undecoded = bytes[..2].to_vec();
+        assert_eq!(decode_available(Encoding::Utf8, &mut undecoded), "a");
+        assert_eq!(undecoded, bytes[1..2]);
+
+        undecoded.extend_from_slice(&bytes[2..]);
+        assert_eq!(decode_available(Encoding::Utf8, &mut undecoded), "€");
+        assert!(undecoded.is_empty());
+    }
+
+    #[test]
+    fn keeps_split_surrogate_pairs_for_the_next_read() {
+        let mut bytes = vec![];
+        Encoding::Utf16Le.encode("a😀", &mut bytes);
+        assert_eq!(bytes.len(), 6);
+
+        // the read ends in the middle of the high surrogate, then after it
+        let mut undecoded = bytes[..3].to_vec();
+        assert_eq!(decode_available(Encoding::Utf16Le, &mut undecoded), "a");
+        assert_eq!(undecoded, bytes[2..3]);
+
+        undecoded.extend_from_slice(&bytes[3..4]);
+        assert_eq!(decode_available(Encoding::Utf16Le, &mut undecoded), "");
+        assert_eq!(undecoded, bytes[2..4]);
+
+        undecoded.extend_from_slice(&bytes[4..]);
+        assert_eq!(decode_available(Encoding::Utf16Le, &mut undecoded), "😀");
+        assert!(undecoded.is_empty());
+    }
+}
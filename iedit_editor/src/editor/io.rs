@@ -1,108 +1,388 @@
 use std::{
-    fs::File,
-    io::{Seek, SeekFrom, Write},
+    ffi::OsString,
+    fs::{self, File, Metadata, OpenOptions},
+    io::{self, Seek, SeekFrom, Write},
+    os::unix::fs::{MetadataExt, fchown},
     path::PathBuf,
-    time::SystemTime,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use iedit_document::DocumentLine;
+use iedit_document::{
+    Document, DocumentLine, DocumentOptions, DocumentSyntax, EditOperation, Encoding, InverseStack,
+    LineStore, Text,
+};
 
-use crate::editor::commands::{CommandExecutionResult, send_simple_notification};
+use crate::{
+    config::{BackupMode, EditorConfig, FinalNewline, SaveMode},
+    editor::{
+        buffers::Buffer,
+        commands::{CommandExecutionResult, send_simple_notification},
+        modes::EditorMode,
+        watcher::get_modified_time,
+    },
+    terminal::{CLEAR_LINE, CURSOR_TO_COL1},
+};
 
 use super::Editor;
 
 impl Editor {
-    pub fn save_file(&mut self, display_notification: bool) -> std::io::Result<()> {
-        if self.is_viewing_execution_output {
+    /// Opens the file at `path`, files larger than `large_file_threshold_mb` are paged
+    /// in as needed instead of being read whole, showing the progress of indexing them
+    pub fn open_document(path: &str, config: &EditorConfig) -> io::Result<Document> {
+        let large_file_threshold = config.large_file_threshold_mb as u64 * 1024 * 1024;
+        let is_large_file = large_file_threshold > 0
+            && fs::metadata(path)
+                .is_ok_and(|metadata| metadata.is_file() && metadata.len() > large_file_threshold);
+
+        if !is_large_file {
+            let options = DocumentOptions {
+                syntaxes_path: config.syntax_highlighting_dir.as_ref().map(PathBuf::from),
+                undo_history_dir: config.get_undo_history_dir().map(PathBuf::from),
+                swap_dir: config.get_swap_dir().map(PathBuf::from),
+            };
+
+            return Document::from_file_with(path, &options);
+        }
+
+        // the editor isn't drawn yet, the first render overwrites the progress
+        let mut stdout = io::stdout();
+        let mut last_percentage = None;
+        let document = Document::from_large_file(
+            path,
+            config.syntax_highlighting_dir.as_ref(),
+            |n_indexed_bytes, file_len| {
+                let percentage = n_indexed_bytes * 100 / file_len.max(1);
+                if last_percentage != Some(percentage) {
+                    last_percentage = Some(percentage);
+                    let _ = write!(
+                        stdout,
+                        "{}{}Indexing {}: {}%",
+                        CURSOR_TO_COL1, CLEAR_LINE, path, percentage
+                    );
+                    let _ = stdout.flush();
+                }
+            },
+        );
+
+        let _ = write!(stdout, "{}{}", CURSOR_TO_COL1, CLEAR_LINE);
+        let _ = stdout.flush();
+
+        document
+    }
+
+    pub fn save_file(&mut self, display_notification: bool) -> io::Result<()> {
+        if self.is_viewing_execution_output() {
             send_simple_notification("Currently viewing execution output, won't save");
             return Ok(());
         }
 
+        if self.is_view_mode {
+            send_simple_notification("Viewing read-only, won't save");
+            return Ok(());
+        }
+
+        // the saved undo history would miss the edits made so far, e.g. while replacing
+        if self.document.is_in_transaction() {
+            send_simple_notification("Edit in progress, won't save");
+            return Ok(());
+        }
+
         if self.document.file.is_none()
             && self.document.canonicalized_file_path.as_os_str().is_empty()
         {
             self.prompt_user("File name: ", Editor::set_file);
 
             return Ok(());
-        } else if self.document.file.is_none() {
+        }
+
+        if self.document.file.is_some() && self.is_file_changed_on_disk() {
+            // the lines of a large file that aren't loaded would be read from the changed file
+            let prompt = if self.document.lines.is_paged() {
+                "File changed on disk: (w)rite elsewhere, (r)eload, (d)iff: "
+            } else {
+                "File changed on disk: (o)verwrite, (w)rite elsewhere, (r)eload, (d)iff: "
+            };
+            self.prompt_user(prompt, Editor::handle_file_changed_answer);
+
+            return Ok(());
+        }
+
+        self.write_file(display_notification)
+    }
+
+    fn write_file(&mut self, display_notification: bool) -> io::Result<()> {
+        // checked upfront, a file written in place would be left half written
+        if let Some((x, y)) = self.document.get_unencodable_char_pos() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Character at {}:{} can't be encoded as {}, file not saved",
+                    y + 1,
+                    x + 1,
+                    self.document.encoding
+                ),
+            ));
+        }
+
+        // undone if the save fails, it would be left behind as an edit nobody asked for
+        let has_final_newline_edit = self.apply_final_newline_config();
+        let bytes_written = match self.write_file_contents() {
+            Ok(bytes_written) => bytes_written,
+            Err(err) => {
+                if has_final_newline_edit {
+                    self.document.undo_last_edit();
+                    self.clamp_cursor();
+                }
+
+                return Err(err);
+            }
+        };
+
+        // only once the file is written in full, a failed save leaves the lines to be saved again
+        let n_lines = self.document.n_lines();
+        for (_, line) in self.document.lines.loaded_lines_mut(0..n_lines) {
+            line.has_been_modified = false;
+        }
+
+        self.document.last_save_time = SystemTime::now();
+        self.document.has_mixed_line_endings = false;
+
+        if let Err(err) = self.document.update_pages() {
+            send_simple_notification(format!("Could not read back saved file: {}", err));
+        }
+
+        if let Err(err) = self.document.clear_swap_file() {
+            send_simple_notification(format!("Could not delete swap file: {}", err));
+        }
+
+        if let Some(undo_history_dir) = self.config.get_undo_history_dir()
+            && let Err(err) = self
+                .document
+                .save_undo_history(undo_history_dir, self.config.undo_history_max_kb * 1024)
+        {
+            send_simple_notification(format!("Could not save undo history: {}", err));
+            return Ok(());
+        }
+
+        if display_notification {
+            send_simple_notification(format!("File saved. {} bytes written", bytes_written));
+        }
+
+        Ok(())
+    }
+
+    /// Backs up the file if configured to, then writes the document to it.
+    /// Returns the number of bytes written
+    fn write_file_contents(&mut self) -> io::Result<usize> {
+        let path = &self.document.canonicalized_file_path;
+        let metadata = fs::metadata(path).ok();
+
+        if let Some(metadata) = &metadata
+            && self.config.backup_mode != BackupMode::Off
+            && let Err(err) = self.write_backup(metadata)
+        {
+            return Err(io::Error::new(
+                err.kind(),
+                format!("Could not write backup, file not saved: {}", err),
+            ));
+        }
+
+        // a large file with mixed line endings is rewritten as a whole, which in place would mean
+        // loading all of it first
+        let must_rewrite_paged = self.document.has_mixed_line_endings
+            && self
+                .document
+                .file
+                .as_ref()
+                .is_some_and(|file| self.document.lines.is_paged_from(file));
+
+        // replacing the file would break its hard links
+        match metadata {
+            Some(metadata)
+                if self.document.file.is_some()
+                    && (self.config.save_mode == SaveMode::Atomic && metadata.nlink() == 1
+                        || must_rewrite_paged) =>
+            {
+                match self.write_atomically(&metadata) {
+                    // e.g. the directory isn't writable, the file itself still might be
+                    Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                        self.write_in_place()
+                    }
+                    res => res,
+                }
+            }
+            _ => self.write_in_place(),
+        }
+    }
+
+    /// Rewrites the file from its first modified line onwards, returns the number of bytes written
+    fn write_in_place(&mut self) -> io::Result<usize> {
+        if self.document.file.is_none() {
             self.document.file = Some(File::create_new(&self.document.canonicalized_file_path)?);
         }
 
-        let mut bytes_written = 0;
+        // the lines that keep their offsets would also keep their line endings
+        let is_changed_on_disk = self.is_file_changed_on_disk();
+        let is_untouched = !is_changed_on_disk && !self.document.has_mixed_line_endings;
         let n_lines = self.document.n_lines();
         let file = self.document.file.as_mut().unwrap();
+        let is_paged_from_file = self.document.lines.is_paged_from(file);
         file.seek(SeekFrom::Start(0))?;
 
-        let is_untouched = file.metadata().is_ok_and(|metadata| {
-            metadata
-                .modified()
-                .is_ok_and(|modified| modified <= self.document.last_save_time)
-        });
-
         let first_modified_line_idx = if is_untouched {
+            // paged out lines are never modified
             let first_modified_line_idx = self
                 .document
                 .lines
-                .iter()
-                .position(|line| line.has_been_modified)
-                .unwrap_or(n_lines);
+                .loaded_lines(0..n_lines)
+                .find(|(_, line)| line.has_been_modified)
+                .map_or(n_lines, |(line_idx, _)| line_idx);
 
-            if let Some(new_file_len) = self.document.line_offsets.get(first_modified_line_idx) {
-                let new_file_len = *new_file_len;
-                file.set_len(new_file_len)?;
-                file.seek(SeekFrom::End(0))?;
+            // the file is only truncated once written, the lines of a large file that are
+            // read on demand are read ahead of being overwritten
+            if let Some(first_modified_offset) =
+                self.document.line_offsets.get(first_modified_line_idx)
+            {
+                file.seek(SeekFrom::Start(*first_modified_offset))?;
             } else {
                 file.seek(SeekFrom::End(0))?;
                 if !self.document.line_offsets.is_empty() {
-                    file.write_all(self.document.end_of_line_seq.as_bytes())?;
+                    let mut end_of_line_bytes = Vec::new();
+                    self.document
+                        .encoding
+                        .encode(&self.document.end_of_line_seq, &mut end_of_line_bytes);
+                    file.write_all(&end_of_line_bytes)?;
                 }
             }
 
             first_modified_line_idx
         } else {
-            // NOTE: file has been modified outiside of iedit, present user with options:
-            // - write to different file
-            // - overwrite
-            // - reload file
+            if is_paged_from_file {
+                // the lines that aren't loaded would be read from where the file changed
+                if is_changed_on_disk {
+                    return Err(io::Error::other(
+                        "Large file changed on disk, file not saved",
+                    ));
+                }
+
+                // the offsets of the lines on disk can't be trusted anymore
+                self.document.lines.load_from(0);
+            }
             file.set_len(0)?;
             0
         };
 
-        let mut new_offsets = Vec::with_capacity(n_lines - first_modified_line_idx);
-        let mut file_writer = std::io::BufWriter::new(file);
-        let mut last_offset = file_writer.stream_position()?;
-        for (line_idx, line) in self
-            .document
-            .lines
-            .iter_mut()
-            .enumerate()
-            .skip(first_modified_line_idx)
-        {
-            line.has_been_modified = false;
-
-            let text = line.as_ref();
-            write!(file_writer, "{}", text)?;
-            let mut line_bytes = text.as_bytes().len();
-            if line_idx != n_lines - 1 {
-                file_writer.write_all(self.document.end_of_line_seq.as_bytes())?;
-                line_bytes += self.document.end_of_line_seq.as_bytes().len();
-            }
-
-            new_offsets.push(last_offset);
-            bytes_written += line_bytes;
-            last_offset += line_bytes as u64;
-        }
+        let mut file_writer = io::BufWriter::new(file);
+        let first_offset = file_writer.stream_position()?;
+        let (bytes_written, new_offsets) = write_lines(
+            &mut file_writer,
+            &self.document.lines,
+            &self.document.end_of_line_seq,
+            (self.document.encoding, self.document.has_bom),
+            first_modified_line_idx,
+            first_offset,
+            is_paged_from_file,
+        )?;
 
         file_writer.flush()?;
+        drop(file_writer);
+        if let Some(file) = self.document.file.as_mut() {
+            file.set_len(first_offset + bytes_written as u64)?;
+        }
 
-        self.document.last_save_time = SystemTime::now();
         self.document.line_offsets.truncate(first_modified_line_idx);
         self.document.line_offsets.extend(new_offsets);
 
-        if display_notification {
-            send_simple_notification(format!("File saved. {} bytes written", bytes_written));
+        Ok(bytes_written)
+    }
+
+    /// Writes the whole document to a temporary file next to the original one, then renames it
+    /// over the original, so that the file on disk is never left half written.
+    /// Returns the number of bytes written
+    fn write_atomically(&mut self, metadata: &Metadata) -> io::Result<usize> {
+        // replacing a symlink would turn it into a regular file, replace its target instead
+        let path = fs::canonicalize(&self.document.canonicalized_file_path)?;
+        let mut temp_file_name = OsString::from(".");
+        temp_file_name.push(path.file_name().unwrap_or_default());
+        temp_file_name.push(format!(".iedit-{}.tmp", std::process::id()));
+        let temp_path = path.with_file_name(temp_file_name);
+
+        let temp_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+
+        let res = (|| {
+            let mut file_writer = io::BufWriter::new(&temp_file);
+            let (bytes_written, new_offsets) = write_lines(
+                &mut file_writer,
+                &self.document.lines,
+                &self.document.end_of_line_seq,
+                (self.document.encoding, self.document.has_bom),
+                0,
+                0,
+                false,
+            )?;
+            file_writer.flush()?;
+            drop(file_writer);
+
+            copy_metadata(metadata, self.document.file.as_ref(), &temp_file)?;
+            temp_file.sync_all()?;
+            fs::rename(&temp_path, &path)?;
+
+            // the rename itself is only durable once the directory is synced
+            if let Some(dir) = path.parent()
+                && let Ok(dir) = File::open(dir)
+            {
+                let _ = dir.sync_all();
+            }
+
+            Ok((bytes_written, new_offsets))
+        })();
+
+        let (bytes_written, new_offsets) = match res {
+            Ok(res) => res,
+            Err(err) => {
+                let _ = fs::remove_file(&temp_path);
+                return Err(err);
+            }
+        };
+
+        self.document.file = Some(temp_file);
+        self.document.line_offsets = new_offsets;
+
+        Ok(bytes_written)
+    }
+
+    /// Copies the file as it is on disk before it gets overwritten
+    fn write_backup(&self, metadata: &Metadata) -> io::Result<()> {
+        let path = &self.document.canonicalized_file_path;
+        let backup_dir = self.config.backup_dir.as_ref().map(PathBuf::from);
+
+        // backups of files from different directories can't clash in a shared backup dir
+        let mut backup_name = match &backup_dir {
+            Some(_) => OsString::from(path.to_string_lossy().replace('/', "%")),
+            None => path.file_name().unwrap_or_default().to_owned(),
+        };
+
+        if self.config.backup_mode == BackupMode::Timestamped {
+            let modified = metadata.modified().unwrap_or_else(|_| SystemTime::now());
+            backup_name.push(format!(".{}", format_timestamp(modified)));
         }
 
+        backup_name.push("~");
+
+        let backup_path = match backup_dir {
+            Some(backup_dir) => {
+                fs::create_dir_all(&backup_dir)?;
+                backup_dir.join(backup_name)
+            }
+            None => path.with_file_name(backup_name),
+        };
+
+        fs::copy(path, backup_path)?;
+
         Ok(())
     }
 
@@ -120,14 +400,467 @@ impl Editor {
             return CommandExecutionResult::Continue;
         };
 
-        if let Ok(file) = File::create_new(&canonicalized_file_path) {
-            self.document.file = Some(file);
-            self.document.canonicalized_file_path = canonicalized_file_path;
+        match File::create_new(&canonicalized_file_path) {
+            Ok(file) => {
+                self.file_watcher
+                    .unwatch(&self.document.canonicalized_file_path);
+                self.file_watcher.watch(&canonicalized_file_path);
+                self.document.file = Some(file);
+                self.document.canonicalized_file_path = canonicalized_file_path;
+            }
+            Err(err) => {
+                send_simple_notification(format!(
+                    "Could not create {}: {}",
+                    canonicalized_file_path.display(),
+                    err
+                ));
+
+                return CommandExecutionResult::Continue;
+            }
         }
 
-        // TODO: handle error here
-        let _ = self.save_file(false);
+        if let Err(err) = self.write_file(false) {
+            send_simple_notification(err.to_string());
+        }
 
         CommandExecutionResult::Continue
     }
+
+    /// Replaces the active document with the file on disk, discarding unsaved changes.
+    /// The file is decoded as `encoding`, or as the detected one when `None`
+    pub fn reload_file(&mut self, encoding: Option<Encoding>) {
+        if self.document.file.is_none() {
+            send_simple_notification("No file to reload");
+            return;
+        }
+
+        if let Err(err) = self.document.reload(encoding) {
+            send_simple_notification(format!("Could not reload: {}", err));
+            return;
+        }
+
+        self.clear_secondary_carets();
+        self.cursor.selection_anchor = None;
+        self.clamp_cursor();
+        self.first_quit_sent = false;
+        self.needs_full_rerender = true;
+        self.check_line_endings();
+    }
+
+    /// Adds or removes the newline at the end of the document before saving, as an edit
+    /// that can be undone. Returns whether it did
+    fn apply_final_newline_config(&mut self) -> bool {
+        let Some(edit) = get_final_newline_edit(&self.document, self.config.final_newline) else {
+            return false;
+        };
+
+        self.document.apply_edit(edit, InverseStack::Undo);
+        self.clamp_cursor();
+
+        true
+    }
+
+    /// Warns that saving converts the line endings of a file that mixes them
+    pub fn check_line_endings(&self) {
+        // the notification would hide the prompt
+        if self.document.has_mixed_line_endings && !matches!(self.mode, EditorMode::Prompt(_)) {
+            send_simple_notification(format!(
+                "Mixed line endings, saving converts them all to {}",
+                get_line_ending_name(&self.document.end_of_line_seq)
+            ));
+        }
+    }
+
+    /// Handles the `eol` prompt command: without argument it displays the line ending
+    /// of the file, otherwise it converts the file to `lf`, `crlf` or `cr` line endings
+    pub fn execute_eol_command(&mut self, arg: &str) -> CommandExecutionResult {
+        let end_of_line_seq = match arg.trim().to_lowercase().as_str() {
+            "" => {
+                let name = get_line_ending_name(&self.document.end_of_line_seq);
+                if self.document.has_mixed_line_endings {
+                    send_simple_notification(format!("{} (mixed)", name));
+                } else {
+                    send_simple_notification(name);
+                }
+
+                return CommandExecutionResult::Continue;
+            }
+            "lf" | "unix" => "\n",
+            "crlf" | "dos" => "\r\n",
+            "cr" | "mac" => "\r",
+            arg => {
+                send_simple_notification(format!("Unknown line ending: {}", arg));
+                return CommandExecutionResult::Continue;
+            }
+        };
+
+        self.document.set_end_of_line_seq(end_of_line_seq);
+        self.needs_full_rerender = true;
+        send_simple_notification(format!(
+            "File will be saved with {} line endings",
+            get_line_ending_name(end_of_line_seq)
+        ));
+
+        CommandExecutionResult::Continue
+    }
+
+    /// Handles the `encoding` prompt command: without argument it displays the encoding
+    /// of the file, otherwise it changes the one used when saving it.
+    /// A `-bom` suffix, as in `utf-8-bom`, saves the file with a byte order mark
+    pub fn execute_encoding_command(&mut self, arg: &str) -> CommandExecutionResult {
+        let arg = arg.trim();
+        if arg.is_empty() {
+            send_simple_notification(self.get_encoding_label());
+            return CommandExecutionResult::Continue;
+        }
+
+        let (name, has_bom) = match arg.strip_suffix("-bom") {
+            Some(name) => (name, true),
+            None => (arg, false),
+        };
+
+        match name.parse::<Encoding>() {
+            Ok(encoding) if has_bom && encoding.bom().is_empty() => {
+                send_simple_notification(format!("{} has no byte order mark", encoding));
+            }
+            Ok(encoding) => {
+                // UTF-16 is hardly readable without a BOM
+                self.document
+                    .set_encoding(encoding, has_bom || encoding.is_utf16());
+                self.needs_full_rerender = true;
+                send_simple_notification(format!(
+                    "File will be saved as {}",
+                    self.get_encoding_label()
+                ));
+            }
+            Err(err) => send_simple_notification(err),
+        }
+
+        CommandExecutionResult::Continue
+    }
+
+    /// Encoding of the active document, as accepted by the `encoding` command
+    pub fn get_encoding_label(&self) -> String {
+        if self.document.has_bom {
+            format!("{}-bom", self.document.encoding)
+        } else {
+            self.document.encoding.to_string()
+        }
+    }
+
+    /// Opens the diff between the file on disk and the active document in a read-only buffer
+    pub fn open_disk_diff(&mut self) {
+        let diff = match self.document.get_disk_diff() {
+            Ok(diff) => diff,
+            Err(err) => {
+                send_simple_notification(format!("Could not read file: {}", err));
+                return;
+            }
+        };
+
+        let mut diff_path = self
+            .document
+            .canonicalized_file_path
+            .clone()
+            .into_os_string();
+        diff_path.push(".diff");
+        let mut diff_document = Document::from_strings(diff, diff_path, true);
+        diff_document.syntax = Some(DocumentSyntax::builtin_diff());
+        for line in diff_document.lines.iter_mut() {
+            line.has_been_modified = false;
+        }
+
+        // a previous diff of the same file is replaced
+        let diff_buffer_idx = (0..self.buffers.len()).position(|buffer_idx| {
+            buffer_idx != self.active_buffer_idx
+                && self.buffers[buffer_idx].document.canonicalized_file_path
+                    == diff_document.canonicalized_file_path
+        });
+
+        let buffer_idx = match diff_buffer_idx {
+            Some(buffer_idx) => {
+                self.buffers[buffer_idx] = Buffer::new(diff_document, false);
+                buffer_idx
+            }
+            None => self.add_buffer(diff_document),
+        };
+
+        self.switch_to_buffer(buffer_idx);
+    }
+
+    /// Whether another program modified the file since it was last loaded or saved
+    fn is_file_changed_on_disk(&self) -> bool {
+        get_modified_time(&self.document.canonicalized_file_path)
+            .is_some_and(|modified| modified > self.document.last_save_time)
+    }
+
+    fn handle_file_changed_answer(&mut self, answer: DocumentLine) -> CommandExecutionResult {
+        match answer.as_ref().trim() {
+            "o" | "overwrite" if !self.document.lines.is_paged() => {
+                // the file may have been replaced rather than modified in place
+                let res = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(&self.document.canonicalized_file_path)
+                    .and_then(|file| {
+                        self.document.file = Some(file);
+                        self.write_file(true)
+                    });
+
+                if let Err(err) = res {
+                    send_simple_notification(err.to_string());
+                }
+            }
+            "w" | "write" => self.prompt_user("File name: ", Editor::set_file),
+            "r" | "reload" => self.reload_file(None),
+            "d" | "diff" => self.open_disk_diff(),
+            _ => {
+                if let Err(err) = self.save_file(true) {
+                    send_simple_notification(err.to_string());
+                }
+            }
+        }
+
+        CommandExecutionResult::Continue
+    }
+}
+
+/// Name of a line ending, as accepted by the `eol` command
+pub fn get_line_ending_name(end_of_line_seq: &str) -> &'static str {
+    match end_of_line_seq {
+        "\r\n" => "crlf",
+        "\r" => "cr",
+        _ => "lf",
+    }
+}
+
+/// Edit adding or removing the newline at the end of `document`, if it needs one
+fn get_final_newline_edit(
+    document: &Document,
+    final_newline: FinalNewline,
+) -> Option<EditOperation> {
+    let n_lines = document.n_lines();
+    let last_line = document.lines.last()?;
+
+    let has_final_newline = n_lines > 1 && last_line.is_empty();
+    match final_newline {
+        // a newline typed in would be auto-indented
+        FinalNewline::Ensure if !last_line.is_empty() => Some(EditOperation::Insertion {
+            pos: (last_line.len(), n_lines - 1),
+            text: Text::Lines(vec![String::new(), String::new()]),
+        }),
+        FinalNewline::Strip if has_final_newline => Some(EditOperation::Deletion {
+            pos: (0, n_lines - 1),
+        }),
+        _ => None,
+    }
+}
+
+/// Writes the lines starting from `first_line_idx`, which begins at byte `first_offset`
+/// in the file, encoded as `encoding` and preceded by its BOM at the start of the file
+/// if `has_bom`. `is_in_place` when writing over the file the lines are paged from.
+/// Returns the number of bytes written and the offsets of the written lines
+fn write_lines(
+    writer: &mut impl Write,
+    lines: &LineStore,
+    end_of_line_seq: &str,
+    (encoding, has_bom): (Encoding, bool),
+    first_line_idx: usize,
+    first_offset: u64,
+    is_in_place: bool,
+) -> io::Result<(usize, Vec<u64>)> {
+    let n_lines = lines.len();
+    let mut bytes_written = 0;
+    let mut last_offset = first_offset;
+    let mut offsets = Vec::with_capacity(n_lines.saturating_sub(first_line_idx));
+
+    if first_offset == 0 && has_bom {
+        writer.write_all(encoding.bom())?;
+        bytes_written += encoding.bom().len();
+        last_offset += encoding.bom().len() as u64;
+    }
+
+    let mut end_of_line_bytes = Vec::new();
+    encoding.encode(end_of_line_seq, &mut end_of_line_bytes);
+
+    let mut line_bytes = Vec::new();
+    let write_err = lines.find_map_from(first_line_idx, |line_idx, line| {
+        line_bytes.clear();
+        encoding.encode(line.as_ref(), &mut line_bytes);
+        if line_idx != n_lines - 1 {
+            line_bytes.extend_from_slice(&end_of_line_bytes);
+        }
+
+        // the lines still to be written may be read from the file being overwritten
+        if is_in_place {
+            lines.load_pages_after(line_idx, last_offset + line_bytes.len() as u64);
+        }
+
+        if let Err(err) = writer.write_all(&line_bytes) {
+            return Some(err);
+        }
+
+        offsets.push(last_offset);
+        bytes_written += line_bytes.len();
+        last_offset += line_bytes.len() as u64;
+
+        None
+    });
+
+    match write_err {
+        Some(err) => Err(err),
+        None => Ok((bytes_written, offsets)),
+    }
+}
+
+/// Gives `new_file` the permissions, ownership and extended attributes of the original file
+fn copy_metadata(
+    metadata: &Metadata,
+    original_file: Option<&File>,
+    new_file: &File,
+) -> io::Result<()> {
+    new_file.set_permissions(metadata.permissions())?;
+
+    // only root can give away files, keeping the group is still worth a try
+    if fchown(new_file, Some(metadata.uid()), Some(metadata.gid())).is_err() {
+        let _ = fchown(new_file, None, Some(metadata.gid()));
+    }
+
+    if let Some(original_file) = original_file {
+        copy_xattrs(original_file, new_file);
+    }
+
+    Ok(())
+}
+
+/// Best effort, some attributes (e.g. `security.*`) can't be set by regular users
+#[cfg(target_os = "linux")]
+fn copy_xattrs(from: &File, to: &File) {
+    use std::os::fd::AsRawFd;
+
+    let (from_fd, to_fd) = (from.as_raw_fd(), to.as_raw_fd());
+
+    // SAFETY: the buffers are sized by the preceding calls and outlive them
+    unsafe {
+        let names_len = libc::flistxattr(from_fd, std::ptr::null_mut(), 0);
+        if names_len <= 0 {
+            return;
+        }
+
+        let mut names = vec![0u8; names_len as usize];
+        let names_len = libc::flistxattr(from_fd, names.as_mut_ptr().cast(), names.len());
+        if names_len <= 0 {
+            return;
+        }
+
+        // names are nul terminated, one after the other
+        for name in names[..names_len as usize].split_inclusive(|byte| *byte == 0) {
+            let value_len = libc::fgetxattr(from_fd, name.as_ptr().cast(), std::ptr::null_mut(), 0);
+            if value_len < 0 {
+                continue;
+            }
+
+            let mut value = vec![0u8; value_len as usize];
+            let value_len = libc::fgetxattr(
+                from_fd,
+                name.as_ptr().cast(),
+                value.as_mut_ptr().cast(),
+                value.len(),
+            );
+            if value_len < 0 {
+                continue;
+            }
+
+            libc::fsetxattr(
+                to_fd,
+                name.as_ptr().cast(),
+                value.as_ptr().cast(),
+                value_len as usize,
+                0,
+            );
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn copy_xattrs(_from: &File, _to: &File) {}
+
+/// `YYYYMMDD-HHMMSS`, in UTC
+fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let (days, day_secs) = (secs / 86400, secs % 86400);
+
+    // days since the epoch to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let days = days as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_idx = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_idx + 2) / 5 + 1;
+    let month = if month_idx < 10 {
+        month_idx + 3
+    } else {
+        month_idx - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        day_secs / 3600,
+        day_secs % 3600 / 60,
+        day_secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use iedit_document::{Document, InverseStack};
+
+    use super::get_final_newline_edit;
+    use crate::config::FinalNewline;
+
+    fn apply_final_newline(document: &mut Document, final_newline: FinalNewline) {
+        if let Some(edit) = get_final_newline_edit(document, final_newline) {
+            document.apply_edit(edit, InverseStack::Undo);
+        }
+    }
+
+    fn get_lines(document: &Document) -> Vec<String> {
+        (0..document.n_lines())
+            .map(|idx| document.lines[idx].as_ref().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn ensures_an_unindented_final_newline_once() {
+        let mut document =
+            Document::from_strings(vec!["fn f() {".into(), "    x".into()], "test", false);
+
+        apply_final_newline(&mut document, FinalNewline::Ensure);
+        assert_eq!(get_lines(&document), ["fn f() {", "    x", ""]);
+
+        apply_final_newline(&mut document, FinalNewline::Ensure);
+        assert_eq!(get_lines(&document), ["fn f() {", "    x", ""]);
+    }
+
+    #[test]
+    fn strips_the_final_newline() {
+        let mut document = Document::from_strings(vec!["x".into(), "".into()], "test", false);
+
+        apply_final_newline(&mut document, FinalNewline::Strip);
+        assert_eq!(get_lines(&document), ["x"]);
+
+        apply_final_newline(&mut document, FinalNewline::Strip);
+        assert_eq!(get_lines(&document), ["x"]);
+    }
 }
@@ -1,44 +1,165 @@
-pub static HELP_POPUP_LINES: [&'static str; 11] = [
-    "Ctrl-k      │ enter chord",
-    "Ctrl-g      │ go to",
-    "Ctrl-e      │ open command prompt",
-    "Ctrl-f (-b) │ forward (backward) search",
-    "Ctrl-z (-r) │ undo (redo)",
-    "Ctrl-y (-x) │ copy (cut)",
-    "Ctrl-p      │ paste",
-    "Ctrl-u (-d) │ go up (down) a page",
-    "Alt-o  (-i) │ go to previous (next) jump",
-    "Alt-n  (-m) │ go to next (previous) match",
-    "Alt-p       │ go to matching paren",
-];
+use crate::editor::keymap::KeyCommand;
 
-pub static CHORDS_POPUP_LINES: [&'static str; 6] = [
-    "l │ line",
-    "x │ execute",
-    "s │ selection",
-    "t │ find char forward",
-    "T │ find char backward",
-    "v │ view",
+/// Default bindings, in the same format as the `bind` lines of the config.
+/// Bindings from the config are added after these, and take precedence over them
+pub static DEFAULT_KEYBINDINGS: [&str; 122] = [
+    "Ctrl-q quit",
+    "Ctrl-s save",
+    "Ctrl-t help",
+    "Ctrl-z undo",
+    "Ctrl-r redo",
+    "Ctrl-f search_forward",
+    "Ctrl-b search_backward",
+    "Ctrl-g go_to_line",
+    "Ctrl-e command_prompt",
+    "Alt-e command_prompt",
+    "Ctrl-y copy",
+    "Ctrl-x cut",
+    "Ctrl-p paste",
+    "Ctrl-u page_up",
+    "Ctrl-d page_down",
+    "PageDown page_down",
+    "Alt-Up scroll_up",
+    "Alt-Down scroll_down",
+    "Alt-o previous_jump",
+    "Alt-i next_jump",
+    "Alt-n next_match",
+    "Alt-m previous_match",
+    "Alt-p matching_paren",
+    "Alt-d add_cursor_at_next_match",
+    "Alt-a line_start",
+    "Alt-s line_end",
+    "Ctrl-Up previous_paragraph",
+    "Ctrl-Down next_paragraph",
+    "Ctrl-Shift-Up select_previous_paragraph",
+    "Ctrl-Shift-Down select_next_paragraph",
+    "Alt-Right expand_selection",
+    "Alt-Left shrink_selection",
+    "Alt-/ toggle_comment",
+    "Left left",
+    "Alt-h left",
+    "Right right",
+    "Alt-l right",
+    "Up up",
+    "Alt-k up",
+    "Down down",
+    "Alt-j down",
+    "Shift-Left select_left",
+    "Shift-Right select_right",
+    "Shift-Up select_up",
+    "Shift-Down select_down",
+    "Ctrl-Right word_forward",
+    "Alt-w word_forward",
+    "Ctrl-Left word_backward",
+    "Alt-b word_backward",
+    "Ctrl-Shift-Right select_word_forward",
+    "Ctrl-Shift-Left select_word_backward",
+    "Ctrl-h delete_word_backward",
+    "Ctrl-Backspace delete_word_backward",
+    "Alt-Backspace delete_word_backward",
+    "Ctrl-k b l buffer_list",
+    "Ctrl-k b n next_buffer",
+    "Ctrl-k b p previous_buffer",
+    "Ctrl-k b d close_buffer",
+    "Ctrl-k c k add_cursor_above",
+    "Ctrl-k c j add_cursor_below",
+    "Ctrl-k c n add_cursor_at_next_match",
+    "Ctrl-k c l split_selection",
+    "Ctrl-k e n next_error",
+    "Ctrl-k e p previous_error",
+    "Ctrl-k e l error_list",
+    "Ctrl-k l d delete_line",
+    "Ctrl-k l k move_lines_up",
+    "Ctrl-k l j move_lines_down",
+    "Ctrl-k l y duplicate_lines",
+    "Ctrl-k l J join_lines",
+    "Ctrl-k l s sort_lines",
+    "Ctrl-k l r reverse_lines",
+    "Ctrl-k l u unique_lines",
+    "Ctrl-k l x shuffle_lines",
+    "Ctrl-k l c toggle_comment",
+    "Ctrl-k l n toggle_line_numbers",
+    "Ctrl-k l w line_start",
+    "Ctrl-k l e line_end",
+    "Ctrl-k r r replace_confirm",
+    "Ctrl-k r a replace_all",
+    "Ctrl-k x x run",
+    "Ctrl-k x ? run_with",
+    "Ctrl-k x p run_python3",
+    "Ctrl-k x P run_python",
+    "Ctrl-k x n run_node",
+    "Ctrl-k x b run_bash",
+    "Ctrl-k x i interrupt",
+    "Ctrl-k x k kill",
+    "Ctrl-k s l toggle_selection_lock",
+    "Ctrl-k t find_char_forward",
+    "Ctrl-k T find_char_backward",
+    "Ctrl-k i select_inside",
+    "Ctrl-k a select_around",
+    "Ctrl-k v o toggle_output",
+    "Ctrl-k v u undo_tree",
+    "Ctrl-k v w toggle_soft_wrap",
+    "normal u undo",
+    "normal : command_prompt",
+    "normal / search_forward",
+    "normal ? search_backward",
+    "view q quit",
+    "view Q quit",
+    "view h help",
+    "view j down",
+    "view k up",
+    "view Enter down",
+    "view Space page_down",
+    "view f page_down",
+    "view b page_up",
+    "view PageUp page_up",
+    "view d half_page_down",
+    "view u half_page_up",
+    "view g file_start",
+    "view < file_start",
+    "view G file_end",
+    "view > file_end",
+    "view F file_end",
+    "view / search_forward",
+    "view ? search_backward",
+    "view n next_match",
+    "view N previous_match",
+    "view : command_prompt",
 ];
 
-pub static L_CHORD_POPUP_LINES: [&'static str; 4] = [
-    "d │ delete",
-    "n │ toggle numbers",
-    "w │ go to start",
-    "e │ go to end",
+/// Names shown in the chords popup for the groups of chords sharing their second key
+pub static CHORD_GROUP_NAMES: [(char, &str); 8] = [
+    ('b', "buffers"),
+    ('c', "cursors"),
+    ('e', "errors"),
+    ('l', "line"),
+    ('r', "replace"),
+    ('x', "execute"),
+    ('s', "selection"),
+    ('v', "view"),
 ];
 
-pub static X_CHORD_POPUP_LINES: [&'static str; 6] = [
-    "x │ auto",
-    "? │ manual",
-    "p │ python3",
-    "P │ python",
-    "n │ node",
-    "b │ bash",
+/// Commands listed in the help popup, along with the keys they are bound to
+pub static HELP_COMMANDS: [KeyCommand; 17] = [
+    KeyCommand::GoToLine,
+    KeyCommand::CommandPrompt,
+    KeyCommand::SearchForward,
+    KeyCommand::SearchBackward,
+    KeyCommand::Undo,
+    KeyCommand::Redo,
+    KeyCommand::Copy,
+    KeyCommand::Cut,
+    KeyCommand::Paste,
+    KeyCommand::PageUp,
+    KeyCommand::PageDown,
+    KeyCommand::PreviousJump,
+    KeyCommand::NextJump,
+    KeyCommand::NextMatch,
+    KeyCommand::PreviousMatch,
+    KeyCommand::MatchingParen,
+    KeyCommand::AddCursorAtNextMatch,
 ];
 
-pub static T_CHORD_POPUP_LINES: [&'static str; 1] = ["press a key..."];
-
-pub static S_CHORD_POPUP_LINES: [&'static str; 1] = ["l │ lock/unlock selection"];
-
-pub static V_CHORD_POPUP_LINES: [&'static str; 1] = ["o │ output/original"];
+/// Commands whose keys are shown at the right of the status bar
+pub static STATUS_BAR_COMMANDS: [KeyCommand; 3] =
+    [KeyCommand::Quit, KeyCommand::Save, KeyCommand::Help];
@@ -0,0 +1,913 @@
+use std::{fmt::Display, str::FromStr};
+
+use termion::event::Key;
+
+use crate::{
+    editor::{
+        Editor,
+        commands::{CursorMovement, EditorCommand},
+        keybindings::{CHORD_GROUP_NAMES, DEFAULT_KEYBINDINGS, HELP_COMMANDS, STATUS_BAR_COMMANDS},
+        lines::{LineOrder, SortOptions},
+        modes::EditorMode,
+        selection::parse_text_object,
+    },
+    input::Input,
+};
+
+/// Key that starts a chord, chords are made of it and two more keys
+pub const CHORD_LEADER: Key = Key::Ctrl('k');
+
+/// Commands that can be bound to keys, referred to by their name in the config
+#[derive(Clone, Copy, PartialEq)]
+pub enum KeyCommand {
+    Quit,
+    Save,
+    Help,
+    Undo,
+    Redo,
+    SearchForward,
+    SearchBackward,
+    GoToLine,
+    CommandPrompt,
+    Copy,
+    Cut,
+    Paste,
+    PageUp,
+    PageDown,
+    ScrollUp,
+    ScrollDown,
+    HalfPageUp,
+    HalfPageDown,
+    FileStart,
+    FileEnd,
+    PreviousJump,
+    NextJump,
+    NextMatch,
+    PreviousMatch,
+    MatchingParen,
+    AddCursorAtNextMatch,
+    LineStart,
+    LineEnd,
+    PreviousParagraph,
+    NextParagraph,
+    SelectPreviousParagraph,
+    SelectNextParagraph,
+    Left,
+    Right,
+    Up,
+    Down,
+    SelectLeft,
+    SelectRight,
+    SelectUp,
+    SelectDown,
+    WordForward,
+    WordBackward,
+    SelectWordForward,
+    SelectWordBackward,
+    DeleteWordBackward,
+    DeleteLine,
+    MoveLinesUp,
+    MoveLinesDown,
+    DuplicateLines,
+    JoinLines,
+    SortLines,
+    ReverseLines,
+    UniqueLines,
+    ShuffleLines,
+    ToggleComment,
+    ToggleLineNumbers,
+    ToggleSoftWrap,
+    ToggleSelectionLock,
+    FindCharForward,
+    FindCharBackward,
+    Run,
+    RunWith,
+    RunPython3,
+    RunPython,
+    RunNode,
+    RunBash,
+    Interrupt,
+    Kill,
+    ToggleOutput,
+    UndoTree,
+    BufferList,
+    NextBuffer,
+    PreviousBuffer,
+    CloseBuffer,
+    NextError,
+    PreviousError,
+    ErrorList,
+    AddCursorAbove,
+    AddCursorBelow,
+    SplitSelection,
+    ExpandSelection,
+    ShrinkSelection,
+    SelectInside,
+    SelectAround,
+    ReplaceConfirm,
+    ReplaceAll,
+    /// Removes a default binding
+    Unbound,
+}
+
+/// Name used in the config and description shown in the popups of each command
+static KEY_COMMANDS: [(KeyCommand, &str, &str); 87] = [
+    (KeyCommand::Quit, "quit", "quit"),
+    (KeyCommand::Save, "save", "save"),
+    (KeyCommand::Help, "help", "display help"),
+    (KeyCommand::Undo, "undo", "undo"),
+    (KeyCommand::Redo, "redo", "redo"),
+    (
+        KeyCommand::SearchForward,
+        "search_forward",
+        "forward search",
+    ),
+    (
+        KeyCommand::SearchBackward,
+        "search_backward",
+        "backward search",
+    ),
+    (KeyCommand::GoToLine, "go_to_line", "go to line"),
+    (
+        KeyCommand::CommandPrompt,
+        "command_prompt",
+        "open command prompt",
+    ),
+    (KeyCommand::Copy, "copy", "copy"),
+    (KeyCommand::Cut, "cut", "cut"),
+    (KeyCommand::Paste, "paste", "paste"),
+    (KeyCommand::PageUp, "page_up", "go up a page"),
+    (KeyCommand::PageDown, "page_down", "go down a page"),
+    (KeyCommand::ScrollUp, "scroll_up", "scroll up"),
+    (KeyCommand::ScrollDown, "scroll_down", "scroll down"),
+    (KeyCommand::HalfPageUp, "half_page_up", "go up half a page"),
+    (
+        KeyCommand::HalfPageDown,
+        "half_page_down",
+        "go down half a page",
+    ),
+    (KeyCommand::FileStart, "file_start", "go to start of file"),
+    (KeyCommand::FileEnd, "file_end", "go to end of file"),
+    (
+        KeyCommand::PreviousJump,
+        "previous_jump",
+        "go to previous jump",
+    ),
+    (KeyCommand::NextJump, "next_jump", "go to next jump"),
+    (KeyCommand::NextMatch, "next_match", "go to next match"),
+    (
+        KeyCommand::PreviousMatch,
+        "previous_match",
+        "go to previous match",
+    ),
+    (
+        KeyCommand::MatchingParen,
+        "matching_paren",
+        "go to matching paren",
+    ),
+    (
+        KeyCommand::AddCursorAtNextMatch,
+        "add_cursor_at_next_match",
+        "add cursor at next match",
+    ),
+    (KeyCommand::LineStart, "line_start", "go to start of line"),
+    (KeyCommand::LineEnd, "line_end", "go to end of line"),
+    (
+        KeyCommand::PreviousParagraph,
+        "previous_paragraph",
+        "go to previous paragraph",
+    ),
+    (
+        KeyCommand::NextParagraph,
+        "next_paragraph",
+        "go to next paragraph",
+    ),
+    (
+        KeyCommand::SelectPreviousParagraph,
+        "select_previous_paragraph",
+        "select to previous paragraph",
+    ),
+    (
+        KeyCommand::SelectNextParagraph,
+        "select_next_paragraph",
+        "select to next paragraph",
+    ),
+    (KeyCommand::Left, "left", "move left"),
+    (KeyCommand::Right, "right", "move right"),
+    (KeyCommand::Up, "up", "move up"),
+    (KeyCommand::Down, "down", "move down"),
+    (KeyCommand::SelectLeft, "select_left", "select left"),
+    (KeyCommand::SelectRight, "select_right", "select right"),
+    (KeyCommand::SelectUp, "select_up", "select up"),
+    (KeyCommand::SelectDown, "select_down", "select down"),
+    (KeyCommand::WordForward, "word_forward", "go to next word"),
+    (
+        KeyCommand::WordBackward,
+        "word_backward",
+        "go to previous word",
+    ),
+    (
+        KeyCommand::SelectWordForward,
+        "select_word_forward",
+        "select to next word",
+    ),
+    (
+        KeyCommand::SelectWordBackward,
+        "select_word_backward",
+        "select to previous word",
+    ),
+    (
+        KeyCommand::DeleteWordBackward,
+        "delete_word_backward",
+        "delete previous word",
+    ),
+    (KeyCommand::DeleteLine, "delete_line", "delete line"),
+    (KeyCommand::MoveLinesUp, "move_lines_up", "move line(s) up"),
+    (
+        KeyCommand::MoveLinesDown,
+        "move_lines_down",
+        "move line(s) down",
+    ),
+    (
+        KeyCommand::DuplicateLines,
+        "duplicate_lines",
+        "duplicate line(s)",
+    ),
+    (KeyCommand::JoinLines, "join_lines", "join lines"),
+    (KeyCommand::SortLines, "sort_lines", "sort lines"),
+    (KeyCommand::ReverseLines, "reverse_lines", "reverse lines"),
+    (
+        KeyCommand::UniqueLines,
+        "unique_lines",
+        "remove duplicate lines",
+    ),
+    (KeyCommand::ShuffleLines, "shuffle_lines", "shuffle lines"),
+    (
+        KeyCommand::ToggleComment,
+        "toggle_comment",
+        "comment/uncomment line(s)",
+    ),
+    (
+        KeyCommand::ToggleLineNumbers,
+        "toggle_line_numbers",
+        "toggle line numbers",
+    ),
+    (
+        KeyCommand::ToggleSoftWrap,
+        "toggle_soft_wrap",
+        "toggle soft wrap",
+    ),
+    (
+        KeyCommand::ToggleSelectionLock,
+        "toggle_selection_lock",
+        "lock/unlock selection",
+    ),
+    (
+        KeyCommand::FindCharForward,
+        "find_char_forward",
+        "find char forward",
+    ),
+    (
+        KeyCommand::FindCharBackward,
+        "find_char_backward",
+        "find char backward",
+    ),
+    (KeyCommand::Run, "run", "run with inferred runner"),
+    (KeyCommand::RunWith, "run_with", "run with..."),
+    (KeyCommand::RunPython3, "run_python3", "run with python3"),
+    (KeyCommand::RunPython, "run_python", "run with python"),
+    (KeyCommand::RunNode, "run_node", "run with node"),
+    (KeyCommand::RunBash, "run_bash", "run with bash"),
+    (
+        KeyCommand::Interrupt,
+        "interrupt",
+        "interrupt running command",
+    ),
+    (KeyCommand::Kill, "kill", "kill running command"),
+    (
+        KeyCommand::ToggleOutput,
+        "toggle_output",
+        "switch to output/original",
+    ),
+    (KeyCommand::UndoTree, "undo_tree", "browse undo tree"),
+    (KeyCommand::BufferList, "buffer_list", "list buffers"),
+    (KeyCommand::NextBuffer, "next_buffer", "next buffer"),
+    (
+        KeyCommand::PreviousBuffer,
+        "previous_buffer",
+        "previous buffer",
+    ),
+    (KeyCommand::CloseBuffer, "close_buffer", "close buffer"),
+    (KeyCommand::NextError, "next_error", "next error"),
+    (
+        KeyCommand::PreviousError,
+        "previous_error",
+        "previous error",
+    ),
+    (KeyCommand::ErrorList, "error_list", "list errors"),
+    (
+        KeyCommand::AddCursorAbove,
+        "add_cursor_above",
+        "add cursor above",
+    ),
+    (
+        KeyCommand::AddCursorBelow,
+        "add_cursor_below",
+        "add cursor below",
+    ),
+    (
+        KeyCommand::SplitSelection,
+        "split_selection",
+        "split selection into lines",
+    ),
+    (
+        KeyCommand::ExpandSelection,
+        "expand_selection",
+        "expand selection to enclosing word, string, brackets, line...",
+    ),
+    (
+        KeyCommand::ShrinkSelection,
+        "shrink_selection",
+        "undo last selection expansion",
+    ),
+    (
+        KeyCommand::SelectInside,
+        "select_inside",
+        "select inside brackets, quotes, word or paragraph (p)",
+    ),
+    (
+        KeyCommand::SelectAround,
+        "select_around",
+        "select around brackets, quotes, word or paragraph (p)",
+    ),
+    (
+        KeyCommand::ReplaceConfirm,
+        "replace_confirm",
+        "replace, confirm each match",
+    ),
+    (KeyCommand::ReplaceAll, "replace_all", "replace all"),
+    (KeyCommand::Unbound, "none", ""),
+];
+
+impl KeyCommand {
+    pub fn get_name(self) -> &'static str {
+        KEY_COMMANDS
+            .iter()
+            .find(|(command, _, _)| *command == self)
+            .map_or("", |(_, name, _)| name)
+    }
+
+    pub fn get_description(self) -> &'static str {
+        KEY_COMMANDS
+            .iter()
+            .find(|(command, _, _)| *command == self)
+            .map_or("", |(_, _, description)| description)
+    }
+
+    /// Commands completed by the key pressed after them, e.g. the character to look for
+    pub fn takes_key(self) -> bool {
+        matches!(
+            self,
+            KeyCommand::FindCharForward
+                | KeyCommand::FindCharBackward
+                | KeyCommand::SelectInside
+                | KeyCommand::SelectAround
+        )
+    }
+}
+
+impl FromStr for KeyCommand {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KEY_COMMANDS
+            .iter()
+            .find(|(_, name, _)| *name == s)
+            .map(|(command, _, _)| *command)
+            .ok_or(())
+    }
+}
+
+/// Modes with their own bindings, the bindings without a mode apply to all of them
+#[derive(Clone, Copy, PartialEq)]
+pub enum KeymapMode {
+    Insert,
+    /// vi normal and visual modes
+    Normal,
+    /// read-only pager mode
+    View,
+}
+
+impl FromStr for KeymapMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "insert" => Ok(Self::Insert),
+            "normal" => Ok(Self::Normal),
+            "view" => Ok(Self::View),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for KeymapMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Insert => write!(f, "insert"),
+            Self::Normal => write!(f, "normal"),
+            Self::View => write!(f, "view"),
+        }
+    }
+}
+
+/// A `bind` line of the config: `[mode] <key> <command>` or
+/// `[mode] Ctrl-k <key> <key> <command>` for chords, e.g. `normal Ctrl-k x x run`
+#[derive(Clone)]
+pub struct KeyBinding {
+    mode: Option<KeymapMode>,
+    keys: Vec<Key>,
+    command: KeyCommand,
+}
+
+impl FromStr for KeyBinding {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace().collect::<Vec<_>>();
+        let command = tokens.pop().ok_or(())?.parse::<KeyCommand>()?;
+        let mode = match tokens.first().map(|token| token.parse::<KeymapMode>()) {
+            Some(Ok(mode)) => {
+                tokens.remove(0);
+                Some(mode)
+            }
+            _ => None,
+        };
+
+        let keys = tokens
+            .into_iter()
+            .map(parse_key)
+            .collect::<Option<Vec<_>>>()
+            .ok_or(())?;
+
+        // the input parser only knows chords of the leader and two keys,
+        // the commands taking a key are completed by the third one
+        let n_chord_keys = if command.takes_key() { 2 } else { 3 };
+        let is_valid = match keys.as_slice() {
+            [key] => *key != CHORD_LEADER && !command.takes_key(),
+            [CHORD_LEADER, ..] => keys.len() == n_chord_keys,
+            _ => false,
+        };
+
+        if !is_valid {
+            return Err(());
+        }
+
+        Ok(Self {
+            mode,
+            keys,
+            command,
+        })
+    }
+}
+
+impl Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(mode) = self.mode {
+            write!(f, "{} ", mode)?;
+        }
+
+        for key in &self.keys {
+            write!(f, "{} ", format_key(*key))?;
+        }
+
+        write!(f, "{}", self.command.get_name())
+    }
+}
+
+/// Bindings of the editing modes, the default ones followed by the ones from the config
+pub struct Keymap {
+    bindings: Vec<KeyBinding>,
+    default_bindings_count: usize,
+}
+
+impl Keymap {
+    pub fn new(user_bindings: Vec<KeyBinding>) -> Self {
+        let bindings = DEFAULT_KEYBINDINGS
+            .iter()
+            .map(|binding| binding.parse().expect("invalid default keybinding"))
+            .chain(user_bindings)
+            .collect();
+
+        Self {
+            bindings,
+            default_bindings_count: DEFAULT_KEYBINDINGS.len(),
+        }
+    }
+
+    /// Command bound to `keys`, the last binding wins
+    pub fn get_command(&self, mode: KeymapMode, keys: &[Key]) -> Option<KeyCommand> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|binding| binding.applies_to(mode) && binding.keys == keys)
+            .map(|binding| binding.command)
+            .filter(|command| *command != KeyCommand::Unbound)
+    }
+
+    /// Lines of the help popup: the chord leader, then the keys of the main commands
+    /// and of the ones bound in the config
+    pub fn get_help_lines(&self, mode: KeymapMode) -> Vec<String> {
+        // commands bound in the config are listed too
+        let extra_commands = self.bindings[self.default_bindings_count..]
+            .iter()
+            .filter(|binding| {
+                binding.keys.len() == 1
+                    && self.get_command(mode, &binding.keys) == Some(binding.command)
+            })
+            .map(|binding| binding.command)
+            .filter(|command| !STATUS_BAR_COMMANDS.contains(command));
+
+        let mut rows = vec![(format_key(CHORD_LEADER), "enter chord")];
+        let mut listed_commands = vec![];
+        for command in HELP_COMMANDS.into_iter().chain(extra_commands) {
+            if listed_commands.contains(&command) {
+                continue;
+            }
+
+            listed_commands.push(command);
+            let keys = self
+                .get_active_bindings(mode)
+                .filter(|binding| binding.command == command && binding.keys.len() == 1)
+                .map(|binding| format_key(binding.keys[0]))
+                .collect::<Vec<_>>();
+
+            if !keys.is_empty() {
+                rows.push((keys.join(", "), command.get_description()));
+            }
+        }
+
+        format_popup_rows(rows)
+    }
+
+    /// Lines of the chords popup, for the chords starting with the leader and `prefix`,
+    /// or for all of them when `prefix` is `None`
+    pub fn get_chord_help_lines(&self, mode: KeymapMode, prefix: Option<Key>) -> Vec<String> {
+        let chord_bindings = self
+            .get_active_bindings(mode)
+            .filter(|binding| binding.keys.len() > 1)
+            .collect::<Vec<_>>();
+
+        let mut rows: Vec<(String, String)> = vec![];
+        for binding in &chord_bindings {
+            let (key, description) = match prefix {
+                Some(prefix) if binding.keys[1] == prefix && binding.keys.len() == 3 => (
+                    binding.keys[2],
+                    binding.command.get_description().to_owned(),
+                ),
+                Some(_) => continue,
+                None if binding.keys.len() == 2 => (
+                    binding.keys[1],
+                    binding.command.get_description().to_owned(),
+                ),
+                None => (
+                    binding.keys[1],
+                    get_chord_group_name(binding.keys[1], &chord_bindings),
+                ),
+            };
+
+            let key = format_key(key);
+            if !rows.iter().any(|(row_key, _)| *row_key == key) {
+                rows.push((key, description));
+            }
+        }
+
+        format_popup_rows(rows)
+    }
+
+    /// e.g. `│ Ctrl-q: quit │ Ctrl-s: save │ Ctrl-t: help`
+    pub fn get_status_bar_hint(&self, mode: KeymapMode) -> String {
+        STATUS_BAR_COMMANDS
+            .iter()
+            .filter_map(|command| {
+                let binding = self
+                    .get_active_bindings(mode)
+                    .find(|binding| binding.command == *command && binding.keys.len() == 1)?;
+
+                Some(format!(
+                    "│ {}: {} ",
+                    format_key(binding.keys[0]),
+                    command.get_name()
+                ))
+            })
+            .collect::<String>()
+            .trim_end()
+            .to_owned()
+    }
+
+    /// Bindings that aren't overridden by a later one, in the order they were defined
+    fn get_active_bindings(&self, mode: KeymapMode) -> impl Iterator<Item = &KeyBinding> {
+        self.bindings.iter().filter(move |binding| {
+            binding.applies_to(mode)
+                && binding.command != KeyCommand::Unbound
+                && self.get_command(mode, &binding.keys) == Some(binding.command)
+        })
+    }
+}
+
+impl KeyBinding {
+    fn applies_to(&self, mode: KeymapMode) -> bool {
+        self.mode.is_none_or(|binding_mode| binding_mode == mode)
+    }
+}
+
+fn get_chord_group_name(key: Key, chord_bindings: &[&KeyBinding]) -> String {
+    if let Key::Char(ch) = key
+        && let Some((_, name)) = CHORD_GROUP_NAMES.iter().find(|(group, _)| *group == ch)
+    {
+        return (*name).to_owned();
+    }
+
+    chord_bindings
+        .iter()
+        .filter(|binding| binding.keys[1] == key)
+        .map(|binding| binding.command.get_description())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Aligns the keys of the popup rows in a column
+fn format_popup_rows(rows: Vec<(String, impl AsRef<str>)>) -> Vec<String> {
+    let keys_width = rows
+        .iter()
+        .map(|(keys, _)| keys.chars().count())
+        .max()
+        .unwrap_or_default();
+
+    rows.into_iter()
+        .map(|(keys, description)| format!("{:keys_width$} │ {}", keys, description.as_ref()))
+        .collect()
+}
+
+/// Keys with a name, the other ones are written as `Ctrl-<char>`, `Alt-<char>`,
+/// `F<n>` or the character itself
+static NAMED_KEYS: [(&str, Key); 38] = [
+    ("Enter", Key::Char('\n')),
+    ("Tab", Key::Char('\t')),
+    ("Space", Key::Char(' ')),
+    ("Esc", Key::Esc),
+    ("Backspace", Key::Backspace),
+    ("Ctrl-Backspace", Key::Ctrl('\x7F')),
+    ("Alt-Backspace", Key::Alt('\x7F')),
+    ("Delete", Key::Delete),
+    ("Insert", Key::Insert),
+    ("BackTab", Key::BackTab),
+    ("PageUp", Key::PageUp),
+    ("PageDown", Key::PageDown),
+    ("Home", Key::Home),
+    ("Ctrl-Home", Key::CtrlHome),
+    ("Ctrl-Shift-Home", Key::CtrlShiftHome),
+    ("End", Key::End),
+    ("Ctrl-End", Key::CtrlEnd),
+    ("Ctrl-Shift-End", Key::CtrlShiftEnd),
+    ("Left", Key::Left),
+    ("Shift-Left", Key::ShiftLeft),
+    ("Alt-Left", Key::AltLeft),
+    ("Ctrl-Left", Key::CtrlLeft),
+    ("Ctrl-Shift-Left", Key::CtrlShiftLeft),
+    ("Right", Key::Right),
+    ("Shift-Right", Key::ShiftRight),
+    ("Alt-Right", Key::AltRight),
+    ("Ctrl-Right", Key::CtrlRight),
+    ("Ctrl-Shift-Right", Key::CtrlShiftRight),
+    ("Up", Key::Up),
+    ("Shift-Up", Key::ShiftUp),
+    ("Alt-Up", Key::AltUp),
+    ("Ctrl-Up", Key::CtrlUp),
+    ("Ctrl-Shift-Up", Key::CtrlShiftUp),
+    ("Down", Key::Down),
+    ("Shift-Down", Key::ShiftDown),
+    ("Alt-Down", Key::AltDown),
+    ("Ctrl-Down", Key::CtrlDown),
+    ("Ctrl-Shift-Down", Key::CtrlShiftDown),
+];
+
+pub fn parse_key(s: &str) -> Option<Key> {
+    if let Some((_, key)) = NAMED_KEYS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(s))
+    {
+        return Some(*key);
+    }
+
+    let mut chars = s.chars();
+    let first_char = chars.next()?;
+    if chars.next().is_none() {
+        return Some(Key::Char(first_char));
+    }
+
+    let single_char = |s: &str| {
+        let mut chars = s.chars();
+        chars.next().filter(|_| chars.next().is_none())
+    };
+
+    if let Some(ch) = s.strip_prefix("Ctrl-").and_then(single_char) {
+        Some(Key::Ctrl(ch.to_ascii_lowercase()))
+    } else if let Some(ch) = s.strip_prefix("Alt-").and_then(single_char) {
+        Some(Key::Alt(ch))
+    } else {
+        s.strip_prefix('F')
+            .and_then(|n| n.parse::<u8>().ok())
+            .filter(|n| (1..=12).contains(n))
+            .map(Key::F)
+    }
+}
+
+pub fn format_key(key: Key) -> String {
+    if let Some((name, _)) = NAMED_KEYS.iter().find(|(_, named_key)| *named_key == key) {
+        return (*name).to_owned();
+    }
+
+    match key {
+        Key::Char(ch) => ch.to_string(),
+        Key::Ctrl(ch) => format!("Ctrl-{}", ch),
+        Key::Alt(ch) => format!("Alt-{}", ch),
+        Key::F(n) => format!("F{}", n),
+        _ => "?".to_owned(),
+    }
+}
+
+impl Editor {
+    pub fn get_keymap_mode(&self) -> KeymapMode {
+        match self.mode {
+            EditorMode::Normal | EditorMode::Visual | EditorMode::VisualLine => KeymapMode::Normal,
+            EditorMode::View => KeymapMode::View,
+            _ => KeymapMode::Insert,
+        }
+    }
+
+    /// Command bound to the key or chord, `None` if there's none
+    pub fn parse_keymap_input(&self, input: &Input) -> Option<EditorCommand> {
+        let mode = self.get_keymap_mode();
+        match *input {
+            Input::Keypress(key) => {
+                let command = self.keymap.get_command(mode, &[key])?;
+                self.get_key_command(command, None)
+            }
+            Input::KeyChord([_, Key::Null, Key::Null]) => {
+                Some(EditorCommand::DisplayChordsHelp(None))
+            }
+            Input::KeyChord([leader, key, Key::Null]) => {
+                let command = match self.keymap.get_command(mode, &[leader, key]) {
+                    Some(command) if command.takes_key() => EditorCommand::DisplayPressKeyPopup,
+                    _ => EditorCommand::DisplayChordsHelp(Some(key)),
+                };
+
+                Some(command)
+            }
+            Input::KeyChord([leader, key, last_key]) => {
+                match self.keymap.get_command(mode, &[leader, key]) {
+                    Some(command) if command.takes_key() => {
+                        self.get_key_command(command, Some(last_key))
+                    }
+                    _ => {
+                        let command = self.keymap.get_command(mode, &[leader, key, last_key])?;
+                        self.get_key_command(command, None)
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Command to execute for the bound command, `key` is the one completing
+    /// the commands that take one
+    pub fn get_key_command(&self, command: KeyCommand, key: Option<Key>) -> Option<EditorCommand> {
+        use CursorMovement as M;
+        use EditorCommand as C;
+        use KeyCommand as K;
+
+        let move_cursor = |movement| C::MoveCursor {
+            movement,
+            with_selection: self.is_selection_locked,
+        };
+        let select = |movement| C::MoveCursor {
+            movement,
+            with_selection: true,
+        };
+        let page_lines = self.ui.editor_lines as usize;
+
+        let command = match command {
+            K::Quit => C::Quit,
+            K::Save => C::Save,
+            K::Help => C::DisplayHelp,
+            K::Undo => C::UndoLastEdit,
+            K::Redo => C::RedoLastEdit,
+            K::SearchForward | K::SearchBackward => C::SwitchMode(EditorMode::Search {
+                original_cursor_pos: self.cursor.pos(),
+                is_backwards: command == K::SearchBackward,
+            }),
+            K::GoToLine => C::SwitchMode(EditorMode::Goto {
+                original_cursor_pos: self.cursor.pos(),
+            }),
+            K::CommandPrompt => C::OpenCommandLine,
+            K::Copy => C::YankSelection,
+            K::Cut => C::CutSelection,
+            K::Paste => C::Paste,
+            K::PageUp => move_cursor(M::Up(page_lines)),
+            K::PageDown => move_cursor(M::Down(page_lines)),
+            K::ScrollUp => C::ScrollViewportUp,
+            K::ScrollDown => C::ScrollViewportDown,
+            K::HalfPageUp => move_cursor(M::Up(page_lines / 2)),
+            K::HalfPageDown => move_cursor(M::Down(page_lines / 2)),
+            K::FileStart => move_cursor(M::StartOfFile),
+            K::FileEnd => move_cursor(M::EndOfFile),
+            K::PreviousJump => move_cursor(M::PreviousJump),
+            K::NextJump => move_cursor(M::NextJump),
+            K::NextMatch => C::FindMatchForward,
+            K::PreviousMatch => C::FindMatchBackward,
+            K::MatchingParen => move_cursor(M::MatchingParenthesis),
+            K::AddCursorAtNextMatch => C::AddCursorAtNextOccurrence,
+            K::LineStart => move_cursor(M::StartOfLine),
+            K::LineEnd => move_cursor(M::EndOfLine),
+            K::PreviousParagraph => move_cursor(M::PreviousParagraph),
+            K::NextParagraph => move_cursor(M::NextParagraph),
+            K::SelectPreviousParagraph => select(M::PreviousParagraph),
+            K::SelectNextParagraph => select(M::NextParagraph),
+            K::Left => move_cursor(M::Left(1)),
+            K::Right => move_cursor(M::Right(1)),
+            K::Up => move_cursor(M::Up(1)),
+            K::Down => move_cursor(M::Down(1)),
+            K::SelectLeft => select(M::Left(1)),
+            K::SelectRight => select(M::Right(1)),
+            K::SelectUp => select(M::Up(1)),
+            K::SelectDown => select(M::Down(1)),
+            K::WordForward => move_cursor(M::NextWordEnd),
+            K::WordBackward => move_cursor(M::PreviousWordStart),
+            K::SelectWordForward => select(M::NextWordEnd),
+            K::SelectWordBackward => select(M::PreviousWordStart),
+            K::DeleteWordBackward => return self.get_delete_word_backward_command(),
+            K::DeleteLine => C::DeleteLine,
+            K::MoveLinesUp => C::MoveLines { is_up: true },
+            K::MoveLinesDown => C::MoveLines { is_up: false },
+            K::DuplicateLines => C::DuplicateLines,
+            K::JoinLines => C::JoinLines,
+            K::SortLines => C::ReorderLines(LineOrder::Sort(SortOptions::default())),
+            K::ReverseLines => C::ReorderLines(LineOrder::Reverse),
+            K::UniqueLines => C::ReorderLines(LineOrder::Unique),
+            K::ShuffleLines => C::ReorderLines(LineOrder::Shuffle),
+            K::ToggleComment => C::ToggleComment,
+            K::ToggleLineNumbers => C::ToggleLineNumbers,
+            K::ToggleSoftWrap => C::ToggleSoftWrap,
+            K::ToggleSelectionLock => C::ToggleLockSelection,
+            K::ExpandSelection => C::ExpandSelection,
+            K::ShrinkSelection => C::ShrinkSelection,
+            K::SelectInside | K::SelectAround => {
+                let Some(Key::Char(ch)) = key else {
+                    return None;
+                };
+
+                C::SelectTextObject {
+                    object: parse_text_object(ch)?,
+                    is_around: command == K::SelectAround,
+                }
+            }
+            K::FindCharForward | K::FindCharBackward => {
+                let Some(Key::Char(ch)) = key else {
+                    return None;
+                };
+
+                if command == K::FindCharForward {
+                    move_cursor(M::NextOccurrenceOf(ch))
+                } else {
+                    move_cursor(M::PreviousOccurrenceOf(ch))
+                }
+            }
+            K::Run => C::ExecuteFile(Key::Char('x')),
+            K::RunWith => C::PromptExecutor,
+            K::RunPython3 => C::ExecuteFile(Key::Char('p')),
+            K::RunPython => C::ExecuteFile(Key::Char('P')),
+            K::RunNode => C::ExecuteFile(Key::Char('n')),
+            K::RunBash => C::ExecuteFile(Key::Char('b')),
+            K::Interrupt => C::InterruptExecution,
+            K::Kill => C::KillExecution,
+            K::ToggleOutput => C::ViewExecutionOutput,
+            K::UndoTree => C::OpenUndoTree,
+            K::BufferList => C::OpenBufferPicker,
+            K::NextBuffer => C::SwitchToNextBuffer,
+            K::PreviousBuffer => C::SwitchToPreviousBuffer,
+            K::CloseBuffer => C::CloseBuffer,
+            K::NextError => C::GoToNextError,
+            K::PreviousError => C::GoToPreviousError,
+            K::ErrorList => C::OpenErrorList,
+            K::AddCursorAbove => C::AddCursorAbove,
+            K::AddCursorBelow => C::AddCursorBelow,
+            K::SplitSelection => C::SplitSelectionIntoLines,
+            K::ReplaceConfirm => C::OpenReplacePrompt { confirm: true },
+            K::ReplaceAll => C::OpenReplacePrompt { confirm: false },
+            K::Unbound => return None,
+        };
+
+        Some(command)
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    cmp::Ordering,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use iedit_document::{EditOperation, Text};
+
+use crate::editor::{
+    Editor,
+    commands::{CommandExecutionResult, send_simple_notification},
+    modes::EditorMode,
+};
+
+/// How `reorder_lines` rearranges lines
+#[derive(Clone, Copy)]
+pub enum LineOrder {
+    Sort(SortOptions),
+    Reverse,
+    /// Drops the lines already seen, keeping the first occurrence of each
+    Unique,
+    Shuffle,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct SortOptions {
+    /// Compares the first number of each line, lines without one come first
+    pub is_numeric: bool,
+    pub ignore_case: bool,
+    pub is_reversed: bool,
+    /// Drops the lines comparing equal to the previous one once sorted
+    pub is_unique: bool,
+}
+
+impl Editor {
+    /// Moves the selected lines, or the cursor's line, past the line above or below them
+    pub fn move_lines(&mut self, is_up: bool) {
+        let (y_from, y_to) = self.get_selected_lines();
+        if (is_up && y_from == 0) || (!is_up && y_to + 1 >= self.document.n_lines()) {
+            return;
+        }
+
+        let mut lines = self.get_lines(y_from, y_to);
+        let (range_from, range_to) = if is_up {
+            lines.push(self.get_lines(y_from - 1, y_from - 1).remove(0));
+            (y_from - 1, y_to)
+        } else {
+            lines.insert(0, self.get_lines(y_to + 1, y_to + 1).remove(0));
+            (y_from, y_to + 1)
+        };
+
+        let selection_anchor = self.cursor.selection_anchor;
+        let cursor_pos = self.cursor.pos();
+        if self.replace_lines(range_from, range_to, lines).is_none() {
+            return;
+        }
+
+        // the cursor and the selection move along with the lines
+        let shift_y = |(x, y): (usize, usize)| if is_up { (x, y - 1) } else { (x, y + 1) };
+        self.cursor.update_pos(shift_y(cursor_pos), false);
+        self.cursor.selection_anchor = selection_anchor.map(shift_y);
+    }
+
+    /// Inserts a copy of the selected lines, or the cursor's line, below them
+    /// and moves the cursor and the selection to the copy
+    pub fn duplicate_lines(&mut self) {
+        let (y_from, y_to) = self.get_selected_lines();
+        let mut lines = self.get_lines(y_from, y_to);
+        let n_lines = lines.len();
+        lines.insert(0, String::new());
+
+        let selection_anchor = self.cursor.selection_anchor;
+        let cursor_pos = self.cursor.pos();
+        let edit = EditOperation::Insertion {
+            pos: (self.get_line_len(y_to), y_to),
+            text: Text::Lines(lines),
+        };
+
+        if self.apply_edit_in_transaction(edit).is_none() {
+            return;
+        }
+
+        let shift_y = |(x, y): (usize, usize)| (x, y + n_lines);
+        self.cursor.update_pos(shift_y(cursor_pos), false);
+        self.cursor.selection_anchor = selection_anchor.map(shift_y);
+    }
+
+    /// Joins the selected lines, or the cursor's line and the next one, into a single line.
+    /// The indentation of the joined lines is collapsed into a single space
+    pub fn join_lines(&mut self) {
+        let (y_from, mut y_to) = self.get_selected_lines();
+        if y_from == y_to {
+            y_to += 1;
+        }
+
+        if y_to >= self.document.n_lines() {
+            return;
+        }
+
+        let lines = self.get_lines(y_from, y_to);
+        let first_line = lines[0].trim_end();
+        let joined_x = first_line.chars().count();
+        let mut pieces = vec![first_line];
+        pieces.extend(
+            lines[1..]
+                .iter()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty()),
+        );
+
+        let joined_line = if first_line.is_empty() {
+            pieces[1..].join(" ")
+        } else {
+            pieces.join(" ")
+        };
+
+        if self
+            .replace_lines(y_from, y_to, vec![joined_line])
+            .is_some()
+        {
+            self.cursor.update_pos((joined_x, y_from), false);
+            if self.is_in_vi_mode() {
+                self.mode = EditorMode::Normal;
+            }
+        }
+    }
+
+    /// Reorders the selected lines, or every line of the document if nothing is selected
+    pub fn reorder_lines(&mut self, order: LineOrder) {
+        let has_selection = self.get_highlighted_range().is_some();
+        let (y_from, y_to) = if has_selection {
+            self.get_selected_lines()
+        } else {
+            // the empty line following the final newline stays last
+            let last_line_idx = self.document.n_lines().saturating_sub(1);
+            match last_line_idx.checked_sub(1) {
+                Some(line_idx) if self.get_line_len(last_line_idx) == 0 => (0, line_idx),
+                _ => (0, last_line_idx),
+            }
+        };
+
+        let lines = self.get_lines(y_from, y_to);
+        let n_lines = lines.len();
+        let reordered_lines = match order {
+            LineOrder::Sort(options) => sort_lines(lines.clone(), options),
+            LineOrder::Reverse => lines.iter().rev().cloned().collect(),
+            LineOrder::Unique => {
+                let mut seen_lines = std::collections::HashSet::new();
+                lines
+                    .iter()
+                    .filter(|line| seen_lines.insert(line.as_str()))
+                    .cloned()
+                    .collect()
+            }
+            LineOrder::Shuffle => shuffle_lines(lines.clone()),
+        };
+
+        if reordered_lines == lines {
+            send_simple_notification("Lines unchanged");
+            return;
+        }
+
+        let n_reordered_lines = reordered_lines.len();
+        let cursor_pos = self.cursor.pos();
+        if self.replace_lines(y_from, y_to, reordered_lines).is_none() {
+            return;
+        }
+
+        if has_selection {
+            let y_last = y_from + n_reordered_lines - 1;
+            self.cursor.selection_anchor = Some((0, y_from));
+            self.cursor
+                .update_pos((self.get_line_len(y_last), y_last), false);
+        } else {
+            let y = cursor_pos.1.min(self.document.n_lines().saturating_sub(1));
+            self.cursor
+                .update_pos((cursor_pos.0.min(self.get_line_len(y)), y), false);
+        }
+
+        if n_reordered_lines < n_lines {
+            send_simple_notification(format!(
+                "Removed {} duplicate lines",
+                n_lines - n_reordered_lines
+            ));
+        }
+    }
+
+    /// Handles the `sort` prompt command, whose arguments are flags: `n` compares
+    /// numbers, `i` ignores case, `r` sorts in reverse and `u` drops duplicates.
+    /// `sort!` sorts in reverse as well
+    pub fn execute_sort_command(&mut self, args: &str) -> CommandExecutionResult {
+        let (is_reversed, flags) = match args.strip_prefix('!') {
+            Some(flags) => (true, flags),
+            None => (false, args),
+        };
+
+        let mut options = SortOptions {
+            is_reversed,
+            ..Default::default()
+        };
+        for flag in flags.chars().filter(|ch| !ch.is_whitespace()) {
+            match flag {
+                'n' => options.is_numeric = true,
+                'i' => options.ignore_case = true,
+                'r' => options.is_reversed = true,
+                'u' => options.is_unique = true,
+                flag => {
+                    send_simple_notification(format!("Unknown sort flag: {}", flag));
+                    return CommandExecutionResult::Continue;
+                }
+            }
+        }
+
+        self.reorder_lines(LineOrder::Sort(options));
+        CommandExecutionResult::Continue
+    }
+
+    /// First and last line touched by the selection, or the cursor's line.
+    /// A selection ending at the start of a line doesn't include it
+    pub fn get_selected_lines(&self) -> (usize, usize) {
+        match self.get_highlighted_range() {
+            Some((pos_from, pos_to)) if pos_to.0 == 0 && pos_to.1 > pos_from.1 => {
+                (pos_from.1, pos_to.1 - 1)
+            }
+            Some((pos_from, pos_to)) => (pos_from.1, pos_to.1),
+            None => (self.cursor.cur_y, self.cursor.cur_y),
+        }
+    }
+
+    pub fn get_lines(&self, y_from: usize, y_to: usize) -> Vec<String> {
+        (y_from..=y_to)
+            .filter_map(|line_idx| self.document.lines.get(line_idx))
+            .map(|line| line.as_ref().to_owned())
+            .collect()
+    }
+
+    /// Replaces the lines from `y_from` to `y_to` with `lines` as a single edit
+    fn replace_lines(
+        &mut self,
+        y_from: usize,
+        y_to: usize,
+        lines: Vec<String>,
+    ) -> Option<(usize, usize)> {
+        self.first_quit_sent = false;
+        self.clear_secondary_carets();
+        self.apply_edit_in_transaction(EditOperation::Replacement {
+            pos_from: (0, y_from),
+            pos_to: (self.get_line_len(y_to), y_to),
+            text: Text::Lines(lines),
+        })
+    }
+}
+
+fn sort_lines(mut lines: Vec<String>, options: SortOptions) -> Vec<String> {
+    let compare = |line: &String, other_line: &String| {
+        let ordering = if options.is_numeric {
+            match (parse_first_number(line), parse_first_number(other_line)) {
+                (Some(number), Some(other_number)) => number.total_cmp(&other_number),
+                (number, other_number) => number.is_some().cmp(&other_number.is_some()),
+            }
+        } else {
+            Ordering::Equal
+        };
+
+        ordering.then_with(|| {
+            if options.ignore_case {
+                line.to_lowercase().cmp(&other_line.to_lowercase())
+            } else {
+                line.cmp(other_line)
+            }
+        })
+    };
+
+    lines.sort_by(|line, other_line| {
+        let ordering = compare(line, other_line);
+        if options.is_reversed {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+
+    if options.is_unique {
+        lines.dedup_by(|line, previous_line| compare(line, previous_line) == Ordering::Equal);
+    }
+
+    lines
+}
+
+/// The first number in `line`, with its sign and decimals
+fn parse_first_number(line: &str) -> Option<f64> {
+    let start = line.find(|ch: char| ch.is_ascii_digit())?;
+    let start = if line[..start].ends_with('-') {
+        start - 1
+    } else {
+        start
+    };
+
+    let mut has_dot = false;
+    let len = line[start..]
+        .char_indices()
+        .take_while(|(idx, ch)| {
+            if *ch == '.' && !has_dot {
+                has_dot = true;
+                return true;
+            }
+
+            ch.is_ascii_digit() || (*idx == 0 && *ch == '-')
+        })
+        .count();
+
+    line[start..start + len].trim_end_matches('.').parse().ok()
+}
+
+/// Fisher-Yates shuffle, random enough to reorder lines
+fn shuffle_lines(mut lines: Vec<String>) -> Vec<String> {
+    let mut state = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos() as u64)
+        | 1;
+
+    for idx in (1..lines.len()).rev() {
+        // xorshift64
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        lines.swap(idx, (state % (idx as u64 + 1)) as usize);
+    }
+
+    lines
+}
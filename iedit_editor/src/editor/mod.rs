@@ -5,13 +5,20 @@ use std::{
         Arc, Mutex,
         atomic::{AtomicBool, Ordering},
     },
+    time::Instant,
 };
 
 use crate::{
     config::EditorConfig,
     editor::{
+        buffers::Buffer,
         clipboard::{EditorClipboard, get_clipboard},
-        search::SearchItem,
+        keymap::Keymap,
+        quickfix::ErrorLocation,
+        search::{ReplaceItem, SearchItem},
+        selection::SelectionExpansion,
+        vi::ViState,
+        watcher::FileWatcher,
     },
     input::Notification,
 };
@@ -20,51 +27,83 @@ use signal_hook::{consts::SIGWINCH, flag};
 
 use crate::{
     editor::{
-        commands::CommandExecutionResult, cursor::Cursor, modes::EditorMode, renderer::Renderer,
-        status::StatusBar, viewport::Viewport,
+        commands::{CommandExecutionResult, RunningExecution},
+        cursor::Cursor,
+        modes::EditorMode,
+        renderer::Renderer,
+        status::StatusBar,
+        viewport::Viewport,
     },
-    input::InputParser,
+    input::{Input, InputParser},
     terminal::UILayout,
 };
 
 use crossbeam_channel::{Sender, unbounded};
 
+mod buffers;
 mod clipboard;
 mod commands;
+mod comments;
 mod cursor;
+mod follow;
 mod highlight;
 mod io;
 mod keybindings;
+mod keymap;
+mod lines;
 mod modes;
+mod multi_cursor;
+mod quickfix;
 mod renderer;
 mod search;
+mod selection;
+mod settings;
 mod status;
+mod swap;
+mod vi;
 mod viewport;
+mod watcher;
+
+pub use keymap::KeyBinding;
 
 pub struct Editor {
     config: EditorConfig,
     document: Document,
+    buffers: Vec<Buffer>,
+    active_buffer_idx: usize,
+    previous_buffer_idx: usize,
     mode: EditorMode,
     status_bar: StatusBar,
     cursor: Cursor,
     viewport: Viewport,
     ui: UILayout,
     search_item: Option<SearchItem>,
+    replace_item: Option<ReplaceItem>,
     matched_range: Option<((usize, usize), (usize, usize))>,
-    displayed_popup: Option<&'static [&'static str]>,
+    displayed_popup: Option<Vec<String>>,
+    keymap: Keymap,
     clipboard: Box<dyn EditorClipboard>,
+    last_swap_write_time: Instant,
+    file_watcher: FileWatcher,
+    error_locations: Vec<ErrorLocation>,
+    current_error_idx: Option<usize>,
+    vi: ViState,
+    selection_expansions: Vec<SelectionExpansion>,
 
     // TODO: turn into EditorFlags bitfield
     needs_full_rerender: bool,
     is_selection_locked: bool,
+    is_view_mode: bool,
     first_quit_sent: bool,
-    is_running_external_command: bool,
-    is_viewing_execution_output: bool,
+    running_execution: Option<RunningExecution>,
 }
 
 // Store sender in a static or global location for access anywhere
 pub static NOTIFICATION_SENDER: Mutex<Option<Sender<Notification>>> = Mutex::new(None);
-pub static FILE_EXECUTION_OUTPUT: Mutex<Option<Document>> = Mutex::new(None);
+/// Lines printed by the running command, waiting to be appended to the output buffer
+pub static EXECUTION_OUTPUT: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// Text read from the followed file or input, waiting to be appended to its buffer
+pub static FOLLOWED_OUTPUT: Mutex<String> = Mutex::new(String::new());
 
 pub enum EditorRunResult {
     RestartInFullscreenMode,
@@ -79,50 +118,59 @@ impl Editor {
         ui: UILayout,
     ) -> std::io::Result<Self> {
         let viewport = Viewport::new(ui.editor_lines, open_at_line);
+        let keymap = Keymap::new(config.bind.clone());
         let clipboard = get_clipboard(config.use_system_clipboard);
 
         let cur_y = min(open_at_line.saturating_sub(1), document.n_lines());
 
-        Ok(Self {
+        let file_watcher = FileWatcher::new();
+        file_watcher.watch(&document.canonicalized_file_path);
+
+        let mut editor = Self {
             document,
-            mode: EditorMode::Insert,
+            buffers: vec![Buffer::new(Document::default(), false)],
+            active_buffer_idx: 0,
+            previous_buffer_idx: 0,
+            mode: if config.vi_mode {
+                EditorMode::Normal
+            } else {
+                EditorMode::Insert
+            },
             config,
             status_bar: StatusBar::default(),
             cursor: Cursor::new((0, cur_y)),
             ui,
             viewport,
             clipboard,
+            last_swap_write_time: Instant::now(),
+            file_watcher,
+            error_locations: vec![],
+            current_error_idx: None,
+            vi: ViState::default(),
+            selection_expansions: vec![],
             search_item: None,
+            replace_item: None,
             matched_range: None,
             displayed_popup: None,
+            keymap,
             needs_full_rerender: true,
             is_selection_locked: false,
+            is_view_mode: false,
             first_quit_sent: false,
-            is_running_external_command: false,
-            is_viewing_execution_output: false,
-        })
-    }
+            running_execution: None,
+        };
 
-    pub fn toggle_execution_output(&mut self) {
-        if let Ok(mut execution_output) = FILE_EXECUTION_OUTPUT.lock()
-            && let Some(execution_output) = execution_output.as_mut()
-        {
-            self.is_viewing_execution_output = !self.is_viewing_execution_output;
-            self.swap_docuemnt(execution_output);
-        }
+        editor.check_swap_file();
+
+        Ok(editor)
     }
 
     pub fn set_ui(&mut self, ui: UILayout) {
         self.ui = ui;
     }
 
-    pub fn swap_docuemnt(&mut self, new_doc: &mut Document) {
-        std::mem::swap(&mut self.document, new_doc);
-        self.needs_full_rerender = true;
-    }
-
     pub fn run<Term: Write>(&mut self, term: &mut Term) -> std::io::Result<EditorRunResult> {
-        let mut renderer = Renderer::new(term, self.ui.clone(), self.config.tab_size as usize);
+        let mut renderer = Renderer::new(term, self.ui.clone());
         renderer.render(self)?;
 
         let window_resized = Arc::<AtomicBool>::new(AtomicBool::new(false));
@@ -131,11 +179,16 @@ impl Editor {
         let (notification_sender, notification_receiver) = unbounded::<Notification>();
 
         *NOTIFICATION_SENDER.lock().unwrap() = Some(notification_sender);
+        self.check_line_endings();
+        self.check_swap_file_owner();
+        // the text read before the editor could be notified of it
+        self.append_followed_output();
 
         let input_parser = InputParser::new(notification_receiver);
         for input in input_parser {
             self.cursor.set_last_pos();
             let gutter_width_before = self.get_line_number_gutter_width();
+            let wrapped_rows_before = self.get_visible_wrapped_rows();
 
             if window_resized
                 .compare_exchange(true, false, Ordering::Acquire, Ordering::Relaxed)
@@ -144,6 +197,8 @@ impl Editor {
                 return Ok(EditorRunResult::RestartInFullscreenMode);
             }
 
+            self.write_swap_file(matches!(input, Input::NoOp));
+
             let command = self.parse_command(input);
             if command.is_none() {
                 continue;
@@ -152,7 +207,13 @@ impl Editor {
             let command = command.unwrap();
             let res = self.execute_command(command);
 
+            // insert mode may be left for another mode than normal mode, e.g. to search
+            if !matches!(self.mode, EditorMode::Insert) {
+                self.commit_vi_insert();
+            }
+
             if matches!(res, CommandExecutionResult::ShouldQuit) {
+                self.clear_swap_files();
                 break;
             }
 
@@ -160,21 +221,23 @@ impl Editor {
 
             let gutter_width_after = self.get_line_number_gutter_width();
             self.needs_full_rerender |= gutter_width_after != gutter_width_before;
+            // the lines below one whose wrapping changed are shifted
+            self.needs_full_rerender |= self.get_visible_wrapped_rows() != wrapped_rows_before;
 
             self.adjust_viewport();
 
             renderer.render(self)?;
 
-            self.document.reset_lines_need_render(
-                self.viewport.top_line..self.viewport.top_line + self.ui.editor_lines as usize,
-            );
+            let visible_lines =
+                self.viewport.top_line..self.viewport.top_line + self.ui.editor_lines as usize;
+            self.document.reset_lines_need_render(visible_lines.clone());
+            self.document.release_unused_lines(visible_lines);
             self.status_bar.notification.truncate(0);
-            self.needs_full_rerender = self.displayed_popup.is_some();
-            self.displayed_popup = None;
+            self.needs_full_rerender = self.displayed_popup.take().is_some();
         }
 
         renderer.cleanup()?;
 
-        return Ok(EditorRunResult::Quit);
+        Ok(EditorRunResult::Quit)
     }
 }
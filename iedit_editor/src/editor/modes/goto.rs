@@ -2,10 +2,7 @@ use termion::event::Key;
 
 use crate::{
     Editor,
-    editor::{
-        commands::{CommandExecutionResult, CursorMovement, EditorCommand},
-        modes::EditorMode,
-    },
+    editor::commands::{CommandExecutionResult, CursorMovement, EditorCommand},
     input::Input,
 };
 
@@ -17,7 +14,6 @@ impl Editor {
     ) -> CommandExecutionResult {
         use CommandExecutionResult as R;
         use EditorCommand as C;
-        use EditorMode as M;
 
         match command {
             C::InsertCharPrompt { pos_x: _, ch: _ }
@@ -37,7 +33,7 @@ impl Editor {
             }
             C::SubmitPrompt => {
                 self.status_bar.prompt_line.truncate(0);
-                self.mode = M::Insert;
+                self.mode = self.get_default_mode();
                 self.needs_full_rerender = true;
                 self.cursor.jump_history.push(original_pos);
                 R::Continue
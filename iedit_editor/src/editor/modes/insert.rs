@@ -1,14 +1,11 @@
-use iedit_document::{CharacterIndexable, DocumentLine, EditOperation, Text};
+use iedit_document::{CharacterIndexable, DocumentLine, EditOperation, InverseStack, Text};
 use termion::event::Key;
 
 use crate::{
     Editor,
     editor::{
-        commands::{
-            CommandExecutionResult, CursorMovement, EditorCommand, Executor,
-            send_simple_notification,
-        },
-        modes::EditorMode,
+        commands::{CommandExecutionResult, EditorCommand, Executor, send_simple_notification},
+        cursor::Caret,
     },
     input::Input,
 };
@@ -49,20 +46,19 @@ impl Editor {
                         _ => {}
                     };
 
-                    let edit = match self.cursor.get_selected_range() {
-                        Some((pos_from, pos_to)) => EditOperation::Replacement {
-                            pos_from,
-                            pos_to,
-                            text: yanked_text,
-                        },
-                        None => EditOperation::Insertion {
-                            pos: self.cursor.pos(),
-                            text: yanked_text,
-                        },
-                    };
+                    if self.has_multiple_cursors() {
+                        let edits = self
+                            .get_sorted_carets()
+                            .iter()
+                            .map(|(caret, _)| get_paste_edit(caret, yanked_text.clone()))
+                            .collect();
 
-                    self.cursor.selection_anchor = None;
-                    if let Some(cursor_pos) = self.document.apply_edit(edit, S::Undo) {
+                        self.apply_edit_at_cursors(edits);
+                        return R::Continue;
+                    }
+
+                    let edit = get_paste_edit(&self.cursor.caret(), yanked_text);
+                    if let Some(cursor_pos) = self.apply_edit_in_transaction(edit) {
                         self.cursor.update_pos(cursor_pos, false);
                     }
                 }
@@ -104,28 +100,46 @@ impl Editor {
 
                     self.clipboard.set_text(text);
                     if matches!(command, EditorCommand::CutSelection) {
-                        self.cursor.selection_anchor = None;
+                        self.apply_edit_in_transaction(EditOperation::Replacement {
+                            pos_from,
+                            pos_to,
+                            text: Text::Empty,
+                        });
                         self.cursor.update_pos(pos_from, false);
-                        self.document.apply_edit(
-                            EditOperation::Replacement {
-                                pos_from,
-                                pos_to,
-                                text: Text::Empty,
-                            },
-                            S::Undo,
-                        );
                     }
                 }
             }
 
+            EditorCommand::DeleteLine => {
+                self.clear_secondary_carets();
+                let line_idx = self.cursor.cur_y;
+                let edit = EditOperation::Replacement {
+                    pos_from: (0, line_idx),
+                    pos_to: (0, line_idx + 1),
+                    text: Text::Empty,
+                };
+
+                if let Some(new_pos) = self.apply_edit_in_transaction(edit) {
+                    self.cursor.update_pos(new_pos, false);
+                }
+
+                self.first_quit_sent = false;
+            }
+            EditorCommand::MoveLines { is_up } => self.move_lines(is_up),
+            EditorCommand::DuplicateLines => self.duplicate_lines(),
+            EditorCommand::JoinLines => self.join_lines(),
+            EditorCommand::ReorderLines(order) => self.reorder_lines(order),
+            EditorCommand::ToggleComment => self.toggle_comment(),
             EditorCommand::ClearSelection => {
                 self.is_selection_locked = false;
                 self.needs_full_rerender = true;
                 self.cursor.selection_anchor = None;
                 self.matched_range = None;
                 self.search_item = None;
+                self.clear_secondary_carets();
             }
             EditorCommand::Edit(op) => {
+                self.clear_secondary_carets();
                 if let Some(new_pos) = self.document.apply_edit(op, S::Undo) {
                     self.cursor.update_pos(new_pos, false);
                 }
@@ -133,21 +147,24 @@ impl Editor {
                 self.first_quit_sent = false;
                 self.cursor.selection_anchor = None;
             }
-            EditorCommand::UndoLastEdit => {
-                if let Some(new_pos) = self.document.undo_last_edit() {
-                    self.cursor.update_pos(new_pos, false);
-                }
-
+            EditorCommand::EditAtCursors(ops) => {
+                self.apply_edit_at_cursors(ops);
                 self.first_quit_sent = false;
-                self.cursor.selection_anchor = None;
             }
-            EditorCommand::RedoLastEdit => {
-                if let Some(new_pos) = self.document.redo_last_edit() {
-                    self.cursor.update_pos(new_pos, false);
-                }
-
-                self.first_quit_sent = false;
-                self.cursor.selection_anchor = None;
+            EditorCommand::AddCursorAbove => self.add_cursor_above(),
+            EditorCommand::AddCursorBelow => self.add_cursor_below(),
+            EditorCommand::AddCursorAtNextOccurrence => self.add_cursor_at_next_occurrence(),
+            EditorCommand::SplitSelectionIntoLines => self.split_selection_into_lines(),
+            EditorCommand::ExpandSelection => self.expand_selection(),
+            EditorCommand::ShrinkSelection => self.shrink_selection(),
+            EditorCommand::SelectTextObject { object, is_around } => {
+                self.select_text_object(object, is_around)
+            }
+            EditorCommand::UndoLastEdit => self.with_vi_insert_committed(Editor::undo_last_edit),
+            EditorCommand::RedoLastEdit => self.with_vi_insert_committed(Editor::redo_last_edit),
+            EditorCommand::OpenUndoTree => {
+                self.commit_vi_insert();
+                self.open_undo_tree();
             }
             EditorCommand::FindMatchForward | EditorCommand::FindMatchBackward => {
                 let (x_from, x_to) = if self.cursor.selection_anchor.is_some() {
@@ -197,6 +214,18 @@ impl Editor {
             EditorCommand::ViewExecutionOutput => {
                 self.toggle_execution_output();
             }
+            EditorCommand::SwitchToNextBuffer => self.switch_to_next_buffer(),
+            EditorCommand::SwitchToPreviousBuffer => self.switch_to_previous_buffer(),
+            EditorCommand::CloseBuffer => self.close_active_buffer(false),
+            EditorCommand::OpenBufferPicker => self.open_buffer_picker(),
+            EditorCommand::GoToNextError => self.go_to_next_error(),
+            EditorCommand::GoToPreviousError => self.go_to_previous_error(),
+            EditorCommand::GoToErrorAtCursor => self.go_to_error_at_cursor(),
+            EditorCommand::OpenErrorList => self.open_error_list(),
+            EditorCommand::ExitInsertMode => self.finish_vi_insert(),
+            EditorCommand::OpenReplacePrompt { confirm } => {
+                self.prompt_replace(confirm);
+            }
             _ => {}
         }
 
@@ -206,256 +235,152 @@ impl Editor {
     pub fn insert_mode_parse_command(&self, input: Input) -> Option<EditorCommand> {
         use EditOperation as Op;
         use EditorCommand as C;
-        use EditorMode as M;
         use Text as T;
 
+        if let Some(command) = self.parse_keymap_input(&input) {
+            return Some(command);
+        }
+
         match input {
+            Input::Keypress(Key::Esc) if self.config.vi_mode => Some(C::ExitInsertMode),
             Input::Keypress(Key::Esc) => Some(C::ClearSelection),
-            Input::Keypress(Key::Ctrl('z')) => Some(C::UndoLastEdit),
-            Input::Keypress(Key::Ctrl('r')) => Some(C::RedoLastEdit),
-            Input::Keypress(Key::Ctrl('f')) => Some(C::SwitchMode(M::Search {
-                original_cursor_pos: self.cursor.pos(),
-                is_backwards: false,
-            })),
-            Input::Keypress(Key::Ctrl('b')) => Some(C::SwitchMode(M::Search {
-                original_cursor_pos: self.cursor.pos(),
-                is_backwards: true,
-            })),
-            Input::Keypress(Key::Ctrl('g')) => Some(C::SwitchMode(M::Goto {
-                original_cursor_pos: self.cursor.pos(),
-            })),
-            Input::Keypress(Key::AltDown) => Some(C::ScrollViewportDown),
-            Input::Keypress(Key::AltUp) => Some(C::ScrollViewportUp),
-            Input::Keypress(Key::Alt('i')) => Some(C::MoveCursor {
-                movement: CursorMovement::NextJump,
-                with_selection: self.is_selection_locked,
-            }),
-            Input::Keypress(Key::Alt('o')) => Some(C::MoveCursor {
-                movement: CursorMovement::PreviousJump,
-                with_selection: self.is_selection_locked,
-            }),
-            Input::Keypress(Key::Ctrl('d')) | Input::Keypress(Key::PageDown) => {
-                Some(C::MoveCursor {
-                    movement: CursorMovement::Down(self.ui.editor_lines as usize),
-                    with_selection: self.is_selection_locked,
-                })
-            }
-            Input::Keypress(Key::Ctrl('u')) => Some(C::MoveCursor {
-                movement: CursorMovement::Up(self.ui.editor_lines as usize),
-                with_selection: self.is_selection_locked,
-            }),
-            Input::Keypress(Key::Alt('a')) => Some(C::MoveCursor {
-                movement: CursorMovement::StartOfLine,
-                with_selection: self.is_selection_locked,
-            }),
-            Input::Keypress(Key::Alt('s')) => Some(C::MoveCursor {
-                movement: CursorMovement::EndOfLine,
-                with_selection: self.is_selection_locked,
-            }),
-            Input::Keypress(Key::CtrlDown) => Some(C::MoveCursor {
-                movement: CursorMovement::NextParagraph,
-                with_selection: self.is_selection_locked,
-            }),
-            Input::Keypress(Key::CtrlShiftDown) => Some(C::MoveCursor {
-                movement: CursorMovement::NextParagraph,
-                with_selection: true,
-            }),
-            Input::Keypress(Key::CtrlUp) => Some(C::MoveCursor {
-                movement: CursorMovement::PreviousParagraph,
-                with_selection: self.is_selection_locked,
-            }),
-            Input::Keypress(Key::CtrlShiftUp) => Some(C::MoveCursor {
-                movement: CursorMovement::PreviousParagraph,
-                with_selection: true,
-            }),
-            Input::Keypress(Key::Alt('p')) => Some(C::MoveCursor {
-                movement: CursorMovement::MatchingParenthesis,
-                with_selection: self.is_selection_locked,
-            }),
-            Input::Keypress(Key::Alt('n')) => Some(C::FindMatchForward),
-            Input::Keypress(Key::Alt('m')) => Some(C::FindMatchBackward),
-            Input::Keypress(Key::Ctrl('e')) | Input::Keypress(Key::Alt('e')) => {
-                Some(C::OpenCommandLine)
-            }
-            Input::Keypress(Key::Ctrl('y')) => Some(C::YankSelection),
-            Input::Keypress(Key::Ctrl('x')) => Some(C::CutSelection),
-            Input::Keypress(Key::Ctrl('p')) => Some(C::Paste),
-            Input::Keypress(Key::Char(ch)) => {
-                let text = if ch == '\t' && self.config.tab_emit_spaces {
-                    let n_spaces = self.config.tab_size as usize
-                        - (self.cursor.cur_x % self.config.tab_size as usize);
+            Input::Keypress(Key::Char('\n')) | Input::Keypress(Key::Char('\r'))
+                if self.is_viewing_execution_output() =>
+            {
+                Some(C::GoToErrorAtCursor)
+            }
+            Input::Keypress(Key::Char(ch)) => self.edit_at_cursors(|editor, caret| {
+                let text = if ch == '\t' && editor.config.tab_emit_spaces {
+                    let n_spaces = editor.config.tab_size as usize
+                        - (caret.cur_x % editor.config.tab_size as usize);
                     T::String(" ".repeat(n_spaces))
                 } else {
                     T::Char(ch)
                 };
-                match self.cursor.get_selected_range() {
-                    None => Some(C::Edit(Op::Insertion {
-                        pos: self.cursor.pos(),
+                match caret.get_selected_range() {
+                    None => Op::Insertion {
+                        pos: caret.pos(),
                         text,
-                    })),
-                    Some((pos_from, pos_to)) => Some(C::Edit(Op::Replacement {
+                    },
+                    Some((pos_from, pos_to)) => Op::Replacement {
                         pos_from,
                         pos_to,
                         text,
-                    })),
+                    },
                 }
-            }
+            }),
             Input::Keypress(Key::Backspace) | Input::Keypress(Key::Delete) => {
-                match self.cursor.get_selected_range() {
-                    None => Some(C::Edit(Op::Deletion {
-                        pos: self.cursor.pos(),
-                    })),
-                    Some((pos_from, pos_to)) => Some(C::Edit(Op::Replacement {
+                self.edit_at_cursors(|editor, caret| match caret.get_selected_range() {
+                    None => {
+                        let (x, y) = caret.pos();
+                        let grapheme_x = editor
+                            .document
+                            .lines
+                            .get(y)
+                            .map_or(x.saturating_sub(1), |line| line.previous_grapheme_idx(x));
+
+                        // a grapheme cluster made of several chars is deleted as a whole
+                        if x > 0 && grapheme_x + 1 < x {
+                            Op::Replacement {
+                                pos_from: (grapheme_x, y),
+                                pos_to: (x, y),
+                                text: Text::Empty,
+                            }
+                        } else {
+                            Op::Deletion { pos: caret.pos() }
+                        }
+                    }
+                    Some((pos_from, pos_to)) => Op::Replacement {
                         pos_from,
                         pos_to,
                         text: Text::Empty,
-                    })),
-                }
+                    },
+                })
             }
-            Input::Keypress(Key::Ctrl('h'))
-            | Input::Keypress(Key::Ctrl('\x7F'))
-            | Input::Keypress(Key::Alt('\x7F')) => {
-                if self.cursor.cur_x == 0 {
-                    Some(C::Edit(Op::Deletion {
-                        pos: self.cursor.pos(),
-                    }))
-                } else {
-                    match self.cursor.get_selected_range() {
-                        None => {
-                            let word_start_pos =
-                                self.document.get_previous_word_start_pos(self.cursor.pos());
-                            Some(C::Edit(Op::Replacement {
-                                pos_from: word_start_pos,
-                                pos_to: self.cursor.pos(),
-                                text: Text::Empty,
-                            }))
-                        }
-                        Some((pos_from, pos_to)) => Some(C::Edit(Op::Replacement {
-                            pos_from,
-                            pos_to,
+            _ => None,
+        }
+    }
+
+    /// Deletes from each cursor to the start of the previous word, or the selection
+    pub fn get_delete_word_backward_command(&self) -> Option<EditorCommand> {
+        use EditOperation as Op;
+
+        self.edit_at_cursors(|editor, caret| {
+            if caret.cur_x == 0 {
+                Op::Deletion { pos: caret.pos() }
+            } else {
+                match caret.get_selected_range() {
+                    None => {
+                        let word_start_pos =
+                            editor.document.get_previous_word_start_pos(caret.pos());
+                        Op::Replacement {
+                            pos_from: word_start_pos,
+                            pos_to: caret.pos(),
                             text: Text::Empty,
-                        })),
+                        }
                     }
+                    Some((pos_from, pos_to)) => Op::Replacement {
+                        pos_from,
+                        pos_to,
+                        text: Text::Empty,
+                    },
                 }
             }
-            Input::Keypress(Key::Left) | Input::Keypress(Key::Alt('h')) => Some(C::MoveCursor {
-                with_selection: self.is_selection_locked,
-                movement: CursorMovement::Left(1),
-            }),
-            Input::Keypress(Key::Right) | Input::Keypress(Key::Alt('l')) => Some(C::MoveCursor {
-                with_selection: self.is_selection_locked,
-                movement: CursorMovement::Right(1),
-            }),
-            Input::Keypress(Key::Up) | Input::Keypress(Key::Alt('k')) => Some(C::MoveCursor {
-                with_selection: self.is_selection_locked,
-                movement: CursorMovement::Up(1),
-            }),
-            Input::Keypress(Key::Down) | Input::Keypress(Key::Alt('j')) => Some(C::MoveCursor {
-                with_selection: self.is_selection_locked,
-                movement: CursorMovement::Down(1),
-            }),
-            Input::Keypress(Key::ShiftLeft) => Some(C::MoveCursor {
-                with_selection: true,
-                movement: CursorMovement::Left(1),
-            }),
-            Input::Keypress(Key::ShiftRight) => Some(C::MoveCursor {
-                with_selection: true,
-                movement: CursorMovement::Right(1),
-            }),
-            Input::Keypress(Key::ShiftUp) => Some(C::MoveCursor {
-                with_selection: true,
-                movement: CursorMovement::Up(1),
-            }),
-            Input::Keypress(Key::ShiftDown) => Some(C::MoveCursor {
-                with_selection: true,
-                movement: CursorMovement::Down(1),
-            }),
-            Input::Keypress(Key::CtrlRight) | Input::Keypress(Key::Alt('w')) => {
-                Some(C::MoveCursor {
-                    with_selection: self.is_selection_locked,
-                    movement: CursorMovement::NextWordEnd,
-                })
-            }
-            Input::Keypress(Key::CtrlShiftRight) => Some(C::MoveCursor {
-                with_selection: true,
-                movement: CursorMovement::NextWordEnd,
-            }),
-            Input::Keypress(Key::CtrlLeft) | Input::Keypress(Key::Alt('b')) => {
-                Some(C::MoveCursor {
-                    with_selection: self.is_selection_locked,
-                    movement: CursorMovement::PreviousWordStart,
-                })
-            }
-            Input::Keypress(Key::CtrlShiftLeft) => Some(C::MoveCursor {
-                with_selection: true,
-                movement: CursorMovement::PreviousWordStart,
-            }),
-            Input::Keypress(Key::Ctrl('t')) => Some(EditorCommand::DisplayHelp),
-            Input::KeyChord([Key::Ctrl('k'), Key::Null, Key::Null]) => {
-                Some(EditorCommand::DisplayChordsHelp)
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('x'), Key::Null]) => {
-                Some(EditorCommand::DisplayExecuteChordHelp)
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('l'), Key::Null]) => {
-                Some(EditorCommand::DisplayLineChordHelp)
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('v'), Key::Null]) => {
-                Some(EditorCommand::DisplayViewChordHelp)
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('t') | Key::Char('T'), Key::Null]) => {
-                Some(EditorCommand::DisplayPressCharacterPopup)
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('l'), Key::Char('n')]) => {
-                Some(C::ToggleLineNumbers)
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('l'), Key::Char('d')]) => {
-                Some(C::Edit(Op::Replacement {
-                    pos_from: (0, self.cursor.cur_y),
-                    pos_to: (0, self.cursor.cur_y + 1),
-                    text: Text::Empty,
-                }))
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('l'), Key::Char('w')]) => {
-                Some(C::MoveCursor {
-                    movement: CursorMovement::StartOfLine,
-                    with_selection: self.is_selection_locked,
-                })
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('l'), Key::Char('e')]) => {
-                Some(C::MoveCursor {
-                    movement: CursorMovement::EndOfLine,
-                    with_selection: self.is_selection_locked,
-                })
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('t'), Key::Char(ch)]) => {
-                Some(C::MoveCursor {
-                    movement: CursorMovement::NextOccurrenceOf(ch),
-                    with_selection: self.is_selection_locked,
-                })
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('T'), Key::Char(ch)]) => {
-                Some(C::MoveCursor {
-                    movement: CursorMovement::PreviousOccurrenceOf(ch),
-                    with_selection: self.is_selection_locked,
-                })
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('x'), Key::Char('?')]) => {
-                Some(C::PromptExecutor)
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('x'), executor_key]) => {
-                Some(C::ExecuteFile(executor_key))
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('v'), Key::Char('o')]) => {
-                Some(C::ViewExecutionOutput)
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('s'), Key::Null]) => {
-                Some(C::DisplaySelectionChordHelp)
-            }
-            Input::KeyChord([Key::Ctrl('k'), Key::Char('s'), Key::Char('l')]) => {
-                Some(C::ToggleLockSelection)
+        })
+    }
+}
+
+impl Editor {
+    /// Applies `op` as a transaction, so that undoing it brings back the cursor and the selection.
+    /// NOTE: the selection is cleared
+    pub fn apply_edit_in_transaction(&mut self, op: EditOperation) -> Option<(usize, usize)> {
+        self.document
+            .begin_transaction(self.cursor.pos(), self.cursor.selection_anchor);
+        self.cursor.selection_anchor = None;
+        let edit_result = self.document.apply_edit(op, InverseStack::Undo);
+        self.document.commit();
+
+        edit_result
+    }
+
+    fn undo_last_edit(&mut self) {
+        self.clear_secondary_carets();
+        self.cursor.selection_anchor = None;
+        let undo_selection = self.document.get_undo_selection();
+        if let Some(new_pos) = self.document.undo_last_edit() {
+            self.cursor.update_pos(new_pos, false);
+
+            // edits made in a transaction give back the selection they were made on
+            if let Some(selection) = undo_selection {
+                self.cursor.update_pos(selection.cursor, false);
+                self.cursor.selection_anchor = selection.anchor;
             }
-            _ => None,
         }
+
+        self.first_quit_sent = false;
+    }
+
+    fn redo_last_edit(&mut self) {
+        self.clear_secondary_carets();
+        if let Some(new_pos) = self.document.redo_last_edit() {
+            self.cursor.update_pos(new_pos, false);
+        }
+
+        self.first_quit_sent = false;
+        self.cursor.selection_anchor = None;
+    }
+}
+
+fn get_paste_edit(caret: &Caret, text: Text) -> EditOperation {
+    match caret.get_selected_range() {
+        Some((pos_from, pos_to)) => EditOperation::Replacement {
+            pos_from,
+            pos_to,
+            text,
+        },
+        None => EditOperation::Insertion {
+            pos: caret.pos(),
+            text,
+        },
     }
 }
@@ -1,24 +1,31 @@
-use termion::event::Key;
-
 use crate::{
     Editor,
     editor::{
         commands::{CommandExecutionResult, EditorCommand, send_simple_notification},
-        keybindings::{
-            CHORDS_POPUP_LINES, HELP_POPUP_LINES, L_CHORD_POPUP_LINES, S_CHORD_POPUP_LINES,
-            T_CHORD_POPUP_LINES, V_CHORD_POPUP_LINES, X_CHORD_POPUP_LINES,
-        },
+        keymap::KeyCommand,
     },
     input::{Input, Notification},
 };
 
 mod goto;
 mod insert;
+mod normal;
+mod pick_buffer;
+mod pick_error;
 mod prompt;
+mod replace;
 mod search;
+mod undo_tree;
+mod view;
 
 pub enum EditorMode {
     Insert,
+    /// vi modes, only used when `vi_mode` is enabled
+    Normal,
+    Visual,
+    VisualLine,
+    /// read-only pager mode, only used when started with `--view` or `--follow`
+    View,
     Prompt(&'static str),
     Goto {
         original_cursor_pos: (usize, usize),
@@ -27,10 +34,21 @@ pub enum EditorMode {
         original_cursor_pos: (usize, usize),
         is_backwards: bool,
     },
+    Replace,
+    PickBuffer {
+        selected_idx: usize,
+    },
+    PickError {
+        selected_idx: usize,
+    },
+    UndoTree {
+        original_state_id: usize,
+        selected_idx: usize,
+    },
 }
 
 static UNSAVED_CHANGES_WARNING: &str =
-    "\x1b[33mBuffer contains unsaved changes.\x1b[0m Ctrl-s: save, Ctrl-q: quit";
+    "\x1b[33mBuffers contain unsaved changes.\x1b[0m Ctrl-s: save, Ctrl-q: quit";
 
 impl Editor {
     pub fn execute_command(&mut self, command: EditorCommand) -> CommandExecutionResult {
@@ -41,45 +59,31 @@ impl Editor {
                 movement: _,
                 with_selection: _,
             } => self.execute_cursor_movement_command(command),
-            C::EndFileExecution(status, is_output_available) => {
-                self.status_bar.notification = format!(
-                    "{}. {}",
-                    status,
-                    if is_output_available {
-                        "Ctrl-k + v + o: view output"
-                    } else {
-                        "output unavailable"
-                    }
-                );
-                self.is_running_external_command = false;
-            }
+            C::AppendExecutionOutput => self.append_execution_output(),
+            C::AppendFollowedOutput => self.append_followed_output(),
+            C::EndFileExecution(status) => self.end_execution(status),
+            C::InterruptExecution => self.signal_execution(libc::SIGINT),
+            C::KillExecution => self.signal_execution(libc::SIGKILL),
+            // the elapsed time of the running command is re-rendered
+            C::RefreshStatus => {}
+            C::HandleFileChangedOnDisk(path) => self.handle_file_changed_on_disk(&path),
             C::DisplayHelp => {
-                self.displayed_popup = Some(&HELP_POPUP_LINES);
-            }
-            C::DisplayChordsHelp => {
-                self.displayed_popup = Some(&CHORDS_POPUP_LINES);
-            }
-            C::DisplayLineChordHelp => {
-                self.displayed_popup = Some(&L_CHORD_POPUP_LINES);
+                let mode = self.get_keymap_mode();
+                self.displayed_popup = Some(self.keymap.get_help_lines(mode));
             }
-            C::DisplayExecuteChordHelp => {
-                self.displayed_popup = Some(&X_CHORD_POPUP_LINES);
+            C::DisplayChordsHelp(prefix) => {
+                let mode = self.get_keymap_mode();
+                self.displayed_popup = Some(self.keymap.get_chord_help_lines(mode, prefix));
             }
-            C::DisplayViewChordHelp => {
-                self.displayed_popup = Some(&V_CHORD_POPUP_LINES);
-            }
-            C::DisplaySelectionChordHelp => {
-                self.displayed_popup = Some(&S_CHORD_POPUP_LINES);
-            }
-            C::DisplayPressCharacterPopup => {
-                self.displayed_popup = Some(&T_CHORD_POPUP_LINES);
+            C::DisplayPressKeyPopup => {
+                self.displayed_popup = Some(vec!["press a key...".to_owned()]);
             }
             C::DisplayMessage(notification) => {
                 self.status_bar.notification = notification;
             }
             C::Quit => return self.quit(false),
             C::Save => {
-                if let Err(err) = self.save_file(true) {
+                if let Err(err) = self.with_vi_insert_committed(|editor| editor.save_file(true)) {
                     send_simple_notification(err.to_string());
                 };
             }
@@ -91,6 +95,10 @@ impl Editor {
                 self.needs_full_rerender = true;
                 self.config.show_line_numbers = !self.config.show_line_numbers;
             }
+            C::ToggleSoftWrap => {
+                self.needs_full_rerender = true;
+                self.config.soft_wrap = !self.config.soft_wrap;
+            }
             C::ScrollViewportUp => {
                 if self.viewport.top_line > 0 {
                     self.viewport.vertical_offset -= 1;
@@ -105,6 +113,10 @@ impl Editor {
             }
             _ => match self.mode {
                 EditorMode::Insert => return self.insert_mode_execute_command(command),
+                EditorMode::Normal | EditorMode::Visual | EditorMode::VisualLine => {
+                    return self.normal_mode_execute_command(command);
+                }
+                EditorMode::View => return self.view_mode_execute_command(command),
                 EditorMode::Prompt(_) => return self.prompt_mode_execute_command(command),
                 EditorMode::Goto {
                     original_cursor_pos,
@@ -119,6 +131,17 @@ impl Editor {
                         is_backwards,
                     );
                 }
+                EditorMode::Replace => return self.replace_mode_execute_command(command),
+                EditorMode::PickBuffer { selected_idx: _ } => {
+                    return self.pick_buffer_mode_execute_command(command);
+                }
+                EditorMode::PickError { selected_idx: _ } => {
+                    return self.pick_error_mode_execute_command(command);
+                }
+                EditorMode::UndoTree {
+                    original_state_id: _,
+                    selected_idx: _,
+                } => return self.undo_tree_mode_execute_command(command),
             },
         };
 
@@ -127,18 +150,37 @@ impl Editor {
 
     #[inline]
     pub fn parse_command(&self, input: Input) -> Option<EditorCommand> {
+        // saving and quitting work in every mode
+        if let Input::Keypress(key) = input
+            && let Some(command @ (KeyCommand::Quit | KeyCommand::Save)) =
+                self.keymap.get_command(self.get_keymap_mode(), &[key])
+        {
+            return self.get_key_command(command, None);
+        }
+
         match input {
             Input::ExternalNotification(Notification::Simple(message)) => {
                 Some(EditorCommand::DisplayMessage(message))
             }
-            Input::ExternalNotification(Notification::ExecutionEnd {
-                status,
-                output_available,
-            }) => Some(EditorCommand::EndFileExecution(status, output_available)),
-            Input::Keypress(Key::Ctrl('q')) => Some(EditorCommand::Quit),
-            Input::Keypress(Key::Ctrl('s')) => Some(EditorCommand::Save),
+            Input::ExternalNotification(Notification::ExecutionOutput) => {
+                Some(EditorCommand::AppendExecutionOutput)
+            }
+            Input::ExternalNotification(Notification::FollowedOutput) => {
+                Some(EditorCommand::AppendFollowedOutput)
+            }
+            Input::ExternalNotification(Notification::ExecutionEnd { status }) => {
+                Some(EditorCommand::EndFileExecution(status))
+            }
+            Input::NoOp if self.running_execution.is_some() => Some(EditorCommand::RefreshStatus),
+            Input::ExternalNotification(Notification::FileChanged(path)) => {
+                Some(EditorCommand::HandleFileChangedOnDisk(path))
+            }
             _ => match self.mode {
                 EditorMode::Insert => self.insert_mode_parse_command(input),
+                EditorMode::Normal | EditorMode::Visual | EditorMode::VisualLine => {
+                    self.normal_mode_parse_command(input)
+                }
+                EditorMode::View => self.view_mode_parse_command(input),
                 EditorMode::Prompt(_) => self.prompt_mode_parse_command(input),
                 EditorMode::Goto {
                     original_cursor_pos: _,
@@ -147,12 +189,34 @@ impl Editor {
                     original_cursor_pos: _,
                     is_backwards: _,
                 } => self.search_mode_parse_command(input),
+                EditorMode::Replace => self.replace_mode_parse_command(input),
+                EditorMode::PickBuffer { selected_idx } => {
+                    self.pick_buffer_mode_parse_command(input, selected_idx)
+                }
+                EditorMode::PickError { selected_idx } => {
+                    self.pick_error_mode_parse_command(input, selected_idx)
+                }
+                EditorMode::UndoTree {
+                    original_state_id,
+                    selected_idx,
+                } => self.undo_tree_mode_parse_command(input, original_state_id, selected_idx),
             },
         }
     }
 
+    /// Mode the editor goes back to once a prompt or a picker is closed
+    pub fn get_default_mode(&self) -> EditorMode {
+        if self.is_view_mode {
+            EditorMode::View
+        } else if self.config.vi_mode {
+            EditorMode::Normal
+        } else {
+            EditorMode::Insert
+        }
+    }
+
     pub fn quit(&mut self, force: bool) -> CommandExecutionResult {
-        if !self.document.has_been_modified()
+        if !self.has_unsaved_buffers()
             || !self.config.confirm_quit_unsaved_changes
             || self.first_quit_sent
             || force
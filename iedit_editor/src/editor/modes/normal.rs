@@ -0,0 +1,90 @@
+use termion::event::Key;
+
+use crate::{
+    Editor,
+    editor::{
+        commands::{CommandExecutionResult, EditorCommand},
+        modes::EditorMode,
+        vi::{ParsedKeys, parse_vi_keys},
+    },
+    input::Input,
+};
+
+impl Editor {
+    pub fn normal_mode_execute_command(
+        &mut self,
+        command: EditorCommand,
+    ) -> CommandExecutionResult {
+        use EditorCommand as C;
+
+        match command {
+            C::SetViPendingKeys(keys) => {
+                self.vi.pending_keys = keys;
+            }
+            C::ExecuteViAction { action, count } => {
+                self.vi.pending_keys.clear();
+                self.execute_vi_action(action, count);
+            }
+            C::ClearSelection => {
+                self.vi.pending_keys.clear();
+                self.mode = EditorMode::Normal;
+                return self.insert_mode_execute_command(command);
+            }
+            _ => return self.insert_mode_execute_command(command),
+        }
+
+        CommandExecutionResult::Continue
+    }
+
+    pub fn normal_mode_parse_command(&self, input: Input) -> Option<EditorCommand> {
+        use EditorCommand as C;
+        use EditorMode as M;
+
+        // keys that vi users expect to work outside of insert mode too
+        let input = match input {
+            Input::Keypress(Key::Left) | Input::Keypress(Key::Backspace) => {
+                Input::Keypress(Key::Char('h'))
+            }
+            Input::Keypress(Key::Right) => Input::Keypress(Key::Char('l')),
+            Input::Keypress(Key::Up) => Input::Keypress(Key::Char('k')),
+            Input::Keypress(Key::Down) => Input::Keypress(Key::Char('j')),
+            Input::Keypress(Key::Home) => Input::Keypress(Key::Char('0')),
+            Input::Keypress(Key::End) => Input::Keypress(Key::Char('$')),
+            Input::Keypress(Key::Delete) => Input::Keypress(Key::Char('x')),
+            input => input,
+        };
+
+        // bound keys keep their usual meaning, but nothing is typed in the document
+        if self.vi.pending_keys.is_empty()
+            && let Some(command) = self.parse_keymap_input(&input)
+        {
+            return match command {
+                C::Edit(_) | C::EditAtCursors(_) => None,
+                command => Some(command),
+            };
+        }
+
+        let is_visual = matches!(self.mode, M::Visual | M::VisualLine);
+        match input {
+            Input::Keypress(Key::Esc) => Some(C::ClearSelection),
+            Input::Keypress(Key::Char('\n')) | Input::Keypress(Key::Char('\r'))
+                if self.is_viewing_execution_output() =>
+            {
+                Some(C::GoToErrorAtCursor)
+            }
+            Input::Keypress(Key::Char(ch)) => {
+                let mut keys = self.vi.pending_keys.clone();
+                keys.push(ch);
+
+                match parse_vi_keys(&keys, is_visual) {
+                    ParsedKeys::Pending => Some(C::SetViPendingKeys(keys)),
+                    ParsedKeys::Invalid => Some(C::SetViPendingKeys(String::new())),
+                    ParsedKeys::Complete((action, count)) => {
+                        Some(C::ExecuteViAction { action, count })
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use termion::event::Key;
+
+use crate::{
+    Editor,
+    editor::{
+        commands::{CommandExecutionResult, EditorCommand},
+        modes::EditorMode,
+    },
+    input::Input,
+};
+
+impl Editor {
+    pub fn pick_buffer_mode_execute_command(
+        &mut self,
+        command: EditorCommand,
+    ) -> CommandExecutionResult {
+        use EditorCommand as C;
+
+        match command {
+            C::SwitchMode(mode) => {
+                self.mode = mode;
+                self.needs_full_rerender = true;
+            }
+            C::SwitchToBuffer(buffer_idx) => {
+                self.mode = self.get_default_mode();
+                self.switch_to_buffer(buffer_idx);
+                self.needs_full_rerender = true;
+            }
+            _ => {}
+        }
+
+        CommandExecutionResult::Continue
+    }
+
+    pub fn pick_buffer_mode_parse_command(
+        &self,
+        input: Input,
+        selected_idx: usize,
+    ) -> Option<EditorCommand> {
+        use EditorCommand as C;
+        use EditorMode as M;
+
+        let n_buffers = self.buffers.len();
+        match input {
+            Input::Keypress(Key::Esc) | Input::Keypress(Key::Char('q')) => {
+                Some(C::SwitchMode(self.get_default_mode()))
+            }
+            Input::Keypress(Key::Up) | Input::Keypress(Key::Char('k')) => {
+                Some(C::SwitchMode(M::PickBuffer {
+                    selected_idx: (selected_idx + n_buffers - 1) % n_buffers,
+                }))
+            }
+            Input::Keypress(Key::Down) | Input::Keypress(Key::Char('j')) => {
+                Some(C::SwitchMode(M::PickBuffer {
+                    selected_idx: (selected_idx + 1) % n_buffers,
+                }))
+            }
+            Input::Keypress(Key::Char('\n')) | Input::Keypress(Key::Char('\r')) => {
+                Some(C::SwitchToBuffer(selected_idx))
+            }
+            Input::Keypress(Key::Char(ch)) if ch.is_ascii_digit() && ch != '0' => {
+                let buffer_idx = ch.to_digit(10).unwrap() as usize - 1;
+                (buffer_idx < n_buffers).then_some(C::SwitchToBuffer(buffer_idx))
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use termion::event::Key;
+
+use crate::{
+    Editor,
+    editor::{
+        commands::{CommandExecutionResult, EditorCommand},
+        modes::EditorMode,
+    },
+    input::Input,
+};
+
+impl Editor {
+    pub fn pick_error_mode_execute_command(
+        &mut self,
+        command: EditorCommand,
+    ) -> CommandExecutionResult {
+        use EditorCommand as C;
+
+        match command {
+            C::SwitchMode(mode) => {
+                self.mode = mode;
+                self.needs_full_rerender = true;
+            }
+            C::GoToError(error_idx) => {
+                self.mode = self.get_default_mode();
+                self.go_to_error(error_idx);
+                self.needs_full_rerender = true;
+            }
+            _ => {}
+        }
+
+        CommandExecutionResult::Continue
+    }
+
+    pub fn pick_error_mode_parse_command(
+        &self,
+        input: Input,
+        selected_idx: usize,
+    ) -> Option<EditorCommand> {
+        use EditorCommand as C;
+        use EditorMode as M;
+
+        let n_errors = self.error_locations.len();
+        match input {
+            Input::Keypress(Key::Esc) | Input::Keypress(Key::Char('q')) => {
+                Some(C::SwitchMode(self.get_default_mode()))
+            }
+            Input::Keypress(Key::Up) | Input::Keypress(Key::Char('k')) => {
+                Some(C::SwitchMode(M::PickError {
+                    selected_idx: selected_idx.saturating_sub(1),
+                }))
+            }
+            Input::Keypress(Key::Down) | Input::Keypress(Key::Char('j')) => {
+                Some(C::SwitchMode(M::PickError {
+                    selected_idx: (selected_idx + 1).min(n_errors.saturating_sub(1)),
+                }))
+            }
+            Input::Keypress(Key::Char('\n')) | Input::Keypress(Key::Char('\r')) => {
+                Some(C::GoToError(selected_idx))
+            }
+            _ => None,
+        }
+    }
+}
@@ -6,7 +6,8 @@ use termion::event::Key;
 use crate::{
     Editor,
     editor::{
-        commands::{CommandExecutionResult, EditorCommand},
+        commands::{CommandExecutionResult, EditorCommand, send_simple_notification},
+        lines::LineOrder,
         modes::EditorMode,
     },
     input::Input,
@@ -18,8 +19,10 @@ impl Editor {
             "q" | "quit" => self.quit(false),
             "q!" | "quit!" => self.quit(true),
             "w" | "write" => {
-                // TODO: handle error here
-                let _ = self.save_file(true);
+                if let Err(err) = self.save_file(true) {
+                    send_simple_notification(err.to_string());
+                }
+
                 CommandExecutionResult::Continue
             }
             "wq" => {
@@ -35,9 +38,105 @@ impl Editor {
 
                 CommandExecutionResult::Continue
             }
-            // TODO: support config modification
-            // e.g. > set show_keybindings = 0
-            _ => CommandExecutionResult::Continue,
+            "bn" | "bnext" => {
+                self.switch_to_next_buffer();
+                CommandExecutionResult::Continue
+            }
+            "bp" | "bprevious" => {
+                self.switch_to_previous_buffer();
+                CommandExecutionResult::Continue
+            }
+            "bd" | "bdelete" => {
+                self.close_active_buffer(false);
+                CommandExecutionResult::Continue
+            }
+            "bd!" | "bdelete!" => {
+                self.close_active_buffer(true);
+                CommandExecutionResult::Continue
+            }
+            "ls" | "buffers" => {
+                self.open_buffer_picker();
+                CommandExecutionResult::Continue
+            }
+            "cn" | "cnext" => {
+                self.go_to_next_error();
+                CommandExecutionResult::Continue
+            }
+            "cp" | "cprevious" => {
+                self.go_to_previous_error();
+                CommandExecutionResult::Continue
+            }
+            "cl" | "clist" => {
+                self.open_error_list();
+                CommandExecutionResult::Continue
+            }
+            "undotree" => {
+                self.open_undo_tree();
+                CommandExecutionResult::Continue
+            }
+            cmd_string if cmd_string == "earlier" || cmd_string.starts_with("earlier ") => {
+                self.execute_time_travel_command(cmd_string.strip_prefix("earlier").unwrap(), true);
+                CommandExecutionResult::Continue
+            }
+            cmd_string if cmd_string == "later" || cmd_string.starts_with("later ") => {
+                self.execute_time_travel_command(cmd_string.strip_prefix("later").unwrap(), false);
+                CommandExecutionResult::Continue
+            }
+            "e!" | "edit!" => {
+                self.reload_file(None);
+                CommandExecutionResult::Continue
+            }
+            cmd_string if cmd_string.starts_with("e! ") || cmd_string.starts_with("edit! ") => {
+                let (_, encoding_name) = cmd_string.split_once(' ').unwrap();
+                match encoding_name.trim().parse() {
+                    Ok(encoding) => self.reload_file(Some(encoding)),
+                    Err(err) => send_simple_notification(err),
+                }
+
+                CommandExecutionResult::Continue
+            }
+            cmd_string if cmd_string == "encoding" || cmd_string.starts_with("encoding ") => {
+                self.execute_encoding_command(cmd_string.strip_prefix("encoding").unwrap())
+            }
+            cmd_string if cmd_string == "eol" || cmd_string.starts_with("eol ") => {
+                self.execute_eol_command(cmd_string.strip_prefix("eol").unwrap())
+            }
+            cmd_string if cmd_string.starts_with("e ") => {
+                self.open_file(cmd_string.strip_prefix("e ").unwrap());
+                CommandExecutionResult::Continue
+            }
+            cmd_string
+                if cmd_string.starts_with("s")
+                    && cmd_string
+                        .chars()
+                        .nth(1)
+                        .is_some_and(|ch| ch.is_ascii_punctuation()) =>
+            {
+                self.execute_substitute_command(cmd_string)
+            }
+            cmd_string if cmd_string.starts_with("sort") => {
+                self.execute_sort_command(cmd_string.strip_prefix("sort").unwrap())
+            }
+            "reverse" => {
+                self.reorder_lines(LineOrder::Reverse);
+                CommandExecutionResult::Continue
+            }
+            "uniq" | "unique" => {
+                self.reorder_lines(LineOrder::Unique);
+                CommandExecutionResult::Continue
+            }
+            "shuffle" => {
+                self.reorder_lines(LineOrder::Shuffle);
+                CommandExecutionResult::Continue
+            }
+            cmd_string if cmd_string == "set" || cmd_string.starts_with("set ") => {
+                self.execute_set_command(cmd_string.strip_prefix("set").unwrap())
+            }
+            "" => CommandExecutionResult::Continue,
+            cmd_string => {
+                send_simple_notification(format!("Unknown command: {}", cmd_string));
+                CommandExecutionResult::Continue
+            }
         }
     }
 
@@ -83,8 +182,14 @@ impl Editor {
 
                 R::Continue
             }
+            C::CompletePrompt => {
+                self.complete_set_command();
+
+                R::Continue
+            }
             C::SubmitPrompt => {
                 let prompt = self.status_bar.prompt_line.split_off(0);
+                self.status_bar.cursor_pos = 0;
                 if let Some(fun) = self.status_bar.submit_action.take() {
                     let res = fun(self, prompt);
                     if !matches!(res, R::Continue) {
@@ -92,7 +197,12 @@ impl Editor {
                     }
                 }
 
-                self.mode = EditorMode::Insert;
+                // the callback may have opened another prompt or switched mode
+                if matches!(self.mode, EditorMode::Prompt(_))
+                    && self.status_bar.submit_action.is_none()
+                {
+                    self.mode = self.get_default_mode();
+                }
 
                 R::Continue
             }
@@ -102,10 +212,9 @@ impl Editor {
 
     pub fn prompt_mode_parse_command(&self, input: Input) -> Option<EditorCommand> {
         use EditorCommand as C;
-        use EditorMode as M;
 
         match input {
-            Input::Keypress(Key::Esc) => Some(C::SwitchMode(M::Insert)),
+            Input::Keypress(Key::Esc) => Some(C::SwitchMode(self.get_default_mode())),
             Input::Keypress(Key::Left) => Some(C::MovePromptCursorLeft),
             Input::Keypress(Key::Right) => Some(C::MovePromptCursorRight),
             Input::Keypress(Key::Backspace) | Input::Keypress(Key::Delete) => {
@@ -116,6 +225,9 @@ impl Editor {
             Input::Keypress(Key::Char('\n')) | Input::Keypress(Key::Char('\r')) => {
                 Some(C::SubmitPrompt)
             }
+            Input::Keypress(Key::Char('\t')) if matches!(self.mode, EditorMode::Prompt("> ")) => {
+                Some(C::CompletePrompt)
+            }
             Input::Keypress(Key::Char(ch)) => Some(C::InsertCharPrompt {
                 pos_x: self.status_bar.cursor_pos,
                 ch,
@@ -129,6 +241,7 @@ impl Editor {
         prompt: &'static str,
         callback: impl FnOnce(&mut Editor, DocumentLine) -> CommandExecutionResult + 'static,
     ) {
+        self.commit_vi_insert();
         self.mode = EditorMode::Prompt(prompt);
         self.status_bar.submit_action = Some(Box::from(callback));
     }
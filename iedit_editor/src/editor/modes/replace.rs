@@ -0,0 +1,345 @@
+use iedit_document::{Document, EditOperation, InverseStack, RegexReplacement, Text};
+use regex_lite::Regex;
+use termion::event::Key;
+
+use crate::{
+    Editor,
+    editor::{
+        commands::{CommandExecutionResult, EditorCommand, send_simple_notification},
+        modes::EditorMode,
+        search::ReplaceItem,
+    },
+    input::Input,
+};
+
+impl Editor {
+    pub fn replace_mode_execute_command(
+        &mut self,
+        command: EditorCommand,
+    ) -> CommandExecutionResult {
+        use EditorCommand as C;
+
+        match command {
+            C::ReplaceCurrentMatch => {
+                self.replace_current_match();
+                if !self.find_next_replace_match() {
+                    self.finish_replace();
+                }
+            }
+            C::SkipCurrentMatch => {
+                if let Some(item) = self.replace_item.as_mut()
+                    && let Some((start, end, _)) = item.current_match.take()
+                {
+                    item.next_pos =
+                        get_resume_pos(&self.document, item.is_global, start == end, end);
+                }
+
+                if !self.find_next_replace_match() {
+                    self.finish_replace();
+                }
+            }
+            C::ReplaceRemainingMatches => {
+                if let Some(item) = self.replace_item.as_mut()
+                    && let Some((start, _, _)) = item.current_match.take()
+                {
+                    item.next_pos = start;
+                }
+
+                self.replace_remaining_matches();
+                self.finish_replace();
+            }
+            C::StopReplacing => self.finish_replace(),
+            _ => {}
+        }
+
+        CommandExecutionResult::Continue
+    }
+
+    pub fn replace_mode_parse_command(&self, input: Input) -> Option<EditorCommand> {
+        use EditorCommand as C;
+
+        match input {
+            Input::Keypress(Key::Char('y')) => Some(C::ReplaceCurrentMatch),
+            Input::Keypress(Key::Char('n')) => Some(C::SkipCurrentMatch),
+            Input::Keypress(Key::Char('a')) => Some(C::ReplaceRemainingMatches),
+            Input::Keypress(Key::Char('q')) | Input::Keypress(Key::Esc) => Some(C::StopReplacing),
+            _ => None,
+        }
+    }
+
+    /// Handles a `s/pattern/replacement/flags` command from the command prompt.
+    /// Any punctuation character following `s` can be used as delimiter. Supported flags are:
+    /// - g: replace every match in a line, not just the first one
+    /// - i: case insensitive matching
+    /// - c: ask for confirmation before each replacement
+    pub fn execute_substitute_command(&mut self, cmd: &str) -> CommandExecutionResult {
+        let Some((pattern, replacement, flags)) = parse_substitute_command(cmd) else {
+            send_simple_notification("Usage: s/pattern/replacement/flags");
+            return CommandExecutionResult::Continue;
+        };
+
+        if let Some(flag) = flags.chars().find(|flag| !matches!(flag, 'g' | 'i' | 'c')) {
+            send_simple_notification(format!("Unknown flag: {}", flag));
+            return CommandExecutionResult::Continue;
+        }
+
+        let pattern = if flags.contains('i') {
+            format!("(?i){}", pattern)
+        } else {
+            pattern
+        };
+
+        match Regex::new(&pattern) {
+            Ok(regex) => {
+                self.start_replace(regex, replacement, flags.contains('g'), flags.contains('c'))
+            }
+            Err(err) => send_simple_notification(format!("Invalid pattern: {}", err)),
+        }
+
+        CommandExecutionResult::Continue
+    }
+
+    /// Prompts for a pattern and a replacement, optionally asking confirmation for every match
+    pub fn prompt_replace(&mut self, confirm: bool) {
+        self.prompt_user("Replace: ", move |editor, pattern| {
+            match Regex::new(pattern.as_ref()) {
+                Ok(regex) => editor.prompt_user("Replace with: ", move |editor, replacement| {
+                    editor.start_replace(regex, replacement.into(), true, confirm);
+                    CommandExecutionResult::Continue
+                }),
+                Err(err) => send_simple_notification(format!("Invalid pattern: {}", err)),
+            }
+
+            CommandExecutionResult::Continue
+        });
+    }
+
+    /// Replaces the matches of `regex` in the current selection, or in the whole document
+    /// if nothing is selected. The whole batch is undone as a single edit.
+    pub fn start_replace(
+        &mut self,
+        regex: Regex,
+        replacement: String,
+        is_global: bool,
+        confirm: bool,
+    ) {
+        let (scope_start, scope_end) = self
+            .cursor
+            .get_selected_range()
+            .unwrap_or(((0, 0), (0, self.document.n_lines())));
+
+        self.document
+            .begin_transaction(self.cursor.pos(), self.cursor.selection_anchor);
+        self.cursor.selection_anchor = None;
+        self.is_selection_locked = false;
+        self.needs_full_rerender = true;
+        self.replace_item = Some(ReplaceItem {
+            regex,
+            replacement,
+            is_global,
+            next_pos: scope_start,
+            scope_end,
+            current_match: None,
+            n_replaced: 0,
+        });
+
+        if confirm && self.find_next_replace_match() {
+            self.cursor.jump_history.push(self.cursor.pos());
+            self.mode = EditorMode::Replace;
+        } else if confirm {
+            self.finish_replace();
+        } else {
+            self.replace_remaining_matches();
+            self.finish_replace();
+        }
+    }
+
+    fn find_next_replace_match(&mut self) -> bool {
+        let Some(item) = self.replace_item.as_mut() else {
+            return false;
+        };
+
+        let next_match = self
+            .document
+            .get_next_regex_replacement(item.next_pos, &item.regex, &item.replacement)
+            .filter(|(_, end, _)| is_within_scope(*end, item.scope_end));
+
+        if let Some((start, end, _)) = next_match {
+            self.matched_range = Some((start, end));
+            self.cursor.update_pos(start, false);
+        }
+
+        item.current_match = next_match;
+        self.needs_full_rerender = true;
+
+        item.current_match.is_some()
+    }
+
+    fn replace_current_match(&mut self) {
+        let Some(item) = self.replace_item.as_mut() else {
+            return;
+        };
+        let Some((start, end, replacement)) = item.current_match.take() else {
+            return;
+        };
+
+        let op = EditOperation::Replacement {
+            pos_from: start,
+            pos_to: end,
+            text: Text::from(replacement),
+        };
+
+        match self.document.apply_edit(op, InverseStack::Undo) {
+            Some(new_pos) => {
+                item.n_replaced += 1;
+
+                // replacements can change the length of the line and add new lines
+                if item.scope_end.1 == end.1 {
+                    item.scope_end.0 = (item.scope_end.0 + new_pos.0).saturating_sub(end.0);
+                }
+                item.scope_end.1 += new_pos.1 - end.1;
+
+                item.next_pos =
+                    get_resume_pos(&self.document, item.is_global, start == end, new_pos);
+                self.cursor.update_pos(new_pos, false);
+            }
+            None => {
+                item.next_pos = get_resume_pos(&self.document, item.is_global, start == end, end);
+            }
+        }
+    }
+
+    fn replace_remaining_matches(&mut self) {
+        let Some(item) = self.replace_item.as_mut() else {
+            return;
+        };
+
+        let mut matches: Vec<RegexReplacement> = vec![];
+        let mut pos = item.next_pos;
+        while let Some((start, end, replacement)) =
+            self.document
+                .get_next_regex_replacement(pos, &item.regex, &item.replacement)
+        {
+            if !is_within_scope(end, item.scope_end) {
+                break;
+            }
+
+            pos = get_resume_pos(&self.document, item.is_global, start == end, end);
+            matches.push((start, end, replacement));
+        }
+
+        if matches.is_empty() {
+            return;
+        }
+
+        // apply from the last match backwards, so that earlier positions stay valid
+        let n_matches = matches.len();
+        let ops = matches
+            .into_iter()
+            .rev()
+            .map(
+                |(pos_from, pos_to, replacement)| EditOperation::Replacement {
+                    pos_from,
+                    pos_to,
+                    text: Text::from(replacement),
+                },
+            )
+            .collect();
+
+        if let Some(new_pos) = self
+            .document
+            .apply_edit(EditOperation::Group(ops), InverseStack::Undo)
+        {
+            item.n_replaced += n_matches;
+            self.cursor.update_pos(new_pos, true);
+        }
+    }
+
+    fn finish_replace(&mut self) {
+        if let Some(item) = self.replace_item.take() {
+            self.document.commit();
+            send_simple_notification(format!("Replaced {} occurrences", item.n_replaced));
+        }
+
+        self.matched_range = None;
+        self.mode = self.get_default_mode();
+        self.needs_full_rerender = true;
+    }
+}
+
+/// Position to resume searching from after a match ending at `pos`
+fn get_resume_pos(
+    document: &Document,
+    is_global: bool,
+    is_empty_match: bool,
+    pos: (usize, usize),
+) -> (usize, usize) {
+    if !is_global {
+        return (0, pos.1 + 1);
+    } else if !is_empty_match {
+        return pos;
+    }
+
+    // step past empty matches, otherwise they would be found over and over
+    let line_len = document.lines.get(pos.1).map_or(0, |line| line.len());
+    if pos.0 < line_len {
+        (pos.0 + 1, pos.1)
+    } else {
+        (0, pos.1 + 1)
+    }
+}
+
+#[inline]
+fn is_within_scope(pos: (usize, usize), scope_end: (usize, usize)) -> bool {
+    pos.1 < scope_end.1 || (pos.1 == scope_end.1 && pos.0 <= scope_end.0)
+}
+
+/// Splits `s/pattern/replacement/flags` into its parts. Delimiters can be escaped with a backslash,
+/// other escape sequences are passed through to the regex untouched.
+fn parse_substitute_command(cmd: &str) -> Option<(String, String, String)> {
+    let mut chars = cmd.strip_prefix('s')?.chars();
+    let delimiter = chars.next().filter(char::is_ascii_punctuation)?;
+    if delimiter == '\\' {
+        return None;
+    }
+
+    let mut parts = vec![String::new()];
+    let mut is_escaped = false;
+    for ch in chars {
+        if ch == delimiter && !is_escaped && parts.len() < 3 {
+            parts.push(String::new());
+            continue;
+        }
+
+        let part = parts.last_mut().unwrap();
+        if is_escaped {
+            if ch != delimiter {
+                part.push('\\');
+            }
+            part.push(ch);
+            is_escaped = false;
+        } else if ch == '\\' {
+            is_escaped = true;
+        } else {
+            part.push(ch);
+        }
+    }
+
+    if is_escaped {
+        parts.last_mut().unwrap().push('\\');
+    }
+
+    if parts.len() < 2 {
+        return None;
+    }
+
+    let flags = if parts.len() == 3 {
+        parts.pop().unwrap()
+    } else {
+        String::new()
+    };
+    let replacement = parts.pop().unwrap();
+    let pattern = parts.pop().unwrap();
+
+    Some((pattern, replacement, flags))
+}
@@ -7,7 +7,6 @@ use crate::{
     Editor,
     editor::{
         commands::{CommandExecutionResult, EditorCommand},
-        modes::EditorMode,
         search::SearchItem,
     },
     input::Input,
@@ -27,7 +26,7 @@ impl Editor {
             C::SubmitPrompt => {
                 self.search_item = None;
                 self.status_bar.prompt_line.truncate(0);
-                self.mode = EditorMode::Insert;
+                self.mode = self.get_default_mode();
                 self.needs_full_rerender = true;
                 self.cursor.jump_history.push(original_pos);
                 R::Continue
@@ -0,0 +1,194 @@
+use std::time::{Duration, SystemTime};
+
+use iedit_document::TimeTravel;
+use termion::event::Key;
+
+use crate::{
+    Editor,
+    editor::{
+        commands::{CommandExecutionResult, EditorCommand, send_simple_notification},
+        modes::EditorMode,
+    },
+    input::Input,
+};
+
+impl Editor {
+    pub fn undo_tree_mode_execute_command(
+        &mut self,
+        command: EditorCommand,
+    ) -> CommandExecutionResult {
+        use EditorCommand as C;
+        use EditorMode as M;
+
+        match command {
+            C::SwitchMode(mode) => {
+                // moving through the tree previews the selected state
+                if let M::UndoTree {
+                    original_state_id: _,
+                    selected_idx,
+                } = mode
+                    && let Some(undo_state) = self.document.get_undo_states().get(selected_idx)
+                {
+                    self.go_to_undo_state(undo_state.id);
+                }
+
+                self.mode = mode;
+                self.needs_full_rerender = true;
+            }
+            C::GoToUndoState(state_id) => {
+                self.go_to_undo_state(state_id);
+                self.mode = self.get_default_mode();
+                self.needs_full_rerender = true;
+            }
+            _ => {}
+        }
+
+        CommandExecutionResult::Continue
+    }
+
+    pub fn undo_tree_mode_parse_command(
+        &self,
+        input: Input,
+        original_state_id: usize,
+        selected_idx: usize,
+    ) -> Option<EditorCommand> {
+        use EditorCommand as C;
+        use EditorMode as M;
+
+        let n_states = self.document.get_undo_states().len();
+        match input {
+            Input::Keypress(Key::Esc) | Input::Keypress(Key::Char('q')) => {
+                Some(C::GoToUndoState(original_state_id))
+            }
+            Input::Keypress(Key::Up) | Input::Keypress(Key::Char('k')) => {
+                Some(C::SwitchMode(M::UndoTree {
+                    original_state_id,
+                    selected_idx: selected_idx.saturating_sub(1),
+                }))
+            }
+            Input::Keypress(Key::Down) | Input::Keypress(Key::Char('j')) => {
+                Some(C::SwitchMode(M::UndoTree {
+                    original_state_id,
+                    selected_idx: (selected_idx + 1).min(n_states.saturating_sub(1)),
+                }))
+            }
+            Input::Keypress(Key::Char('\n')) | Input::Keypress(Key::Char('\r')) => {
+                Some(C::SwitchMode(self.get_default_mode()))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn open_undo_tree(&mut self) {
+        let current_state_id = self.document.get_current_undo_state_id();
+        let selected_idx = self
+            .document
+            .get_undo_states()
+            .iter()
+            .position(|undo_state| undo_state.is_current)
+            .unwrap_or_default();
+
+        self.mode = EditorMode::UndoTree {
+            original_state_id: current_state_id,
+            selected_idx,
+        };
+    }
+
+    /// Lines of the undo tree popup, restricted to the ones around the selected state
+    /// when they don't all fit
+    pub fn get_undo_tree_lines(&self, selected_idx: usize) -> Vec<String> {
+        let undo_states = self.document.get_undo_states();
+        let max_lines = (self.ui.editor_lines as usize).saturating_sub(2).max(1);
+        let first_line_idx = selected_idx
+            .saturating_sub(max_lines / 2)
+            .min(undo_states.len().saturating_sub(max_lines));
+
+        let now = SystemTime::now();
+        undo_states
+            .iter()
+            .enumerate()
+            .skip(first_line_idx)
+            .take(max_lines)
+            .map(|(state_idx, undo_state)| {
+                let age = if undo_state.id == 0 {
+                    "original".to_owned()
+                } else {
+                    format_age(now.duration_since(undo_state.time).unwrap_or_default())
+                };
+
+                format!(
+                    "{} {}{} {} │ {}",
+                    if state_idx == selected_idx { '>' } else { ' ' },
+                    "  ".repeat(undo_state.depth),
+                    if undo_state.is_current { '●' } else { '○' },
+                    undo_state.id,
+                    age,
+                )
+            })
+            .collect()
+    }
+
+    /// Handles the `earlier` and `later` prompt commands, e.g. `earlier 5m` or `later 3`
+    pub fn execute_time_travel_command(&mut self, arg: &str, is_earlier: bool) {
+        let Some(time_travel) = parse_time_travel(arg) else {
+            send_simple_notification(format!("Invalid time: {}", arg));
+            return;
+        };
+
+        let new_pos = if is_earlier {
+            self.document.go_earlier(time_travel)
+        } else {
+            self.document.go_later(time_travel)
+        };
+
+        match new_pos {
+            Some(new_pos) => self.update_cursor_after_undo(new_pos),
+            None if is_earlier => send_simple_notification("Already at oldest change"),
+            None => send_simple_notification("Already at newest change"),
+        }
+    }
+
+    fn go_to_undo_state(&mut self, state_id: usize) {
+        if let Some(new_pos) = self.document.go_to_undo_state(state_id) {
+            self.update_cursor_after_undo(new_pos);
+        }
+    }
+
+    fn update_cursor_after_undo(&mut self, new_pos: (usize, usize)) {
+        self.clear_secondary_carets();
+        self.cursor.selection_anchor = None;
+        self.cursor.update_pos(new_pos, false);
+        self.first_quit_sent = false;
+    }
+}
+
+/// Either a number of steps (`3`) or a duration (`30s`, `5m`, `2h`, `1d`), one step if empty
+fn parse_time_travel(arg: &str) -> Option<TimeTravel> {
+    let arg = arg.trim();
+    if arg.is_empty() {
+        return Some(TimeTravel::Steps(1));
+    }
+
+    let unit_secs = match arg.chars().last()? {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return arg.parse().ok().map(TimeTravel::Steps),
+    };
+
+    arg[..arg.len() - 1]
+        .parse::<u64>()
+        .ok()
+        .map(|amount| TimeTravel::Duration(Duration::from_secs(amount * unit_secs)))
+}
+
+fn format_age(age: Duration) -> String {
+    match age.as_secs() {
+        0 => "now".to_owned(),
+        secs @ ..60 => format!("{}s ago", secs),
+        secs @ ..3600 => format!("{}m ago", secs / 60),
+        secs @ ..86400 => format!("{}h ago", secs / 3600),
+        secs => format!("{}d ago", secs / 86400),
+    }
+}
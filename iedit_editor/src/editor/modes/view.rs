@@ -0,0 +1,47 @@
+use termion::event::Key;
+
+use crate::{
+    Editor,
+    editor::{
+        commands::{CommandExecutionResult, EditorCommand},
+        modes::EditorMode,
+    },
+    input::Input,
+};
+
+impl Editor {
+    /// Makes every open document read-only, and moves around them with pager keys
+    pub fn enter_view_mode(&mut self) {
+        self.is_view_mode = true;
+        self.mode = EditorMode::View;
+        self.document.is_readonly = true;
+        for buffer in self.buffers.iter_mut() {
+            buffer.document.is_readonly = true;
+        }
+    }
+
+    pub fn view_mode_execute_command(&mut self, command: EditorCommand) -> CommandExecutionResult {
+        self.insert_mode_execute_command(command)
+    }
+
+    pub fn view_mode_parse_command(&self, input: Input) -> Option<EditorCommand> {
+        use EditorCommand as C;
+
+        if let Input::Keypress(Key::Esc) = input {
+            return Some(C::ClearSelection);
+        }
+
+        // only bound keys do something, nothing is typed in the document
+        match self.parse_keymap_input(&input)? {
+            C::Edit(_)
+            | C::EditAtCursors(_)
+            | C::DeleteLine
+            | C::MoveLines { .. }
+            | C::DuplicateLines
+            | C::JoinLines
+            | C::ReorderLines(_)
+            | C::ToggleComment => None,
+            command => Some(command),
+        }
+    }
+}
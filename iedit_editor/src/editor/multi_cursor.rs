@@ -0,0 +1,301 @@
+use std::{cmp::min, mem};
+
+use iedit_document::{EditOperation, Text};
+use regex_lite::Regex;
+
+use crate::editor::{
+    Editor,
+    commands::{CursorMovement, EditorCommand, send_simple_notification},
+    cursor::Caret,
+};
+
+impl Editor {
+    #[inline]
+    pub fn has_multiple_cursors(&self) -> bool {
+        !self.cursor.secondary_carets.is_empty()
+    }
+
+    /// Every caret, the primary one included and flagged, sorted by position
+    pub fn get_sorted_carets(&self) -> Vec<(Caret, bool)> {
+        let mut carets: Vec<(Caret, bool)> = self
+            .cursor
+            .secondary_carets
+            .iter()
+            .map(|caret| (*caret, false))
+            .chain([(self.cursor.caret(), true)])
+            .collect();
+
+        carets.sort_by_key(|(caret, _)| get_caret_start(caret));
+
+        carets
+    }
+
+    /// Builds the command applying the edit returned by `get_edit` at every caret
+    pub fn edit_at_cursors(
+        &self,
+        get_edit: impl Fn(&Self, &Caret) -> EditOperation,
+    ) -> Option<EditorCommand> {
+        if !self.has_multiple_cursors() {
+            return Some(EditorCommand::Edit(get_edit(self, &self.cursor.caret())));
+        }
+
+        let ops = self
+            .get_sorted_carets()
+            .iter()
+            .map(|(caret, _)| get_edit(self, caret))
+            .collect();
+
+        Some(EditorCommand::EditAtCursors(ops))
+    }
+
+    /// Applies `ops`, one for each caret as returned by `get_sorted_carets`, as a single edit
+    pub fn apply_edit_at_cursors(&mut self, ops: Vec<EditOperation>) {
+        let carets = self.get_sorted_carets();
+        let new_positions = self.document.apply_cursor_edits(ops);
+
+        let mut secondary_carets = Vec::with_capacity(carets.len() - 1);
+        for ((caret, is_primary), new_pos) in carets.into_iter().zip(new_positions) {
+            let caret = Caret::new(new_pos.unwrap_or(caret.pos()), None);
+            if is_primary {
+                self.cursor.set_caret(caret);
+            } else {
+                secondary_carets.push(caret);
+            }
+        }
+
+        self.cursor.secondary_carets = secondary_carets;
+        self.normalize_carets();
+        self.needs_full_rerender = true;
+    }
+
+    pub fn clear_secondary_carets(&mut self) {
+        if self.has_multiple_cursors() {
+            self.cursor.secondary_carets.clear();
+            self.needs_full_rerender = true;
+        }
+    }
+
+    /// Repeats a movement of the primary cursor for every other caret
+    pub fn move_secondary_carets(&mut self, movement: CursorMovement, with_selection: bool) {
+        // jumps would send every caret to the same place
+        if matches!(
+            movement,
+            CursorMovement::AbsolutePos(_)
+                | CursorMovement::StartOfFile
+                | CursorMovement::EndOfFile
+                | CursorMovement::NextJump
+                | CursorMovement::PreviousJump
+        ) {
+            return self.clear_secondary_carets();
+        }
+
+        let primary_caret = self.cursor.caret();
+        let jump_history = mem::take(&mut self.cursor.jump_history);
+        let jump_history_head = self.cursor.jump_history_head;
+
+        let carets = mem::take(&mut self.cursor.secondary_carets);
+        let carets = carets
+            .into_iter()
+            .map(|caret| {
+                self.cursor.set_caret(caret);
+                self.move_cursor(movement, with_selection);
+                self.clamp_cursor();
+
+                self.cursor.caret()
+            })
+            .collect();
+
+        self.cursor.set_caret(primary_caret);
+        self.cursor.secondary_carets = carets;
+        self.cursor.jump_history = jump_history;
+        self.cursor.jump_history_head = jump_history_head;
+
+        self.normalize_carets();
+        self.needs_full_rerender = true;
+    }
+
+    pub fn add_cursor_above(&mut self) {
+        let carets = self.get_sorted_carets();
+        let Some((top_caret, _)) = carets.first() else {
+            return;
+        };
+
+        if top_caret.cur_y > 0 {
+            self.add_caret_on_line(top_caret.ideal_x, top_caret.cur_y - 1);
+        }
+    }
+
+    pub fn add_cursor_below(&mut self) {
+        let carets = self.get_sorted_carets();
+        let Some((bottom_caret, _)) = carets.last() else {
+            return;
+        };
+
+        if bottom_caret.cur_y + 1 < self.document.n_lines() {
+            self.add_caret_on_line(bottom_caret.ideal_x, bottom_caret.cur_y + 1);
+        }
+    }
+
+    /// Selects the word under the cursor or, if something is already selected,
+    /// adds a cursor selecting the next occurrence of the selected text
+    pub fn add_cursor_at_next_occurrence(&mut self) {
+        let Some((start, end)) = self.cursor.get_selected_range() else {
+            if let Some((left, right)) = self.document.get_word_boundaries(self.cursor.pos()) {
+                self.cursor.selection_anchor = Some((left, self.cursor.cur_y));
+                self.cursor
+                    .update_pos((right + 1, self.cursor.cur_y), false);
+                self.needs_full_rerender = true;
+            }
+
+            return;
+        };
+
+        let text = self.document.get_range_text(start, end);
+        let regex = match &text {
+            Text::Lines(_) => None,
+            text => match Regex::new(&regex_lite::escape(&text.to_string())) {
+                Ok(regex) => Some(regex),
+                Err(_) => return,
+            },
+        };
+
+        let get_match_from = |pos| match (&text, &regex) {
+            (Text::Lines(lines), _) => self.document.get_next_lines_match_pos(pos, lines),
+            (_, Some(regex)) => self
+                .document
+                .get_next_regex_replacement(pos, regex, "")
+                .map(|(match_start, match_end, _)| (match_start, match_end)),
+            _ => None,
+        };
+
+        // from the caret added last, skipping the occurrences that already have one
+        let carets = self.get_sorted_carets();
+        let mut search_from = end;
+        let next_match = (0..=carets.len()).find_map(|_| {
+            let (match_start, match_end) =
+                get_match_from(search_from).or_else(|| get_match_from((0, 0)))?;
+            search_from = match_end;
+
+            let is_taken = carets.iter().any(|(caret, _)| {
+                caret
+                    .get_selected_range()
+                    .is_some_and(|(caret_start, _)| caret_start == match_start)
+            });
+
+            (!is_taken).then_some((match_start, match_end))
+        });
+
+        match next_match {
+            Some((match_start, match_end)) => {
+                let previous_primary = self.cursor.caret();
+                self.cursor.secondary_carets.push(previous_primary);
+                self.cursor
+                    .set_caret(Caret::new(match_end, Some(match_start)));
+                self.normalize_carets();
+                self.needs_full_rerender = true;
+            }
+            None => send_simple_notification("No more occurrences"),
+        }
+    }
+
+    /// Turns a selection spanning several lines into one cursor per line
+    pub fn split_selection_into_lines(&mut self) {
+        let Some((start, end)) = self.cursor.get_selected_range() else {
+            send_simple_notification("Nothing selected");
+            return;
+        };
+
+        // a selection ending at the start of a line doesn't include it
+        let last_line_idx = if end.0 == 0 && end.1 > start.1 {
+            end.1 - 1
+        } else {
+            end.1
+        };
+
+        let mut carets: Vec<Caret> = (start.1..=last_line_idx)
+            .map(|line_idx| {
+                let line_len = self
+                    .document
+                    .lines
+                    .get(line_idx)
+                    .map_or(0, |line| line.len());
+                let from = if line_idx == start.1 { start.0 } else { 0 };
+                let to = if line_idx == end.1 { end.0 } else { line_len };
+
+                Caret::new((to, line_idx), (from != to).then_some((from, line_idx)))
+            })
+            .collect();
+
+        if let Some(last_caret) = carets.pop() {
+            self.cursor.set_caret(last_caret);
+            self.cursor.secondary_carets.extend(carets);
+            self.normalize_carets();
+            self.needs_full_rerender = true;
+        }
+    }
+
+    /// Adds a caret at `x` (or the end of the line if shorter), making it the primary one
+    fn add_caret_on_line(&mut self, x: usize, line_idx: usize) {
+        let line_len = self
+            .document
+            .lines
+            .get(line_idx)
+            .map_or(0, |line| line.len());
+
+        let previous_primary = self.cursor.caret();
+        self.cursor.secondary_carets.push(previous_primary);
+
+        let mut caret = Caret::new((min(x, line_len), line_idx), None);
+        caret.ideal_x = x;
+        self.cursor.set_caret(caret);
+
+        self.normalize_carets();
+        self.needs_full_rerender = true;
+    }
+
+    /// Sorts the secondary carets and drops the ones overlapping other carets,
+    /// edits at overlapping carets would clash with each other
+    pub fn normalize_carets(&mut self) {
+        if !self.has_multiple_cursors() {
+            return;
+        }
+
+        let mut kept: Vec<(Caret, bool)> = vec![];
+        for (caret, is_primary) in self.get_sorted_carets() {
+            let overlaps_previous = kept.last().is_some_and(|(previous, _)| {
+                let (_, previous_end) = get_caret_range(previous);
+                let (start, _) = get_caret_range(&caret);
+
+                previous_end.1 > start.1 || (previous_end.1 == start.1 && previous_end.0 >= start.0)
+            });
+
+            if !overlaps_previous {
+                kept.push((caret, is_primary));
+            } else if is_primary {
+                // the primary caret always wins
+                kept.pop();
+                kept.push((caret, is_primary));
+            }
+        }
+
+        self.cursor.secondary_carets = kept
+            .into_iter()
+            .filter_map(|(caret, is_primary)| (!is_primary).then_some(caret))
+            .collect();
+    }
+}
+
+#[inline]
+fn get_caret_range(caret: &Caret) -> ((usize, usize), (usize, usize)) {
+    caret
+        .get_selected_range()
+        .unwrap_or((caret.pos(), caret.pos()))
+}
+
+/// Sort key of a caret, (line, column) of the start of its selection
+#[inline]
+fn get_caret_start(caret: &Caret) -> (usize, usize) {
+    let (start, _) = get_caret_range(caret);
+
+    (start.1, start.0)
+}
@@ -0,0 +1,239 @@
+use std::path::{Path, PathBuf};
+
+use iedit_document::Document;
+use regex_lite::{Captures, Regex};
+
+use crate::editor::{
+    Editor,
+    commands::{STDERR_TAG, STDOUT_TAG, send_simple_notification},
+    modes::EditorMode,
+};
+
+/// A location reported by a compiler, linter or runtime in the execution output
+pub struct ErrorLocation {
+    pub path: PathBuf,
+    /// 0-based line and column in the file
+    pub pos: (usize, usize),
+    pub message: String,
+    /// Line of the execution output the location was found on
+    pub output_line_idx: usize,
+}
+
+/// Regexes for the formats of error locations found in the output of common tools
+struct ErrorPatterns {
+    /// `  File "main.py", line 3, in <module>`
+    python_frame: Regex,
+    /// `    at main (/home/user/main.js:10:5)` or `    at /home/user/main.js:10:5`
+    node_frame: Regex,
+    /// rustc's ` --> src/main.rs:3:5`
+    rust_arrow: Regex,
+    /// `main.c:3:5: error: ...` (gcc, clang, eslint -f unix, rustc --error-format short, ...)
+    file_line_col: Regex,
+}
+
+impl ErrorPatterns {
+    fn new() -> Self {
+        Self {
+            python_frame: Regex::new(r#"^\s*File "(?P<path>[^"]+)", line (?P<line>\d+)"#).unwrap(),
+            node_frame: Regex::new(
+                r"^\s*at (?:.* \()?(?:file://)?(?P<path>[^\s()]+?):(?P<line>\d+):(?P<col>\d+)\)?$",
+            )
+            .unwrap(),
+            rust_arrow: Regex::new(r"^\s*--> (?P<path>[^:]+):(?P<line>\d+):(?P<col>\d+)$").unwrap(),
+            file_line_col: Regex::new(
+                r"^(?P<path>[^:\s][^:]*):(?P<line>\d+):(?:(?P<col>\d+):)?\s*(?P<message>.+)$",
+            )
+            .unwrap(),
+        }
+    }
+}
+
+/// Finds the error locations in the execution output, ignoring the ones
+/// pointing to files that don't exist (e.g. `<stdin>` or `node:internal/...`)
+pub fn parse_error_locations(output: &Document) -> Vec<ErrorLocation> {
+    let patterns = ErrorPatterns::new();
+    let mut error_locations: Vec<ErrorLocation> = vec![];
+
+    // tracebacks, rustc diagnostics and node stack traces print the message
+    // before or after the locations, on a line that isn't indented
+    let mut last_message = String::new();
+    let mut python_traceback_start: Option<usize> = None;
+
+    for (line_idx, line) in output.lines.iter().enumerate() {
+        let line = line.as_ref();
+        let line = line
+            .strip_prefix(STDOUT_TAG)
+            .or_else(|| line.strip_prefix(STDERR_TAG))
+            .unwrap_or(line);
+
+        let (captures, message) = if let Some(captures) = patterns.python_frame.captures(line) {
+            python_traceback_start.get_or_insert(error_locations.len());
+            (captures, None)
+        } else if let Some(captures) = patterns.node_frame.captures(line) {
+            (captures, Some(last_message.clone()))
+        } else if let Some(captures) = patterns.rust_arrow.captures(line) {
+            (captures, Some(last_message.clone()))
+        } else if let Some(captures) = patterns.file_line_col.captures(line) {
+            let message = captures["message"].to_owned();
+            (captures, Some(message))
+        } else {
+            if !line.is_empty() && !line.starts_with(char::is_whitespace) {
+                // the exception closing a python traceback
+                if let Some(traceback_start) = python_traceback_start.take() {
+                    for error_location in &mut error_locations[traceback_start..] {
+                        error_location.message = line.to_owned();
+                    }
+                }
+
+                last_message = line.to_owned();
+            }
+
+            continue;
+        };
+
+        if let Some(error_location) = get_error_location(&captures, message, line_idx) {
+            error_locations.push(error_location);
+        }
+    }
+
+    error_locations
+}
+
+fn get_error_location(
+    captures: &Captures,
+    message: Option<String>,
+    output_line_idx: usize,
+) -> Option<ErrorLocation> {
+    let path = Path::new(&captures["path"]);
+    if !path.is_file() {
+        return None;
+    }
+
+    let line = captures["line"].parse::<usize>().ok()?;
+    let col = captures
+        .name("col")
+        .and_then(|col| col.as_str().parse::<usize>().ok())
+        .unwrap_or(1);
+
+    Some(ErrorLocation {
+        path: path.to_owned(),
+        pos: (col.saturating_sub(1), line.saturating_sub(1)),
+        message: message.unwrap_or_default(),
+        output_line_idx,
+    })
+}
+
+impl Editor {
+    pub fn go_to_next_error(&mut self) {
+        match self.current_error_idx {
+            _ if self.error_locations.is_empty() => send_simple_notification("No errors"),
+            Some(error_idx) if error_idx + 1 >= self.error_locations.len() => {
+                send_simple_notification("Already at last error")
+            }
+            Some(error_idx) => self.go_to_error(error_idx + 1),
+            None => self.go_to_error(0),
+        }
+    }
+
+    pub fn go_to_previous_error(&mut self) {
+        match self.current_error_idx {
+            _ if self.error_locations.is_empty() => send_simple_notification("No errors"),
+            Some(0) => send_simple_notification("Already at first error"),
+            Some(error_idx) => self.go_to_error(error_idx - 1),
+            None => self.go_to_error(self.error_locations.len() - 1),
+        }
+    }
+
+    /// Jumps to the error reported on the current line of the execution output
+    pub fn go_to_error_at_cursor(&mut self) {
+        let error_idx = self
+            .error_locations
+            .iter()
+            .position(|error_location| error_location.output_line_idx == self.cursor.cur_y);
+
+        match error_idx {
+            Some(error_idx) => self.go_to_error(error_idx),
+            None => send_simple_notification("No error location on this line"),
+        }
+    }
+
+    /// Opens the file of the error in its buffer and moves the cursor to it
+    pub fn go_to_error(&mut self, error_idx: usize) {
+        let Some(error_location) = self.error_locations.get(error_idx) else {
+            return;
+        };
+
+        let path = error_location.path.to_string_lossy().into_owned();
+        let (x, y) = error_location.pos;
+        let output_line_idx = error_location.output_line_idx;
+        let notification = format!(
+            "({}/{}) {}",
+            error_idx + 1,
+            self.error_locations.len(),
+            error_location.message
+        );
+
+        self.current_error_idx = Some(error_idx);
+        // keeps the output buffer on the current error
+        if let Some(output_cursor) = self.get_execution_output_cursor_mut() {
+            output_cursor.update_pos((0, output_line_idx), false);
+        }
+
+        if !self.open_file(&path) {
+            return;
+        }
+
+        let y = y.min(self.document.n_lines().saturating_sub(1));
+        let x = self
+            .document
+            .lines
+            .get(y)
+            .map_or(0, |line| x.min(line.len()));
+
+        self.clear_secondary_carets();
+        self.cursor.selection_anchor = None;
+        self.cursor.update_pos((x, y), true);
+        self.needs_full_rerender = true;
+        send_simple_notification(notification);
+    }
+
+    pub fn open_error_list(&mut self) {
+        if self.error_locations.is_empty() {
+            send_simple_notification("No errors");
+            return;
+        }
+
+        self.mode = EditorMode::PickError {
+            selected_idx: self.current_error_idx.unwrap_or_default(),
+        };
+    }
+
+    /// Lines of the error list popup, restricted to the ones around the selected error
+    /// when they don't all fit
+    pub fn get_error_list_lines(&self, selected_idx: usize) -> Vec<String> {
+        let max_lines = (self.ui.editor_lines as usize).saturating_sub(2).max(1);
+        let max_width = (self.ui.term_width as usize).saturating_sub(2);
+        let first_line_idx = selected_idx
+            .saturating_sub(max_lines / 2)
+            .min(self.error_locations.len().saturating_sub(max_lines));
+
+        self.error_locations
+            .iter()
+            .enumerate()
+            .skip(first_line_idx)
+            .take(max_lines)
+            .map(|(error_idx, error_location)| {
+                let line = format!(
+                    "{} {}:{}:{} │ {}",
+                    if error_idx == selected_idx { '>' } else { ' ' },
+                    error_location.path.display(),
+                    error_location.pos.1 + 1,
+                    error_location.pos.0 + 1,
+                    error_location.message
+                );
+
+                line.chars().take(max_width).collect()
+            })
+            .collect()
+    }
+}
@@ -1,4 +1,4 @@
-use std::io::Write;
+use std::{cmp::min, io::Write};
 
 use crate::{
     Editor,
@@ -6,42 +6,24 @@ use crate::{
         highlight::RangeHighlight,
         renderer::{Renderer, line::LineRenderer},
     },
-    terminal::{EMPTY_CURSOR, V_BAR},
+    terminal::{CURSOR_DOWN1, CURSOR_TO_COL1, EMPTY_CURSOR, V_BAR, WRAP_INDICATOR},
 };
 
 impl Editor {
+    /// Renders the rows of a line, at most `max_rows` of them when it is wrapped
     pub fn render_line<'renderer, 'term, Term: Write>(
         &self,
         renderer: &'renderer mut Renderer<'term, Term>,
         line_idx: usize,
+        max_rows: usize,
     ) -> std::io::Result<()> {
         let line = &self.document.lines[line_idx];
+        let ui_width = self.get_text_width();
+        let row_starts = self.get_line_row_starts(line_idx);
 
-        let mut ui_width = self.ui.term_width as usize;
+        renderer.add(self.get_gutter(line_idx, false))?;
 
-        if self.config.show_line_numbers {
-            let line_number_color = if line_idx == self.cursor.cur_y {
-                termion::color::White.fg_str()
-            } else {
-                termion::color::LightBlack.fg_str()
-            };
-
-            let padding =
-                (self.get_line_number_gutter_width() - 1) - (line_idx + 1).ilog10() as usize;
-
-            renderer.add(format!(
-                "{}{}{}{} {}",
-                line_number_color,
-                " ".repeat(padding),
-                line_idx + 1,
-                termion::color::Reset.fg_str(),
-                V_BAR,
-            ))?;
-
-            ui_width = ui_width.saturating_sub(self.get_line_number_gutter_width() + 2);
-        }
-
-        let highlighted_range = self.cursor.get_selected_range();
+        let highlighted_range = self.get_highlighted_range();
 
         let mut line_renderer = LineRenderer::new(
             line,
@@ -49,10 +31,10 @@ impl Editor {
             self.viewport.left_col,
             ui_width,
             &mut renderer.term,
-            renderer.tab_size,
+            self.config.tab_size as usize,
         );
 
-        if !self.is_viewing_execution_output
+        if !self.is_viewing_execution_output()
             && self.config.enable_syntax_highlighting
             && let Some(syntax) = self.document.syntax.as_ref()
         {
@@ -69,15 +51,86 @@ impl Editor {
             line_renderer.add_range_highlight(highlight, true, termion::color::LightBlue.bg_str());
         };
 
+        for caret in self.cursor.secondary_carets.iter() {
+            if let Some(selected_range) = caret.get_selected_range() {
+                let highlight = RangeHighlight::new(line_idx, &selected_range);
+                line_renderer.add_range_highlight(
+                    highlight,
+                    true,
+                    termion::color::LightBlue.bg_str(),
+                );
+            }
+
+            if caret.cur_y == line_idx {
+                line_renderer.add_cursor(caret.cur_x);
+            }
+        }
+
         if self.config.render_trailing_whitespace {
             line_renderer.add_trailing_whitespace();
         }
 
-        line_renderer.render()?;
+        if !self.config.soft_wrap {
+            return line_renderer.render();
+        }
+
+        let n_rows = min(row_starts.len(), max_rows);
+        for (row_idx, row_start) in row_starts.iter().take(n_rows).enumerate() {
+            if row_idx > 0 {
+                write!(line_renderer.writer, "{}{}", CURSOR_DOWN1, CURSOR_TO_COL1)?;
+                write!(line_renderer.writer, "{}", self.get_gutter(line_idx, true))?;
+            }
+
+            let row_width = row_starts
+                .get(row_idx + 1)
+                .map_or(ui_width, |next_row_start| {
+                    line.char_to_visual_idx(*next_row_start, self.config.tab_size as usize)
+                        - line.char_to_visual_idx(*row_start, self.config.tab_size as usize)
+                });
+
+            line_renderer.render_row(*row_start, row_width, row_idx + 1 == row_starts.len())?;
+        }
 
         Ok(())
     }
 
+    /// Line number, or wrap indicator for the rows continuing a wrapped line,
+    /// empty when line numbers are hidden
+    fn get_gutter(&self, line_idx: usize, is_wrapped_row: bool) -> String {
+        if !self.config.show_line_numbers {
+            return String::new();
+        }
+
+        let gutter_width = self.get_line_number_gutter_width();
+        if is_wrapped_row {
+            return format!(
+                "{}{}{}{} {}",
+                termion::color::LightBlack.fg_str(),
+                " ".repeat(gutter_width - 1),
+                WRAP_INDICATOR,
+                termion::color::Reset.fg_str(),
+                V_BAR,
+            );
+        }
+
+        let line_number_color = if line_idx == self.cursor.cur_y {
+            termion::color::White.fg_str()
+        } else {
+            termion::color::LightBlack.fg_str()
+        };
+
+        let padding = (gutter_width - 1) - (line_idx + 1).ilog10() as usize;
+
+        format!(
+            "{}{}{}{} {}",
+            line_number_color,
+            " ".repeat(padding),
+            line_idx + 1,
+            termion::color::Reset.fg_str(),
+            V_BAR,
+        )
+    }
+
     pub fn render_empty_line<'renderer, 'term, Term: Write>(
         &self,
         renderer: &'renderer mut Renderer<'term, Term>,
@@ -102,24 +155,34 @@ impl Editor {
         &self,
         renderer: &'renderer mut Renderer<'term, Term>,
     ) -> std::io::Result<()> {
-        let row_span_low = self.viewport.top_line;
-        let row_span_high =
-            (self.viewport.top_line + self.ui.editor_lines as usize).min(self.document.n_lines());
-
-        for line_idx in row_span_low..row_span_high {
+        let n_rows = self.ui.editor_lines as usize;
+        let mut rendered_rows = 0;
+        let mut line_idx = self.viewport.top_line;
+
+        while rendered_rows < n_rows && line_idx < self.document.n_lines() {
+            let line_rows = min(
+                self.get_line_row_starts(line_idx).len(),
+                n_rows - rendered_rows,
+            );
             let should_render_line = self.needs_full_rerender
                 || line_idx == self.cursor.cur_y
                 || line_idx == self.cursor.past_y
                 || self.document.line_needs_render(line_idx);
 
             if should_render_line {
-                self.render_line(renderer, line_idx)?;
+                self.render_line(renderer, line_idx, line_rows)?;
+                renderer.next_line()?;
+            } else {
+                for _ in 0..line_rows {
+                    renderer.next_line()?;
+                }
             }
 
-            renderer.next_line()?;
+            rendered_rows += line_rows;
+            line_idx += 1;
         }
 
-        let empty_lines = self.ui.editor_lines as usize - (row_span_high - row_span_low);
+        let empty_lines = n_rows - rendered_rows;
         for empty_line_idx in 0..empty_lines {
             let with_cursor = empty_line_idx == 0 && self.cursor.cur_y >= self.document.lines.len();
             self.render_empty_line(renderer, with_cursor)?;
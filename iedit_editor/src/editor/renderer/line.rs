@@ -4,7 +4,7 @@ use crate::{
     editor::highlight::RangeHighlight,
     terminal::{self, EMPTY_CURSOR},
 };
-use iedit_document::{DocumentLine, DocumentSyntax, SyntaxBlock, SyntaxRule};
+use iedit_document::{DocumentLine, DocumentSyntax, SyntaxBlock, SyntaxRule, grapheme_width};
 use termion::color::{self};
 
 pub struct ColorRange<'renderer> {
@@ -53,10 +53,12 @@ impl<'line, 'writer, Writer: Write> LineRenderer<'line, 'writer, Writer> {
         }
     }
 
-    fn render_line_char(
+    /// Renders the grapheme cluster made of the chars from `char_idx` to `last_char_idx`
+    fn render_line_grapheme(
         &mut self,
-        ch: char,
+        grapheme: &str,
         char_idx: usize,
+        last_char_idx: usize,
         visual_idx: usize,
     ) -> std::io::Result<()> {
         self.color_ranges
@@ -67,14 +69,14 @@ impl<'line, 'writer, Writer: Write> LineRenderer<'line, 'writer, Writer> {
                 }
 
                 if char_idx == self.char_offset {
-                    range.start <= char_idx
+                    range.start <= last_char_idx && char_idx <= range.end
                 } else {
-                    range.start == char_idx
+                    char_idx <= range.start && range.start <= last_char_idx
                 }
             })
             .try_for_each(|range| write!(self.writer, "{}", range.color_str))?;
 
-        if ch == '\t' {
+        if grapheme == "\t" {
             let n_spaces = self.tab_size - (visual_idx % self.tab_size);
             let tab_string = if char_idx < self.trailing_whitespace_offset {
                 " ".repeat(n_spaces)
@@ -83,14 +85,25 @@ impl<'line, 'writer, Writer: Write> LineRenderer<'line, 'writer, Writer> {
             };
             write!(self.writer, "{}", &tab_string)?;
         } else if char_idx < self.trailing_whitespace_offset {
-            write!(self.writer, "{}", ch)?;
+            write!(self.writer, "{}", grapheme)?;
         } else {
             write!(self.writer, "•")?;
         }
 
+        self.reset_ending_color_ranges(char_idx, last_char_idx)
+    }
+
+    /// Resets the colors of the ranges ending between `char_idx` and `last_char_idx`
+    fn reset_ending_color_ranges(
+        &mut self,
+        char_idx: usize,
+        last_char_idx: usize,
+    ) -> std::io::Result<()> {
         self.color_ranges
             .iter()
-            .filter(|range| range.end == char_idx && range.end >= range.start)
+            .filter(|range| {
+                char_idx <= range.end && range.end <= last_char_idx && range.end >= range.start
+            })
             .try_for_each(|range| {
                 write!(
                     self.writer,
@@ -101,9 +114,7 @@ impl<'line, 'writer, Writer: Write> LineRenderer<'line, 'writer, Writer> {
                         color::Reset.fg_str()
                     }
                 )
-            })?;
-
-        Ok(())
+            })
     }
 
     pub fn add_trailing_whitespace(&mut self) {
@@ -241,29 +252,28 @@ impl<'line, 'writer, Writer: Write> LineRenderer<'line, 'writer, Writer> {
 
             let offset_byte_idx = offset_byte_idx.unwrap();
             for rule in syntax.rules.iter() {
-                if let SyntaxRule::Inline { pattern, color } = rule {
-                    if let Some(rx_match) =
+                if let SyntaxRule::Inline { pattern, color } = rule
+                    && let Some(rx_match) =
                         pattern.find_anchored_at(self.line.as_ref(), offset_byte_idx)
-                    {
-                        let start_char = self
-                            .line
-                            .byte_to_char_idx(rx_match.start().saturating_sub(1))
-                            .unwrap_or_default();
-                        let end_char = self
-                            .line
-                            .byte_to_char_idx(rx_match.end().saturating_sub(1))
-                            .unwrap_or(self.line.len());
+                {
+                    let start_char = self
+                        .line
+                        .byte_to_char_idx(rx_match.start().saturating_sub(1))
+                        .unwrap_or_default();
+                    let end_char = self
+                        .line
+                        .byte_to_char_idx(rx_match.end().saturating_sub(1))
+                        .unwrap_or(self.line.len());
 
-                        self.color_ranges.push(ColorRange {
-                            start: start_char,
-                            end: end_char,
-                            is_bg: false,
-                            color_str: &color,
-                        });
+                    self.color_ranges.push(ColorRange {
+                        start: start_char,
+                        end: end_char,
+                        is_bg: false,
+                        color_str: color,
+                    });
 
-                        offset = end_char + 1;
-                        continue 'outer;
-                    }
+                    offset = end_char + 1;
+                    continue 'outer;
                 }
             }
 
@@ -291,22 +301,43 @@ impl<'line, 'writer, Writer: Write> LineRenderer<'line, 'writer, Writer> {
         });
     }
 
+    /// Renders one of the screen rows of a wrapped line, starting at the char at `row_start`
+    pub fn render_row(
+        &mut self,
+        row_start: usize,
+        row_width: usize,
+        is_last_row: bool,
+    ) -> std::io::Result<()> {
+        let cursor_at_end = self.cursor_at_end;
+        self.char_offset = row_start;
+        self.visual_offset = self.line.char_to_visual_idx(row_start, self.tab_size);
+        self.ui_width = row_width;
+        self.cursor_at_end &= is_last_row;
+        self.render()?;
+        self.cursor_at_end = cursor_at_end;
+
+        Ok(())
+    }
+
     pub fn render(&mut self) -> std::io::Result<()> {
         self.color_ranges
             .iter()
-            .filter(|range| range.start <= range.end && range.start <= self.char_offset)
+            .filter(|range| {
+                range.start <= range.end
+                    && range.start <= self.char_offset
+                    && range.end >= self.char_offset
+            })
             .try_for_each(|range| write!(self.writer, "{}", range.color_str))?;
 
-        if self.visual_offset % self.tab_size != 0 && self.line.at(self.char_offset) == Some('\t') {
-            let hidden_length = self.visual_offset % self.tab_size;
-            let partial_tab_length = self.tab_size - hidden_length;
-            write!(self.writer, "{}", " ".repeat(partial_tab_length))?;
-        }
-
-        for (char_idx, ch) in self.line.iter().enumerate() {
-            let visual_idx = self.line.char_to_visual_idx(char_idx, self.tab_size);
+        let mut visual_idx = 0;
+        for (char_idx, grapheme) in self.line.graphemes() {
+            let width = grapheme_width(grapheme, visual_idx, self.tab_size);
+            let last_char_idx = char_idx + grapheme.chars().count() - 1;
 
-            if visual_idx >= self.ui_width + self.visual_offset {
+            // wide characters that don't fit are left out, tabs are cut by the terminal
+            if visual_idx >= self.ui_width + self.visual_offset
+                || (grapheme != "\t" && visual_idx + width > self.ui_width + self.visual_offset)
+            {
                 break;
             }
 
@@ -315,8 +346,15 @@ impl<'line, 'writer, Writer: Write> LineRenderer<'line, 'writer, Writer> {
             }
 
             if visual_idx >= self.visual_offset {
-                self.render_line_char(ch, char_idx, visual_idx)?;
+                self.render_line_grapheme(grapheme, char_idx, last_char_idx, visual_idx)?;
+            } else if visual_idx + width > self.visual_offset {
+                // the viewport starts in the middle of a tab or of a wide character
+                let partial_length = visual_idx + width - self.visual_offset;
+                write!(self.writer, "{}", " ".repeat(partial_length))?;
+                self.reset_ending_color_ranges(char_idx, last_char_idx)?;
             }
+
+            visual_idx += width;
         }
 
         if self.cursor_at_end {
@@ -9,6 +9,7 @@ use termion::cursor::Goto;
 
 use crate::{
     Editor,
+    editor::modes::EditorMode,
     terminal::{
         self, CLEAR_BELOW_CURSOR, CLEAR_LINE, CURSOR_DOWN1, CURSOR_TO_COL1, H_BAR, UILayout,
     },
@@ -18,23 +19,17 @@ pub struct Renderer<'editor, Term: Write> {
     term: BufWriter<&'editor mut Term>,
     ui: UILayout,
     horizontal_bar: String,
-    tab_size: usize,
     is_first_render: bool,
 }
 
 impl<'term, Term: Write> Renderer<'term, Term> {
-    pub fn new(
-        term: &'term mut Term,
-        ui: UILayout,
-        tab_size: usize,
-    ) -> Self {
+    pub fn new(term: &'term mut Term, ui: UILayout) -> Self {
         let horizontal_bar = str::repeat(H_BAR, ui.term_width as usize);
 
         Self {
             term: BufWriter::with_capacity(64 * 1024, term),
             ui,
             horizontal_bar,
-            tab_size,
             is_first_render: true,
         }
     }
@@ -86,25 +81,40 @@ impl<'term, Term: Write> Renderer<'term, Term> {
         Ok(())
     }
 
-    pub fn position_cursor<'editor>(&mut self, editor: &'editor Editor) -> std::io::Result<()> {
-        let cursor_rel_y =
-            (editor.cursor.cur_y - editor.viewport.top_line) as u16 + self.ui.ui_origin.1;
-
-        let tab_size = editor.config.tab_size as usize;
-        let cursor_visual_x: usize = editor
-            .document
-            .lines
-            .get(editor.cursor.cur_y)
-            .map(|line| line.char_to_visual_idx(editor.cursor.cur_x, tab_size))
-            .unwrap_or_default();
+    pub fn position_cursor(&mut self, editor: &Editor) -> std::io::Result<()> {
+        if !editor.viewport_contains_y(editor.cursor.cur_y) {
+            return Ok(());
+        }
+
+        let (cursor_row, cursor_visual_x) = if editor.config.soft_wrap {
+            let (row, col) = editor.get_wrapped_cursor_pos();
+            let rows_above = (editor.viewport.top_line..editor.cursor.cur_y)
+                .map(|line_idx| editor.get_line_row_starts(line_idx).len())
+                .sum::<usize>();
+
+            (rows_above + row, col)
+        } else {
+            let tab_size = editor.config.tab_size as usize;
+            let cursor_visual_x: usize = editor
+                .document
+                .lines
+                .get(editor.cursor.cur_y)
+                .map(|line| line.char_to_visual_idx(editor.cursor.cur_x, tab_size))
+                .unwrap_or_default();
+
+            (
+                editor.cursor.cur_y - editor.viewport.top_line,
+                cursor_visual_x,
+            )
+        };
+
+        let cursor_rel_y = cursor_row as u16 + self.ui.ui_origin.1;
         let cursor_rel_x = (cursor_visual_x.saturating_sub(editor.viewport.left_col)) as u16
             + self.ui.ui_origin.0
             + (editor.get_line_number_gutter_width() + 2) as u16
                 * editor.config.show_line_numbers as u16;
 
-        if editor.cursor.cur_y >= editor.viewport.top_line
-            && editor.cursor.cur_y < editor.viewport.top_line + self.ui.editor_lines as usize
-        {
+        if cursor_row < self.ui.editor_lines as usize {
             self.add(termion::cursor::Goto(cursor_rel_x, cursor_rel_y).to_string())?;
             self.add(termion::cursor::Show)?;
         }
@@ -122,8 +132,18 @@ impl<'term, Term: Write> Renderer<'term, Term> {
         editor.render_edit_buffer(self)?;
         editor.render_status(self)?;
 
-        if let Some(popup_lines) = editor.displayed_popup {
+        if let Some(popup_lines) = &editor.displayed_popup {
             self.render_popup(popup_lines)?;
+        } else if let EditorMode::PickBuffer { selected_idx } = editor.mode {
+            self.render_popup(&editor.get_buffer_picker_lines(selected_idx))?;
+        } else if let EditorMode::PickError { selected_idx } = editor.mode {
+            self.render_popup(&editor.get_error_list_lines(selected_idx))?;
+        } else if let EditorMode::UndoTree {
+            original_state_id: _,
+            selected_idx,
+        } = editor.mode
+        {
+            self.render_popup(&editor.get_undo_tree_lines(selected_idx))?;
         }
 
         self.position_cursor(editor)?;
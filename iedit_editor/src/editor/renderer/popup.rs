@@ -10,11 +10,11 @@ use crate::editor::renderer::Renderer;
 
 impl<'editor, Term: Write> Renderer<'editor, Term> {
     /// this will wrap the provided lines in a frame and render it in the top-right corner of the ui
-    pub fn render_popup(&mut self, lines: &'editor [&'editor str]) -> io::Result<()> {
+    pub fn render_popup(&mut self, lines: &[impl AsRef<str>]) -> io::Result<()> {
         let popup_height = min(self.ui.editor_lines as usize, lines.len() + 2) as u16;
         let popup_width = lines
             .iter()
-            .map(|line| line.n_chars())
+            .map(|line| line.as_ref().n_chars())
             .max()
             .unwrap_or_default()
             + 2;
@@ -26,8 +26,9 @@ impl<'editor, Term: Write> Renderer<'editor, Term> {
         for line_idx in 1..popup_height - 1 {
             self.add(cursor::Goto(popup_origin_x, line_idx + self.ui.ui_origin.1).to_string())?;
             self.add("│")?;
-            self.add(lines[line_idx as usize - 1])?;
-            self.add(" ".repeat(popup_width - lines[line_idx as usize - 1].n_chars() - 2))?;
+            let line = lines[line_idx as usize - 1].as_ref();
+            self.add(line)?;
+            self.add(" ".repeat(popup_width - line.n_chars() - 2))?;
             self.add("│")?;
         }
 
@@ -1,12 +1,14 @@
 use std::{cmp::max, io::Write};
 
+use iedit_document::Encoding;
+
 use crate::{
     Editor,
     editor::{
+        io::get_line_ending_name,
         modes::EditorMode,
         renderer::{Renderer, line::LineRenderer},
         search::SearchItem,
-        status::KEYBINDINGS,
     },
     terminal::CLEAR_TO_END_OF_LINE,
 };
@@ -37,19 +39,68 @@ impl Editor {
             return self.render_notification(renderer);
         }
 
-        if matches!(self.mode, EditorMode::Insert) {
+        if matches!(
+            self.mode,
+            EditorMode::Insert
+                | EditorMode::Normal
+                | EditorMode::Visual
+                | EditorMode::VisualLine
+                | EditorMode::View
+        ) {
             let mut left_status_len = 0;
             let document_lines = max(self.document.n_lines(), 1);
+            if self.config.vi_mode || self.is_view_mode {
+                let mode_chunk = format!("{} {} ", self.get_vi_mode_label(), self.vi.pending_keys);
+                left_status_len += mode_chunk.chars().count();
+                renderer.add(mode_chunk)?;
+            }
+
             for flag_str in self.get_flag_strings() {
                 left_status_len += flag_str.len() - 13;
                 renderer.add(flag_str)?;
             }
 
+            if let Some(elapsed) = self.get_execution_elapsed() {
+                let elapsed_chunk = format!(" {}", elapsed);
+                left_status_len += elapsed_chunk.len();
+                renderer.add(elapsed_chunk)?;
+            }
+
+            let n_cursors_chunk = if self.has_multiple_cursors() {
+                format!(" [{} cursors]", self.cursor.secondary_carets.len() + 1)
+            } else {
+                String::new()
+            };
+
+            // plain UTF-8 with LF line endings is the norm, only the rest is worth pointing out
+            let mut file_format = vec![];
+            if self.document.is_large_file() {
+                file_format.push("large file".to_owned());
+            }
+
+            if self.document.encoding != Encoding::Utf8 || self.document.has_bom {
+                file_format.push(self.get_encoding_label());
+            }
+
+            if self.document.has_mixed_line_endings {
+                file_format.push("mixed eol".to_owned());
+            } else if self.document.end_of_line_seq != "\n" {
+                file_format.push(get_line_ending_name(&self.document.end_of_line_seq).to_owned());
+            }
+
+            let encoding_chunk = if file_format.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", file_format.join(" "))
+            };
+
             let cursor_pos_chunk = format!(
-                "   {}:{} ({}%) ── {}",
+                "   {}:{}{} ({}%){} ── {}",
                 self.cursor.cur_y + 1,
                 self.cursor.cur_x + 1,
+                n_cursors_chunk,
                 (100 * self.cursor.cur_y / document_lines).min(100),
+                encoding_chunk,
                 self.document
                     .syntax
                     .as_ref()
@@ -61,11 +112,12 @@ impl Editor {
             renderer.add(cursor_pos_chunk)?;
 
             if self.config.show_keybindings {
+                let keybindings = self.keymap.get_status_bar_hint(self.get_keymap_mode());
                 let padding = (self.ui.term_width as usize)
                     .saturating_sub(left_status_len)
-                    .saturating_sub(KEYBINDINGS.len());
+                    .saturating_sub(keybindings.len());
                 renderer.add(" ".repeat(padding))?;
-                renderer.add(&KEYBINDINGS)?;
+                renderer.add(keybindings)?;
             }
 
             renderer.add(CLEAR_TO_END_OF_LINE)?;
@@ -73,8 +125,55 @@ impl Editor {
             return Ok(());
         }
 
+        let mode_hint = match self.mode {
+            EditorMode::Replace => {
+                let replacement = self
+                    .replace_item
+                    .as_ref()
+                    .and_then(|item| item.current_match.as_ref())
+                    .map(|(_, _, replacement)| replacement.as_str())
+                    .unwrap_or_default();
+
+                Some(format!(
+                    "REPLACE with {:?}? (y)es (n)o (a)ll (q)uit",
+                    replacement
+                ))
+            }
+            EditorMode::PickBuffer { selected_idx: _ } => {
+                Some("BUFFERS ↑/↓: select, 1-9/Enter: open, Esc: cancel".to_owned())
+            }
+            EditorMode::PickError { selected_idx: _ } => {
+                Some("ERRORS ↑/↓: select, Enter: go to, Esc: cancel".to_owned())
+            }
+            EditorMode::UndoTree {
+                original_state_id: _,
+                selected_idx: _,
+            } => Some("UNDO TREE ↑/↓: travel, Enter: keep, Esc: cancel".to_owned()),
+            _ => None,
+        };
+
+        if let Some(mode_hint) = mode_hint {
+            renderer.add(mode_hint)?;
+            renderer.add(CLEAR_TO_END_OF_LINE)?;
+
+            return Ok(());
+        }
+
         let content = match self.mode {
-            EditorMode::Insert => unreachable!(),
+            EditorMode::Insert
+            | EditorMode::Normal
+            | EditorMode::Visual
+            | EditorMode::VisualLine
+            | EditorMode::View
+            | EditorMode::Replace
+            | EditorMode::PickBuffer { selected_idx: _ }
+            | EditorMode::PickError { selected_idx: _ }
+            | EditorMode::UndoTree {
+                original_state_id: _,
+                selected_idx: _,
+            } => {
+                unreachable!()
+            }
             EditorMode::Prompt(prompt) => {
                 renderer.add(prompt)?;
                 &self.status_bar.prompt_line
@@ -112,7 +211,7 @@ impl Editor {
             0,
             self.ui.term_width as usize,
             &mut renderer.term,
-            renderer.tab_size,
+            self.config.tab_size as usize,
         );
 
         if !matches!(self.mode, EditorMode::Insert) {
@@ -1,6 +1,23 @@
+use iedit_document::RegexReplacement;
 use regex_lite::Regex;
 
 pub enum SearchItem {
     Regex(Regex),
     PromptString,
 }
+
+/// State of an ongoing search and replace
+pub struct ReplaceItem {
+    pub regex: Regex,
+    /// Replacement string, may reference capture groups as `$1` or `${name}`
+    pub replacement: String,
+    /// Replace every match in a line rather than just the first one
+    pub is_global: bool,
+    /// Position the next match is searched from (inclusive)
+    pub next_pos: (usize, usize),
+    /// Matches starting at or past this position are not replaced
+    pub scope_end: (usize, usize),
+    /// Match currently waiting for confirmation, with its interpolated replacement
+    pub current_match: Option<RegexReplacement>,
+    pub n_replaced: usize,
+}
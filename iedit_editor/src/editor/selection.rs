@@ -0,0 +1,172 @@
+use iedit_document::{Document, TextObject, TextRange};
+
+use crate::editor::{Editor, commands::send_simple_notification, cursor::Caret, modes::EditorMode};
+
+/// The carets before and after an expansion of their selections, shrinking it goes back
+/// to the former as long as they are still the latter
+pub struct SelectionExpansion {
+    carets_before: Vec<Caret>,
+    carets_after: Vec<Caret>,
+}
+
+/// Text object given by the key typed after `i` or `a`, as in vi
+pub fn parse_text_object(key: char) -> Option<TextObject> {
+    match key {
+        'w' | 'W' => Some(TextObject::Word),
+        '(' | ')' | 'b' => Some(TextObject::Brackets('(', ')')),
+        '[' | ']' => Some(TextObject::Brackets('[', ']')),
+        '{' | '}' | 'B' => Some(TextObject::Brackets('{', '}')),
+        '"' | '\'' | '`' => Some(TextObject::Quotes(key)),
+        'p' => Some(TextObject::Paragraph),
+        _ => None,
+    }
+}
+
+impl Editor {
+    /// Grows the selection of every caret to the smallest word, quoted string, brackets,
+    /// line or paragraph around it, up to the whole document
+    pub fn expand_selection(&mut self) {
+        let carets_before = self.get_carets();
+        if self
+            .selection_expansions
+            .last()
+            .is_some_and(|expansion| expansion.carets_after != carets_before)
+        {
+            self.selection_expansions.clear();
+        }
+
+        if self.select_ranges(|document, range| document.get_expanded_range(range)) {
+            self.selection_expansions.push(SelectionExpansion {
+                carets_before,
+                carets_after: self.get_carets(),
+            });
+        }
+    }
+
+    /// Goes back to the selections preceding the last expansion
+    pub fn shrink_selection(&mut self) {
+        match self.selection_expansions.pop() {
+            Some(expansion) if expansion.carets_after == self.get_carets() => {
+                self.set_carets(expansion.carets_before);
+                if self.is_in_vi_mode() {
+                    self.mode = if self.cursor.selection_anchor.is_some() {
+                        EditorMode::Visual
+                    } else {
+                        EditorMode::Normal
+                    };
+                }
+
+                self.needs_full_rerender = true;
+            }
+            _ => {
+                self.selection_expansions.clear();
+                send_simple_notification("Selection wasn't expanded");
+            }
+        }
+    }
+
+    /// Selects the `object` around every caret, along with its delimiters when `is_around`
+    pub fn select_text_object(&mut self, object: TextObject, is_around: bool) {
+        if !self.select_ranges(|document, range| {
+            document.get_text_object_range(object, range, is_around)
+        }) {
+            send_simple_notification("Nothing to select");
+        }
+    }
+
+    /// Selects the range `get_range` returns for the one selected by each caret,
+    /// returns whether any selection changed.
+    /// NOTE: in vi modes, only the primary caret is kept and visual mode is entered
+    fn select_ranges(
+        &mut self,
+        get_range: impl Fn(&Document, TextRange) -> Option<TextRange>,
+    ) -> bool {
+        let is_in_vi_mode = self.is_in_vi_mode();
+        if is_in_vi_mode {
+            self.clear_secondary_carets();
+        }
+
+        let mut carets = self.get_carets();
+        let mut has_changed = false;
+        for caret in carets.iter_mut() {
+            let range = if is_in_vi_mode {
+                self.get_highlighted_range()
+            } else {
+                caret.get_selected_range()
+            };
+            let range = range.unwrap_or((caret.pos(), caret.pos()));
+
+            let Some((start, mut end)) = get_range(&self.document, range) else {
+                continue;
+            };
+
+            if (start, end) == range {
+                continue;
+            }
+
+            // visual mode selections include the character under the cursor
+            if is_in_vi_mode {
+                end = self.get_last_selected_pos(start, end);
+            }
+
+            *caret = Caret::new(end, Some(start));
+            has_changed = true;
+        }
+
+        if !has_changed {
+            return false;
+        }
+
+        self.set_carets(carets);
+        if is_in_vi_mode {
+            self.mode = EditorMode::Visual;
+        }
+
+        self.normalize_carets();
+        self.needs_full_rerender = true;
+
+        true
+    }
+
+    #[inline]
+    pub fn is_in_vi_mode(&self) -> bool {
+        matches!(
+            self.mode,
+            EditorMode::Normal | EditorMode::Visual | EditorMode::VisualLine
+        )
+    }
+
+    /// Position of the last character of the range from `start` to `end`
+    fn get_last_selected_pos(&self, start: (usize, usize), end: (usize, usize)) -> (usize, usize) {
+        match self.document.lines.get(end.1) {
+            Some(line) if end.0 > 0 => (line.previous_grapheme_idx(end.0), end.1),
+            _ if end.1 > start.1 => {
+                let line_len = self
+                    .document
+                    .lines
+                    .get(end.1 - 1)
+                    .map_or(0, |line| line.len());
+                (line_len, end.1 - 1)
+            }
+            _ => end,
+        }
+    }
+
+    /// The primary caret followed by the secondary ones
+    fn get_carets(&self) -> Vec<Caret> {
+        let mut carets = vec![self.cursor.caret()];
+        carets.extend_from_slice(&self.cursor.secondary_carets);
+
+        carets
+    }
+
+    fn set_carets(&mut self, mut carets: Vec<Caret>) {
+        if carets.is_empty() {
+            return;
+        }
+
+        let primary_caret = carets.remove(0);
+        self.cursor.set_caret(primary_caret);
+        self.cursor.secondary_carets = carets;
+    }
+}
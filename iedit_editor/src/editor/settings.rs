@@ -0,0 +1,132 @@
+use crate::{
+    Editor,
+    config::EditorConfig,
+    editor::{
+        clipboard::get_clipboard,
+        commands::{CommandExecutionResult, send_simple_notification},
+        keymap::Keymap,
+    },
+};
+
+impl Editor {
+    /// Handles the `set` prompt command: `set key=value`, `set key?` (or `set key`)
+    /// to display a value, `set key!` to toggle a boolean
+    pub fn execute_set_command(&mut self, arg: &str) -> CommandExecutionResult {
+        let arg = arg.trim();
+
+        let (key, change_res) = if let Some((key, value)) = arg.split_once('=') {
+            let key = key.trim();
+            (key, Some(self.set_config_field(key, value.trim())))
+        } else if let Some(key) = arg.strip_suffix('!') {
+            let key = key.trim();
+            (key, Some(self.config.toggle_field(key)))
+        } else {
+            (arg.trim_end_matches('?').trim(), None)
+        };
+
+        match change_res {
+            Some(Ok(())) => self.apply_config_change(key),
+            Some(Err(err)) => {
+                send_simple_notification(err);
+                return CommandExecutionResult::Continue;
+            }
+            None => {}
+        }
+
+        match self.config.get_field(key) {
+            Some(value) => {
+                // the layout is only computed on startup
+                if matches!(key, "fullscreen" | "min_lines") {
+                    send_simple_notification(format!("{} = {} (after restart)", key, value));
+                } else {
+                    send_simple_notification(format!("{} = {}", key, value));
+                }
+            }
+            None if key.is_empty() => send_simple_notification("Usage: set <option>[=value|?|!]"),
+            None => send_simple_notification(format!("Unknown option: {}", key)),
+        }
+
+        CommandExecutionResult::Continue
+    }
+
+    /// Completes the option name of a `set` command in the prompt, listing the candidates
+    /// when there are several
+    pub fn complete_set_command(&mut self) {
+        let prompt = self.status_bar.prompt_line.as_ref();
+        let Some(partial_key) = prompt.strip_prefix("set ").map(str::trim_start) else {
+            return;
+        };
+
+        if self.status_bar.cursor_pos != self.status_bar.prompt_line.len()
+            || partial_key.contains(['=', '?', '!', ' '])
+        {
+            return;
+        }
+
+        let candidates = EditorConfig::field_names()
+            .iter()
+            .filter(|name| name.starts_with(partial_key))
+            .collect::<Vec<_>>();
+
+        let Some(first_candidate) = candidates.first() else {
+            return;
+        };
+
+        let common_prefix_len = candidates.iter().fold(first_candidate.len(), |len, name| {
+            first_candidate
+                .bytes()
+                .zip(name.bytes())
+                .take(len)
+                .take_while(|(a, b)| a == b)
+                .count()
+        });
+
+        let completion = first_candidate[partial_key.len()..common_prefix_len].to_owned();
+        self.status_bar.prompt_line.push_str(&completion);
+        self.status_bar.cursor_pos = self.status_bar.prompt_line.len();
+
+        if candidates.len() > 1 {
+            send_simple_notification(
+                candidates
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            );
+        }
+    }
+
+    fn set_config_field(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let old_value = self.config.get_field(key);
+        self.config.set_field(key, value)?;
+
+        // rendering tabs needs a positive tab size
+        if self.config.tab_size == 0 {
+            if let Some(old_value) = old_value {
+                let _ = self.config.set_field(key, &old_value);
+            }
+
+            return Err("tab_size must be at least 1".to_owned());
+        }
+
+        Ok(())
+    }
+
+    /// Makes a changed option take effect right away, where it isn't read on each use
+    fn apply_config_change(&mut self, key: &str) {
+        match key {
+            "bind" => self.keymap = Keymap::new(self.config.bind.clone()),
+            "use_system_clipboard" => {
+                self.clipboard = get_clipboard(self.config.use_system_clipboard);
+            }
+            "vi_mode" => {
+                self.commit_vi_insert();
+                self.vi.pending_keys.clear();
+                self.mode = self.get_default_mode();
+            }
+            _ => {}
+        }
+
+        self.needs_full_rerender = true;
+    }
+}
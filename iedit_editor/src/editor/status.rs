@@ -1,11 +1,12 @@
 use iedit_document::DocumentLine;
 
-use crate::{Editor, editor::commands::CommandExecutionResult};
+use crate::{
+    Editor,
+    editor::{commands::CommandExecutionResult, modes::EditorMode},
+};
 
 type SubmitAction = Box<dyn FnOnce(&mut Editor, DocumentLine) -> CommandExecutionResult>;
 
-pub static KEYBINDINGS: &str = "│ Ctrl-q: quit │ Ctrl-s: save │ Ctrl-t: help";
-
 #[derive(Default)]
 pub struct StatusBar {
     pub prompt_line: DocumentLine,
@@ -22,18 +23,20 @@ impl StatusBar {
     }
 }
 
-pub static FLAGS: [&str; 4] = [
+pub static FLAGS: [&str; 5] = [
     "\x1b[30;103m modified \x1b[0m",
     "\x1b[30;104m sel. lock \x1b[0m",
     "\x1b[30;101m running cmd \x1b[0m",
     "\x1b[30;102m cmd output \x1b[0m",
+    "\x1b[30;106m following \x1b[0m",
 ];
 
-pub static FLAGS_SMALL: [&str; 4] = [
+pub static FLAGS_SMALL: [&str; 5] = [
     "\x1b[30;103m * \x1b[0m",
     "\x1b[30;104m sel \x1b[0m",
     "\x1b[30;101m cmd \x1b[0m",
     "\x1b[30;102m out \x1b[0m",
+    "\x1b[30;106m fol \x1b[0m",
 ];
 
 impl Editor {
@@ -43,12 +46,23 @@ impl Editor {
         [
             self.document.has_been_modified(),
             self.is_selection_locked,
-            self.is_running_external_command,
-            self.is_viewing_execution_output,
+            self.running_execution.is_some(),
+            self.is_viewing_execution_output(),
+            self.buffers[self.active_buffer_idx].is_followed,
         ]
         .into_iter()
         .enumerate()
         .filter(|(_, flag)| *flag)
         .map(move |(idx, _)| if small { FLAGS_SMALL[idx] } else { FLAGS[idx] })
     }
+    /// Name of the mode shown at the start of the status bar
+    pub fn get_vi_mode_label(&self) -> &'static str {
+        match self.mode {
+            EditorMode::Normal => "NORMAL",
+            EditorMode::Visual => "VISUAL",
+            EditorMode::VisualLine => "V-LINE",
+            EditorMode::View => "VIEW",
+            _ => "INSERT",
+        }
+    }
 }
@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+use iedit_document::DocumentLine;
+
+use crate::editor::{
+    Editor,
+    commands::{CommandExecutionResult, send_simple_notification},
+    modes::EditorMode,
+};
+
+/// Unsaved edits are written to the swap file at least this often while typing
+const SWAP_WRITE_INTERVAL: Duration = Duration::from_secs(4);
+
+impl Editor {
+    /// Journals the unsaved edits of the active document to its swap file,
+    /// when the user stopped typing (`is_idle`) or hasn't been for a while
+    pub fn write_swap_file(&mut self, is_idle: bool) {
+        if !self.document.has_unwritten_swap_edits()
+            || !(is_idle || self.last_swap_write_time.elapsed() >= SWAP_WRITE_INTERVAL)
+        {
+            return;
+        }
+
+        self.last_swap_write_time = Instant::now();
+        if let Err(err) = self.document.write_swap_file() {
+            send_simple_notification(format!("Could not write swap file: {}", err));
+        }
+    }
+
+    /// Deletes the swap files of every buffer, stale ones excepted
+    /// since their edits haven't been recovered yet
+    pub fn clear_swap_files(&mut self) {
+        let documents = std::iter::once(&mut self.document)
+            .chain(self.buffers.iter_mut().map(|buffer| &mut buffer.document));
+
+        for document in documents {
+            if !document.has_stale_swap_file() {
+                let _ = document.clear_swap_file();
+            }
+        }
+    }
+
+    /// Asks what to do with the swap file left behind by a crashed session, if any
+    pub fn check_swap_file(&mut self) {
+        if self.document.has_stale_swap_file() {
+            self.prompt_user(
+                "Unsaved changes from a crashed session: (r)ecover, (d)iscard, (c)ompare: ",
+                Editor::handle_swap_file_answer,
+            );
+        }
+    }
+
+    /// Warns that the active document is being edited by another running instance too
+    pub fn check_swap_file_owner(&self) {
+        // the notification would hide the prompt
+        if let Some(pid) = self.document.get_swap_file_owner_pid()
+            && !matches!(self.mode, EditorMode::Prompt(_))
+        {
+            send_simple_notification(format!("File is open in another iedit, pid {}", pid));
+        }
+    }
+
+    fn handle_swap_file_answer(&mut self, answer: DocumentLine) -> CommandExecutionResult {
+        match answer.as_ref().trim() {
+            "r" | "recover" => match self.document.recover_from_swap_file() {
+                Ok(n_recovered) => {
+                    self.needs_full_rerender = true;
+                    send_simple_notification(format!("Recovered {} unsaved edits", n_recovered));
+                }
+                Err(err) => send_simple_notification(format!("Could not recover: {}", err)),
+            },
+            "d" | "discard" => {
+                if let Err(err) = self.document.clear_swap_file() {
+                    send_simple_notification(format!("Could not delete swap file: {}", err));
+                }
+            }
+            "c" | "compare" => match self.document.get_swap_file_recovery() {
+                // switching back to the document asks again
+                Ok(recovered) => {
+                    let buffer_idx = self.add_buffer(recovered);
+                    self.switch_to_buffer(buffer_idx);
+                }
+                Err(err) => send_simple_notification(format!("Could not recover: {}", err)),
+            },
+            _ => self.check_swap_file(),
+        }
+
+        CommandExecutionResult::Continue
+    }
+}
@@ -0,0 +1,982 @@
+use std::iter::Peekable;
+
+use iedit_document::{EditOperation, InverseStack, Text, TextObject};
+
+use crate::editor::{
+    Editor,
+    commands::{CursorMovement, send_simple_notification},
+    modes::EditorMode,
+    selection::parse_text_object,
+};
+
+/// Counts are capped so that a typo can't freeze the editor
+const MAX_COUNT: usize = 10_000;
+
+/// State of the vi-style modal editing, see `EditorConfig::vi_mode`
+#[derive(Default)]
+pub struct ViState {
+    /// Keys typed so far of a command that isn't complete yet, e.g. `2d`
+    pub pending_keys: String,
+    /// Change repeated by `.`
+    last_change: Option<ViChange>,
+    /// Change whose text is being typed in insert mode
+    insert: Option<ViInsert>,
+}
+
+#[derive(Clone)]
+struct ViChange {
+    action: ViAction,
+    count: Option<usize>,
+    inserted_text: Text,
+}
+
+struct ViInsert {
+    action: ViAction,
+    count: Option<usize>,
+    start_pos: (usize, usize),
+}
+
+/// How an operator treats the text between the cursor and the target of a motion
+#[derive(Clone, Copy, PartialEq)]
+pub enum MotionKind {
+    /// Up to the target, excluded
+    Exclusive,
+    /// Up to the target, included
+    Inclusive,
+    /// Every line from the cursor's to the target's
+    Linewise,
+}
+
+#[derive(Clone, Copy)]
+pub struct ViMotion {
+    pub movement: CursorMovement,
+    pub kind: MotionKind,
+    /// `t` and `T` stop one character before the one they look for
+    pub is_till: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ViOperator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Dedent,
+    /// `gc`, toggles the comments of the lines
+    Comment,
+}
+
+#[derive(Clone, Copy)]
+pub enum ViTarget {
+    Motion(ViMotion),
+    /// `iw`, `a(`, `i"`, ...
+    TextObject {
+        object: char,
+        is_around: bool,
+    },
+    /// The operator typed twice, e.g. `dd` or `>>`
+    Lines,
+}
+
+#[derive(Clone, Copy)]
+pub enum InsertPos {
+    Cursor,
+    AfterCursor,
+    LineStart,
+    LineEnd,
+    LineBelow,
+    LineAbove,
+}
+
+#[derive(Clone, Copy)]
+pub enum ViAction {
+    Move(ViMotion),
+    Operate(ViOperator, ViTarget),
+    /// An operator typed in visual mode
+    OperateOnSelection(ViOperator),
+    Insert(InsertPos),
+    ReplaceChar(char),
+    Paste {
+        is_before: bool,
+    },
+    JoinLines,
+    RepeatLastChange,
+    ToggleVisual {
+        is_linewise: bool,
+    },
+    /// `i` or `a` and a text object typed in visual mode
+    SelectTextObject {
+        object: char,
+        is_around: bool,
+    },
+}
+
+pub enum ParsedKeys<T> {
+    Pending,
+    Invalid,
+    Complete(T),
+}
+
+/// Range of text an operator applies to
+enum ViRange {
+    Chars((usize, usize), (usize, usize)),
+    Lines(usize, usize),
+}
+
+/// Parses the keys typed in normal or visual mode, either `[count] command`
+/// or `[count] operator [count] (motion | text object)`
+pub fn parse_vi_keys(keys: &str, is_visual: bool) -> ParsedKeys<(ViAction, Option<usize>)> {
+    use ParsedKeys as P;
+    use ViAction as A;
+    use ViOperator as O;
+
+    let mut chars = keys.chars().peekable();
+    let count = parse_count(&mut chars);
+    let Some(key) = chars.next() else {
+        return P::Pending;
+    };
+
+    let operator = match key {
+        'd' => Some(O::Delete),
+        'c' => Some(O::Change),
+        'y' => Some(O::Yank),
+        '>' => Some(O::Indent),
+        '<' => Some(O::Dedent),
+        'g' if chars.peek() == Some(&'c') => {
+            chars.next();
+            Some(O::Comment)
+        }
+        'x' if is_visual => Some(O::Delete),
+        's' if is_visual => Some(O::Change),
+        _ => None,
+    };
+
+    if let Some(operator) = operator {
+        if is_visual {
+            return P::Complete((A::OperateOnSelection(operator), count));
+        }
+
+        let motion_count = parse_count(&mut chars);
+        let count = match (count, motion_count) {
+            (Some(count), Some(motion_count)) => Some((count * motion_count).min(MAX_COUNT)),
+            (count, motion_count) => count.or(motion_count),
+        };
+
+        let target = match chars.next() {
+            None => return P::Pending,
+            Some(motion_key) if motion_key == key => ViTarget::Lines,
+            Some('c') if operator == O::Comment => ViTarget::Lines,
+            Some(motion_key @ ('i' | 'a')) => match chars.next() {
+                Some(object) => ViTarget::TextObject {
+                    object,
+                    is_around: motion_key == 'a',
+                },
+                None => return P::Pending,
+            },
+            Some(motion_key) => match parse_motion(motion_key, &mut chars) {
+                P::Complete(motion) => ViTarget::Motion(motion),
+                P::Pending => return P::Pending,
+                P::Invalid => return P::Invalid,
+            },
+        };
+
+        return P::Complete((A::Operate(operator, target), count));
+    }
+
+    let char_motion = |movement| {
+        ViTarget::Motion(ViMotion {
+            movement,
+            kind: MotionKind::Exclusive,
+            is_till: false,
+        })
+    };
+
+    let action = match key {
+        'x' => A::Operate(O::Delete, char_motion(CursorMovement::Right(1))),
+        'X' => A::Operate(O::Delete, char_motion(CursorMovement::Left(1))),
+        's' => A::Operate(O::Change, char_motion(CursorMovement::Right(1))),
+        'S' => A::Operate(O::Change, ViTarget::Lines),
+        'D' => A::Operate(O::Delete, char_motion(CursorMovement::EndOfLine)),
+        'C' => A::Operate(O::Change, char_motion(CursorMovement::EndOfLine)),
+        'Y' => A::Operate(O::Yank, ViTarget::Lines),
+        'i' | 'a' if is_visual => match chars.next() {
+            Some(object) => A::SelectTextObject {
+                object,
+                is_around: key == 'a',
+            },
+            None => return P::Pending,
+        },
+        'i' => A::Insert(InsertPos::Cursor),
+        'a' => A::Insert(InsertPos::AfterCursor),
+        'I' => A::Insert(InsertPos::LineStart),
+        'A' => A::Insert(InsertPos::LineEnd),
+        'o' => A::Insert(InsertPos::LineBelow),
+        'O' => A::Insert(InsertPos::LineAbove),
+        'r' => match chars.next() {
+            Some(ch) => A::ReplaceChar(ch),
+            None => return P::Pending,
+        },
+        'p' => A::Paste { is_before: false },
+        'P' => A::Paste { is_before: true },
+        'J' => A::JoinLines,
+        '.' => A::RepeatLastChange,
+        'v' => A::ToggleVisual { is_linewise: false },
+        'V' => A::ToggleVisual { is_linewise: true },
+        _ => match parse_motion(key, &mut chars) {
+            P::Complete(motion) => A::Move(motion),
+            P::Pending => return P::Pending,
+            P::Invalid => return P::Invalid,
+        },
+    };
+
+    // visual mode only moves the cursor and applies operators to the selection
+    if is_visual
+        && !matches!(
+            action,
+            A::Move(_) | A::ToggleVisual { is_linewise: _ } | A::SelectTextObject { .. }
+        )
+    {
+        return P::Invalid;
+    }
+
+    P::Complete((action, count))
+}
+
+fn parse_count(chars: &mut Peekable<impl Iterator<Item = char>>) -> Option<usize> {
+    // a leading 0 is the start of line motion
+    chars.peek().filter(|ch| matches!(ch, '1'..='9'))?;
+
+    let mut count = 0usize;
+    while let Some(digit) = chars.peek().and_then(|ch| ch.to_digit(10)) {
+        count = (count * 10 + digit as usize).min(MAX_COUNT);
+        chars.next();
+    }
+
+    Some(count)
+}
+
+fn parse_motion(key: char, chars: &mut impl Iterator<Item = char>) -> ParsedKeys<ViMotion> {
+    use CursorMovement as M;
+    use MotionKind as K;
+
+    let (movement, kind) = match key {
+        'h' => (M::Left(1), K::Exclusive),
+        'l' | ' ' => (M::Right(1), K::Exclusive),
+        'j' | '\n' | '\r' => (M::Down(1), K::Linewise),
+        'k' => (M::Up(1), K::Linewise),
+        'w' => (M::NextWordStart, K::Exclusive),
+        'e' => (M::NextWordLastChar, K::Inclusive),
+        'b' => (M::PreviousWordStart, K::Exclusive),
+        '0' => (M::StartOfLine, K::Exclusive),
+        '^' => (M::FirstNonBlank, K::Exclusive),
+        '$' => (M::EndOfLine, K::Exclusive),
+        '{' => (M::PreviousParagraph, K::Exclusive),
+        '}' => (M::NextParagraph, K::Exclusive),
+        '%' => (M::MatchingParenthesis, K::Inclusive),
+        'G' => (M::EndOfFile, K::Linewise),
+        'g' => match chars.next() {
+            Some('g') => (M::StartOfFile, K::Linewise),
+            Some(_) => return ParsedKeys::Invalid,
+            None => return ParsedKeys::Pending,
+        },
+        'f' | 't' | 'F' | 'T' => match (key, chars.next()) {
+            (_, None) => return ParsedKeys::Pending,
+            ('f' | 't', Some(ch)) => (M::NextOccurrenceOf(ch), K::Inclusive),
+            (_, Some(ch)) => (M::PreviousOccurrenceOf(ch), K::Exclusive),
+        },
+        _ => return ParsedKeys::Invalid,
+    };
+
+    ParsedKeys::Complete(ViMotion {
+        movement,
+        kind,
+        is_till: matches!(key, 't' | 'T'),
+    })
+}
+
+impl Editor {
+    pub fn execute_vi_action(&mut self, action: ViAction, count: Option<usize>) {
+        let is_edit = match action {
+            ViAction::Operate(operator, _) | ViAction::OperateOnSelection(operator) => {
+                operator != ViOperator::Yank
+            }
+            ViAction::Insert(_)
+            | ViAction::ReplaceChar(_)
+            | ViAction::Paste { is_before: _ }
+            | ViAction::JoinLines => true,
+            _ => false,
+        };
+
+        if !is_edit {
+            return self.apply_vi_action(action, count);
+        }
+
+        // undoing the change brings the cursor back to where it was made
+        self.document.begin_transaction(self.cursor.pos(), None);
+        self.apply_vi_action(action, count);
+
+        // the text typed in insert mode is part of the change, it is committed once left
+        if self.vi.insert.is_none() {
+            self.document.commit();
+        }
+    }
+
+    fn apply_vi_action(&mut self, action: ViAction, count: Option<usize>) {
+        let n_times = count.unwrap_or(1);
+
+        match action {
+            ViAction::Move(motion) => {
+                self.move_by_motion(motion, count);
+                if self.cursor.selection_anchor.is_some() {
+                    self.needs_full_rerender = true;
+                }
+            }
+            ViAction::Operate(operator, target) => {
+                // like in vi, `cw` on a word only changes up to the end of that word
+                let target = match target {
+                    ViTarget::Motion(ViMotion {
+                        movement: CursorMovement::NextWordStart,
+                        ..
+                    }) if operator == ViOperator::Change
+                        && self
+                            .document
+                            .get_char_at_pos(self.cursor.pos())
+                            .is_some_and(|ch| !ch.is_whitespace()) =>
+                    {
+                        ViTarget::Motion(ViMotion {
+                            movement: CursorMovement::NextWordLastChar,
+                            kind: MotionKind::Inclusive,
+                            is_till: false,
+                        })
+                    }
+                    target => target,
+                };
+
+                let Some(range) = self.get_target_range(target, count) else {
+                    return;
+                };
+
+                self.apply_operator(operator, range);
+                if operator == ViOperator::Change {
+                    self.start_vi_insert(action, count);
+                }
+            }
+            ViAction::OperateOnSelection(operator) => {
+                let Some((pos_from, pos_to)) = self.cursor.get_selected_range() else {
+                    return;
+                };
+
+                let range = if matches!(self.mode, EditorMode::VisualLine) {
+                    ViRange::Lines(pos_from.1, pos_to.1)
+                } else {
+                    // the character under the cursor is part of the selection
+                    ViRange::Chars(pos_from, (self.get_next_grapheme_x(pos_to), pos_to.1))
+                };
+
+                self.cursor.selection_anchor = None;
+                self.needs_full_rerender = true;
+                self.mode = EditorMode::Normal;
+                self.apply_operator(operator, range);
+                if operator == ViOperator::Change {
+                    self.start_vi_insert(action, count);
+                }
+            }
+            ViAction::Insert(insert_pos) => {
+                self.move_to_insert_pos(insert_pos);
+                self.start_vi_insert(action, count);
+            }
+            ViAction::ReplaceChar(ch) => {
+                let (x, y) = self.cursor.pos();
+                let x_to = (0..n_times).fold(x, |x, _| self.get_next_grapheme_x((x, y)));
+                if x_to > self.get_line_len(y) {
+                    return;
+                }
+
+                self.apply_vi_edit(EditOperation::Replacement {
+                    pos_from: (x, y),
+                    pos_to: (x_to, y),
+                    text: Text::from(ch.to_string().repeat(n_times)),
+                });
+                self.cursor.update_pos((x + n_times - 1, y), false);
+            }
+            ViAction::Paste { is_before } => {
+                for _ in 0..n_times {
+                    self.paste_after_cursor(is_before);
+                }
+            }
+            ViAction::JoinLines => {
+                for _ in 0..n_times.max(2) - 1 {
+                    self.join_line_below();
+                }
+            }
+            ViAction::RepeatLastChange => {
+                self.repeat_last_change(count);
+                return;
+            }
+            ViAction::ToggleVisual { is_linewise } => {
+                self.toggle_visual_mode(is_linewise);
+                return;
+            }
+            ViAction::SelectTextObject { object, is_around } => {
+                if let Some(object) = parse_text_object(object) {
+                    self.select_text_object(object, is_around);
+                }
+
+                return;
+            }
+        }
+
+        let is_change = match action {
+            ViAction::Operate(operator, _) => operator != ViOperator::Yank,
+            ViAction::ReplaceChar(_) | ViAction::Paste { is_before: _ } | ViAction::JoinLines => {
+                true
+            }
+            _ => false,
+        };
+
+        // changes typed in insert mode are recorded once it is left
+        if is_change && self.vi.insert.is_none() {
+            self.vi.last_change = Some(ViChange {
+                action,
+                count,
+                inserted_text: Text::Empty,
+            });
+        }
+    }
+
+    /// Leaves insert mode for normal mode, the text typed since entering insert mode
+    /// becomes part of the change repeated by `.`
+    pub fn finish_vi_insert(&mut self) {
+        self.mode = EditorMode::Normal;
+        self.clear_secondary_carets();
+        self.cursor.selection_anchor = None;
+        self.needs_full_rerender = true;
+
+        // back on the last inserted character
+        if self.commit_vi_insert() && self.cursor.cur_x > 0 {
+            self.move_cursor(CursorMovement::Left(1), false);
+        }
+    }
+
+    /// Ends the change being typed in insert mode, whichever way insert mode is left.
+    /// Returns whether there was one
+    pub fn commit_vi_insert(&mut self) -> bool {
+        let Some(insert) = self.vi.insert.take() else {
+            return false;
+        };
+
+        let start_pos = insert.start_pos;
+        let end_pos = self.cursor.pos();
+        let inserted_text = if (end_pos.1, end_pos.0) > (start_pos.1, start_pos.0) {
+            self.document.get_range_text(start_pos, end_pos)
+        } else {
+            Text::Empty
+        };
+
+        let n_times = insert.count.unwrap_or(1);
+        if n_times > 1
+            && matches!(
+                insert.action,
+                ViAction::Insert(
+                    InsertPos::Cursor
+                        | InsertPos::AfterCursor
+                        | InsertPos::LineStart
+                        | InsertPos::LineEnd
+                )
+            )
+        {
+            let repeated_text = inserted_text.to_string().repeat(n_times - 1);
+            self.insert_at_cursor(Text::from(repeated_text));
+        }
+
+        self.document.commit();
+        self.vi.last_change = Some(ViChange {
+            action: insert.action,
+            count: insert.count,
+            inserted_text,
+        });
+
+        true
+    }
+
+    /// Runs `f` with the text typed so far in insert mode committed as a change of its own,
+    /// the text typed after it is another change
+    pub fn with_vi_insert_committed<T>(&mut self, f: impl FnOnce(&mut Editor) -> T) -> T {
+        let was_inserting = self.commit_vi_insert();
+        let res = f(self);
+        if was_inserting && matches!(self.mode, EditorMode::Insert) {
+            self.document.begin_transaction(self.cursor.pos(), None);
+            self.start_vi_insert(ViAction::Insert(InsertPos::Cursor), None);
+        }
+
+        res
+    }
+
+    /// Range highlighted as selected, in visual mode it includes the character
+    /// under the cursor or, in visual line mode, the whole lines
+    pub fn get_highlighted_range(&self) -> Option<((usize, usize), (usize, usize))> {
+        let (pos_from, pos_to) = self.cursor.get_selected_range()?;
+
+        match self.mode {
+            EditorMode::Visual => Some((pos_from, (self.get_next_grapheme_x(pos_to), pos_to.1))),
+            EditorMode::VisualLine => {
+                Some(((0, pos_from.1), (self.get_line_len(pos_to.1), pos_to.1)))
+            }
+            _ => Some((pos_from, pos_to)),
+        }
+    }
+
+    fn repeat_last_change(&mut self, count: Option<usize>) {
+        let Some(change) = self.vi.last_change.clone() else {
+            return;
+        };
+
+        self.execute_vi_action(change.action, count.or(change.count));
+        if self.vi.insert.is_some() {
+            self.insert_at_cursor(change.inserted_text);
+            self.finish_vi_insert();
+        }
+    }
+
+    fn toggle_visual_mode(&mut self, is_linewise: bool) {
+        self.needs_full_rerender = true;
+        match self.mode {
+            EditorMode::Visual if !is_linewise => {
+                self.mode = EditorMode::Normal;
+                self.cursor.selection_anchor = None;
+            }
+            EditorMode::VisualLine if is_linewise => {
+                self.mode = EditorMode::Normal;
+                self.cursor.selection_anchor = None;
+            }
+            _ => {
+                self.cursor
+                    .selection_anchor
+                    .get_or_insert(self.cursor.pos());
+                self.mode = if is_linewise {
+                    EditorMode::VisualLine
+                } else {
+                    EditorMode::Visual
+                };
+            }
+        }
+    }
+
+    fn start_vi_insert(&mut self, action: ViAction, count: Option<usize>) {
+        self.vi.insert = Some(ViInsert {
+            action,
+            count,
+            start_pos: self.cursor.pos(),
+        });
+        self.mode = EditorMode::Insert;
+    }
+
+    /// Moves the cursor `count` times, to the line `count` for `gg` and `G`
+    fn move_by_motion(&mut self, motion: ViMotion, count: Option<usize>) {
+        use CursorMovement as M;
+
+        let start_pos = self.cursor.pos();
+        let n_times = count.unwrap_or(1);
+        let (movement, n_moves) = match motion.movement {
+            M::Up(_) => (M::Up(n_times), 1),
+            M::Down(_) => (M::Down(n_times), 1),
+            M::Left(_) => (M::Left(n_times), 1),
+            M::Right(_) => (M::Right(n_times), 1),
+            M::StartOfFile | M::EndOfFile if let Some(line) = count => {
+                (M::AbsolutePos((0, line - 1)), 1)
+            }
+            movement => (movement, n_times),
+        };
+
+        let with_selection = self.cursor.selection_anchor.is_some();
+        for _ in 0..n_moves {
+            let previous_pos = self.cursor.pos();
+            match movement {
+                // j and k move by line even when lines are soft wrapped, like in vim
+                M::Up(lines) => self.cursor.move_up(lines),
+                M::Down(lines) => self.cursor.move_down(lines),
+                movement => self.move_cursor(movement, with_selection),
+            }
+            if self.cursor.pos() == previous_pos {
+                break;
+            }
+        }
+
+        let (mut x, mut y) = self.cursor.pos();
+        match movement {
+            M::NextOccurrenceOf(_) if motion.is_till && (x, y) != start_pos => {
+                x = x.saturating_sub(1)
+            }
+            M::PreviousOccurrenceOf(_) if motion.is_till && (x, y) != start_pos => x += 1,
+            _ => {}
+        }
+
+        // the cursor is clamped here rather than when rendering, operators need its
+        // actual position. ideal_x is left untouched so that j and k keep the column
+        y = y.min(self.document.n_lines().saturating_sub(1));
+        x = x.min(self.get_line_len(y));
+        self.cursor.cur_x = x;
+        self.cursor.cur_y = y;
+    }
+
+    fn get_target_range(&mut self, target: ViTarget, count: Option<usize>) -> Option<ViRange> {
+        let (x, y) = self.cursor.pos();
+        match target {
+            ViTarget::Lines => {
+                let last_line_idx = self.document.n_lines().saturating_sub(1);
+                let n_lines = count.unwrap_or(1);
+                Some(ViRange::Lines(y, (y + n_lines - 1).min(last_line_idx)))
+            }
+            ViTarget::Motion(motion) => {
+                self.move_by_motion(motion, count);
+                let mut target_pos = self.cursor.pos();
+                self.cursor.update_pos((x, y), false);
+
+                // a word motion going past the end of the line stops there when operating
+                if matches!(motion.movement, CursorMovement::NextWordStart)
+                    && target_pos.1 > y
+                    && target_pos.0 <= self.get_first_non_blank_x(target_pos.1)
+                {
+                    let previous_y = target_pos.1 - 1;
+                    target_pos = (self.get_line_len(previous_y), previous_y);
+                }
+
+                let (pos_from, mut pos_to) = if (target_pos.1, target_pos.0) < (y, x) {
+                    (target_pos, (x, y))
+                } else {
+                    ((x, y), target_pos)
+                };
+
+                match motion.kind {
+                    MotionKind::Linewise => Some(ViRange::Lines(pos_from.1, pos_to.1)),
+                    _ if pos_from == pos_to && motion.kind == MotionKind::Exclusive => None,
+                    MotionKind::Inclusive => {
+                        pos_to.0 = self
+                            .get_next_grapheme_x(pos_to)
+                            .min(self.get_line_len(pos_to.1));
+                        Some(ViRange::Chars(pos_from, pos_to))
+                    }
+                    MotionKind::Exclusive => Some(ViRange::Chars(pos_from, pos_to)),
+                }
+            }
+            ViTarget::TextObject { object, is_around } => {
+                self.get_text_object_range(object, is_around)
+            }
+        }
+    }
+
+    fn get_text_object_range(&self, object: char, is_around: bool) -> Option<ViRange> {
+        let object = parse_text_object(object)?;
+        let pos = self.cursor.pos();
+        let (pos_from, pos_to) =
+            self.document
+                .get_text_object_range(object, (pos, pos), is_around)?;
+
+        if object != TextObject::Paragraph {
+            return Some(ViRange::Chars(pos_from, pos_to));
+        }
+
+        // paragraphs are made of whole lines, their ranges start at the end
+        // of the previous line or end at the start of the next one
+        let y_from = if pos_from.0 > 0 {
+            pos_from.1 + 1
+        } else {
+            pos_from.1
+        };
+        let y_to = if pos_to.0 == 0 && pos_to.1 > pos_from.1 {
+            pos_to.1 - 1
+        } else {
+            pos_to.1
+        };
+
+        Some(ViRange::Lines(y_from, y_to))
+    }
+
+    fn apply_operator(&mut self, operator: ViOperator, range: ViRange) {
+        match (operator, range) {
+            (ViOperator::Indent | ViOperator::Dedent, ViRange::Chars(pos_from, pos_to)) => {
+                self.shift_lines(pos_from.1, pos_to.1, operator == ViOperator::Dedent);
+            }
+            (ViOperator::Indent | ViOperator::Dedent, ViRange::Lines(y_from, y_to)) => {
+                self.shift_lines(y_from, y_to, operator == ViOperator::Dedent);
+            }
+            (ViOperator::Comment, ViRange::Chars((_, y_from), (_, y_to)))
+            | (ViOperator::Comment, ViRange::Lines(y_from, y_to)) => {
+                self.toggle_comment_lines(y_from, y_to);
+                self.cursor
+                    .update_pos((self.get_first_non_blank_x(y_from), y_from), false);
+            }
+            (_, ViRange::Chars(pos_from, pos_to)) => {
+                self.yank_text(self.document.get_range_text(pos_from, pos_to));
+                if operator != ViOperator::Yank {
+                    self.apply_vi_edit(EditOperation::Replacement {
+                        pos_from,
+                        pos_to,
+                        text: Text::Empty,
+                    });
+                }
+
+                self.cursor.update_pos(pos_from, false);
+            }
+            (_, ViRange::Lines(y_from, y_to)) => {
+                let mut lines = (y_from..=y_to)
+                    .filter_map(|line_idx| self.document.lines.get(line_idx))
+                    .map(|line| line.as_ref().to_owned())
+                    .collect::<Vec<_>>();
+                lines.push(String::new());
+                self.yank_text(Text::Lines(lines));
+
+                match operator {
+                    ViOperator::Delete => {
+                        let (pos_from, pos_to) = self.get_lines_deletion_range(y_from, y_to);
+                        self.apply_vi_edit(EditOperation::Replacement {
+                            pos_from,
+                            pos_to,
+                            text: Text::Empty,
+                        });
+
+                        let y = y_from.min(self.document.n_lines().saturating_sub(1));
+                        self.cursor
+                            .update_pos((self.get_first_non_blank_x(y), y), false);
+                    }
+                    // the indentation of the first line is kept
+                    ViOperator::Change => {
+                        let indent_len = self.get_first_non_blank_x(y_from);
+                        self.apply_vi_edit(EditOperation::Replacement {
+                            pos_from: (indent_len, y_from),
+                            pos_to: (self.get_line_len(y_to), y_to),
+                            text: Text::Empty,
+                        });
+                        self.cursor.update_pos((indent_len, y_from), false);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Range to delete to remove the lines from `y_from` to `y_to`, the last line
+    /// of the document has no newline after it so the one before it is removed instead
+    fn get_lines_deletion_range(
+        &self,
+        y_from: usize,
+        y_to: usize,
+    ) -> ((usize, usize), (usize, usize)) {
+        if y_to + 1 < self.document.n_lines() {
+            ((0, y_from), (0, y_to + 1))
+        } else if y_from > 0 {
+            (
+                (self.get_line_len(y_from - 1), y_from - 1),
+                (self.get_line_len(y_to), y_to),
+            )
+        } else {
+            ((0, 0), (self.get_line_len(y_to), y_to))
+        }
+    }
+
+    fn shift_lines(&mut self, y_from: usize, y_to: usize, is_dedent: bool) {
+        let tab_size = self.config.tab_size as usize;
+        let indent = if self.config.tab_emit_spaces {
+            " ".repeat(tab_size)
+        } else {
+            "\t".to_owned()
+        };
+
+        let ops = (y_from..=y_to)
+            .filter_map(|line_idx| {
+                let line = self.document.lines.get(line_idx)?;
+                if !is_dedent {
+                    return (!line.is_empty()).then(|| EditOperation::Insertion {
+                        pos: (0, line_idx),
+                        text: Text::from(indent.clone()),
+                    });
+                }
+
+                let n_chars = if line.starts_with("\t") {
+                    1
+                } else {
+                    line.iter()
+                        .take(tab_size)
+                        .take_while(|ch| *ch == ' ')
+                        .count()
+                };
+
+                (n_chars > 0).then_some(EditOperation::Replacement {
+                    pos_from: (0, line_idx),
+                    pos_to: (n_chars, line_idx),
+                    text: Text::Empty,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if !ops.is_empty() {
+            self.document.apply_cursor_edits(ops);
+            self.first_quit_sent = false;
+        }
+
+        self.cursor
+            .update_pos((self.get_first_non_blank_x(y_from), y_from), false);
+    }
+
+    fn move_to_insert_pos(&mut self, insert_pos: InsertPos) {
+        let (x, y) = self.cursor.pos();
+        let line_len = self.get_line_len(y);
+
+        let pos = match insert_pos {
+            InsertPos::Cursor => (x, y),
+            InsertPos::AfterCursor => (self.get_next_grapheme_x((x, y)).min(line_len), y),
+            InsertPos::LineStart => (self.get_first_non_blank_x(y), y),
+            InsertPos::LineEnd => (line_len, y),
+            InsertPos::LineBelow => self
+                .apply_vi_edit(EditOperation::Insertion {
+                    pos: (line_len, y),
+                    text: Text::Char('\n'),
+                })
+                .unwrap_or((x, y)),
+            InsertPos::LineAbove => {
+                let indent_len = self.get_first_non_blank_x(y);
+                let indent = self
+                    .document
+                    .lines
+                    .get(y)
+                    .map(|line| line.get_range(..indent_len).to_owned())
+                    .unwrap_or_default();
+
+                self.apply_vi_edit(EditOperation::Insertion {
+                    pos: (0, y),
+                    text: Text::Lines(vec![indent, String::new()]),
+                });
+                (indent_len, y)
+            }
+        };
+
+        self.cursor.update_pos(pos, false);
+    }
+
+    /// `p` and `P`: whole lines are pasted below or above the current one
+    fn paste_after_cursor(&mut self, is_before: bool) {
+        let Some(text) = self.clipboard.get_text() else {
+            return;
+        };
+
+        let (x, y) = self.cursor.pos();
+        match text {
+            Text::Lines(mut lines)
+                if lines.len() > 1 && lines.last().is_some_and(String::is_empty) =>
+            {
+                let y = if is_before {
+                    self.apply_vi_edit(EditOperation::Insertion {
+                        pos: (0, y),
+                        text: Text::Lines(lines),
+                    });
+                    y
+                } else if y + 1 < self.document.n_lines() {
+                    self.apply_vi_edit(EditOperation::Insertion {
+                        pos: (0, y + 1),
+                        text: Text::Lines(lines),
+                    });
+                    y + 1
+                } else {
+                    lines.pop();
+                    lines.insert(0, String::new());
+                    self.apply_vi_edit(EditOperation::Insertion {
+                        pos: (self.get_line_len(y), y),
+                        text: Text::Lines(lines),
+                    });
+                    y + 1
+                };
+
+                self.cursor
+                    .update_pos((self.get_first_non_blank_x(y), y), false);
+            }
+            text => {
+                let x = if is_before {
+                    x
+                } else {
+                    self.get_next_grapheme_x((x, y)).min(self.get_line_len(y))
+                };
+
+                self.cursor.update_pos((x, y), false);
+                self.insert_at_cursor(text);
+                self.move_cursor(CursorMovement::Left(1), false);
+            }
+        }
+    }
+
+    fn join_line_below(&mut self) {
+        let y = self.cursor.cur_y;
+        if y + 1 >= self.document.n_lines() {
+            return;
+        }
+
+        let line_len = self.get_line_len(y);
+        let next_line_x = self.get_first_non_blank_x(y + 1);
+        let is_separated = line_len == 0
+            || next_line_x == self.get_line_len(y + 1)
+            || self.document.lines[y]
+                .at(line_len - 1)
+                .is_some_and(char::is_whitespace);
+
+        self.apply_vi_edit(EditOperation::Replacement {
+            pos_from: (line_len, y),
+            pos_to: (next_line_x, y + 1),
+            text: if is_separated {
+                Text::Empty
+            } else {
+                Text::Char(' ')
+            },
+        });
+        self.cursor.update_pos((line_len, y), false);
+    }
+
+    fn insert_at_cursor(&mut self, text: Text) {
+        if matches!(text, Text::Empty) {
+            return;
+        }
+
+        let edit = EditOperation::Insertion {
+            pos: self.cursor.pos(),
+            text,
+        };
+        if let Some(new_pos) = self.apply_vi_edit(edit) {
+            self.cursor.update_pos(new_pos, false);
+        }
+    }
+
+    fn yank_text(&mut self, text: Text) {
+        match &text {
+            Text::Lines(lines) if lines.len() > 2 => {
+                send_simple_notification(format!("Yanked {} lines", lines.len() - 1))
+            }
+            _ => {}
+        }
+
+        self.clipboard.set_text(text);
+    }
+
+    fn apply_vi_edit(&mut self, op: EditOperation) -> Option<(usize, usize)> {
+        self.first_quit_sent = false;
+        self.document.apply_edit(op, InverseStack::Undo)
+    }
+
+    pub fn get_line_len(&self, line_idx: usize) -> usize {
+        self.document
+            .lines
+            .get(line_idx)
+            .map_or(0, |line| line.len())
+    }
+
+    /// Start of the grapheme cluster after the one at `pos`
+    fn get_next_grapheme_x(&self, (x, y): (usize, usize)) -> usize {
+        self.document
+            .lines
+            .get(y)
+            .map_or(x + 1, |line| line.next_grapheme_idx(x))
+    }
+
+    pub fn get_first_non_blank_x(&self, line_idx: usize) -> usize {
+        self.document.lines.get(line_idx).map_or(0, |line| {
+            line.iter().take_while(|ch| ch.is_whitespace()).count()
+        })
+    }
+}
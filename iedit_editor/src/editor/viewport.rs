@@ -1,5 +1,7 @@
 use std::cmp::{max, min};
 
+use iedit_document::{DocumentLine, grapheme_width};
+
 use crate::editor::Editor;
 
 #[derive(Default)]
@@ -95,6 +97,12 @@ impl Editor {
             max(0, (self.viewport.top_line as isize) + offset) as usize,
         );
 
+        if self.config.soft_wrap {
+            self.viewport.left_col = 0;
+            self.scroll_to_wrapped_cursor();
+            return;
+        }
+
         let left_limit = self.viewport.left_col + horizontal_margin;
         let right_limit = self.viewport.left_col + term_width - horizontal_margin;
 
@@ -122,14 +130,186 @@ impl Editor {
         }
     }
 
+    /// Moves the viewport down until the screen row of the cursor is visible, lines
+    /// taking several rows when wrapped
+    fn scroll_to_wrapped_cursor(&mut self) {
+        // the user scrolled away from the cursor
+        if self.viewport.vertical_offset != 0 {
+            return;
+        }
+
+        let n_rows = self.ui.editor_lines as usize;
+        let y = self.cursor.cur_y;
+        let (cursor_row, _) = self.get_wrapped_cursor_pos();
+
+        if y < self.viewport.top_line {
+            self.viewport.top_line = y;
+            self.needs_full_rerender = true;
+        }
+
+        let mut rows_above_cursor = cursor_row
+            + (self.viewport.top_line..y)
+                .map(|line_idx| self.get_line_row_starts(line_idx).len())
+                .sum::<usize>();
+
+        while rows_above_cursor >= n_rows && self.viewport.top_line < y {
+            rows_above_cursor -= self.get_line_row_starts(self.viewport.top_line).len();
+            self.viewport.top_line += 1;
+            self.needs_full_rerender = true;
+        }
+
+        self.viewport.pre_scroll_top_line = self.viewport.top_line;
+    }
+
     #[inline(always)]
     pub fn viewport_contains_y(&self, y: usize) -> bool {
+        if self.config.soft_wrap {
+            return self.viewport.top_line <= y && y < self.get_wrapped_viewport_end();
+        }
+
         self.viewport.top_line <= y && y < self.viewport.top_line + self.ui.editor_lines as usize
     }
 
+    /// Index of the first line below the viewport when lines are wrapped, the last
+    /// visible line may only be partially displayed
+    pub fn get_wrapped_viewport_end(&self) -> usize {
+        let mut n_rows = 0;
+        let mut line_idx = self.viewport.top_line;
+        while n_rows < self.ui.editor_lines as usize {
+            n_rows += self.get_line_row_starts(line_idx).len();
+            line_idx += 1;
+        }
+
+        line_idx
+    }
+
+    /// Number of screen rows of each visible line, empty unless soft wrap is enabled
+    pub fn get_visible_wrapped_rows(&self) -> Vec<usize> {
+        if !self.config.soft_wrap {
+            return vec![];
+        }
+
+        (self.viewport.top_line..self.get_wrapped_viewport_end())
+            .map(|line_idx| self.get_line_row_starts(line_idx).len())
+            .collect()
+    }
+
+    /// Width of the text area, the screen minus the line numbers
+    pub fn get_text_width(&self) -> usize {
+        let mut text_width = self.ui.term_width as usize;
+        if self.config.show_line_numbers {
+            text_width = text_width.saturating_sub(self.get_line_number_gutter_width() + 2);
+        }
+
+        text_width
+    }
+
+    /// Char index at which each screen row of a line starts, a single row unless
+    /// soft wrap is enabled
+    pub fn get_line_row_starts(&self, line_idx: usize) -> Vec<usize> {
+        match self.document.lines.get(line_idx) {
+            Some(line) if self.config.soft_wrap => {
+                get_row_starts(line, self.get_text_width(), self.config.tab_size as usize)
+            }
+            _ => vec![0],
+        }
+    }
+
+    /// Screen row of the cursor within its line, and its column within that row
+    pub fn get_wrapped_cursor_pos(&self) -> (usize, usize) {
+        let tab_size = self.config.tab_size as usize;
+        let Some(line) = self.document.lines.get(self.cursor.cur_y) else {
+            return (0, self.cursor.cur_x);
+        };
+
+        let row_starts = self.get_line_row_starts(self.cursor.cur_y);
+        let row = get_row_idx(&row_starts, self.cursor.cur_x);
+        let col = line.char_to_visual_idx(self.cursor.cur_x, tab_size)
+            - line.char_to_visual_idx(row_starts[row], tab_size);
+
+        (row, col)
+    }
+
+    /// Moves the cursor by screen rows rather than by lines, for soft wrapped lines
+    pub fn move_cursor_by_rows(&mut self, n_rows: usize, is_up: bool) {
+        let tab_size = self.config.tab_size as usize;
+        let (mut row, col) = self.get_wrapped_cursor_pos();
+        // keep the column the cursor was at on a shorter row, like ideal_x does for lines
+        let col = col + self.cursor.ideal_x.saturating_sub(self.cursor.cur_x);
+
+        let mut y = self.cursor.cur_y;
+        for _ in 0..n_rows {
+            if is_up && row > 0 {
+                row -= 1;
+            } else if is_up && y > 0 {
+                y -= 1;
+                row = self.get_line_row_starts(y).len() - 1;
+            } else if !is_up && row + 1 < self.get_line_row_starts(y).len() {
+                row += 1;
+            } else if !is_up && y < self.document.n_lines() {
+                y += 1;
+                row = 0;
+            }
+        }
+
+        let row_starts = self.get_line_row_starts(y);
+        let Some(line) = self.document.lines.get(y) else {
+            self.cursor.update_pos((col, y), n_rows > 1);
+            return;
+        };
+
+        let row_start_visual_x = line.char_to_visual_idx(row_starts[row], tab_size);
+        let target_x = line.visual_to_char_idx(row_start_visual_x + col, tab_size);
+        let x = match row_starts.get(row + 1) {
+            // stay on the row rather than going to the start of the next one
+            Some(next_row_start) => min(target_x, line.previous_grapheme_idx(*next_row_start)),
+            None if target_x == line.len() => {
+                let line_visual_len = line.char_to_visual_idx(line.len(), tab_size);
+                line.len() + (row_start_visual_x + col).saturating_sub(line_visual_len)
+            }
+            None => target_x,
+        };
+
+        self.cursor.update_pos((x, y), n_rows > 1);
+    }
+
     #[inline(always)]
     pub fn get_line_number_gutter_width(&self) -> usize {
         // NOTE: this also includes one space of padding on each side of the number
         (max(self.document.n_lines(), 1)).ilog10() as usize + 2
     }
 }
+
+/// Splits a line into screen rows of `width` columns, a grapheme cluster that doesn't fit
+/// in what is left of a row starts the next one
+fn get_row_starts(line: &DocumentLine, width: usize, tab_size: usize) -> Vec<usize> {
+    let mut row_starts = vec![0];
+    let mut visual_idx = 0;
+    let mut row_visual_start = 0;
+
+    for (char_idx, grapheme) in line.graphemes() {
+        let char_width = grapheme_width(grapheme, visual_idx, tab_size);
+
+        if visual_idx + char_width > row_visual_start + width && visual_idx > row_visual_start {
+            row_starts.push(char_idx);
+            row_visual_start = visual_idx;
+        }
+
+        visual_idx += char_width;
+    }
+
+    // the cursor can be past the last char, it needs a cell too
+    if visual_idx >= row_visual_start + width && width > 0 {
+        row_starts.push(line.len());
+    }
+
+    row_starts
+}
+
+/// Index of the row containing the char at `x`
+fn get_row_idx(row_starts: &[usize], x: usize) -> usize {
+    row_starts
+        .iter()
+        .rposition(|row_start| *row_start <= x)
+        .unwrap_or_default()
+}
@@ -0,0 +1,101 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    Editor,
+    editor::commands::{send_notification, send_simple_notification},
+    input::Notification,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Watches the modification time of the open files from a background thread,
+/// sending a `Notification::FileChanged` whenever one of them changes on disk
+pub struct FileWatcher {
+    /// Last modification time seen for each watched file, `None` if it doesn't exist
+    watched_files: Arc<Mutex<HashMap<PathBuf, Option<SystemTime>>>>,
+}
+
+impl FileWatcher {
+    pub fn new() -> Self {
+        let watched_files: Arc<Mutex<HashMap<PathBuf, Option<SystemTime>>>> = Default::default();
+        let weak_watched_files = Arc::downgrade(&watched_files);
+
+        // the thread stops along with the watcher
+        thread::spawn(move || {
+            while let Some(watched_files) = weak_watched_files.upgrade() {
+                if let Ok(mut watched_files) = watched_files.lock() {
+                    for (path, last_modified) in watched_files.iter_mut() {
+                        let modified = get_modified_time(path);
+                        if modified != *last_modified {
+                            *last_modified = modified;
+                            send_notification(Notification::FileChanged(path.clone()));
+                        }
+                    }
+                }
+
+                drop(watched_files);
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+
+        Self { watched_files }
+    }
+
+    pub fn watch(&self, path: &Path) {
+        if path.as_os_str().is_empty() {
+            return;
+        }
+
+        if let Ok(mut watched_files) = self.watched_files.lock() {
+            watched_files.insert(path.to_owned(), get_modified_time(path));
+        }
+    }
+
+    pub fn unwatch(&self, path: &Path) {
+        if let Ok(mut watched_files) = self.watched_files.lock() {
+            watched_files.remove(path);
+        }
+    }
+}
+
+impl Editor {
+    /// Tells the user when a file open in a buffer has been changed by another program
+    pub fn handle_file_changed_on_disk(&mut self, path: &Path) {
+        let document = std::iter::once(&self.document)
+            .chain(self.buffers.iter().map(|buffer| &buffer.document))
+            .find(|document| document.canonicalized_file_path == path);
+
+        let Some(document) = document else {
+            return;
+        };
+
+        let name = document.get_name().unwrap_or_default();
+        match get_modified_time(path) {
+            // our own saves happen before `last_save_time`
+            Some(modified) if modified > document.last_save_time => {
+                if std::ptr::eq(document, &self.document) {
+                    send_simple_notification(format!("{} changed on disk. e!: reload", name));
+                } else {
+                    send_simple_notification(format!("{} changed on disk", name));
+                }
+            }
+            None if document.file.is_some() => {
+                send_simple_notification(format!("{} was removed from disk", name));
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn get_modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -1,13 +1,13 @@
 use crossbeam_channel::{Receiver, select, unbounded};
-use std::{fs, process::ExitStatus, thread};
+use std::{fs, path::PathBuf, process::ExitStatus, thread, time::Duration};
 use termion::{event::Key, input::TermRead};
 
 pub enum Notification {
     Simple(String),
-    ExecutionEnd {
-        status: ExitStatus,
-        output_available: bool,
-    }, // others?...
+    ExecutionOutput,
+    ExecutionEnd { status: Option<ExitStatus> },
+    FollowedOutput,
+    FileChanged(PathBuf), // others?...
 }
 
 #[non_exhaustive]
@@ -92,6 +92,8 @@ impl Iterator for InputParser {
                     Err(_) => Some(Input::NoOp),
                 }
             }
+            // lets the editor catch up on background work, e.g. writing swap files
+            default(Duration::from_secs(1)) => Some(Input::NoOp),
         }
     }
 }
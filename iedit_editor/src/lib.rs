@@ -1,6 +1,6 @@
+pub mod config;
 pub mod editor;
 pub mod input;
-pub mod config;
 pub mod terminal;
 
 pub use editor::Editor;
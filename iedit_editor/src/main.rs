@@ -9,25 +9,48 @@ use iedit_editor::{
 use termion::raw::IntoRawMode;
 
 fn main() -> std::io::Result<()> {
-    let mut args = std::env::args().skip(1);
-    let path = args.next();
-    let open_at = args.next();
+    let mut paths: Vec<String> = std::env::args().skip(1).collect();
 
-    match [path.as_deref(), open_at.as_deref()] {
-        [Some("--version"), None] => {
+    let is_follow_mode = paths.iter().any(|arg| arg == "--follow");
+    let is_view_mode = is_follow_mode || paths.iter().any(|arg| arg == "--view");
+    paths.retain(|arg| arg != "--follow" && arg != "--view");
+
+    // a trailing number after the file paths is the line to open the first file at
+    let open_at = match paths.as_slice() {
+        [_, .., open_at] => open_at.parse::<usize>().ok(),
+        _ => None,
+    };
+    if open_at.is_some() {
+        paths.pop();
+    }
+
+    match paths.as_slice() {
+        [flag] if flag == "--version" => {
             println!("iedit version {}", env!("CARGO_PKG_VERSION"));
             return Ok(());
         }
-        [Some("--help"), None] => {
-            println!("Usage: iedit [FILE] [LINE_NUMBER]");
+        [flag] if flag == "--help" => {
+            println!("Usage: iedit [OPTIONS] [FILE]... [LINE_NUMBER]");
             println!();
-            println!("Open FILE in the editor, optionally starting at LINE_NUMBER");
+            println!(
+                "Open each FILE in its own buffer, optionally starting the first one at LINE_NUMBER"
+            );
             println!();
             println!("Options:");
+            println!("  --view     Open the files, or the piped input, read-only with pager keys");
+            println!(
+                "  --follow   Like --view, also showing what gets appended to the first FILE,"
+            );
+            println!("             or to the piped input, as it arrives");
             println!("  --help     Show this help message");
             println!("  --version  Show version information");
             return Ok(());
         }
+        [] if is_follow_mode && stdin().is_terminal() => {
+            return Err(io::Error::other(
+                "Nothing to follow, pass a file or pipe some input",
+            ));
+        }
         _ => {}
     };
 
@@ -48,8 +71,8 @@ fn main() -> std::io::Result<()> {
         UILayout::new(editor_config.min_lines, &mut terminal)
     }?;
 
-    let mut editor = match [path.as_deref(), open_at.as_deref()] {
-        [Some("--config"), None] => {
+    let mut editor = match paths.as_slice() {
+        [flag] if flag == "--config" => {
             let document = if let Some(editor_config_path) = &editor_config_path {
                 let mut doc = Document::from_file(editor_config_path, Option::<&str>::None)?;
                 doc.syntax = Some(editor_config_syntax());
@@ -61,22 +84,29 @@ fn main() -> std::io::Result<()> {
 
             Editor::new(document, 0, editor_config, ui)?
         }
-        [Some(path), open_at] => {
-            let open_at = open_at
-                .and_then(|open_at| open_at.parse::<usize>().ok())
-                .unwrap_or_default();
+        [path, other_paths @ ..] => {
+            let document = Editor::open_document(path, &editor_config)?;
+            let other_documents = other_paths
+                .iter()
+                .map(|path| Editor::open_document(path, &editor_config))
+                .collect::<io::Result<Vec<_>>>()?;
 
-            let document =
-                Document::from_file(path, editor_config.syntax_highlighting_dir.as_ref())?;
+            let mut editor = Editor::new(document, open_at.unwrap_or_default(), editor_config, ui)?;
+            for document in other_documents {
+                editor.add_buffer(document);
+            }
 
-            Editor::new(document, open_at, editor_config, ui)?
+            editor
         }
-        [None, _] => {
-            let document = if !stdin().is_terminal() {
+        [] => {
+            let document = if is_follow_mode && !stdin().is_terminal() {
+                // the input is read as it arrives
+                Document::from_strings(vec![String::new()], "", true)
+            } else if !stdin().is_terminal() {
                 let mut buffer = String::new();
                 stdin().read_to_string(&mut buffer)?;
                 let lines: Vec<String> = buffer.lines().map(|s| s.to_string()).collect();
-                Document::from_strings(lines, "", false)
+                Document::from_strings(lines, "", is_view_mode)
             } else {
                 Document::default()
             };
@@ -85,6 +115,14 @@ fn main() -> std::io::Result<()> {
         }
     };
 
+    if is_view_mode {
+        editor.enter_view_mode();
+    }
+
+    if is_follow_mode {
+        editor.follow()?;
+    }
+
     loop {
         let result = editor.run(&mut terminal)?;
 
@@ -30,6 +30,7 @@ pub static SYNC_UPDATE_START: &str = "\x1b[?2026h";
 pub static SYNC_UPDATE_END: &str = "\x1b[?2026l";
 pub static H_BAR: &str = "─";
 pub static V_BAR: char = '│';
+pub static WRAP_INDICATOR: char = '↪';
 
 #[derive(Clone)]
 pub struct UILayout {
@@ -15,7 +15,11 @@ pub fn derive_config_parse(input: TokenStream) -> TokenStream {
         _ => panic!("ConfigParse only supports structs"),
     };
 
-    let parse_arms = fields.iter().map(|f| {
+    let mut set_arms = vec![];
+    let mut get_arms = vec![];
+    let mut toggle_arms = vec![];
+
+    for f in fields {
         let field_name = &f.ident;
         let field_name_str = field_name.as_ref().unwrap().to_string();
         let field_type = &f.ty;
@@ -23,33 +27,65 @@ pub fn derive_config_parse(input: TokenStream) -> TokenStream {
         // Check if it's a bool type
         let is_bool = quote!(#field_type).to_string().contains("bool");
         let is_option = quote!(#field_type).to_string().starts_with("Option <");
+        let is_vec = quote!(#field_type).to_string().starts_with("Vec <");
 
         if is_bool {
-            quote! {
+            set_arms.push(quote! {
                 #field_name_str => {
-                    config.#field_name = match value.to_lowercase().as_str() {
+                    self.#field_name = match value.to_lowercase().as_str() {
                         "true" | "1" | "yes" | "on" => true,
                         "false" | "0" | "no" | "off" => false,
-                        _ => config.#field_name,
+                        _ => return Err(format!("Invalid value for {}: {}", key, value)),
                     };
                 }
-            }
+            });
+            get_arms.push(quote! {
+                #field_name_str => Some(self.#field_name.to_string()),
+            });
+            toggle_arms.push(quote! {
+                #field_name_str => self.#field_name = !self.#field_name,
+            });
         } else if is_option {
-            quote! {
+            set_arms.push(quote! {
                 #field_name_str => {
-                    config.#field_name = Some(value.to_string());
+                    self.#field_name = Some(value.to_string());
                 }
-            }
+            });
+            get_arms.push(quote! {
+                #field_name_str => Some(self.#field_name.clone().unwrap_or_default()),
+            });
+        } else if is_vec {
+            // the key can be repeated, each line adds an item
+            set_arms.push(quote! {
+                #field_name_str => {
+                    let v = value
+                        .parse()
+                        .map_err(|_| format!("Invalid value for {}: {}", key, value))?;
+                    self.#field_name.push(v);
+                }
+            });
+            get_arms.push(quote! {
+                #field_name_str => Some(
+                    self.#field_name
+                        .iter()
+                        .map(|v| v.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ),
+            });
         } else {
-            quote! {
+            set_arms.push(quote! {
                 #field_name_str => {
-                    if let Ok(v) = value.parse() {
-                        config.#field_name = v;
-                    }
+                    self.#field_name = value
+                        .parse()
+                        .map_err(|_| format!("Invalid value for {}: {}", key, value))?;
                 }
-            }
+            });
+            get_arms.push(quote! {
+                #field_name_str => Some(self.#field_name.to_string()),
+            });
         }
-    });
+    }
 
     let expanded = quote! {
         impl #name {
@@ -71,21 +107,46 @@ pub fn derive_config_parse(input: TokenStream) -> TokenStream {
                         continue;
                     }
 
-                    // Split on first '=' only
+                    // Split on first '=' only, invalid values and unknown keys are ignored
                     if let Some((key, value)) = line.split_once('=') {
-                        let key = key.trim();
-                        let value = value.trim();
-
-                        match key {
-                            #(#parse_arms)*
-                            _ => {} // Ignore unknown keys
-                        }
+                        let _ = config.set_field(key.trim(), value.trim());
                     }
                 }
 
                 config
             }
 
+            /// Set a field from a value written as in the config file
+            pub fn set_field(&mut self, key: &str, value: &str) -> Result<(), String> {
+                match key {
+                    #(#set_arms)*
+                    _ => return Err(format!("Unknown option: {}", key)),
+                }
+
+                Ok(())
+            }
+
+            /// Value of a field written as in the config file, `None` for unknown keys
+            pub fn get_field(&self, key: &str) -> Option<String> {
+                match key {
+                    #(#get_arms)*
+                    _ => None,
+                }
+            }
+
+            /// Flip a bool field
+            pub fn toggle_field(&mut self, key: &str) -> Result<(), String> {
+                match key {
+                    #(#toggle_arms)*
+                    _ if self.get_field(key).is_some() => {
+                        return Err(format!("Not a boolean option: {}", key));
+                    }
+                    _ => return Err(format!("Unknown option: {}", key)),
+                }
+
+                Ok(())
+            }
+
             /// Load config from file if it exists, otherwise return defaults
             pub fn load_or_default<P: AsRef<std::path::Path>>(path: P) -> Self {
                 Self::from_file(path).unwrap_or_default()
@@ -120,7 +181,7 @@ pub fn derive_reflective(input: TokenStream) -> TokenStream {
 
     let expanded = quote! {
         impl #name {
-            fn field_names() -> &'static[&'static str] {
+            pub fn field_names() -> &'static[&'static str] {
                 &[
                     #(#field_names)*
                 ]